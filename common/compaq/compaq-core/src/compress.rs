@@ -9,6 +9,58 @@ pub trait Compress: Sized {
     fn decompress(val: Self::Compressed) -> Self;
 }
 
+/// Implemented by compressed types which always serialize to the same number
+/// of bytes, in formats like postcard which write bytes, bools, and arrays
+/// without any framing. This lets the space a type takes in a frame be
+/// checked at compile time.
+pub trait ExactSize {
+    /// The number of bytes the type serializes to.
+    const EXACT_SIZE: usize;
+}
+
+/// Implemented by types which fit in a few bits, so that fields of them
+/// attributed with `#[pack]` can share bytes. `unpack` only needs to handle
+/// values which `pack` produces.
+pub trait Pack: Sized {
+    /// The number of bits the type is packed into.
+    const BITS: u32;
+
+    fn pack(&self) -> u64;
+    fn unpack(bits: u64) -> Self;
+}
+
+impl Pack for bool {
+    const BITS: u32 = 1;
+
+    fn pack(&self) -> u64 {
+        u64::from(*self)
+    }
+
+    fn unpack(bits: u64) -> Self {
+        bits != 0
+    }
+}
+
+impl ExactSize for u8 {
+    const EXACT_SIZE: usize = 1;
+}
+
+impl ExactSize for i8 {
+    const EXACT_SIZE: usize = 1;
+}
+
+impl ExactSize for bool {
+    const EXACT_SIZE: usize = 1;
+}
+
+impl ExactSize for () {
+    const EXACT_SIZE: usize = 0;
+}
+
+impl<T: ExactSize, const N: usize> ExactSize for [T; N] {
+    const EXACT_SIZE: usize = T::EXACT_SIZE * N;
+}
+
 impl Compress for f64 {
     type Compressed = [u8; 2];
 
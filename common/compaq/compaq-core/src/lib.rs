@@ -1,4 +1,5 @@
 pub mod compress;
+pub mod quantize;
 
 pub type Result<T> = ::core::result::Result<T, CompaqError>;
 
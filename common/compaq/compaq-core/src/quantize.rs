@@ -0,0 +1,66 @@
+//! Fixed-point encodings for floating-point values, used by the `#[quantize]`
+//! and `#[scale]` field attributes to pack values into integers of arbitrary
+//! bit width, along with the bit packing shared with `#[pack]`.
+
+/// The most bits a single quantized value may take.
+pub const MAX_BITS: u32 = 32;
+
+fn mask(bits: u32) -> u64 {
+    (1 << bits) - 1
+}
+
+/// Maps a value in `min..=max` evenly onto the unsigned integers that fit in
+/// `bits` bits. Values outside of the range are clamped to it, and NaN is
+/// stored as `min`.
+pub fn quantize(value: f64, min: f64, max: f64, bits: u32) -> u64 {
+    let levels = mask(bits) as f64;
+    let normalized = ((value - min) / (max - min)).clamp(0.0, 1.0);
+
+    // casting NaN to an integer gives zero.
+    (normalized * levels).round() as u64
+}
+
+/// Restores a value stored by [`quantize`] with the same range and width.
+pub fn dequantize(raw: u64, min: f64, max: f64, bits: u32) -> f64 {
+    let levels = mask(bits) as f64;
+    min + (raw & mask(bits)) as f64 / levels * (max - min)
+}
+
+/// Stores a value as a signed count of `step`s from zero in two's complement,
+/// clamping values too large for `bits` bits. NaN is stored as zero.
+pub fn scale(value: f64, step: f64, bits: u32) -> u64 {
+    let limit = (1_i64 << (bits - 1)) as f64;
+    let steps = (value / step).round().clamp(-limit, limit - 1.0);
+
+    steps as i64 as u64 & mask(bits)
+}
+
+/// Restores a value stored by [`scale`] with the same step and width.
+pub fn unscale(raw: u64, step: f64, bits: u32) -> f64 {
+    let unused = u64::BITS - bits;
+    let steps = ((raw & mask(bits)) << unused) as i64 >> unused;
+
+    steps as f64 * step
+}
+
+/// Writes the low `bits` bits of a value into a buffer, starting `offset`
+/// bits in and filling each byte from its least significant bit.
+pub fn write_bits(buf: &mut [u8], offset: usize, bits: u32, value: u64) {
+    for bit in 0..bits as usize {
+        let position = offset + bit;
+
+        if value >> bit & 1 == 1 {
+            buf[position / 8] |= 1 << (position % 8);
+        }
+    }
+}
+
+/// Reads a value of `bits` bits written by [`write_bits`].
+pub fn read_bits(buf: &[u8], offset: usize, bits: u32) -> u64 {
+    (0..bits as usize).fold(0, |value, bit| {
+        let position = offset + bit;
+        let set = buf[position / 8] >> (position % 8) & 1;
+
+        value | u64::from(set) << bit
+    })
+}
@@ -1,6 +1,8 @@
+use std::collections::HashMap;
+
 use proc_macro2::{Span, TokenStream};
-use quote::{quote, quote_spanned};
-use syn::{AttrStyle, punctuated::Punctuated, Attribute, Data, DeriveInput, Error, Field, Fields, GenericArgument, Ident, Meta, Path, PathArguments, Token, Type, TypePath, parse_macro_input, parse_quote, spanned::Spanned};
+use quote::{format_ident, quote, quote_spanned};
+use syn::{AttrStyle, punctuated::Punctuated, Attribute, Data, DataEnum, DeriveInput, Error, Expr, ExprLit, ExprUnary, Field, Fields, GenericArgument, Ident, Lit, Meta, MetaNameValue, Path, PathArguments, Token, Type, TypePath, UnOp, parse_macro_input, parse_quote, spanned::Spanned};
 
 // Attributes:
 // Exclude ("exclude"): Removes a field from compression.
@@ -11,26 +13,166 @@ const FREEZE_ATTRIBUTE_STRING: &str = "freeze";
 
 // Order ("order"):
 const ORDER_ATTRIBUTE_STRING: &str = "order";
+
+// Pack ("pack"): Stores a field implementing `Pack`, such as a bool or an enum
+// without fields, in as few bits as it needs.
 const PACK_ATTRIBUTE_STRING: &str = "pack";
 
+// Quantize ("quantize(min = .., max = .., bits = ..)"): Stores a float as a
+// fixed-point integer spread evenly over a range.
+const QUANTIZE_ATTRIBUTE_STRING: &str = "quantize";
+
+// Scale ("scale(step = .., bits = ..)"): Stores a float as a signed number of
+// steps from zero.
+const SCALE_ATTRIBUTE_STRING: &str = "scale";
+
+// Passed alongside the compressed type's name to implement `ExactSize` for it.
+const EXACT_SIZE_ARGUMENT_STRING: &str = "exact_size";
+
 #[derive(Clone)]
 enum Tag<'a> {
     Excluded,
     Frozen,
     Ordered { is_frozen: bool, k: &'a Type, v: &'a Type },
     Packed,
+    Quantized(Quantization),
+}
+
+/// How a field attributed with `#[quantize]` or `#[scale]` is stored.
+#[derive(Clone, Copy)]
+enum Quantization {
+    Range { min: f64, max: f64, bits: u32 },
+    Scale { step: f64, bits: u32 },
+}
+
+impl Quantization {
+    fn bits(self) -> u32 {
+        match self {
+            Quantization::Range { bits, .. } | Quantization::Scale { bits, .. } => bits,
+        }
+    }
+
+    fn parse(attribute: &Attribute, is_scale: bool) -> syn::Result<Self> {
+        fn parse_number(expr: &Expr) -> syn::Result<f64> {
+            match expr {
+                Expr::Lit(ExprLit { lit: Lit::Float(value), .. }) => value.base10_parse(),
+                Expr::Lit(ExprLit { lit: Lit::Int(value), .. }) => value.base10_parse::<i64>().map(|value| value as f64),
+                Expr::Unary(ExprUnary { op: UnOp::Neg(_), expr, .. }) => parse_number(expr).map(|value| -value),
+                _ => Err(Error::new(expr.span(), "Expected a number literal.")),
+            }
+        }
+
+        let arguments = attribute.parse_args_with(Punctuated::<MetaNameValue, Token![,]>::parse_terminated)?;
+
+        let mut min = None;
+        let mut max = None;
+        let mut step = None;
+        let mut bits = None;
+
+        for argument in &arguments {
+            let Some(name) = argument.path.get_ident() else {
+                return Err(Error::new(argument.path.span(), "Expected an argument name."));
+            };
+
+            match name.to_string().as_str() {
+                "min" if !is_scale => min = Some(parse_number(&argument.value)?),
+                "max" if !is_scale => max = Some(parse_number(&argument.value)?),
+                "step" if is_scale => step = Some(parse_number(&argument.value)?),
+                "bits" => {
+                    let Expr::Lit(ExprLit { lit: Lit::Int(value), .. }) = &argument.value else {
+                        return Err(Error::new(argument.value.span(), "`bits` must be an integer literal."));
+                    };
+
+                    bits = Some((value.base10_parse::<u32>()?, value.span()));
+                }
+                _ => return Err(Error::new(name.span(), format!("Unexpected argument `{name}`."))),
+            }
+        }
+
+        let Some((bits, bits_span)) = bits else {
+            return Err(Error::new(attribute.span(), "Missing the `bits` argument."));
+        };
+
+        let min_bits = if is_scale { 2 } else { 1 };
+        if !(min_bits..=::compaq_core::quantize::MAX_BITS).contains(&bits) {
+            return Err(Error::new(
+                bits_span,
+                format!("`bits` must be between {min_bits} and {}.", ::compaq_core::quantize::MAX_BITS),
+            ));
+        }
+
+        if is_scale {
+            let Some(step) = step else {
+                return Err(Error::new(attribute.span(), "`#[scale]` requires a `step` argument."));
+            };
+
+            if step <= 0.0 {
+                return Err(Error::new(attribute.span(), "`step` must be greater than zero."));
+            }
+
+            Ok(Quantization::Scale { step, bits })
+        } else {
+            let (Some(min), Some(max)) = (min, max) else {
+                return Err(Error::new(attribute.span(), "`#[quantize]` requires `min` and `max` arguments."));
+            };
+
+            if min >= max {
+                return Err(Error::new(attribute.span(), "`min` must be less than `max`."));
+            }
+
+            Ok(Quantization::Range { min, max, bits })
+        }
+    }
+
+    /// Generates the expression converting a field's value to its raw bits.
+    fn encode(self, value: TokenStream) -> TokenStream {
+        match self {
+            Quantization::Range { min, max, bits } => quote! {
+                ::compaq::quantize::quantize(::core::convert::From::from(#value), #min, #max, #bits)
+            },
+            Quantization::Scale { step, bits } => quote! {
+                ::compaq::quantize::scale(::core::convert::From::from(#value), #step, #bits)
+            },
+        }
+    }
+
+    /// Generates the expression converting raw bits back to a field's value.
+    fn decode(self, raw: TokenStream, ty: &Type) -> TokenStream {
+        let value = match self {
+            Quantization::Range { min, max, bits } => quote! { ::compaq::quantize::dequantize(#raw, #min, #max, #bits) },
+            Quantization::Scale { step, bits } => quote! { ::compaq::quantize::unscale(#raw, #step, #bits) },
+        };
+
+        if is_type(ty, "f32") {
+            quote! { (#value) as f32 }
+        } else {
+            value
+        }
+    }
+}
+
+fn is_type(ty: &Type, name: &str) -> bool {
+    matches!(
+        ty,
+        Type::Path(TypePath { path, .. })
+            if path.segments.last().is_some_and(|segment| segment.ident == name)
+    )
 }
 
 #[derive(Clone)]
 struct AttributedField<'a> {
     field: &'a Field,
+    // The field's name, or the name it is bound to in patterns if it is unnamed.
+    name: Ident,
     tag: Option<Tag<'a>>,
 }
 
 impl<'a> AttributedField<'a> {
-    fn new(field: &'a Field, is_excluded: bool, is_frozen: bool, is_ordered: bool, is_packed: bool) -> Self {
-        if is_excluded {
-            AttributedField { field, tag: Some(Tag::Excluded) }
+    fn new(field: &'a Field, name: Ident, is_excluded: bool, is_frozen: bool, is_ordered: bool, is_packed: bool, quantization: Option<Quantization>) -> Self {
+        if let Some(quantization) = quantization {
+            AttributedField { field, name, tag: Some(Tag::Quantized(quantization)) }
+        } else if is_excluded {
+            AttributedField { field, name, tag: Some(Tag::Excluded) }
         } else if is_ordered {
             let Type::Path(path) = &field.ty else {
                 panic!("#[order] attribute must be attributed to a type using a type path.");
@@ -46,29 +188,54 @@ impl<'a> AttributedField<'a> {
                 panic!("The Hashmap type must have at least two generic arguments to use the #[order] attribute.");
             };
 
-            AttributedField { field, tag: Some(Tag::Ordered { is_frozen, k, v }) }
+            AttributedField { field, name, tag: Some(Tag::Ordered { is_frozen, k, v }) }
         } else if is_packed {
-            AttributedField { field, tag: Some(Tag::Packed) }
+            AttributedField { field, name, tag: Some(Tag::Packed) }
         } else if is_frozen {
-            AttributedField { field, tag: Some(Tag::Frozen) }
+            AttributedField { field, name, tag: Some(Tag::Frozen) }
         } else {
-            AttributedField { field, tag: None }
+            AttributedField { field, name, tag: None }
         }
     }
 }
 
 fn process_field_attributes<'a>(raw_fields: impl Iterator<Item = &'a Field>) -> syn::Result<Vec<AttributedField<'a>>> {
-    fn is_bool_type(ty: &Type) -> bool {
-        matches!(
-            ty,
-            Type::Path(TypePath { path, .. })
-                if path.segments.last().is_some_and(|segment| segment.ident == "bool")
-        )
-    }
-
-    fn detect_attribution_rule_violations(field: &Field, excluded: Option<Span>, frozen: Option<Span>, ordered: Option<Span>, packed: Option<Span>) -> Option<Error> {
+    fn detect_attribution_rule_violations(field: &Field, excluded: Option<Span>, frozen: Option<Span>, ordered: Option<Span>, packed: Option<Span>, quantized: Option<(Span, &str)>) -> Option<Error> {
         let mut accumulated_error: Option<Error> = None;
 
+        if let Some((quantized, name)) = quantized {
+            let others = [
+                (excluded, EXCLUDE_ATTRIBUTE_STRING),
+                (frozen, FREEZE_ATTRIBUTE_STRING),
+                (ordered, ORDER_ATTRIBUTE_STRING),
+                (packed, PACK_ATTRIBUTE_STRING),
+            ];
+
+            for (other, other_name) in others {
+                let Some(other) = other else {
+                    continue;
+                };
+
+                let mut error = syn::Error::new(quantized, format!("`#[{name}]` and `#[{other_name}]` cannot be attributed to a field simultaneously."));
+                error.combine(syn::Error::new(other, format!("`#[{other_name}]` and `#[{name}]` cannot be attributed to a field simultaneously.")));
+
+                if let Some(ref mut e) = accumulated_error {
+                    e.combine(error);
+                } else {
+                    accumulated_error = Some(error);
+                }
+            }
+
+            if !is_type(&field.ty, "f32") && !is_type(&field.ty, "f64") {
+                let error = syn::Error::new(quantized, format!("Field attributed with `#[{name}]` must be of type `f32` or `f64`."));
+                if let Some(ref mut e) = accumulated_error {
+                    e.combine(error);
+                } else {
+                    accumulated_error = Some(error);
+                }
+            }
+        }
+
         if let Some(excluded) = excluded && let Some(frozen) = frozen {
             let mut error = syn::Error::new(excluded, "`#[exclude]` and `#[freeze]` cannot be attributed to a field simultaneously.");
             error.combine(syn::Error::new(frozen, "`#[freeze]` and `#[exclude]` cannot be attributed to a field simultaneously."));
@@ -141,25 +308,17 @@ fn process_field_attributes<'a>(raw_fields: impl Iterator<Item = &'a Field>) ->
             }
         }
 
-        if let Some(packed_span) = packed && !is_bool_type(&field.ty) {
-            let error = syn::Error::new(packed_span, "Field attributed with `#[pack]` must be of type `bool`.");
-            if let Some(ref mut e) = accumulated_error {
-                e.combine(error);
-            } else {
-                accumulated_error = Some(error);
-            }
-        }
-
         accumulated_error
     }
 
     let mut formatted_fields = Vec::new();
 
-    for field in raw_fields {
+    for (index, field) in raw_fields.enumerate() {
         let mut excluded = None;
         let mut frozen = None;
         let mut ordered = None;
         let mut packed = None;
+        let mut quantization: Option<(Quantization, Span, &str)> = None;
 
         // attach attribute to some field
         for attribute in &field.attrs {
@@ -167,6 +326,19 @@ fn process_field_attributes<'a>(raw_fields: impl Iterator<Item = &'a Field>) ->
                 continue;
             }
 
+            for name in [QUANTIZE_ATTRIBUTE_STRING, SCALE_ATTRIBUTE_STRING] {
+                if !attribute.path().is_ident(name) {
+                    continue;
+                }
+
+                if let Some((_, _, previous)) = quantization {
+                    return Err(syn::Error::new(attribute.span(), format!("`#[{name}]` cannot be attributed to a field already attributed with `#[{previous}]`.")));
+                }
+
+                let parsed = Quantization::parse(attribute, name == SCALE_ATTRIBUTE_STRING)?;
+                quantization = Some((parsed, attribute.span(), name));
+            }
+
             let Meta::Path(ref p) = attribute.meta else {
                 continue;
             };
@@ -188,18 +360,166 @@ fn process_field_attributes<'a>(raw_fields: impl Iterator<Item = &'a Field>) ->
             }
         }
 
-        if let Some(error) = detect_attribution_rule_violations(field, excluded, frozen, ordered, packed) {
+        let quantized = quantization.map(|(_, span, name)| (span, name));
+        if let Some(error) = detect_attribution_rule_violations(field, excluded, frozen, ordered, packed, quantized) {
             return Err(error);
         }
 
-        formatted_fields.push(AttributedField::new(field, excluded.is_some(), frozen.is_some(), ordered.is_some(), packed.is_some()));
+        let quantization = quantization.map(|(quantization, _, _)| quantization);
+        let name = field.ident.clone().unwrap_or_else(|| format_ident!("__field{index}"));
+        formatted_fields.push(AttributedField::new(field, name, excluded.is_some(), frozen.is_some(), ordered.is_some(), packed.is_some(), quantization));
     }
 
     Ok(formatted_fields)
 }
 
-fn packed_bool_fields<'a>(fields: &'a Vec<AttributedField<'a>>) -> Vec<&'a AttributedField<'a>> {
-    fields.iter().filter(|f| matches!(f.tag, Some(Tag::Packed))).collect()
+fn is_packed(field: &&AttributedField) -> bool {
+    matches!(field.tag, Some(Tag::Packed))
+}
+
+/// Returns the expression for the number of bits a packed field takes.
+fn packed_bits(field: &AttributedField) -> TokenStream {
+    let ty = &field.field.ty;
+    quote_spanned! {ty.span()=> <#ty as ::compaq::Pack>::BITS }
+}
+
+/// Returns every packed field along with the bit its value starts at in the
+/// shared `__packed` bytes, which depends on the widths of the fields before
+/// it and so is only known once their `Pack` impls are.
+fn packed_fields<'a>(fields: &'a Vec<AttributedField<'a>>) -> Vec<(&'a AttributedField<'a>, TokenStream)> {
+    let mut widths = Vec::new();
+
+    fields
+        .iter()
+        .filter(is_packed)
+        .map(|f| {
+            let offset = quote! { (0 #(+ #widths)*) as usize };
+            widths.push(packed_bits(f));
+            (f, offset)
+        })
+        .collect()
+}
+
+/// Returns the expression for the number of bytes the packed fields take, or
+/// `None` if there aren't any.
+fn packed_len(fields: &Vec<AttributedField>) -> Option<TokenStream> {
+    let widths: Vec<_> = fields.iter().filter(is_packed).map(packed_bits).collect();
+    (!widths.is_empty()).then(|| quote! { ((0 #(+ #widths)*) as usize).div_ceil(8) })
+}
+
+/// Generates the statements packing every packed field into a local
+/// `__packed` array, where `place` gives the expression holding a field.
+fn generate_packing(fields: &Vec<AttributedField>, place: impl Fn(&Ident) -> TokenStream) -> Option<TokenStream> {
+    let packed_len = packed_len(fields)?;
+
+    let writes = packed_fields(fields).into_iter().map(|(field, offset)| {
+        let ty = &field.field.ty;
+        let place = place(&field.name);
+        let bits = packed_bits(field);
+
+        quote_spanned! {field.field.span()=>
+            ::compaq::quantize::write_bits(&mut __packed, #offset, #bits, <#ty as ::compaq::Pack>::pack(&#place));
+        }
+    });
+
+    Some(quote! {
+        let mut __packed = [0u8; #packed_len];
+        #(#writes)*
+    })
+}
+
+/// Returns every packed field along with the expression restoring it from the
+/// `source` array.
+fn packed_values<'a>(fields: &'a Vec<AttributedField<'a>>, source: &TokenStream) -> Vec<(&'a AttributedField<'a>, TokenStream)> {
+    packed_fields(fields)
+        .into_iter()
+        .map(|(field, offset)| {
+            let ty = &field.field.ty;
+            let bits = packed_bits(field);
+            let value = quote_spanned! {field.field.span()=>
+                <#ty as ::compaq::Pack>::unpack(::compaq::quantize::read_bits(&#source, #offset, #bits))
+            };
+
+            (field, value)
+        })
+        .collect()
+}
+
+/// Returns every quantized field along with the bit its value starts at in the
+/// shared `__quantized` bytes.
+fn quantized_fields<'a>(fields: &'a Vec<AttributedField<'a>>) -> Vec<(&'a AttributedField<'a>, Quantization, usize)> {
+    let mut offset = 0;
+
+    fields
+        .iter()
+        .filter_map(|f| match f.tag {
+            Some(Tag::Quantized(quantization)) => {
+                let start = offset;
+                offset += quantization.bits() as usize;
+                Some((f, quantization, start))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// Returns the number of bytes the quantized fields are packed into.
+fn quantized_len(fields: &Vec<AttributedField>) -> usize {
+    quantized_fields(fields)
+        .iter()
+        .map(|(_, quantization, _)| quantization.bits() as usize)
+        .sum::<usize>()
+        .div_ceil(8)
+}
+
+/// Generates the statements packing every quantized field into a local
+/// `__quantized` array, where `place` gives the expression holding a field.
+fn generate_quantized_packing(fields: &Vec<AttributedField>, place: impl Fn(&Ident) -> TokenStream) -> Option<TokenStream> {
+    let quantized_len = quantized_len(fields);
+    if quantized_len == 0 {
+        return None;
+    }
+
+    let writes = quantized_fields(fields).into_iter().map(|(field, quantization, offset)| {
+        let bits = quantization.bits();
+        let raw = quantization.encode(place(&field.name));
+
+        quote_spanned! {field.field.span()=>
+            ::compaq::quantize::write_bits(&mut __quantized, #offset, #bits, #raw);
+        }
+    });
+
+    Some(quote! {
+        let mut __quantized = [0u8; #quantized_len];
+        #(#writes)*
+    })
+}
+
+/// Returns every quantized field along with the expression restoring it from
+/// the `source` array.
+fn quantized_values<'a>(fields: &'a Vec<AttributedField<'a>>, source: &TokenStream) -> Vec<(&'a AttributedField<'a>, TokenStream)> {
+    quantized_fields(fields)
+        .into_iter()
+        .map(|(field, quantization, offset)| {
+            let bits = quantization.bits();
+            let value = quantization.decode(quote! { ::compaq::quantize::read_bits(&#source, #offset, #bits) }, &field.field.ty);
+
+            (field, value)
+        })
+        .collect()
+}
+
+/// Generates the initializers restoring every packed and quantized field of a
+/// struct from the arrays of `source`.
+fn generate_bit_unpacking(fields: &Vec<AttributedField>, source: TokenStream) -> Vec<TokenStream> {
+    packed_values(fields, &quote! { #source.__packed })
+        .into_iter()
+        .chain(quantized_values(fields, &quote! { #source.__quantized }))
+        .map(|(field, value)| {
+            let name = &field.name;
+            quote_spanned! {field.field.span()=> #name: #value, }
+        })
+        .collect()
 }
 
 fn get_compressed_struct_name(name: &Ident, has_ordered_member: bool) -> Ident {
@@ -218,6 +538,7 @@ fn generate_struct_members<'a>(fields: &'a Vec<AttributedField<'a>>, enforce_ord
         match f.tag {
             Some(Tag::Excluded) => None,
             Some(Tag::Packed) => None,
+            Some(Tag::Quantized(_)) => None,
             Some(Tag::Frozen) => Some(quote_spanned! {f.field.span()=> #name: #ty, }),
             Some(Tag::Ordered { is_frozen, k, v }) => {
                 let inner_type: Type = if is_frozen { parse_quote! { #v } } else { parse_quote! { <#v as ::compaq::Compress>::Compressed } };
@@ -234,20 +555,21 @@ fn generate_struct_members<'a>(fields: &'a Vec<AttributedField<'a>>, enforce_ord
 }
 
 fn generate_compressed_struct(input: &DeriveInput, compressed_name: &Ident, fields: &Vec<AttributedField>, has_ordered_member: bool) -> TokenStream {
-    let packed_bool_fields = packed_bool_fields(fields);
     let transformed_fields = generate_struct_members(fields, false);
 
     let name = get_compressed_struct_name(compressed_name, has_ordered_member);
     let attributes = strip_compress_attribute(&input.attrs);
     let vis = &input.vis;
-    let packed_len = packed_bool_fields.len().div_ceil(8);
-    let packed_field = (packed_len > 0).then(|| quote! { __packed_bools: [u8; #packed_len], });
+    let packed_field = packed_len(fields).map(|packed_len| quote! { __packed: [u8; #packed_len], });
+    let quantized_len = quantized_len(fields);
+    let quantized_field = (quantized_len > 0).then(|| quote! { __quantized: [u8; #quantized_len], });
 
     quote_spanned! {input.span()=>
         #[allow(dead_code)]
         #(#attributes)*
         #vis struct #name {
             #packed_field
+            #quantized_field
             #(#transformed_fields)*
         }
     }
@@ -270,7 +592,8 @@ fn generate_trait_assertions(fields: &Vec<AttributedField>) -> TokenStream {
 
         match field.tag {
             Some(Tag::Excluded) => asserts.push(assert_impl(ty, parse_quote! { ::core::default::Default })),
-            Some(Tag::Packed) => {},
+            Some(Tag::Packed) => asserts.push(assert_impl(ty, parse_quote! { ::compaq::Pack })),
+            Some(Tag::Quantized(_)) => {},
             Some(Tag::Frozen) => asserts.push(assert_impl(ty, parse_quote! { ::core::clone::Clone })),
             Some(Tag::Ordered { is_frozen, k: _, v }) if is_frozen => asserts.push(assert_impl(v, parse_quote! { ::core::clone::Clone })),
             // The required traits for these fields are Compress, and we get that type check for free with the generated `<#ty as Compress>` statements.
@@ -290,20 +613,21 @@ fn generate_trait_assertions(fields: &Vec<AttributedField>) -> TokenStream {
 
 
 fn generate_ordered_struct(input: &DeriveInput, compressed_name: &Ident, fields: &Vec<AttributedField>) -> TokenStream {
-    let packed_bool_fields = packed_bool_fields(fields);
     let transformed_fields = generate_struct_members(fields, true);
 
     let name = get_compressed_struct_name(compressed_name, false);
     let attributes = strip_compress_attribute(&input.attrs);
     let vis = &input.vis;
-    let packed_len = packed_bool_fields.len().div_ceil(8);
-    let packed_field = (packed_len > 0).then(|| quote! { __packed_bools: [u8; #packed_len], });
+    let packed_field = packed_len(fields).map(|packed_len| quote! { __packed: [u8; #packed_len], });
+    let quantized_len = quantized_len(fields);
+    let quantized_field = (quantized_len > 0).then(|| quote! { __quantized: [u8; #quantized_len], });
 
     quote_spanned! {input.span()=>
         #[allow(dead_code)]
         #(#attributes)*
         #vis struct #name {
             #packed_field
+            #quantized_field
             #(#transformed_fields)*
         }
     }
@@ -312,27 +636,10 @@ fn generate_ordered_struct(input: &DeriveInput, compressed_name: &Ident, fields:
 fn generate_compress_impl(input: &DeriveInput, compressed_name: &Ident, fields: &Vec<AttributedField>, is_ordered: bool) -> TokenStream {
     let compress_name = get_compressed_struct_name(compressed_name, is_ordered);
     let name = &input.ident;
-    let packed_bool_fields = packed_bool_fields(fields);
-    let packed_len = packed_bool_fields.len().div_ceil(8);
-    let packed_compress = if packed_len > 0 {
-        let setters = packed_bool_fields.iter().enumerate().map(|(index, field)| {
-            let name = field.field.ident.as_ref().unwrap();
-            let byte_index = index / 8;
-            let bit_index = index % 8;
-            quote_spanned! {field.field.span()=>
-                if self.#name {
-                    __packed_bools[#byte_index] |= 1 << #bit_index;
-                }
-            }
-        });
-        Some(quote! {
-            let mut __packed_bools = [0u8; #packed_len];
-            #(#setters)*
-        })
-    } else {
-        None
-    };
-    let packed_compress_initializer = (packed_len > 0).then(|| quote! { __packed_bools, });
+    let packed_compress = generate_packing(fields, |name| quote! { self.#name });
+    let packed_compress_initializer = packed_compress.is_some().then(|| quote! { __packed, });
+    let quantized_compress = generate_quantized_packing(fields, |name| quote! { self.#name });
+    let quantized_compress_initializer = quantized_compress.is_some().then(|| quote! { __quantized, });
 
     let compress_initializers = fields.iter().filter_map(|f| {
         let name = f.field.ident.as_ref().unwrap();
@@ -341,18 +648,14 @@ fn generate_compress_impl(input: &DeriveInput, compressed_name: &Ident, fields:
         match f.tag {
             Some(Tag::Excluded) => None,
             Some(Tag::Packed) => None,
+            Some(Tag::Quantized(_)) => None,
             Some(Tag::Frozen) => Some(quote_spanned! {f.field.span()=> #name: ::core::clone::Clone::clone(&self.#name), }),
             Some(Tag::Ordered { is_frozen, .. }) if is_frozen => Some(quote_spanned! {f.field.span()=> #name: ::core::clone::Clone::clone(&self.#name), }),
             Some(Tag::Ordered { .. }) | None => Some(quote_spanned! {f.field.span()=> #name: <#ty as ::compaq::Compress>::compress(&self.#name), })
         }
     });
 
-    let packed_decompress = packed_bool_fields.iter().enumerate().map(|(index, field)| {
-        let name = field.field.ident.as_ref().unwrap();
-        let byte_index = index / 8;
-        let bit_index = index % 8;
-        quote_spanned! {field.field.span()=> #name: val.__packed_bools[#byte_index] & (1 << #bit_index) != 0, }
-    });
+    let bit_decompress = generate_bit_unpacking(fields, quote! { val });
 
     let decompress_initializers = fields.iter().filter_map(|f| {
         let name = f.field.ident.as_ref().unwrap();
@@ -360,6 +663,7 @@ fn generate_compress_impl(input: &DeriveInput, compressed_name: &Ident, fields:
 
         match f.tag {
             Some(Tag::Packed) => None,
+            Some(Tag::Quantized(_)) => None,
             Some(Tag::Excluded) => Some(quote_spanned! {f.field.span()=> #name: ::core::default::Default::default(), }),
             Some(Tag::Frozen) => Some(quote_spanned! {f.field.span()=> #name: val.#name, }),
             Some(Tag::Ordered { is_frozen, .. }) if is_frozen => Some(quote_spanned! {f.field.span()=> #name: val.#name, }),
@@ -374,15 +678,17 @@ fn generate_compress_impl(input: &DeriveInput, compressed_name: &Ident, fields:
 
             fn compress(&self) -> Self::Compressed {
                 #packed_compress
+                #quantized_compress
                 Self::Compressed {
                     #packed_compress_initializer
+                    #quantized_compress_initializer
                     #(#compress_initializers)*
                 }
             }
 
             fn decompress(val: Self::Compressed) -> Self {
                 Self {
-                    #(#packed_decompress)*
+                    #(#bit_decompress)*
                     #(#decompress_initializers)*
                 }
             }
@@ -398,8 +704,6 @@ fn generate_methods(input: &DeriveInput, compressed_name: &Ident, fields: &Vec<A
     let name = &input.ident;
     let vis = &input.vis;
     let compressed_name = get_compressed_struct_name(compressed_name, false);
-    let packed_bool_fields = packed_bool_fields(fields);
-    let packed_len = packed_bool_fields.len().div_ceil(8);
 
     let ordered_fields: Vec<&AttributedField<'_>> = isolate_ordered_fields(fields).collect();
     let deflate_policy_parameters = ordered_fields.iter().map(|f| {
@@ -462,38 +766,18 @@ fn generate_methods(input: &DeriveInput, compressed_name: &Ident, fields: &Vec<A
         match f.tag {
             Some(Tag::Excluded) => None,
             Some(Tag::Packed) => None,
+            Some(Tag::Quantized(_)) => None,
             Some(Tag::Frozen) => Some(quote_spanned! {f.field.span()=> #name: ::core::clone::Clone::clone(&self.#name), }),
             Some(Tag::Ordered { .. }) => Some(quote_spanned! {f.field.span()=> #name, }),
             None => Some(quote_spanned! {f.field.span()=> #name: <#ty as ::compaq::Compress>::compress(&self.#name), }),
         }
     });
 
-    let packed_deflate = if packed_len > 0 {
-        let setters = packed_bool_fields.iter().enumerate().map(|(index, field)| {
-            let name = field.field.ident.as_ref().unwrap();
-            let byte_index = index / 8;
-            let bit_index = index % 8;
-            quote_spanned! {field.field.span()=>
-                if self.#name {
-                    __packed_bools[#byte_index] |= 1 << #bit_index;
-                }
-            }
-        });
-        Some(quote! {
-            let mut __packed_bools = [0u8; #packed_len];
-            #(#setters)*
-        })
-    } else {
-        None
-    };
-    let packed_deflate_initializer = (packed_len > 0).then(|| quote! { __packed_bools, });
-
-    let packed_inflate = packed_bool_fields.iter().enumerate().map(|(index, field)| {
-        let name = field.field.ident.as_ref().unwrap();
-        let byte_index = index / 8;
-        let bit_index = index % 8;
-        quote_spanned! {field.field.span()=> #name: self.__packed_bools[#byte_index] & (1 << #bit_index) != 0, }
-    });
+    let packed_deflate = generate_packing(fields, |name| quote! { self.#name });
+    let packed_deflate_initializer = packed_deflate.is_some().then(|| quote! { __packed, });
+    let quantized_deflate = generate_quantized_packing(fields, |name| quote! { self.#name });
+    let quantized_deflate_initializer = quantized_deflate.is_some().then(|| quote! { __quantized, });
+    let bit_inflate = generate_bit_unpacking(fields, quote! { self });
 
     let inflate_initializers = fields.iter().filter_map(|f| {
         let name = f.field.ident.as_ref().unwrap();
@@ -501,6 +785,7 @@ fn generate_methods(input: &DeriveInput, compressed_name: &Ident, fields: &Vec<A
 
         match f.tag {
             Some(Tag::Packed) => None,
+            Some(Tag::Quantized(_)) => None,
             Some(Tag::Excluded) => Some(quote_spanned! { f.field.span()=> #name: ::core::default::Default::default(), }),
             Some(Tag::Frozen) => Some(quote_spanned! {f.field.span()=> #name: self.#name, }),
             Some(Tag::Ordered { .. }) => Some(quote_spanned! {f.field.span()=> #name, }),
@@ -515,9 +800,11 @@ fn generate_methods(input: &DeriveInput, compressed_name: &Ident, fields: &Vec<A
             #vis fn deflate(&self #(#deflate_policy_parameters)*) -> ::compaq::Result<#compressed_name> {
                 #(#deflate_ordered_logic)*
                 #packed_deflate
+                #quantized_deflate
 
                 ::core::result::Result::Ok(#compressed_name {
                     #packed_deflate_initializer
+                    #quantized_deflate_initializer
                     #(#deflate_initializers)*
                 })
             }
@@ -530,7 +817,7 @@ fn generate_methods(input: &DeriveInput, compressed_name: &Ident, fields: &Vec<A
                 #(#inflate_ordered_logic)*
 
                 ::core::result::Result::Ok(#name {
-                    #(#packed_inflate)*
+                    #(#bit_inflate)*
                     #(#inflate_initializers)*
                 })
             }
@@ -538,11 +825,244 @@ fn generate_methods(input: &DeriveInput, compressed_name: &Ident, fields: &Vec<A
     }
 }
 
-#[proc_macro_derive(__SilenceErrors, attributes(exclude, freeze, order, pack))]
+#[proc_macro_derive(__SilenceErrors, attributes(exclude, freeze, order, pack, quantize, scale))]
 pub fn derive(_input: proc_macro::TokenStream) -> proc_macro::TokenStream {
     TokenStream::new().into()
 }
 
+fn generate_exact_size_impl(compressed_name: &Ident, fields: &Vec<AttributedField>) -> syn::Result<TokenStream> {
+    let packed_len = packed_len(fields).unwrap_or_else(|| quote! { 0 });
+    let quantized_len = quantized_len(fields);
+
+    let mut field_sizes = Vec::new();
+    for f in fields {
+        let ty = &f.field.ty;
+
+        match f.tag {
+            Some(Tag::Excluded) | Some(Tag::Packed) | Some(Tag::Quantized(_)) => {}
+            Some(Tag::Frozen) => field_sizes.push(quote_spanned! {f.field.span()=> <#ty as ::compaq::ExactSize>::EXACT_SIZE }),
+            Some(Tag::Ordered { .. }) => {
+                return Err(Error::new(f.field.span(), "`exact_size` cannot be used with `#[order]` fields, whose size depends on their policy."));
+            }
+            None => field_sizes.push(quote_spanned! {f.field.span()=> <<#ty as ::compaq::Compress>::Compressed as ::compaq::ExactSize>::EXACT_SIZE }),
+        }
+    }
+
+    Ok(quote! {
+        #[automatically_derived]
+        impl ::compaq::ExactSize for #compressed_name {
+            const EXACT_SIZE: usize = #packed_len + #quantized_len #(+ #field_sizes)*;
+        }
+    })
+}
+
+/// Lays out the entries for the fields of a variant in its style, whether to
+/// declare, match, or construct it.
+fn variant_body(fields: &Fields, entries: impl IntoIterator<Item = (Ident, TokenStream)>) -> TokenStream {
+    let entries = entries.into_iter();
+
+    match fields {
+        Fields::Named(_) => {
+            // uses the shorthand for fields bound to or initialized with
+            // variables of the same name.
+            let entries = entries.map(|(name, value)| {
+                if name == value.to_string() { quote! { #name } } else { quote! { #name: #value } }
+            });
+
+            quote! { { #(#entries),* } }
+        }
+        Fields::Unnamed(_) => {
+            let values = entries.map(|(_, value)| value);
+            quote! { ( #(#values),* ) }
+        }
+        Fields::Unit => TokenStream::new(),
+    }
+}
+
+/// Generates the compressed enum and `Compress` impl for an enum. Each variant
+/// is mirrored with its fields compressed like those of a struct, and postcard
+/// writes the variant index as a varint, which takes a single byte for the
+/// first 128 variants. Enums without any fields also implement `Pack`, so they
+/// can be packed into the few bits needed to tell their variants apart.
+fn generate_enum(input: &DeriveInput, compressed_name: &Ident, data: &DataEnum, exact_size: bool) -> syn::Result<TokenStream> {
+    let name = &input.ident;
+    let vis = &input.vis;
+    let attributes = strip_compress_attribute(&input.attrs);
+
+    if data.variants.is_empty() {
+        return Err(Error::new(name.span(), "Compress cannot be used with enums without variants."));
+    }
+
+    let mut compressed_variants = Vec::new();
+    let mut compress_arms = Vec::new();
+    let mut decompress_arms = Vec::new();
+    let mut trait_assertions = Vec::new();
+
+    for variant in &data.variants {
+        let variant_name = &variant.ident;
+        let variant_attributes = &variant.attrs;
+        let fields = process_field_attributes(variant.fields.iter())?;
+
+        if let Some(f) = fields.iter().find(|f| matches!(f.tag, Some(Tag::Ordered { .. }))) {
+            return Err(Error::new(f.field.span(), "`#[order]` cannot be used inside enum variants."));
+        }
+
+        trait_assertions.push(generate_trait_assertions(&fields));
+
+        // the packed and quantized fields share arrays at the start of the
+        // variant, followed by every other field which is kept.
+        let packed_len = packed_len(&fields);
+        let quantized_len = quantized_len(&fields);
+        let bit_members: Vec<(Ident, TokenStream)> = packed_len
+            .map(|len| (format_ident!("__packed"), quote! { [u8; #len] }))
+            .into_iter()
+            .chain((quantized_len > 0).then(|| (format_ident!("__quantized"), quote! { [u8; #quantized_len] })))
+            .collect();
+        let kept_fields: Vec<&AttributedField> = fields.iter().filter(|f| matches!(f.tag, Some(Tag::Frozen) | None)).collect();
+
+        let members = bit_members.iter().cloned().chain(kept_fields.iter().map(|f| {
+            let ty = &f.field.ty;
+            let member = match f.tag {
+                Some(Tag::Frozen) => quote_spanned! {f.field.span()=> #ty },
+                _ => quote_spanned! {f.field.span()=> <#ty as ::compaq::Compress>::Compressed },
+            };
+
+            (f.name.clone(), member)
+        }));
+        let compressed_body = variant_body(&variant.fields, members);
+        compressed_variants.push(quote! { #(#variant_attributes)* #variant_name #compressed_body });
+
+        // binds every field of the variant by reference, except excluded ones.
+        let pattern = variant_body(&variant.fields, fields.iter().map(|f| {
+            let name = &f.name;
+            let binding = if matches!(f.tag, Some(Tag::Excluded)) { quote! { _ } } else { quote! { #name } };
+            (f.name.clone(), binding)
+        }));
+        let packing = generate_packing(&fields, |name| quote! { *#name });
+        let quantized_packing = generate_quantized_packing(&fields, |name| quote! { *#name });
+        let compressed_initializers = bit_members.iter().map(|(member, _)| (member.clone(), quote! { #member })).chain(kept_fields.iter().map(|f| {
+            let name = &f.name;
+            let ty = &f.field.ty;
+            let value = match f.tag {
+                Some(Tag::Frozen) => quote_spanned! {f.field.span()=> ::core::clone::Clone::clone(#name) },
+                _ => quote_spanned! {f.field.span()=> <#ty as ::compaq::Compress>::compress(#name) },
+            };
+
+            (f.name.clone(), value)
+        }));
+        let compressed_construction = variant_body(&variant.fields, compressed_initializers);
+
+        compress_arms.push(quote! {
+            Self::#variant_name #pattern => {
+                #packing
+                #quantized_packing
+                #compressed_name::#variant_name #compressed_construction
+            }
+        });
+
+        let compressed_pattern = variant_body(
+            &variant.fields,
+            bit_members.iter().map(|(member, _)| member).chain(kept_fields.iter().map(|f| &f.name)).map(|name| (name.clone(), quote! { #name })),
+        );
+        let bit_values: HashMap<Ident, TokenStream> = packed_values(&fields, &quote! { __packed })
+            .into_iter()
+            .chain(quantized_values(&fields, &quote! { __quantized }))
+            .map(|(field, value)| (field.name.clone(), value))
+            .collect();
+        let initializers = fields.iter().map(|f| {
+            let name = &f.name;
+            let ty = &f.field.ty;
+            let value = match f.tag {
+                Some(Tag::Excluded) => quote_spanned! {f.field.span()=> ::core::default::Default::default() },
+                Some(Tag::Packed) | Some(Tag::Quantized(_)) => bit_values[name].clone(),
+                Some(Tag::Frozen) => quote! { #name },
+                _ => quote_spanned! {f.field.span()=> <#ty as ::compaq::Compress>::decompress(#name) },
+            };
+
+            (f.name.clone(), value)
+        });
+        let construction = variant_body(&variant.fields, initializers);
+
+        decompress_arms.push(quote! {
+            #compressed_name::#variant_name #compressed_pattern => Self::#variant_name #construction,
+        });
+    }
+
+    let is_fieldless = data.variants.iter().all(|variant| matches!(variant.fields, Fields::Unit));
+
+    let pack_impl = is_fieldless.then(|| {
+        let bits = usize::BITS - (data.variants.len() - 1).leading_zeros();
+        let variants: Vec<&Ident> = data.variants.iter().map(|variant| &variant.ident).collect();
+        let indices: Vec<u64> = (0..variants.len() as u64).collect();
+
+        quote! {
+            #[automatically_derived]
+            impl ::compaq::Pack for #name {
+                const BITS: u32 = #bits;
+
+                fn pack(&self) -> u64 {
+                    match self {
+                        #(Self::#variants => #indices,)*
+                    }
+                }
+
+                fn unpack(bits: u64) -> Self {
+                    match bits {
+                        #(#indices => Self::#variants,)*
+                        _ => ::core::panic!("Invalid packed `{}` variant {}.", ::core::stringify!(#name), bits),
+                    }
+                }
+            }
+        }
+    });
+
+    let exact_size_impl = if exact_size {
+        if !is_fieldless || data.variants.len() > 128 {
+            return Err(Error::new(name.span(), "`exact_size` can only be used with enums of at most 128 variants without fields."));
+        }
+
+        Some(quote! {
+            #[automatically_derived]
+            impl ::compaq::ExactSize for #compressed_name {
+                const EXACT_SIZE: usize = 1;
+            }
+        })
+    } else {
+        None
+    };
+
+    Ok(quote! {
+        // the fields of variants are always public, so they can't be left
+        // undocumented.
+        #[allow(dead_code, missing_docs)]
+        #(#attributes)*
+        #vis enum #compressed_name {
+            #(#compressed_variants,)*
+        }
+
+        #[automatically_derived]
+        impl ::compaq::Compress for #name {
+            type Compressed = #compressed_name;
+
+            fn compress(&self) -> Self::Compressed {
+                match self {
+                    #(#compress_arms)*
+                }
+            }
+
+            fn decompress(val: Self::Compressed) -> Self {
+                match val {
+                    #(#decompress_arms)*
+                }
+            }
+        }
+
+        #pack_impl
+        #exact_size_impl
+        #(#trait_assertions)*
+    })
+}
+
 fn strip_compress_attribute(attrs: &[Attribute]) -> Vec<Attribute> {
     let mut attrs = attrs.to_owned();
     let Some(index) = attrs.iter().position(|val| val.path().is_ident("derive")) else {
@@ -559,11 +1079,39 @@ fn strip_compress_attribute(attrs: &[Attribute]) -> Vec<Attribute> {
 
 #[proc_macro_attribute]
 pub fn compress(attr: proc_macro::TokenStream, input: proc_macro::TokenStream) -> proc_macro::TokenStream {
-    let compressed_name = parse_macro_input!(attr as Ident);
+    let arguments = parse_macro_input!(attr with Punctuated::<Ident, Token![,]>::parse_terminated);
     let input = parse_macro_input!(input as DeriveInput);
 
-    let Data::Struct(structure) = &input.data else {
-        panic!("Compress can only work for struct types.");
+    let mut arguments = arguments.into_iter();
+    let Some(compressed_name) = arguments.next() else {
+        return Error::new(Span::call_site(), "Expected the name of the compressed type.").to_compile_error().into();
+    };
+
+    let mut exact_size = false;
+    for argument in arguments {
+        if argument == EXACT_SIZE_ARGUMENT_STRING {
+            exact_size = true;
+        } else {
+            return Error::new(argument.span(), format!("Unexpected argument `{argument}`.")).to_compile_error().into();
+        }
+    }
+
+    let structure = match &input.data {
+        Data::Struct(structure) => structure,
+        Data::Enum(data) => {
+            let generated = match generate_enum(&input, &compressed_name, data, exact_size) {
+                Ok(generated) => generated,
+                Err(e) => return e.to_compile_error().into(),
+            };
+
+            return quote! {
+                #[derive(::compaq::__SilenceErrors)]
+                #input
+                #generated
+            }
+            .into();
+        }
+        Data::Union(_) => panic!("Compress can only work for struct and enum types."),
     };
 
     let Fields::Named(fields) = &structure.fields else {
@@ -587,6 +1135,14 @@ pub fn compress(attr: proc_macro::TokenStream, input: proc_macro::TokenStream) -
     let compress_impl = generate_compress_impl(&input, &compressed_name, &fields, has_ordered_member);
     let methods = generate_methods(&input, &compressed_name, &fields);
     let ordered_struct = if has_ordered_member { generate_ordered_struct(&input, &compressed_name, &fields) } else { TokenStream::new() };
+    let exact_size_impl = if exact_size {
+        match generate_exact_size_impl(&compressed_name, &fields) {
+            Ok(exact_size_impl) => exact_size_impl,
+            Err(e) => return e.to_compile_error().into(),
+        }
+    } else {
+        TokenStream::new()
+    };
 
     let generated = quote! {
         #[derive(::compaq::__SilenceErrors)]
@@ -596,6 +1152,7 @@ pub fn compress(attr: proc_macro::TokenStream, input: proc_macro::TokenStream) -
         #compress_impl
         #methods
         #trait_assertions
+        #exact_size_impl
     };
 
     generated.into()
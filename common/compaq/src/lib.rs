@@ -1,13 +1,13 @@
 extern crate self as compaq;
 
-pub use compaq_core::{compress::Compress, CompaqError, Result, compress_identity_impl};
+pub use compaq_core::{compress::{Compress, ExactSize, Pack}, quantize, CompaqError, Result, compress_identity_impl};
 pub use compaq_derive::{compress, __SilenceErrors};
 
 #[cfg(test)]
 mod tests {
     use std::collections::HashMap;
 
-    use super::{CompaqError, Compress, compress};
+    use super::{CompaqError, Compress, ExactSize, Pack, compress};
 
     #[compress(CompressedOrderedSubset)]
     #[derive(Clone, Debug, PartialEq)]
@@ -28,6 +28,46 @@ mod tests {
         delta: bool,
     }
 
+    #[compress(CompressedFixedPoint, exact_size)]
+    #[derive(Clone, Debug, PartialEq)]
+    struct FixedPoint {
+        #[quantize(min = -90.0, max = 90.0, bits = 26)]
+        latitude: f64,
+        #[quantize(min = 0, max = 1000, bits = 14)]
+        pressure: f32,
+        #[scale(step = 0.1, bits = 12)]
+        offset: f64,
+        #[pack]
+        valid: bool,
+        count: u8,
+    }
+
+    #[compress(CompressedMode, exact_size)]
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    enum Mode {
+        Idle,
+        Armed,
+        Firing,
+    }
+
+    #[compress(CompressedEvent)]
+    #[derive(Clone, Debug, PartialEq)]
+    enum Event {
+        Reset,
+        Reading(f64, #[exclude] u32),
+        Transition {
+            #[pack]
+            from: Mode,
+            #[pack]
+            to: Mode,
+            #[pack]
+            forced: bool,
+            #[exclude]
+            note: String,
+            count: u8,
+        },
+    }
+
     #[test]
     fn ordered_policy_can_encode_subset_of_hash_map_keys() {
         let state = OrderedSubset {
@@ -81,10 +121,96 @@ mod tests {
         };
 
         let compressed = state.compress();
-        assert_eq!(compressed.__packed_bools, [0b0000_0101]);
+        assert_eq!(compressed.__packed, [0b0000_0101]);
         assert_eq!(compressed.gamma, 7);
 
         let roundtrip = PackedFlags::decompress(compressed);
         assert_eq!(roundtrip, state);
     }
+
+    #[test]
+    fn quantized_fields_pack_into_shared_bits_and_restore_within_resolution() {
+        let state = FixedPoint {
+            latitude: 32.990_321,
+            pressure: 512.34,
+            offset: -12.34,
+            valid: true,
+            count: 3,
+        };
+
+        // 26 + 14 + 12 bits take 7 bytes, next to 1 byte of packed bools and
+        // the 1 byte count.
+        assert_eq!(CompressedFixedPoint::EXACT_SIZE, 9);
+
+        let compressed = state.compress();
+        assert_eq!(compressed.__quantized.len(), 7);
+
+        let roundtrip = FixedPoint::decompress(compressed);
+        assert!((roundtrip.latitude - state.latitude).abs() <= 180.0 / f64::from((1 << 26) - 1));
+        assert!((roundtrip.pressure - state.pressure).abs() <= 1000.0 / ((1 << 14) - 1) as f32);
+        assert!((roundtrip.offset - -12.3).abs() < 1e-9);
+        assert!(roundtrip.valid);
+        assert_eq!(roundtrip.count, 3);
+    }
+
+    #[test]
+    fn quantized_fields_clamp_values_outside_their_range() {
+        let state = FixedPoint {
+            latitude: 120.0,
+            pressure: -5.0,
+            offset: 1.0e6,
+            valid: false,
+            count: 0,
+        };
+
+        let roundtrip = FixedPoint::decompress(state.compress());
+        assert_eq!(roundtrip.latitude, 90.0);
+        assert_eq!(roundtrip.pressure, 0.0);
+        assert!((roundtrip.offset - 204.7).abs() < 1e-9);
+    }
+
+    #[test]
+    fn fieldless_enums_pack_into_the_bits_their_variants_need() {
+        assert_eq!(Mode::BITS, 2);
+        assert_eq!(CompressedMode::EXACT_SIZE, 1);
+
+        for mode in [Mode::Idle, Mode::Armed, Mode::Firing] {
+            assert_eq!(Mode::unpack(mode.pack()), mode);
+            assert_eq!(Mode::decompress(mode.compress()), mode);
+        }
+    }
+
+    #[test]
+    fn enum_variants_honour_exclude_and_pack() {
+        let transition = Event::Transition {
+            from: Mode::Armed,
+            to: Mode::Firing,
+            forced: true,
+            note: "manual".to_string(),
+            count: 4,
+        };
+
+        let compressed = transition.compress();
+        let CompressedEvent::Transition { __packed, count } = compressed.clone() else {
+            panic!("variant should be preserved");
+        };
+
+        // two bits for each mode, followed by the bool.
+        assert_eq!(__packed, [0b1_10_01]);
+        assert_eq!(count, 4);
+
+        assert_eq!(
+            Event::decompress(compressed),
+            Event::Transition {
+                from: Mode::Armed,
+                to: Mode::Firing,
+                forced: true,
+                note: String::new(),
+                count: 4,
+            }
+        );
+
+        assert_eq!(Event::decompress(Event::Reading(2.5, 7).compress()), Event::Reading(2.5, 0));
+        assert_eq!(Event::decompress(Event::Reset.compress()), Event::Reset);
+    }
 }
@@ -1,5 +1,5 @@
 use bytecheck;
-use compaq::{Compress, compress};
+use compaq::compress;
 use core::fmt::Debug;
 use postcard::experimental::max_size::MaxSize;
 use rkyv;
@@ -74,6 +74,10 @@ impl fmt::Display for sam::Unit {
 /// without reconstructing the variant. This is annoying. Essentially, this
 /// looks like bad / less readable code but is necessary, and convenience
 /// constructs are provided to make code cleaner.
+///
+/// Only the value is sent over radio, so a decompressed measurement carries no
+/// source or freshness information of its own.
+#[compress(CompressedMeasurement)]
 #[derive(
   Clone,
   Debug,
@@ -91,22 +95,28 @@ pub struct Measurement {
   pub value: f64,
 
   /// The unit associated with the measurement.
+  #[exclude]
   pub unit: sam::Unit,
-}
 
-impl Compress for Measurement {
-  type Compressed = <f64 as Compress>::Compressed;
+  /// The UNIX timestamp, in seconds, at which the value was sampled, or zero
+  /// if the sensor has never reported.
+  #[serde(default)]
+  #[exclude]
+  pub timestamp: f64,
 
-  fn compress(&self) -> Self::Compressed {
-    self.value.compress()
-  }
+  /// The board which reported the value. `None` for sensors that have never
+  /// reported and for virtual sensors, which are computed on the flight
+  /// computer.
+  #[serde(default)]
+  #[exclude]
+  pub board_id: Option<String>,
 
-  fn decompress(val: Self::Compressed) -> Self {
-    Self {
-      value: f64::decompress(val),
-      unit: sam::Unit::Unknown,
-    }
-  }
+  /// Whether the value is out of date, either because the board reporting it
+  /// has stopped communicating, a sensor it is computed from is stale, or it
+  /// has never been reported at all.
+  #[serde(default)]
+  #[exclude]
+  pub stale: bool,
 }
 
 impl fmt::Display for Measurement {
@@ -139,10 +149,15 @@ pub struct Statistics {
   pub time_since_last_update: f64,
 }
 
+/// Counts how the flight computer's acknowledged commands to a single board
+/// have fared. Only commands that require acknowledgement are counted.
 #[derive(
   Clone,
+  Copy,
   Debug,
+  Default,
   Deserialize,
+  Eq,
   PartialEq,
   Serialize,
   rkyv::Archive,
@@ -150,31 +165,32 @@ pub struct Statistics {
   rkyv::Deserialize,
 )]
 #[archive_attr(derive(bytecheck::CheckBytes))]
+pub struct CommandDelivery {
+  /// Commands sent for the first time.
+  pub sent: u32,
+
+  /// Commands the board acknowledged.
+  pub acknowledged: u32,
+
+  /// Retransmissions of commands that weren't acknowledged in time.
+  pub retransmitted: u32,
+
+  /// Commands given up on after running out of retries.
+  pub failed: u32,
+
+  /// Commands dropped before being acknowledged because a newer command
+  /// replaced them, such as an abort replacing a valve actuation.
+  pub superseded: u32,
+
+  /// Commands currently awaiting acknowledgement.
+  pub pending: u32,
+}
+
 /// GPS state as seen by the flight computer.
 ///
 /// This is intentionally independent of any particular GPS driver so that it's
 /// stable for serialization and logging.
-pub struct GpsState {
-  /// Latitude in degrees (WGS84), positive north.
-  pub latitude_deg: f64,
-  /// Longitude in degrees (WGS84), positive east.
-  pub longitude_deg: f64,
-  /// Ellipsoidal altitude above mean sea level, in meters.
-  pub altitude_m: f64,
-  /// North component of velocity (m/s) in NED frame.
-  pub north_mps: f64,
-  /// East component of velocity (m/s) in NED frame.
-  pub east_mps: f64,
-  /// Down component of velocity (m/s) in NED frame.
-  pub down_mps: f64,
-  /// Unix timestamp in milliseconds for this fix, if available.
-  pub timestamp_unix_ms: Option<i64>,
-  /// Whether this sample corresponds to a valid GNSS fix.
-  pub has_fix: bool,
-  /// Number of satellites used in the fix
-  pub num_satellites: u8,
-}
-
+#[compress(CompressedGpsState)]
 #[derive(
   Clone,
   Debug,
@@ -186,68 +202,37 @@ pub struct GpsState {
   rkyv::Deserialize,
 )]
 #[archive_attr(derive(bytecheck::CheckBytes))]
-/// GPS state as seen by the flight computer.
-///
-/// This is intentionally independent of any particular GPS driver so that it's
-/// stable for serialization and logging.
-pub struct CompressedGpsState {
+pub struct GpsState {
   /// Latitude in degrees (WGS84), positive north.
-  pub latitude_deg: f32,
+  // both coordinates resolve to about 0.3 m.
+  #[quantize(min = -90.0, max = 90.0, bits = 26)]
+  pub latitude_deg: f64,
   /// Longitude in degrees (WGS84), positive east.
-  pub longitude_deg: f32,
+  #[quantize(min = -180.0, max = 180.0, bits = 27)]
+  pub longitude_deg: f64,
   /// Ellipsoidal altitude above mean sea level, in meters.
-  pub altitude_m: f32,
+  // 0.1 m of resolution up to about 200 km in either direction.
+  #[scale(step = 0.1, bits = 22)]
+  pub altitude_m: f64,
   /// North component of velocity (m/s) in NED frame.
-  pub north_mps: <f64 as Compress>::Compressed,
+  pub north_mps: f64,
   /// East component of velocity (m/s) in NED frame.
-  pub east_mps: <f64 as Compress>::Compressed,
+  pub east_mps: f64,
   /// Down component of velocity (m/s) in NED frame.
-  pub down_mps: <f64 as Compress>::Compressed,
+  pub down_mps: f64,
   /// Unix timestamp in milliseconds for this fix, if available.
-  pub timestamp_unix_ms: <Option<i64> as Compress>::Compressed,
+  pub timestamp_unix_ms: Option<i64>,
   /// Whether this sample corresponds to a valid GNSS fix.
-  pub has_fix: <bool as Compress>::Compressed,
+  pub has_fix: bool,
   /// Number of satellites used in the fix
-  pub num_satellites: <u8 as Compress>::Compressed,
-}
-
-impl Compress for GpsState {
-  type Compressed = CompressedGpsState;
-
-  fn compress(&self) -> Self::Compressed {
-      Self::Compressed {
-        latitude_deg: self.latitude_deg as f32,
-        longitude_deg: self.longitude_deg as f32,
-        altitude_m: self.altitude_m as f32,
-        down_mps: self.down_mps.compress(),
-        east_mps: self.east_mps.compress(),
-        has_fix: self.has_fix.compress(),
-        north_mps: self.north_mps.compress(),
-        num_satellites: self.num_satellites.compress(),
-        timestamp_unix_ms: self.timestamp_unix_ms.compress()
-      }
-  }
-
-  fn decompress(val: Self::Compressed) -> Self {
-      Self {
-        latitude_deg: val.latitude_deg as f64,
-        longitude_deg: val.longitude_deg as f64,
-        altitude_m: val.altitude_m as f64,
-        down_mps: Compress::decompress(val.down_mps),
-        east_mps: Compress::decompress(val.east_mps),
-        has_fix: Compress::decompress(val.has_fix),
-        north_mps: Compress::decompress(val.north_mps),
-        num_satellites: Compress::decompress(val.num_satellites),
-        timestamp_unix_ms: Compress::decompress(val.timestamp_unix_ms),
-      }
-  }
+  pub num_satellites: u8,
 }
 
 /// RECO state as seen by the flight computer.
 ///
 /// This is intentionally independent of any particular RECO driver so that it's
 /// stable for serialization and logging.
-#[compress(CompressedRecoState)]
+#[compress(CompressedRecoState, exact_size)]
 #[derive(
   Clone,
   Debug,
@@ -413,8 +398,8 @@ pub struct AbortStage {
   /// Name of the abort stage
   pub name: String,
 
-  /// Condition that, if met, we abort.
-  /// Can use the eval() in python to run strings as code
+  /// Condition that, if met, we abort. Evaluated natively by the flight
+  /// computer every loop, and may hold durations such as `PT1 > 500 for 200ms`.
   pub abort_condition: String,
 
   /// Whether we have aborted in this stage yet
@@ -480,6 +465,69 @@ impl FromSql for Computer {
   }
 }
 
+/// A curve converting the value a board reports for a sensor into the value
+/// of the quantity it measures.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Calibration {
+  /// A polynomial given by its coefficients in order of increasing power, such
+  /// that `[c0, c1, c2]` is `c0 + c1 * x + c2 * x^2`.
+  Polynomial(Vec<f64>),
+
+  /// Straight lines between at least two `(raw, calibrated)` points sorted by
+  /// strictly increasing raw value, extended past either end by the line
+  /// through the two points there.
+  PiecewiseLinear(Vec<(f64, f64)>),
+}
+
+impl Calibration {
+  /// Converts a raw value into a calibrated one.
+  pub fn apply(&self, raw: f64) -> f64 {
+    match self {
+      Self::Polynomial(coefficients) => coefficients
+        .iter()
+        .rev()
+        .fold(0.0, |value, coefficient| value * raw + coefficient),
+      Self::PiecewiseLinear(points) => {
+        let segment = match points.len() {
+          0 => return raw,
+          1 => return points[0].1,
+          n => points
+            .windows(2)
+            .position(|pair| raw < pair[1].0)
+            .unwrap_or(n - 2),
+        };
+
+        let (x0, y0) = points[segment];
+        let (x1, y1) = points[segment + 1];
+        y0 + (raw - x0) * (y1 - y0) / (x1 - x0)
+      }
+    }
+  }
+}
+
+#[cfg(feature = "rusqlite")]
+impl ToSql for Calibration {
+  fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+    let json = serde_json::to_string(&self)
+      .map_err(|error| rusqlite::Error::ToSqlConversionFailure(Box::new(error)))?;
+
+    Ok(ToSqlOutput::Owned(rusqlite::types::Value::Text(json)))
+  }
+}
+
+#[cfg(feature = "rusqlite")]
+impl FromSql for Calibration {
+  fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+    if let ValueRef::Text(text) = value {
+      serde_json::from_slice(text)
+        .map_err(|error| FromSqlError::Other(Box::new(error)))
+    } else {
+      Err(FromSqlError::InvalidType)
+    }
+  }
+}
+
 /// The mapping of an individual node.
 #[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
 pub struct NodeMapping {
@@ -527,20 +575,114 @@ pub struct NodeMapping {
 
   /// Indicator of whether the valve is normally open or normally closed.
   pub normally_closed: Option<bool>,
+
+  /// How long, in seconds, the valve's actual state may disagree with its
+  /// commanded state before the valve is considered faulted. Faults are not
+  /// detected for valves without a timeout.
+  pub fault_timeout: Option<f64>,
+
+  /// Whether the flight computer aborts through the current abort stage when
+  /// the valve faults.
+  pub abort_on_fault: Option<bool>,
+
+  /// The reading below which the sensor raises a warning alarm.
+  pub warning_low: Option<f64>,
+
+  /// The reading above which the sensor raises a warning alarm.
+  pub warning_high: Option<f64>,
+
+  /// The reading below which the sensor raises a critical alarm.
+  pub critical_low: Option<f64>,
+
+  /// The reading above which the sensor raises a critical alarm.
+  pub critical_high: Option<f64>,
+
+  /// Whether the flight computer aborts through the current abort stage when
+  /// the sensor raises a critical alarm.
+  pub abort_on_critical: Option<bool>,
+
+  /// How long, in seconds, the sensor must stay beyond a critical redline
+  /// before the flight computer aborts on it, so that a single bad sample
+  /// can't abort the vehicle. Flight uses a short default when unset.
+  pub critical_persistence: Option<f64>,
+
+  /// The curve converting the value reported by the board into the reading
+  /// of the sensor, replacing the default conversion of its sensor type. The
+  /// calibrated offset is still subtracted afterwards.
+  pub calibration: Option<Calibration>,
+
+  /// An expression over other sensors, such as `PT_FUEL - PT_INJ`, which makes
+  /// this a virtual sensor computed by the flight computer instead of one read
+  /// from a board. The board ID and channel of a virtual sensor are only used
+  /// to decide whether it is sent over radio, and its sensor type only sets
+  /// the unit of its readings.
+  pub expression: Option<String>,
+
+  /// Whether the node is included in the subset of telemetry sent over the
+  /// TEL radio. Every node is always included in umbilical telemetry.
+  #[serde(default)]
+  pub radio: bool,
+
+  /// How important the node is to radio telemetry, with higher values more
+  /// important. Nodes with the lowest priority are the first to be cut when
+  /// the radio subset doesn't fit in a frame.
+  #[serde(default)]
+  pub radio_priority: u8,
 }
 
-/// Returns whether a node mapping should be included in TEL radio telemetry.
-///
-/// Only SAM boards whose hostnames begin with `sam-2` or `sam-3 are mounted on
-/// the vehicle. Other SAMs remain part of the full umbilical state but are
-/// omitted from the radio subset. Additionally, if the flight computer PT channel
-/// is mapped it should be included in the radio subset.
-pub fn include_in_radio_telemetry(mapping: &NodeMapping) -> bool {
-  mapping.board_id == "flight"
-  || mapping.board_id.starts_with("sam-2")
-  || mapping.board_id.starts_with("sam-3")
+impl NodeMapping {
+  /// Returns whether this is a virtual sensor, computed from an expression
+  /// over other sensors rather than read from a board.
+  pub fn is_virtual(&self) -> bool {
+    self.expression.is_some()
+  }
 }
 
+/// An unnamed flight computer PT on channel 0 with every optional parameter
+/// unset, so that a mapping only needs to spell out what sets it apart.
+impl Default for NodeMapping {
+  fn default() -> Self {
+    NodeMapping {
+      text_id: String::new(),
+      board_id: String::new(),
+      sensor_type: SensorType::Pt,
+      channel: 0,
+      computer: Computer::Flight,
+      max: None,
+      min: None,
+      calibrated_offset: 0.0,
+      powered_threshold: None,
+      normally_closed: None,
+      fault_timeout: None,
+      abort_on_fault: None,
+      warning_low: None,
+      warning_high: None,
+      critical_low: None,
+      critical_high: None,
+      abort_on_critical: None,
+      critical_persistence: None,
+      calibration: None,
+      expression: None,
+      radio: false,
+      radio_priority: 0,
+    }
+  }
+}
+
+/// Total MTU enforced by the TEL radio link, counting the IP packet as a
+/// whole. Radio telemetry must fit within this bound because the TEL path does
+/// not fragment frames for us.
+pub const RADIO_MTU: usize = 255;
+
+/// IPv4 plus UDP header overhead for radio telemetry packets. This leaves the
+/// remainder of the TEL MTU available for the compressed `VehicleState`
+/// payload.
+pub const RADIO_IPV4_UDP_OVERHEAD: usize = 20 + 8;
+
+/// Maximum compressed radio telemetry payload that can be serialized into one
+/// TEL UDP datagram without exceeding the radio MTU.
+pub const RADIO_PAYLOAD_MTU: usize = RADIO_MTU - RADIO_IPV4_UDP_OVERHEAD;
+
 /// A sequence written in Python, used by the flight computer to execute
 /// arbitrary operator code.
 #[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
@@ -554,6 +696,263 @@ pub struct Sequence {
 
   /// The script run immediately (except abort) upon being received.
   pub script: String,
+
+  /// The values of the parameters declared by the sequence, each bound to the
+  /// name of its parameter before the script runs.
+  #[serde(default)]
+  pub arguments: HashMap<String, SequenceArgument>,
+}
+
+/// The kind of value a sequence parameter takes, deciding how a value given
+/// for it is checked and what it's bound as in the sequence.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SequenceParameterKind {
+  /// A plain number without a unit.
+  Number,
+
+  /// A current, in amps.
+  Current,
+
+  /// A non-negative duration, in seconds.
+  Duration,
+
+  /// An electric potential, in volts.
+  ElectricPotential,
+
+  /// A force, in pounds-force.
+  Force,
+
+  /// A pressure, in psi.
+  Pressure,
+
+  /// A non-negative temperature, in kelvin.
+  Temperature,
+
+  /// The text ID of a valve mapping.
+  Valve,
+}
+
+impl SequenceParameterKind {
+  /// Checks a value given for a parameter of this kind, returning the
+  /// argument it's passed to the sequence as or why it's invalid.
+  ///
+  /// Valve names are only checked to be non-empty, as whether a valve is
+  /// mapped depends on the active configuration.
+  pub fn argument(
+    self,
+    value: &SequenceValue,
+  ) -> Result<SequenceArgument, String> {
+    let argument: fn(f64) -> SequenceArgument = match self {
+      Self::Number => SequenceArgument::Number,
+      Self::Current => SequenceArgument::Current,
+      Self::Duration => SequenceArgument::Duration,
+      Self::ElectricPotential => SequenceArgument::ElectricPotential,
+      Self::Force => SequenceArgument::Force,
+      Self::Pressure => SequenceArgument::Pressure,
+      Self::Temperature => SequenceArgument::Temperature,
+      Self::Valve => {
+        return match value {
+          SequenceValue::Text(name) if !name.is_empty() => {
+            Ok(SequenceArgument::Valve(name.clone()))
+          }
+          _ => Err("expected the name of a valve".to_owned()),
+        };
+      }
+    };
+
+    let &SequenceValue::Number(number) = value else {
+      return Err("expected a number".to_owned());
+    };
+
+    if !number.is_finite() {
+      return Err("expected a finite number".to_owned());
+    }
+
+    if matches!(self, Self::Duration | Self::Temperature) && number < 0.0 {
+      return Err("expected a non-negative number".to_owned());
+    }
+
+    Ok(argument(number))
+  }
+}
+
+/// A value given for a sequence parameter, as entered by an operator and
+/// before it's checked against the kind of the parameter.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(untagged)]
+pub enum SequenceValue {
+  /// A number, in the unit of the parameter's kind.
+  Number(f64),
+
+  /// Text, such as the name of a valve.
+  Text(String),
+}
+
+/// A typed parameter declared by a stored sequence, whose value is supplied
+/// each time the sequence is run.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct SequenceParameter {
+  /// The name the value is bound to in the sequence, which must be a valid
+  /// Python identifier.
+  pub name: String,
+
+  /// The kind of value the parameter takes.
+  pub kind: SequenceParameterKind,
+
+  /// The value used when none is given at run time. Parameters without a
+  /// default must always be given a value.
+  #[serde(default)]
+  pub default: Option<SequenceValue>,
+}
+
+/// The checked value of a sequence parameter, bound in the sequence as the
+/// unit type of its kind, a plain float, or a `Valve`.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub enum SequenceArgument {
+  /// A plain number without a unit.
+  Number(f64),
+
+  /// A current, in amps.
+  Current(f64),
+
+  /// A duration, in seconds.
+  Duration(f64),
+
+  /// An electric potential, in volts.
+  ElectricPotential(f64),
+
+  /// A force, in pounds-force.
+  Force(f64),
+
+  /// A pressure, in psi.
+  Pressure(f64),
+
+  /// A temperature, in kelvin.
+  Temperature(f64),
+
+  /// The text ID of a valve mapping.
+  Valve(String),
+}
+
+/// The stream of a sequence process that a line of output was written to.
+#[derive(
+  Clone,
+  Copy,
+  Debug,
+  Deserialize,
+  Eq,
+  PartialEq,
+  Serialize,
+  rkyv::Archive,
+  rkyv::Serialize,
+  rkyv::Deserialize,
+)]
+#[archive_attr(derive(bytecheck::CheckBytes))]
+#[serde(rename_all = "snake_case")]
+pub enum SequenceOutputStream {
+  /// Standard output, where `print` writes.
+  Stdout,
+
+  /// Standard error, where uncaught Python exceptions are written.
+  Stderr,
+}
+
+/// Where a sequence process is in its lifecycle.
+#[derive(
+  Clone,
+  Copy,
+  Debug,
+  Deserialize,
+  Eq,
+  PartialEq,
+  Serialize,
+  rkyv::Archive,
+  rkyv::Serialize,
+  rkyv::Deserialize,
+)]
+#[archive_attr(derive(bytecheck::CheckBytes))]
+#[serde(rename_all = "snake_case")]
+pub enum SequenceStatus {
+  /// The process is still running.
+  Running,
+
+  /// The process is still running but paused by an operator, so it holds at
+  /// the wait it's in or reaches next without using up the wait's time.
+  Paused {
+    /// How many times the run has been stepped. Each step releases the wait
+    /// the sequence is held at, after which it holds at the next one.
+    step: u32,
+  },
+
+  /// The process exited on its own with the given exit code. Python exits
+  /// with a code of 1 when a sequence raises an uncaught exception.
+  Exited {
+    /// The exit code of the process.
+    code: i32,
+  },
+
+  /// The process was terminated by a signal, such as when it is stopped by
+  /// an operator or killed during an abort.
+  Killed {
+    /// The number of the signal which terminated the process.
+    signal: i32,
+  },
+}
+
+/// A single run of a sequence on the flight computer.
+#[derive(
+  Clone,
+  Debug,
+  Deserialize,
+  PartialEq,
+  Serialize,
+  rkyv::Archive,
+  rkyv::Serialize,
+  rkyv::Deserialize,
+)]
+#[archive_attr(derive(bytecheck::CheckBytes))]
+pub struct SequenceRun {
+  /// The name of the sequence that was run.
+  pub name: String,
+
+  /// The process ID of the Python interpreter running the sequence, which
+  /// identifies the run while it is in progress.
+  pub pid: u32,
+
+  /// Unix timestamp, in seconds, of when the process was started.
+  pub started_at: f64,
+
+  /// Unix timestamp, in seconds, of when the process was found to have
+  /// exited, if it has.
+  pub finished_at: Option<f64>,
+
+  /// The current status of the run.
+  pub status: SequenceStatus,
+}
+
+/// The sequence which last actuated a valve, kept for review after a test.
+#[derive(
+  Clone,
+  Debug,
+  Deserialize,
+  PartialEq,
+  Serialize,
+  rkyv::Archive,
+  rkyv::Serialize,
+  rkyv::Deserialize,
+)]
+#[archive_attr(derive(bytecheck::CheckBytes))]
+pub struct ValveCommander {
+  /// The name of the sequence, or `None` if the valve was actuated by a
+  /// process the flight computer didn't start as a sequence.
+  pub sequence: Option<String>,
+
+  /// The process ID of the Python interpreter which actuated the valve.
+  pub pid: u32,
+
+  /// Unix timestamp, in seconds, of when the valve was actuated.
+  pub commanded_at: f64,
 }
 
 /// A trigger with a
@@ -562,16 +961,49 @@ pub struct Trigger {
   /// The unique, human-readable name which identifies the trigger.
   pub name: String,
 
-  /// The condition upon which the trigger script is run, written in Python.
+  /// The condition upon which the trigger script is run, written as a Python
+  /// expression. Flight evaluates it natively, so only comparisons,
+  /// arithmetic, boolean logic, and valve and sensor names are available.
   pub condition: String,
 
-  /// The script run when the condition is met, written in Python.
+  /// The script run when the condition becomes met, written in Python.
   pub script: String,
 
   /// Whether or not the trigger is active
   pub active: bool,
 }
 
+/// The flight computer's view of a single registered trigger, reported back in
+/// telemetry so operators can see which triggers have fired and when.
+#[derive(
+  Clone,
+  Debug,
+  Default,
+  Deserialize,
+  PartialEq,
+  Serialize,
+  rkyv::Archive,
+  rkyv::Serialize,
+  rkyv::Deserialize,
+)]
+#[archive_attr(derive(bytecheck::CheckBytes))]
+pub struct TriggerState {
+  /// Whether the trigger is currently being evaluated.
+  pub active: bool,
+
+  /// The result of the most recent evaluation of the trigger's condition.
+  pub condition_met: bool,
+
+  /// How many times the trigger has fired since it was registered.
+  pub fire_count: u32,
+
+  /// Unix timestamp, in seconds, of the most recent firing, if any.
+  pub last_fired: Option<f64>,
+
+  /// Why the condition could not be parsed or evaluated, if it couldn't.
+  pub error: Option<String>,
+}
+
 /// A message sent from the control server to the flight computer.
 #[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
 pub enum FlightControlMessage {
@@ -613,6 +1045,71 @@ pub enum FlightControlMessage {
   /// Sets the current abort stage to an abort stage that has been created
   SetAbortStage(String),
 
+  /// Instructs the flight computer to forget the trigger named with the
+  /// `String` parameter.
+  DeleteTrigger(String),
+
+  /// Instructs the flight computer to pause the sequence named with the
+  /// `String` parameter, holding it at its current or next wait.
+  PauseSequence(String),
+
+  /// Instructs the flight computer to resume the paused sequence named with
+  /// the `String` parameter.
+  ResumeSequence(String),
+
+  /// Instructs the flight computer to let the paused sequence named with the
+  /// `String` parameter past the wait it's held at, pausing it again at the
+  /// next one.
+  StepSequence(String),
+}
+
+/// A `FlightControlMessage` as sent over the wire, tagged with an ID so that
+/// the flight computer's `Ack` or `Nack` can be matched to it.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct FlightControlRequest {
+  /// Identifies the request among those sent over the same connection.
+  pub id: u32,
+
+  /// The message itself.
+  pub message: FlightControlMessage,
+}
+
+/// A message sent from the flight computer to the control server, over the
+/// same TCP stream that carries `FlightControlRequest`s in the other direction.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub enum FlightStatusMessage {
+  /// The request with the given ID was carried out.
+  Ack {
+    /// The ID of the `FlightControlRequest` being acknowledged.
+    id: u32,
+  },
+
+  /// The request with the given ID was rejected or failed.
+  Nack {
+    /// The ID of the `FlightControlRequest` being rejected.
+    id: u32,
+
+    /// Why the request was rejected, to be shown to the operator.
+    reason: String,
+  },
+
+  /// A sequence process has been started.
+  SequenceStarted(SequenceRun),
+
+  /// A sequence process wrote a line of output.
+  SequenceOutput {
+    /// The process ID of the run which wrote the line.
+    pid: u32,
+
+    /// The stream the line was written to.
+    stream: SequenceOutputStream,
+
+    /// The line itself, without its trailing newline.
+    line: String,
+  },
+
+  /// A sequence process has exited and all of its output has been sent.
+  SequenceFinished(SequenceRun),
 }
 
 /// An input config from a user
@@ -621,7 +1118,9 @@ pub struct AbortStageConfig {
   /// The unique, human-readable name which identifies the AbortStage.
   pub stage_name: String,
 
-  /// The condition upon which the trigger script is run, written in Python.
+  /// The condition upon which the vehicle aborts while in this stage. It is
+  /// validated against the active mappings when received, so a stage whose
+  /// condition names an unknown valve or sensor is rejected.
   pub abort_condition: String,
 
   /// Desired safe states of valves that we want
@@ -1059,3 +1558,41 @@ pub trait ADCFamily: Any {
   /// Downcasts the ADC to a mutable dynamic any.
   fn as_any_mut(&mut self) -> &mut dyn Any;
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn polynomial_calibration_uses_increasing_powers() {
+    let calibration = Calibration::Polynomial(vec![1.0, 2.0, 3.0]);
+
+    assert_eq!(calibration.apply(0.0), 1.0);
+    assert_eq!(calibration.apply(2.0), 17.0);
+    assert_eq!(calibration.apply(-1.0), 2.0);
+  }
+
+  #[test]
+  fn piecewise_linear_calibration_interpolates_between_points() {
+    let calibration =
+      Calibration::PiecewiseLinear(vec![(0.0, 0.0), (1.0, 10.0), (2.0, 40.0)]);
+
+    assert_eq!(calibration.apply(0.0), 0.0);
+    assert_eq!(calibration.apply(0.5), 5.0);
+    assert_eq!(calibration.apply(1.0), 10.0);
+    assert_eq!(calibration.apply(1.5), 25.0);
+    assert_eq!(calibration.apply(2.0), 40.0);
+  }
+
+  #[test]
+  fn piecewise_linear_calibration_extrapolates_past_either_end() {
+    let calibration =
+      Calibration::PiecewiseLinear(vec![(0.0, 0.0), (1.0, 10.0), (2.0, 40.0)]);
+
+    // below the table, along the line through the first two points.
+    assert_eq!(calibration.apply(-1.0), -10.0);
+
+    // above the table, along the line through the last two points.
+    assert_eq!(calibration.apply(3.0), 70.0);
+  }
+}
@@ -8,7 +8,7 @@ type Current = f64;
 type Voltage = f64;
 
 /// Describes the state of some power bus
-#[compress(CompressedBus)]
+#[compress(CompressedBus, exact_size)]
 #[derive(
   Clone,
   Copy,
@@ -34,7 +34,7 @@ pub struct Bus {
 pub type Rail = Bus;
 
 /// Represents the state of BMS as a whole
-#[compress(CompressedBms)]
+#[compress(CompressedBms, exact_size)]
 #[derive(
   Clone,
   Copy,
@@ -7,7 +7,7 @@ type Celsius = f64;
 type Pascals = f64;
 
 /// Represents a vector
-#[compress(CompressedImu)]
+#[compress(CompressedImu, exact_size)]
 #[derive(
   Deserialize,
   Serialize,
@@ -41,7 +41,7 @@ type Gyroscope = Vector;
 type Magnetometer = Vector;
 
 /// Represents the state of the IMU
-#[compress(CompressedBarometer)]
+#[compress(CompressedBarometer, exact_size)]
 #[derive(
   Deserialize,
   Serialize,
@@ -64,7 +64,7 @@ pub struct Imu {
 }
 
 /// Represents the state of the Barometer
-#[compress(CompressedVector)]
+#[compress(CompressedVector, exact_size)]
 #[derive(
   Deserialize,
   Serialize,
@@ -87,7 +87,7 @@ pub struct Barometer {
 }
 
 /// Represents ADC data sampled on the flight computer.
-#[compress(CompressedAdcData)]
+#[compress(CompressedAdcData, exact_size)]
 #[derive(
   Deserialize,
   Serialize,
@@ -112,7 +112,7 @@ pub struct AdcData {
 }
 
 /// Represents the state of the flight computer's onboard sensors.
-#[compress(CompressedFcSensors)]
+#[compress(CompressedFcSensors, exact_size)]
 #[derive(
   Clone,
   Copy,
@@ -4,7 +4,7 @@ use super::{
   reco
 };
 use serde::{Deserialize, Serialize};
-use std::{borrow::Cow, collections::HashMap};
+use std::{borrow::Cow, collections::HashMap, fmt};
 
 /// String that represents the ID of a data board
 pub type BoardId = String;
@@ -26,6 +26,10 @@ pub enum DataMessage<'a> {
 
   /// Data originating from the BMS.
   Bms(BoardId, Cow<'a, bms::DataPoint>),
+
+  /// Sent by a SAM board for every `SamControlRequest` it receives, carrying
+  /// the sequence number of that request.
+  SamAck(BoardId, u32),
 }
 
 /// Defines how some data coming into the flight computer should be processed
@@ -66,8 +70,8 @@ pub enum SequenceDomainCommand {
   CreateAbortStage {
     /// Name of the abort stage
     stage_name: String,
-    /// Condition that, if met, we abort.
-    /// Can use the eval() in python to run strings as code
+    /// Condition that, if met, we abort, in the same syntax as
+    /// `AbortStage::abort_condition`.
     abort_condition: String, 
     /// Desired states of valves that we want to go to in an abort during this stage
     valve_safe_states: HashMap<String, ValveSafeState>,
@@ -124,4 +128,87 @@ pub enum SequenceDomainCommand {
     /// Whether to enable the camera
     should_enable: bool,
   },
+
+  /// Claims valves for the sending sequence, so that no other sequence may
+  /// actuate them until they are released or the sequence exits. None of the
+  /// valves are claimed if another sequence already claims one of them.
+  ClaimValves {
+    /// The names of the valves to claim.
+    valves: Vec<String>,
+  },
+
+  /// Releases valves claimed by the sending sequence.
+  ReleaseValves {
+    /// The names of the valves to release.
+    valves: Vec<String>,
+  },
+}
+
+/// A `SequenceDomainCommand` as sent over the sequence command socket, tagged
+/// with an ID so that the flight computer's `SequenceDomainResponse` can be
+/// matched to it.
+#[derive(Serialize, Deserialize)]
+pub struct SequenceDomainRequest {
+  /// Identifies the request among those sent by the same sequence process.
+  /// This comes first so that it can be read even if the command can't be.
+  pub id: u32,
+
+  /// The command itself.
+  pub command: SequenceDomainCommand,
+}
+
+/// Sent by the flight computer to the sequence process which sent a
+/// `SequenceDomainRequest` once it has been handled.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct SequenceDomainResponse {
+  /// The ID of the `SequenceDomainRequest` being responded to.
+  pub id: u32,
+
+  /// Whether the command was carried out, or why it wasn't.
+  pub result: Result<(), SequenceCommandError>,
+}
+
+/// Why the flight computer didn't carry out a command from a sequence.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub enum SequenceCommandError {
+  /// No valve is mapped with the given name.
+  ValveNotFound(String),
+
+  /// No abort stage has been created with the given name.
+  AbortStageNotFound(String),
+
+  /// No SAM board is connected with the given hostname.
+  BoardNotFound(String),
+
+  /// The valve is claimed by another sequence.
+  ValveClaimed {
+    /// The name of the valve.
+    valve: String,
+
+    /// The name of the sequence which claims it.
+    sequence: String,
+  },
+
+  /// The command was rejected or failed for the given reason.
+  Rejected(String),
+}
+
+impl fmt::Display for SequenceCommandError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::ValveNotFound(valve) => {
+        write!(f, "Valve '{valve}' is not defined in the mappings.")
+      }
+      Self::AbortStageNotFound(stage) => {
+        write!(f, "Abort stage '{stage}' has not been created.")
+      }
+      Self::BoardNotFound(board) => {
+        write!(f, "SAM board '{board}' is not connected.")
+      }
+      Self::ValveClaimed { valve, sequence } => {
+        write!(f, "Valve '{valve}' is claimed by the '{sequence}' sequence.")
+      }
+      Self::Rejected(reason) => write!(f, "{reason}"),
+    }
+  }
 }
@@ -1,6 +1,6 @@
 use super::sam::ChannelType;
 use crate::{ToPrettyString, comm::sam::Unit};
-use compaq::{Compress, compress_identity_impl};
+use compaq::compress;
 use postcard::experimental::max_size::MaxSize;
 use serde::{Deserialize, Serialize};
 use std::{fmt, str::FromStr};
@@ -21,6 +21,7 @@ use rusqlite::{
 };
 
 /// The state or commanded state of a valve.
+#[compress(CompressedValveState)]
 #[derive(
   Clone, Copy, Debug, Deserialize, Eq, Hash, MaxSize, PartialEq, Serialize, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize
 )]
@@ -44,8 +45,6 @@ pub enum ValveState {
   /// Fault in valve.
   Fault,
 }
-compress_identity_impl!(ValveState);
-
 
 impl fmt::Display for ValveState {
   fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -89,53 +88,85 @@ impl rusqlite::ToSql for ValveState {
 
 /// Stores the estimated actual valve state as well as the software-commanded
 /// state.
+#[compress(CompressedCompositeValveState)]
 #[derive(
   Clone, Debug, Deserialize, Eq, Hash, MaxSize, PartialEq, Serialize, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize
 )]
 #[archive_attr(derive(bytecheck::CheckBytes))]
 pub struct CompositeValveState {
   /// Commanded state of the valve, according to software.
+  #[pack]
   pub commanded: ValveState,
 
   /// Actual state of the valve, determined using voltage and current
   /// measurements.
+  #[pack]
   pub actual: ValveState,
 }
 
-impl Compress for CompositeValveState {
-  type Compressed = u8;
-
-  fn compress(&self) -> Self::Compressed {
-    fn encode(state: ValveState) -> u8 {
-      match state {
-        ValveState::Undetermined => 0,
-        ValveState::Disconnected => 1,
-        ValveState::Open => 2,
-        ValveState::Closed => 3,
-        ValveState::Fault => 4,
-      }
-    }
+/// How long a valve's actual state has disagreed with its commanded state.
+#[derive(
+  Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize
+)]
+#[archive_attr(derive(bytecheck::CheckBytes))]
+pub struct ValveMismatch {
+  /// Seconds since the actual state of the valve stopped matching its
+  /// commanded state.
+  pub duration: f64,
 
-    encode(self.commanded) | (encode(self.actual) << 3)
-  }
+  /// Whether the mismatch has outlasted the fault timeout of the valve.
+  pub faulted: bool,
+}
 
-  fn decompress(val: Self::Compressed) -> Self {
-    fn decode(value: u8) -> ValveState {
-      match value {
-        0 => ValveState::Undetermined,
-        1 => ValveState::Disconnected,
-        2 => ValveState::Open,
-        3 => ValveState::Closed,
-        4 => ValveState::Fault,
-        _ => panic!("invalid packed CompositeValveState discriminant"),
-      }
-    }
+/// How severe a redline alarm is.
+#[derive(
+  Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize
+)]
+#[serde(rename_all = "snake_case")]
+#[archive_attr(derive(bytecheck::CheckBytes))]
+pub enum AlarmSeverity {
+  /// A warning limit was exceeded.
+  Warning,
 
-    Self {
-      commanded: decode(val & 0b111),
-      actual: decode((val >> 3) & 0b111),
-    }
-  }
+  /// A critical limit was exceeded.
+  Critical,
+}
+
+/// Which side of its operating range a sensor has left.
+#[derive(
+  Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize
+)]
+#[serde(rename_all = "snake_case")]
+#[archive_attr(derive(bytecheck::CheckBytes))]
+pub enum AlarmBound {
+  /// The reading is below a low limit.
+  Low,
+
+  /// The reading is above a high limit.
+  High,
+}
+
+/// A sensor reading outside of one of the redline limits of its mapping.
+#[derive(
+  Clone, Copy, Debug, Deserialize, PartialEq, Serialize, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize
+)]
+#[archive_attr(derive(bytecheck::CheckBytes))]
+pub struct RedlineAlarm {
+  /// The severity of the most severe limit exceeded.
+  pub severity: AlarmSeverity,
+
+  /// Whether the limit exceeded is a low or a high limit.
+  pub bound: AlarmBound,
+
+  /// The limit exceeded.
+  pub limit: f64,
+
+  /// The latest reading of the sensor.
+  pub value: f64,
+
+  /// The UNIX timestamp, in seconds, at which the sensor first exceeded this
+  /// limit since last being within it.
+  pub first_exceeded: f64,
 }
 
 /// Represents all possible sensor types that may be used in a `NodeMapping`.
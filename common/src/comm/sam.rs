@@ -189,6 +189,31 @@ pub enum SamControlMessage {
   // No more LED command it takes up valuable space in code memory
 }
 
+impl SamControlMessage {
+  /// Whether the flight computer must know this message reached the board,
+  /// retransmitting it until the board acknowledges it.
+  pub fn requires_acknowledgement(&self) -> bool {
+    matches!(
+      self,
+      Self::ActuateValve { .. }
+        | Self::AbortStageValveStates { .. }
+        | Self::Abort { .. }
+    )
+  }
+}
+
+/// A control message as it is sent to a SAM board, tagged with a sequence
+/// number that the board echoes back in `DataMessage::SamAck` once received.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct SamControlRequest {
+  /// Distinguishes this request from others sent by the flight computer, so
+  /// that a retransmitted request is executed only once.
+  pub sequence: u32,
+
+  /// The message itself.
+  pub message: SamControlMessage,
+}
+
 /// A single sensor data point for a SAM.
 #[derive(Clone, Debug, Deserialize, MaxSize, PartialEq, Serialize)]
 pub struct SensorDataPoint {
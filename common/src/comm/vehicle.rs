@@ -1,8 +1,8 @@
 //! Defines the comprehensive vehicle state.
 
 use super::{
-  AbortStage, CompositeValveState, GpsState, Measurement, RecoState,
-  Statistics, bms::Bms, fc_sensors::FcSensors, rbf::RbfState, sam,
+  AbortStage, CommandDelivery, CompositeValveState, GpsState, Measurement, RecoState,
+  RedlineAlarm, SequenceRun, Statistics, TriggerState, ValveCommander, ValveMismatch, ValveState, bms::Bms, fc_sensors::FcSensors, rbf::RbfState, sam,
 };
 use bytecheck;
 use compaq::{Compress, compress};
@@ -10,6 +10,14 @@ use rkyv;
 use serde::{Deserialize, Serialize};
 use std::{collections::HashMap, ops::{Index, IndexMut}};
 
+mod delta;
+mod slice;
+mod vote;
+
+pub use delta::{DeltaEncoding, VehicleStateDeltaDecoder, VehicleStateDeltaEncoder};
+pub use slice::{SlicePriorities, VehicleStateSlice, VehicleStateSlicer};
+pub use vote::{RecoMcuDisagreement, RecoVoteThresholds};
+
 /// Errors returned while building or validating a `VehicleState` TEL schema.
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum VehicleStateSchemaError {
@@ -30,6 +38,57 @@ pub enum VehicleStateCompaqError {
   Serialize(postcard::Error),
   /// Postcard failed while deserializing the compressed payload.
   Deserialize(postcard::Error),
+  /// The frame was compressed with a different schema than the one given to
+  /// decompress it, so its values would be assigned to the wrong names.
+  SchemaMismatch {
+    /// The fingerprint of the schema given to decompress the frame.
+    expected: u32,
+    /// The fingerprint of the schema the frame was compressed with.
+    received: u32,
+  },
+  /// The frame only holds what changed since a keyframe that wasn't
+  /// received, so it can't be decoded until the next keyframe arrives.
+  MissingKeyframe,
+  /// The frame only holds a slice of the state, which has to be merged with
+  /// the slices in other frames to rebuild all of it.
+  IncompleteState,
+}
+
+/// The number of bytes at the start of every compaq frame which hold the
+/// fingerprint of the schema it was compressed with.
+pub const SCHEMA_FINGERPRINT_SIZE: usize = 4;
+
+/// Hashes the keys and units of a schema using 32-bit FNV-1a, which unlike
+/// the standard library's hasher is the same on every machine and build.
+fn schema_fingerprint(
+  valve_keys: &[String],
+  sensor_keys: &[String],
+  sensor_units: &[sam::Unit],
+) -> u32 {
+  fn write(hash: &mut u32, bytes: &[u8]) {
+    for byte in bytes {
+      *hash ^= u32::from(*byte);
+      *hash = hash.wrapping_mul(0x0100_0193);
+    }
+  }
+
+  let mut hash = 0x811c_9dc5;
+
+  for key in valve_keys {
+    write(&mut hash, key.as_bytes());
+    write(&mut hash, &[0]);
+  }
+
+  // keeps a name from moving between the valves and sensors without changing
+  // the fingerprint.
+  write(&mut hash, &[0xff]);
+
+  for (key, unit) in sensor_keys.iter().zip(sensor_units) {
+    write(&mut hash, key.as_bytes());
+    write(&mut hash, &[0, *unit as u8]);
+  }
+
+  hash
 }
 
 #[derive(
@@ -47,8 +106,8 @@ pub enum VehicleStateCompaqError {
 #[archive_attr(derive(bytecheck::CheckBytes))]
 /// Three RECO slots as stored in the live vehicle state.
 ///
-/// The TEL codec intentionally treats this as a lossy aggregate: all present
-/// entries are averaged into a single on-wire sample, and the decoded sample is
+/// The TEL codec intentionally treats this as a lossy aggregate: the present
+/// entries are voted into a single on-wire sample, and the decoded sample is
 /// then duplicated back into all three slots.
 pub struct RecoTriState(pub [Option<RecoState>; 3]);
 
@@ -76,9 +135,9 @@ impl IndexMut<usize> for RecoTriState {
 impl Compress for RecoTriState {
   type Compressed = <Option<RecoState> as Compress>::Compressed;
 
-  /// Averages the present RECO samples into the single TEL representation.
+  /// Votes the present RECO samples into the single TEL representation.
   fn compress(&self) -> Self::Compressed {
-    average_reco_states(self).compress()
+    self.vote().compress()
   }
 
   /// Restores the single decoded TEL sample back into all three RECO slots.
@@ -99,40 +158,50 @@ impl Compress for RecoTriState {
 pub struct VehicleStateCompressionSchema {
   valve_keys: Vec<String>,
   sensor_keys: Vec<String>,
+  sensor_units: Vec<sam::Unit>,
+  fingerprint: u32,
 }
 
 impl VehicleStateCompressionSchema {
-  /// Builds a compression schema from explicit valve and sensor key sets.
+  /// Builds a compression schema from explicit valve and sensor metadata.
   pub fn new(
     valve_keys: impl IntoIterator<Item = String>,
-    sensor_keys: impl IntoIterator<Item = String>,
+    sensor_metadata: impl IntoIterator<Item = (String, sam::Unit)>,
   ) -> Result<Self, VehicleStateSchemaError> {
     let mut valve_keys: Vec<_> = valve_keys.into_iter().collect();
     valve_keys.sort_unstable();
     u8::try_from(valve_keys.len())
       .map_err(|_| VehicleStateSchemaError::TooManyValves)?;
 
-    let mut sensor_keys: Vec<_> = sensor_keys.into_iter().collect();
-    sensor_keys.sort_unstable();
-    u8::try_from(sensor_keys.len())
+    let mut sensor_metadata: Vec<_> = sensor_metadata.into_iter().collect();
+    sensor_metadata.sort_unstable_by(|(left, _), (right, _)| left.cmp(right));
+    u8::try_from(sensor_metadata.len())
       .map_err(|_| VehicleStateSchemaError::TooManySensors)?;
 
+    let (sensor_keys, sensor_units): (Vec<_>, Vec<_>) =
+      sensor_metadata.into_iter().unzip();
+
+    let fingerprint =
+      schema_fingerprint(&valve_keys, &sensor_keys, &sensor_units);
+
     Ok(Self {
       valve_keys,
       sensor_keys,
+      sensor_units,
+      fingerprint,
     })
   }
 
   /// Builds a compression schema from the current state.
   pub fn from_state(state: &VehicleState) -> Result<Self, VehicleStateSchemaError> {
     let valve_keys = state.valve_states.keys().cloned();
-    let sensor_keys = state
+    let sensor_metadata = state
       .sensor_readings
-      .keys()
-      .filter(|sensor_name| !should_omit_sensor(sensor_name, &state.valve_states))
-      .cloned();
+      .iter()
+      .filter(|(sensor_name, _)| !should_omit_sensor(sensor_name, &state.valve_states))
+      .map(|(sensor_name, measurement)| (sensor_name.clone(), measurement.unit));
 
-    Self::new(valve_keys, sensor_keys)
+    Self::new(valve_keys, sensor_metadata)
   }
 
   /// Returns the sorted valve keys used by the compressor.
@@ -144,6 +213,83 @@ impl VehicleStateCompressionSchema {
   pub fn sensor_keys(&self) -> &[String] {
     &self.sensor_keys
   }
+
+  /// Returns the sorted sensor units used by the compressor.
+  pub fn sensor_units(&self) -> &[sam::Unit] {
+    &self.sensor_units
+  }
+
+  /// Returns the hash of the keys and units of this schema, which is written
+  /// at the start of every frame compressed with it.
+  pub fn fingerprint(&self) -> u32 {
+    self.fingerprint
+  }
+
+  /// Returns the most bytes a `VehicleState` compressed with this schema can
+  /// take, which is when every optional field is present and every
+  /// variable-length integer takes its longest encoding.
+  pub fn max_compressed_size(&self) -> usize {
+    let state = VehicleState {
+      valve_states: self
+        .valve_keys
+        .iter()
+        .map(|key| {
+          let state = CompositeValveState {
+            commanded: ValveState::Undetermined,
+            actual: ValveState::Undetermined,
+          };
+
+          (key.clone(), state)
+        })
+        .collect(),
+      gps: Some(GpsState {
+        latitude_deg: 0.0,
+        longitude_deg: 0.0,
+        altitude_m: 0.0,
+        north_mps: 0.0,
+        east_mps: 0.0,
+        down_mps: 0.0,
+        timestamp_unix_ms: Some(i64::MIN),
+        has_fix: false,
+        num_satellites: 0,
+      }),
+      reco: RecoTriState([Some(RecoState::default()), None, None]),
+      sensor_readings: self
+        .sensor_keys
+        .iter()
+        .map(|key| {
+          let measurement = Measurement {
+            value: 0.0,
+            unit: sam::Unit::default(),
+            timestamp: 0.0,
+            board_id: None,
+            stale: false,
+          };
+
+          (key.clone(), measurement)
+        })
+        .collect(),
+      ..VehicleState::default()
+    };
+
+    // compressing the state can only fail if it disagrees with the schema,
+    // which it was just built from.
+    let Ok(compressed) = state.deflate_with_schema(self) else {
+      unreachable!("state built from the schema should match it");
+    };
+
+    // keyframes are the largest kind of frame, as delta frames are only sent
+    // when they are smaller.
+    let frame = CompaqFrame::Keyframe {
+      id: u8::MAX,
+      state: compressed,
+    };
+
+    let payload_size = postcard::experimental::serialized_size(&frame)
+      .expect("compressed vehicle state should always serialize");
+
+    SCHEMA_FINGERPRINT_SIZE + payload_size
+  }
 }
 
 /// A persistent decompression schema for `VehicleState`.
@@ -155,6 +301,7 @@ pub struct VehicleStateDecompressionSchema {
   valve_keys: Vec<String>,
   sensor_keys: Vec<String>,
   sensor_units: Vec<sam::Unit>,
+  fingerprint: u32,
 }
 
 impl VehicleStateDecompressionSchema {
@@ -172,10 +319,14 @@ impl VehicleStateDecompressionSchema {
     let (sensor_keys, sensor_units): (Vec<_>, Vec<_>) =
       sensor_metadata.into_iter().unzip();
 
+    let fingerprint =
+      schema_fingerprint(&valve_keys, &sensor_keys, &sensor_units);
+
     Self {
       valve_keys,
       sensor_keys,
       sensor_units,
+      fingerprint,
     }
   }
 
@@ -193,6 +344,12 @@ impl VehicleStateDecompressionSchema {
   pub fn sensor_units(&self) -> &[sam::Unit] {
     &self.sensor_units
   }
+
+  /// Returns the hash of the keys and units of this schema, which must match
+  /// the fingerprint at the start of a frame for it to be decompressed.
+  pub fn fingerprint(&self) -> u32 {
+    self.fingerprint
+  }
 }
 
 /// Holds the state of the SAMs and valves using `HashMap`s which convert a
@@ -214,6 +371,11 @@ pub struct VehicleState {
   #[order]
   pub valve_states: HashMap<String, CompositeValveState>,
 
+  /// Holds every valve whose actual state currently disagrees with its
+  /// commanded state, along with how long it has.
+  #[exclude]
+  pub valve_mismatches: HashMap<String, ValveMismatch>,
+
   /// Holds the state of every device on BMS
   pub bms: Bms,
 
@@ -241,6 +403,15 @@ pub struct VehicleState {
   /// sending telemetry to the server.
   pub reco_valid: bool,
 
+  /// The UNIX timestamp, in seconds, at which the flight computer last
+  /// ingested `reco` samples, or zero if it never has.
+  #[exclude]
+  pub reco_timestamp: f64,
+
+  /// How the state reported by each RECO MCU disagrees with the vote across
+  /// all three, in the same order as `reco`.
+  pub reco_disagreement: [RecoMcuDisagreement; 3],
+
   #[exclude]
   /// Aggregated RBF information for BMS, RECO, and SAM boards.
   pub rbf: RbfState,
@@ -249,15 +420,49 @@ pub struct VehicleState {
   #[order]
   pub sensor_readings: HashMap<String, Measurement>,
 
+  /// Holds the latest value reported for every sensor by its board, before
+  /// the conversion or calibration in its mapping is applied.
+  #[exclude]
+  pub raw_readings: HashMap<String, f64>,
+
+  /// Holds every sensor whose latest reading is outside of a redline limit
+  /// set in its mapping.
+  #[exclude]
+  pub redline_alarms: HashMap<String, RedlineAlarm>,
+
   /// Holds a HashMap from Board ID to a 2-tuple of the Rolling Average of
   /// obtaining a data packet from the Board ID and the duration between the
   /// last recieved and second-to-last recieved packet of the Board ID.
   #[exclude]
   pub rolling: HashMap<String, Statistics>,
 
+  /// Holds a HashMap from Board ID to how the commands that board must
+  /// acknowledge have been delivered.
+  #[exclude]
+  pub command_delivery: HashMap<String, CommandDelivery>,
+
   /// Defines the current abort stage that we are in
   #[exclude]
   pub abort_stage: AbortStage,
+
+  /// Holds the evaluation state and firing history of every trigger
+  /// registered on the flight computer, keyed by trigger name.
+  #[exclude]
+  pub triggers: HashMap<String, TriggerState>,
+
+  /// Holds every sequence currently running on the flight computer, keyed by
+  /// sequence name.
+  #[exclude]
+  pub sequences: HashMap<String, SequenceRun>,
+
+  /// Holds the name of the sequence which has claimed each valve. Only that
+  /// sequence may actuate the valve until it releases it or exits.
+  #[exclude]
+  pub valve_claims: HashMap<String, String>,
+
+  /// Holds the sequence which last actuated each valve.
+  #[exclude]
+  pub valve_commanders: HashMap<String, ValveCommander>,
 }
 
 /// Implements all fields as default except for the AbortStage field whose name becomes "default"
@@ -265,6 +470,7 @@ impl Default for VehicleState {
   fn default() -> Self {
     Self {
       valve_states: HashMap::new(),
+      valve_mismatches: HashMap::new(),
       bms: Bms::default(),
       fc_sensors: FcSensors::default(),
       gps: None,
@@ -272,9 +478,18 @@ impl Default for VehicleState {
       rbf: RbfState::default(),
       reco: RecoTriState::default(),
       reco_valid: false,
+      reco_timestamp: 0.0,
+      reco_disagreement: [RecoMcuDisagreement::default(); 3],
       sensor_readings: HashMap::default(),
+      raw_readings: HashMap::new(),
+      redline_alarms: HashMap::new(),
       rolling: HashMap::default(),
+      command_delivery: HashMap::default(),
       abort_stage: AbortStage::default(),
+      triggers: HashMap::new(),
+      sequences: HashMap::new(),
+      valve_claims: HashMap::new(),
+      valve_commanders: HashMap::new(),
     }
   }
 }
@@ -289,68 +504,6 @@ fn should_omit_sensor(
     .is_some_and(|valve_name| valve_states.contains_key(valve_name))
 }
 
-// TEL intentionally collapses the three RECO sources to one aggregate sample.
-fn average_reco_states(reco: &RecoTriState) -> Option<RecoState> {
-  let present: Vec<_> = reco.0.iter().flatten().collect();
-  if present.is_empty() {
-    return None;
-  }
-
-  let mut combined = RecoState::default();
-
-  fn average_array<const N: usize>(
-    present: &[&RecoState],
-    select: impl Fn(&RecoState) -> [f32; N],
-  ) -> [f32; N] {
-    let mut sum = [0.0; N];
-    for reco in present {
-      let values = select(reco);
-      for (slot, value) in sum.iter_mut().zip(values) {
-        *slot += value;
-      }
-    }
-    for value in &mut sum {
-      *value /= present.len() as f32;
-    }
-    sum
-  }
-
-  fn average_scalar(
-    present: &[&RecoState],
-    select: impl Fn(&RecoState) -> f32,
-  ) -> f32 {
-    present.iter().map(|reco| select(reco)).sum::<f32>() / present.len() as f32
-  }
-
-  combined.quaternion = average_array(&present, |reco| reco.quaternion);
-  combined.lla_pos = average_array(&present, |reco| reco.lla_pos);
-  combined.velocity = average_array(&present, |reco| reco.velocity);
-  combined.g_bias = average_array(&present, |reco| reco.g_bias);
-  combined.a_bias = average_array(&present, |reco| reco.a_bias);
-  combined.g_sf = average_array(&present, |reco| reco.g_sf);
-  combined.a_sf = average_array(&present, |reco| reco.a_sf);
-  combined.lin_accel = average_array(&present, |reco| reco.lin_accel);
-  combined.angular_rate = average_array(&present, |reco| reco.angular_rate);
-  combined.mag_data = average_array(&present, |reco| reco.mag_data);
-  combined.temperature = average_scalar(&present, |reco| reco.temperature);
-  combined.pressure = average_scalar(&present, |reco| reco.pressure);
-
-  macro_rules! or_reco_flag {
-    ($field:ident) => {
-      combined.$field = present.iter().any(|reco| reco.$field);
-    };
-  }
-
-  or_reco_flag!(stage1_enabled);
-  or_reco_flag!(stage2_enabled);
-  or_reco_flag!(reco_recvd_launch);
-  or_reco_flag!(ekf_blown_up);
-  or_reco_flag!(drouge_timer_enable);
-  or_reco_flag!(main_timer_enable);
-
-  Some(combined)
-}
-
 impl VehicleState {
   /// Constructs a new, empty `VehicleState`.
   pub fn new() -> Self {
@@ -367,19 +520,32 @@ impl VehicleState {
     self.compress_compaq_with_schema(buf, &schema)
   }
 
-  /// Serializes this vehicle state using a precomputed compaq schema.
+  /// Serializes this vehicle state using a precomputed compaq schema. The
+  /// frame begins with the fingerprint of the schema, so the receiver can
+  /// tell whether its own schema lines up with the keyless payload.
   pub fn compress_compaq_with_schema(
     &self,
     buf: &mut [u8],
     schema: &VehicleStateCompressionSchema,
   ) -> Result<usize, VehicleStateCompaqError> {
-    let compressed = self
-      .deflate(schema.valve_keys.clone(), schema.sensor_keys.clone())
-      .map_err(|_| VehicleStateCompaqError::PolicyDesynchronized)?;
+    let compressed = self.deflate_with_schema(schema)?;
+    write_frame(buf, schema.fingerprint, &CompaqFrame::State(compressed))
+  }
 
-    postcard::to_slice(&compressed, buf)
-      .map(|bytes| bytes.len())
-      .map_err(VehicleStateCompaqError::Serialize)
+  /// Returns the fingerprint of the schema a compaq frame was compressed
+  /// with, or `None` if the frame is too short to hold one.
+  pub fn compaq_fingerprint(bytes: &[u8]) -> Option<u32> {
+    let fingerprint = bytes.get(..SCHEMA_FINGERPRINT_SIZE)?;
+    Some(u32::from_le_bytes(fingerprint.try_into().ok()?))
+  }
+
+  fn deflate_with_schema(
+    &self,
+    schema: &VehicleStateCompressionSchema,
+  ) -> Result<CompressedVehicleState, VehicleStateCompaqError> {
+    self
+      .deflate(schema.valve_keys.clone(), schema.sensor_keys.clone())
+      .map_err(|_| VehicleStateCompaqError::PolicyDesynchronized)
   }
 
   /// Reconstructs a `VehicleState` from the compaq-derived TEL representation.
@@ -403,31 +569,105 @@ impl VehicleState {
     Self::decompress_compaq_with_schema(bytes, &schema)
   }
 
-  /// Reconstructs a `VehicleState` using a precomputed compaq schema.
+  /// Reconstructs a `VehicleState` using a precomputed compaq schema, failing
+  /// if the frame was compressed with a schema whose fingerprint differs.
   pub fn decompress_compaq_with_schema(
     bytes: &[u8],
     schema: &VehicleStateDecompressionSchema,
   ) -> Result<Self, VehicleStateCompaqError> {
-    let compressed: CompressedVehicleState =
-      postcard::from_bytes(bytes).map_err(VehicleStateCompaqError::Deserialize)?;
+    match read_frame(bytes, schema)? {
+      CompaqFrame::State(compressed)
+      | CompaqFrame::Keyframe {
+        state: compressed, ..
+      } => inflate_with_schema(compressed, schema),
+      CompaqFrame::Delta(_) => Err(VehicleStateCompaqError::MissingKeyframe),
+      CompaqFrame::Slice(_) => Err(VehicleStateCompaqError::IncompleteState),
+    }
+  }
+}
 
-    let mut state = compressed
-      .inflate(schema.valve_keys.clone(), schema.sensor_keys.clone())
-      .map_err(|_| VehicleStateCompaqError::PolicyDesynchronized)?;
+/// The payload of a compaq frame, which follows the schema fingerprint.
+#[derive(Deserialize, Serialize)]
+enum CompaqFrame {
+  /// A complete state, as sent when delta encoding is off.
+  State(CompressedVehicleState),
 
-    for (sensor_name, unit) in schema
-      .sensor_keys
-      .iter()
-      .zip(schema.sensor_units.iter().copied())
-    {
-      if let Some(measurement) = state.sensor_readings.get_mut(sensor_name) {
-        measurement.unit = unit;
-      }
-    }
+  /// A complete state which the delta frames after it are relative to.
+  Keyframe {
+    id: u8,
+    state: CompressedVehicleState,
+  },
+
+  /// Only the parts of a state which changed since a keyframe.
+  Delta(delta::CompressedVehicleStateDelta),
+
+  /// Some of the parts of a state which is too large for one frame.
+  Slice(slice::CompressedVehicleStateSlice),
+}
 
-    Ok(state)
+/// Writes the fingerprint of a schema followed by a frame compressed with it,
+/// returning the number of bytes written.
+fn write_frame(
+  buf: &mut [u8],
+  fingerprint: u32,
+  frame: &CompaqFrame,
+) -> Result<usize, VehicleStateCompaqError> {
+  let (header, payload) = buf
+    .split_at_mut_checked(SCHEMA_FINGERPRINT_SIZE)
+    .ok_or(VehicleStateCompaqError::Serialize(
+      postcard::Error::SerializeBufferFull,
+    ))?;
+
+  header.copy_from_slice(&fingerprint.to_le_bytes());
+
+  postcard::to_slice(frame, payload)
+    .map(|bytes| SCHEMA_FINGERPRINT_SIZE + bytes.len())
+    .map_err(VehicleStateCompaqError::Serialize)
+}
+
+/// Reads a frame, checking that it was compressed with the given schema.
+fn read_frame(
+  bytes: &[u8],
+  schema: &VehicleStateDecompressionSchema,
+) -> Result<CompaqFrame, VehicleStateCompaqError> {
+  let received = VehicleState::compaq_fingerprint(bytes).ok_or(
+    VehicleStateCompaqError::Deserialize(
+      postcard::Error::DeserializeUnexpectedEnd,
+    ),
+  )?;
+
+  if received != schema.fingerprint {
+    return Err(VehicleStateCompaqError::SchemaMismatch {
+      expected: schema.fingerprint,
+      received,
+    });
+  }
+
+  postcard::from_bytes(&bytes[SCHEMA_FINGERPRINT_SIZE..])
+    .map_err(VehicleStateCompaqError::Deserialize)
+}
+
+/// Expands a compressed state using the keys of a schema, restoring the units
+/// of its sensors, which aren't sent.
+fn inflate_with_schema(
+  compressed: CompressedVehicleState,
+  schema: &VehicleStateDecompressionSchema,
+) -> Result<VehicleState, VehicleStateCompaqError> {
+  let mut state = compressed
+    .inflate(schema.valve_keys.clone(), schema.sensor_keys.clone())
+    .map_err(|_| VehicleStateCompaqError::PolicyDesynchronized)?;
+
+  for (sensor_name, unit) in schema
+    .sensor_keys
+    .iter()
+    .zip(schema.sensor_units.iter().copied())
+  {
+    if let Some(measurement) = state.sensor_readings.get_mut(sensor_name) {
+      measurement.unit = unit;
+    }
   }
 
+  Ok(state)
 }
 
 #[cfg(test)]
@@ -468,6 +708,9 @@ mod tests {
           Measurement {
             value: i as f64 + 0.25,
             unit: Unit::Psi,
+            timestamp: 0.0,
+            board_id: None,
+            stale: false,
           },
         )
       })
@@ -482,6 +725,9 @@ mod tests {
             Measurement {
               value: 1.25,
               unit: Unit::Amps,
+              timestamp: 0.0,
+              board_id: None,
+              stale: false,
             },
           ),
           (
@@ -489,6 +735,9 @@ mod tests {
             Measurement {
               value: 28.0,
               unit: Unit::Volts,
+              timestamp: 0.0,
+              board_id: None,
+              stale: false,
             },
           ),
         ]
@@ -927,6 +1176,9 @@ mod tests {
               .zip(schema.sensor_keys().iter())
               .find_map(|(unit, key)| (key == sensor_name).then_some(unit))
               .unwrap(),
+            timestamp: 0.0,
+            board_id: None,
+            stale: false,
           },
         )
       })
@@ -990,7 +1242,7 @@ mod tests {
     });
     expected.gps_valid = state.gps_valid;
     expected.reco_valid = state.reco_valid;
-    let aggregated_reco = quantize_reco(average_reco_states(&state.reco).unwrap());
+    let aggregated_reco = quantize_reco(state.reco.vote().unwrap());
     expected.reco = RecoTriState([
       Some(aggregated_reco.clone()),
       Some(aggregated_reco.clone()),
@@ -1052,6 +1304,281 @@ mod tests {
     );
   }
 
+  #[test]
+  fn compaq_decompress_rejects_frame_from_different_schema() {
+    let state = vehicle_state_with_counts(10, 12);
+    let mut buf = [0u8; 2048];
+    let size = state
+      .compress_compaq(&mut buf)
+      .expect("compaq compression should succeed");
+
+    // the same number of sensors, so the payload alone would still decode.
+    let mut renamed = state.clone();
+    let measurement = renamed
+      .sensor_readings
+      .remove("sensor_000")
+      .expect("the test shape should include sensor_000");
+    renamed
+      .sensor_readings
+      .insert(String::from("sensor_zzz"), measurement);
+
+    let schema = decompression_schema(&state);
+    let renamed_schema = decompression_schema(&renamed);
+    assert_ne!(schema.fingerprint(), renamed_schema.fingerprint());
+    assert_eq!(
+      VehicleState::compaq_fingerprint(&buf[..size]),
+      Some(schema.fingerprint())
+    );
+
+    assert!(
+      matches!(
+        VehicleState::decompress_compaq_with_schema(&buf[..size], &renamed_schema),
+        Err(VehicleStateCompaqError::SchemaMismatch { expected, received })
+          if expected == renamed_schema.fingerprint()
+            && received == schema.fingerprint()
+      ),
+      "a frame should not decode with a schema it wasn't compressed with"
+    );
+  }
+
+  #[test]
+  fn compaq_max_compressed_size_bounds_every_state_with_schema() {
+    let mut state = vehicle_state_with_counts(10, 12);
+    pseudo_randomize_vehicle_state(&mut state);
+    let schema = VehicleStateCompressionSchema::from_state(&state)
+      .expect("schema creation should succeed");
+    let mut buf = [0u8; 2048];
+
+    let max_size = schema.max_compressed_size();
+
+    for gps in [None, state.gps.clone()] {
+      state.gps = gps;
+
+      let size = state
+        .compress_compaq_with_schema(&mut buf, &schema)
+        .expect("compaq compression should succeed");
+
+      assert!(
+        size <= max_size,
+        "compressed state took {size} bytes, more than the {max_size} byte maximum"
+      );
+    }
+  }
+
+  #[test]
+  fn compaq_delta_frames_are_smaller_and_reconstruct_within_deadband() {
+    let mut state = vehicle_state_with_counts(10, 12);
+    pseudo_randomize_vehicle_state(&mut state);
+    let schema = VehicleStateCompressionSchema::from_state(&state)
+      .expect("schema creation should succeed");
+    let decompression_schema = decompression_schema(&state);
+    let mut encoder = VehicleStateDeltaEncoder::new(DeltaEncoding {
+      keyframe_interval: 10,
+      deadband: 0.5,
+    });
+    let mut decoder = VehicleStateDeltaDecoder::new();
+    let mut buf = [0u8; 2048];
+
+    let keyframe_size = encoder
+      .encode(&state, &mut buf, &schema)
+      .expect("keyframe encoding should succeed");
+    decoder
+      .decode(&buf[..keyframe_size], &decompression_schema)
+      .expect("keyframe decoding should succeed");
+
+    // one sensor moves past the deadband, another stays within it.
+    let mut next = state.clone();
+    next.sensor_readings.get_mut("sensor_000").unwrap().value += 100.0;
+    next.sensor_readings.get_mut("sensor_001").unwrap().value += 0.1;
+
+    let delta_size = encoder
+      .encode(&next, &mut buf, &schema)
+      .expect("delta encoding should succeed");
+    assert!(
+      delta_size < keyframe_size,
+      "delta frame took {delta_size} bytes, keyframe took {keyframe_size}"
+    );
+
+    let decoded = decoder
+      .decode(&buf[..delta_size], &decompression_schema)
+      .expect("delta decoding should succeed");
+
+    for (name, measurement) in &decoded.sensor_readings {
+      let expected = next.sensor_readings[name].value;
+      assert!(
+        (measurement.value - expected).abs() <= 0.5 + expected.abs() / 512.0,
+        "{name} decoded as {} but was {expected}",
+        measurement.value
+      );
+    }
+    assert_eq!(
+      decoded.sensor_readings["sensor_000"].value,
+      half_roundtrip_f64(next.sensor_readings["sensor_000"].value)
+    );
+  }
+
+  #[test]
+  fn compaq_delta_decoder_resyncs_after_lost_keyframe() {
+    let state = vehicle_state_with_counts(10, 12);
+    let schema = VehicleStateCompressionSchema::from_state(&state)
+      .expect("schema creation should succeed");
+    let decompression_schema = decompression_schema(&state);
+    let mut encoder = VehicleStateDeltaEncoder::new(DeltaEncoding {
+      keyframe_interval: 3,
+      deadband: 0.0,
+    });
+    let mut decoder = VehicleStateDeltaDecoder::new();
+    let mut buf = [0u8; 2048];
+
+    let mut frames = Vec::new();
+    for i in 0..6 {
+      let mut next = state.clone();
+      next.sensor_readings.get_mut("sensor_000").unwrap().value = i as f64;
+
+      let size = encoder
+        .encode(&next, &mut buf, &schema)
+        .expect("delta encoding should succeed");
+      frames.push(buf[..size].to_vec());
+    }
+
+    // the first keyframe is lost, so its delta frames can't be decoded.
+    for frame in &frames[1..3] {
+      assert!(matches!(
+        decoder.decode(frame, &decompression_schema),
+        Err(VehicleStateCompaqError::MissingKeyframe)
+      ));
+    }
+
+    for (i, frame) in frames.iter().enumerate().skip(3) {
+      let decoded = decoder
+        .decode(frame, &decompression_schema)
+        .expect("frames after the next keyframe should decode");
+      assert_eq!(decoded.sensor_readings["sensor_000"].value, i as f64);
+    }
+
+    // frames compressed without delta encoding never need a keyframe.
+    let size = state
+      .compress_compaq_with_schema(&mut buf, &schema)
+      .expect("compaq compression should succeed");
+    assert!(VehicleStateDeltaDecoder::new()
+      .decode(&buf[..size], &decompression_schema)
+      .is_ok());
+  }
+
+  #[test]
+  fn compaq_slices_fit_in_frame_and_favor_high_priority_sensors() {
+    let mut state = vehicle_state_with_counts(10, 120);
+    pseudo_randomize_vehicle_state(&mut state);
+    let schema = VehicleStateCompressionSchema::from_state(&state)
+      .expect("schema creation should succeed");
+    let decompression_schema = decompression_schema(&state);
+    let mut buf = [0u8; crate::comm::RADIO_PAYLOAD_MTU];
+    assert!(schema.max_compressed_size() > buf.len());
+
+    let urgent = schema
+      .sensor_keys()
+      .iter()
+      .position(|key| key == "sensor_042")
+      .expect("the test shape should include sensor_042");
+    let mut priorities = SlicePriorities {
+      sensors: vec![0; schema.sensor_keys().len()],
+      gps: u8::MAX,
+      ..SlicePriorities::default()
+    };
+    priorities.sensors[urgent] = 9;
+
+    let mut slicer = VehicleStateSlicer::new();
+    let mut received: HashMap<String, usize> = HashMap::new();
+    let mut gps_frames = 0;
+
+    for _ in 0..40 {
+      let size = slicer
+        .encode(&state, &mut buf, &schema, &priorities)
+        .expect("slicing should succeed");
+
+      // every frame decodes on its own, without the frames before it.
+      let slice = VehicleStateDeltaDecoder::new()
+        .decode_slice(&buf[..size], &decompression_schema)
+        .expect("every slice should decode");
+
+      for (name, measurement) in slice.state.sensor_readings {
+        assert_eq!(
+          measurement.value,
+          half_roundtrip_f64(state.sensor_readings[&name].value)
+        );
+        *received.entry(name).or_default() += 1;
+      }
+
+      gps_frames += usize::from(slice.gps);
+    }
+
+    assert_eq!(
+      received.len(),
+      schema.sensor_keys().len(),
+      "every sensor should eventually be sent"
+    );
+    assert!(received["sensor_042"] > received["sensor_043"]);
+    assert_eq!(gps_frames, 40);
+
+    assert!(matches!(
+      VehicleState::decompress_compaq_with_schema(&buf, &decompression_schema),
+      Err(VehicleStateCompaqError::IncompleteState)
+    ));
+  }
+
+  #[test]
+  fn reco_vote_outvotes_one_diverged_mcu_and_flags_it() {
+    let agreeing = RecoState {
+      quaternion: [1.0, 0.0, 0.0, 0.0],
+      lla_pos: [-86.9, 40.4, 200.0],
+      velocity: [0.0, 0.0, 30.0],
+      stage1_enabled: true,
+      ..RecoState::default()
+    };
+    let diverged = RecoState {
+      quaternion: [0.0, 1.0, 0.0, 0.0],
+      lla_pos: [-86.9, 40.4, 5000.0],
+      velocity: [0.0, 0.0, -300.0],
+      stage1_enabled: false,
+      ..agreeing.clone()
+    };
+    let reco = RecoTriState([
+      Some(agreeing.clone()),
+      Some(diverged),
+      Some(RecoState {
+        lla_pos: [-86.9, 40.4, 201.0],
+        ..agreeing.clone()
+      }),
+    ]);
+
+    let voted = reco.vote().expect("a vote needs at least one MCU");
+    assert_eq!(voted.quaternion, agreeing.quaternion);
+    assert_eq!(voted.lla_pos[2], 201.0);
+    assert_eq!(voted.velocity, agreeing.velocity);
+    assert!(voted.stage1_enabled);
+
+    let disagreement = reco.disagreement(&RecoVoteThresholds::default());
+    assert!(!disagreement[0].any());
+    assert_eq!(
+      disagreement[1],
+      RecoMcuDisagreement {
+        missing: false,
+        attitude: true,
+        position: false,
+        altitude: true,
+        velocity: true,
+        flags: true,
+      }
+    );
+    assert!(!disagreement[2].any());
+
+    // with one MCU missing, a flag needs both remaining MCUs to set it.
+    let reco = RecoTriState([Some(agreeing), None, Some(RecoState::default())]);
+    assert!(!reco.vote().unwrap().stage1_enabled);
+    assert!(reco.disagreement(&RecoVoteThresholds::default())[1].missing);
+    assert_eq!(RecoTriState::default().vote(), None);
+  }
+
   #[test]
   fn compaq_vespula_size_breakdown() {
     fn print_breakdown(label: &str, state: &VehicleState) {
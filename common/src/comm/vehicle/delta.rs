@@ -0,0 +1,278 @@
+//! Delta encoding for compaq radio telemetry, which sends a complete keyframe
+//! periodically and, between keyframes, only what changed since the last one.
+
+use super::{
+  inflate_with_schema,
+  read_frame,
+  slice::{CompressedVehicleStateSlice, VehicleStateSlice},
+  write_frame,
+  CompaqFrame,
+  CompressedVehicleState,
+  Measurement,
+  VehicleState,
+  VehicleStateCompaqError,
+  VehicleStateCompressionSchema,
+  VehicleStateDecompressionSchema,
+};
+use compaq::Compress;
+use serde::{Deserialize, Serialize};
+
+/// Settings for sending radio telemetry as keyframes and delta frames.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DeltaEncoding {
+  /// How many frames are sent per keyframe, counting the keyframe itself.
+  pub keyframe_interval: u32,
+
+  /// How far a sensor reading must move from its value in the last keyframe
+  /// before delta frames include it, in the units of the sensor.
+  pub deadband: f64,
+}
+
+/// The parts of a compressed state which changed since a keyframe.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub(super) struct CompressedVehicleStateDelta {
+  /// The ID of the keyframe this frame is relative to.
+  keyframe: u8,
+  changes: CompressedVehicleStateSlice,
+}
+
+impl CompressedVehicleStateDelta {
+  /// Collects everything in a state which differs from a keyframe, ignoring
+  /// sensors which haven't moved further than the deadband.
+  fn between(
+    keyframe: &Keyframe,
+    state: &CompressedVehicleState,
+    deadband: f64,
+  ) -> Self {
+    fn changed<T: Clone + PartialEq>(keyframe: &T, current: &T) -> Option<T> {
+      (keyframe != current).then(|| current.clone())
+    }
+
+    let base = &keyframe.state;
+
+    let sensor_readings =
+      indexed_changes(&base.sensor_readings, &state.sensor_readings, |a, b| {
+        let difference = (Measurement::decompress(b.clone()).value
+          - Measurement::decompress(a.clone()).value)
+          .abs();
+
+        // a reading becoming NaN always counts as a change.
+        difference > deadband || difference.is_nan()
+      });
+
+    let changes = CompressedVehicleStateSlice {
+      valve_states: indexed_changes(
+        &base.valve_states,
+        &state.valve_states,
+        |a, b| a != b,
+      ),
+      sensor_readings,
+      bms: changed(&base.bms, &state.bms),
+      fc_sensors: changed(&base.fc_sensors, &state.fc_sensors),
+      gps: changed(&base.gps, &state.gps),
+      gps_valid: changed(&base.gps_valid, &state.gps_valid),
+      reco: changed(&base.reco, &state.reco),
+      reco_valid: changed(&base.reco_valid, &state.reco_valid),
+      reco_disagreement: changed(
+        &base.reco_disagreement,
+        &state.reco_disagreement,
+      ),
+    };
+
+    Self {
+      keyframe: keyframe.id,
+      changes,
+    }
+  }
+
+  /// Rebuilds the complete compressed state this delta was taken from.
+  fn apply(
+    &self,
+    keyframe: &CompressedVehicleState,
+  ) -> Result<CompressedVehicleState, VehicleStateCompaqError> {
+    let mut state = keyframe.clone();
+    self.changes.apply(&mut state)?;
+    Ok(state)
+  }
+}
+
+/// Returns the index and new value of every entry which changed.
+fn indexed_changes<T: Clone>(
+  keyframe: &[T],
+  current: &[T],
+  changed: impl Fn(&T, &T) -> bool,
+) -> Vec<(u8, T)> {
+  keyframe
+    .iter()
+    .zip(current)
+    .enumerate()
+    .filter(|(_, (old, new))| changed(old, new))
+    // schemas hold at most 255 valves and sensors, so every index fits.
+    .map(|(index, (_, new))| (index as u8, new.clone()))
+    .collect()
+}
+
+/// The last keyframe sent or received, along with the schema it belongs to.
+#[derive(Clone, Debug)]
+struct Keyframe {
+  id: u8,
+  fingerprint: u32,
+  state: CompressedVehicleState,
+}
+
+/// Compresses vehicle states into keyframes and the delta frames between them.
+///
+/// Each delta frame holds what changed since the last keyframe, not since the
+/// frame before it, so a lost delta frame doesn't affect any other. A delta
+/// frame is only sent when it is smaller than a keyframe would be.
+#[derive(Clone, Debug)]
+pub struct VehicleStateDeltaEncoder {
+  encoding: DeltaEncoding,
+  keyframe: Option<Keyframe>,
+  frames_since_keyframe: u32,
+}
+
+impl VehicleStateDeltaEncoder {
+  /// Creates an encoder whose first frame will be a keyframe.
+  pub fn new(encoding: DeltaEncoding) -> Self {
+    Self {
+      encoding,
+      keyframe: None,
+      frames_since_keyframe: 0,
+    }
+  }
+
+  /// Compresses a state into either a keyframe or a delta frame, returning
+  /// the number of bytes written. A keyframe is always sent when the schema
+  /// changes.
+  pub fn encode(
+    &mut self,
+    state: &VehicleState,
+    buf: &mut [u8],
+    schema: &VehicleStateCompressionSchema,
+  ) -> Result<usize, VehicleStateCompaqError> {
+    let compressed = state.deflate_with_schema(schema)?;
+    self.frames_since_keyframe = self.frames_since_keyframe.saturating_add(1);
+
+    if let Some(keyframe) = self.keyframe.as_ref().filter(|keyframe| {
+      keyframe.fingerprint == schema.fingerprint
+        && self.frames_since_keyframe < self.encoding.keyframe_interval
+    }) {
+      let delta = CompressedVehicleStateDelta::between(
+        keyframe,
+        &compressed,
+        self.encoding.deadband,
+      );
+
+      let delta_size = postcard::experimental::serialized_size(&delta)
+        .map_err(VehicleStateCompaqError::Serialize)?;
+      let keyframe_size = postcard::experimental::serialized_size(&compressed)
+        .map_err(VehicleStateCompaqError::Serialize)?;
+
+      if delta_size < keyframe_size {
+        return write_frame(buf, schema.fingerprint, &CompaqFrame::Delta(delta));
+      }
+    }
+
+    let id = self
+      .keyframe
+      .as_ref()
+      .map_or(0, |keyframe| keyframe.id.wrapping_add(1));
+
+    let frame = CompaqFrame::Keyframe {
+      id,
+      state: compressed.clone(),
+    };
+
+    // the keyframe is only kept once it has been written, as delta frames
+    // relative to an unsent keyframe could never be decoded.
+    let size = write_frame(buf, schema.fingerprint, &frame)?;
+
+    self.keyframe = Some(Keyframe {
+      id,
+      fingerprint: schema.fingerprint,
+      state: compressed,
+    });
+    self.frames_since_keyframe = 0;
+
+    Ok(size)
+  }
+}
+
+/// Reconstructs vehicle states from keyframes and delta frames, along with
+/// frames compressed without delta encoding.
+#[derive(Clone, Debug, Default)]
+pub struct VehicleStateDeltaDecoder {
+  keyframe: Option<Keyframe>,
+}
+
+impl VehicleStateDeltaDecoder {
+  /// Creates a decoder which hasn't received a keyframe yet.
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Decodes any compaq frame holding a complete state, remembering
+  /// keyframes so that the delta frames after them can be decoded. A delta
+  /// frame relative to a keyframe which was lost fails with
+  /// `MissingKeyframe`, and decoding resumes with the next keyframe.
+  pub fn decode(
+    &mut self,
+    bytes: &[u8],
+    schema: &VehicleStateDecompressionSchema,
+  ) -> Result<VehicleState, VehicleStateCompaqError> {
+    match read_frame(bytes, schema)? {
+      CompaqFrame::Slice(_) => Err(VehicleStateCompaqError::IncompleteState),
+      frame => self.decode_complete(frame, schema),
+    }
+  }
+
+  /// Decodes any compaq frame like `decode`, along with frames which hold
+  /// only a slice of the state.
+  pub fn decode_slice(
+    &mut self,
+    bytes: &[u8],
+    schema: &VehicleStateDecompressionSchema,
+  ) -> Result<VehicleStateSlice, VehicleStateCompaqError> {
+    match read_frame(bytes, schema)? {
+      CompaqFrame::Slice(slice) => slice.inflate(schema),
+      frame => self
+        .decode_complete(frame, schema)
+        .map(VehicleStateSlice::complete),
+    }
+  }
+
+  fn decode_complete(
+    &mut self,
+    frame: CompaqFrame,
+    schema: &VehicleStateDecompressionSchema,
+  ) -> Result<VehicleState, VehicleStateCompaqError> {
+    match frame {
+      CompaqFrame::State(compressed) => inflate_with_schema(compressed, schema),
+      CompaqFrame::Keyframe { id, state } => {
+        let decoded = inflate_with_schema(state.clone(), schema)?;
+
+        self.keyframe = Some(Keyframe {
+          id,
+          fingerprint: schema.fingerprint,
+          state,
+        });
+
+        Ok(decoded)
+      }
+      CompaqFrame::Delta(delta) => {
+        let keyframe = self
+          .keyframe
+          .as_ref()
+          .filter(|keyframe| {
+            keyframe.id == delta.keyframe
+              && keyframe.fingerprint == schema.fingerprint
+          })
+          .ok_or(VehicleStateCompaqError::MissingKeyframe)?;
+
+        inflate_with_schema(delta.apply(&keyframe.state)?, schema)
+      }
+      CompaqFrame::Slice(_) => Err(VehicleStateCompaqError::IncompleteState),
+    }
+  }
+}
@@ -0,0 +1,404 @@
+//! Splits vehicle states which are too large for one radio frame across
+//! several frames, each holding the parts of the state which have waited
+//! longest to be sent relative to their priority.
+
+use super::{
+  write_frame,
+  CompaqFrame,
+  CompressedVehicleState,
+  RecoMcuDisagreement,
+  RecoTriState,
+  VehicleState,
+  VehicleStateCompaqError,
+  VehicleStateCompressionSchema,
+  VehicleStateDecompressionSchema,
+  SCHEMA_FINGERPRINT_SIZE,
+};
+use crate::comm::{
+  bms::Bms,
+  fc_sensors::FcSensors,
+  CompositeValveState,
+  GpsState,
+  Measurement,
+  RecoState,
+  RADIO_PAYLOAD_MTU,
+};
+use compaq::{Compress, ExactSize};
+use serde::{Deserialize, Serialize};
+
+/// The most bytes a slice frame takes besides the parts it holds: the frame
+/// variant, the lengths of the valve and sensor lists, and a tag for each
+/// optional part.
+const SLICE_FRAME_OVERHEAD: usize = SCHEMA_FINGERPRINT_SIZE + 1 + 2 * 2 + 7;
+
+// the slicer can only send a part which fits in a frame on its own, so the
+// fixed-size parts are checked against the radio frame here.
+const _: () = {
+  let bms = <<Bms as Compress>::Compressed as ExactSize>::EXACT_SIZE;
+  let fc_sensors = <<FcSensors as Compress>::Compressed as ExactSize>::EXACT_SIZE;
+  // RECO is sent as an option, which takes a byte more, along with whether
+  // it's valid and how the MCUs disagree.
+  let reco = <<RecoState as Compress>::Compressed as ExactSize>::EXACT_SIZE
+    + 1
+    + <bool as ExactSize>::EXACT_SIZE
+    + <<[RecoMcuDisagreement; 3] as Compress>::Compressed as ExactSize>::EXACT_SIZE;
+
+  assert!(SLICE_FRAME_OVERHEAD + bms <= RADIO_PAYLOAD_MTU);
+  assert!(SLICE_FRAME_OVERHEAD + fc_sensors <= RADIO_PAYLOAD_MTU);
+  assert!(SLICE_FRAME_OVERHEAD + reco <= RADIO_PAYLOAD_MTU);
+};
+
+/// Some of the parts of a compressed state. Valves and sensors are identified
+/// by their index in the schema, and every other part of the state is either
+/// present whole or not at all.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub(super) struct CompressedVehicleStateSlice {
+  pub(super) valve_states:
+    Vec<(u8, <CompositeValveState as Compress>::Compressed)>,
+  pub(super) sensor_readings: Vec<(u8, <Measurement as Compress>::Compressed)>,
+  pub(super) bms: Option<<Bms as Compress>::Compressed>,
+  pub(super) fc_sensors: Option<<FcSensors as Compress>::Compressed>,
+  pub(super) gps: Option<<Option<GpsState> as Compress>::Compressed>,
+  pub(super) gps_valid: Option<bool>,
+  pub(super) reco: Option<<RecoTriState as Compress>::Compressed>,
+  pub(super) reco_valid: Option<bool>,
+  pub(super) reco_disagreement:
+    Option<<[RecoMcuDisagreement; 3] as Compress>::Compressed>,
+}
+
+impl CompressedVehicleStateSlice {
+  /// Overwrites the parts of a complete compressed state held by this slice.
+  pub(super) fn apply(
+    &self,
+    state: &mut CompressedVehicleState,
+  ) -> Result<(), VehicleStateCompaqError> {
+    fn replace<T: Clone>(
+      values: &mut [T],
+      changes: &[(u8, T)],
+    ) -> Result<(), VehicleStateCompaqError> {
+      for (index, value) in changes {
+        *values
+          .get_mut(usize::from(*index))
+          .ok_or(VehicleStateCompaqError::PolicyDesynchronized)? = value.clone();
+      }
+
+      Ok(())
+    }
+
+    replace(&mut state.valve_states, &self.valve_states)?;
+    replace(&mut state.sensor_readings, &self.sensor_readings)?;
+
+    if let Some(bms) = self.bms {
+      state.bms = bms;
+    }
+
+    if let Some(fc_sensors) = self.fc_sensors {
+      state.fc_sensors = fc_sensors;
+    }
+
+    if let Some(gps) = &self.gps {
+      state.gps = gps.clone();
+    }
+
+    if let Some(gps_valid) = self.gps_valid {
+      state.gps_valid = gps_valid;
+    }
+
+    if let Some(reco) = &self.reco {
+      state.reco = reco.clone();
+    }
+
+    if let Some(reco_valid) = self.reco_valid {
+      state.reco_valid = reco_valid;
+    }
+
+    if let Some(reco_disagreement) = self.reco_disagreement {
+      state.reco_disagreement = reco_disagreement;
+    }
+
+    Ok(())
+  }
+
+  /// Expands this slice on its own, using the keys and units of a schema.
+  pub(super) fn inflate(
+    self,
+    schema: &VehicleStateDecompressionSchema,
+  ) -> Result<VehicleStateSlice, VehicleStateCompaqError> {
+    let mut state = VehicleState::default();
+
+    for (index, valve_state) in self.valve_states {
+      let key = schema
+        .valve_keys
+        .get(usize::from(index))
+        .ok_or(VehicleStateCompaqError::PolicyDesynchronized)?;
+
+      state
+        .valve_states
+        .insert(key.clone(), CompositeValveState::decompress(valve_state));
+    }
+
+    for (index, reading) in self.sensor_readings {
+      let index = usize::from(index);
+      let (Some(key), Some(unit)) =
+        (schema.sensor_keys.get(index), schema.sensor_units.get(index))
+      else {
+        return Err(VehicleStateCompaqError::PolicyDesynchronized);
+      };
+
+      let mut measurement = Measurement::decompress(reading);
+      measurement.unit = *unit;
+      state.sensor_readings.insert(key.clone(), measurement);
+    }
+
+    let slice = VehicleStateSlice {
+      bms: self.bms.is_some(),
+      fc_sensors: self.fc_sensors.is_some(),
+      gps: self.gps.is_some(),
+      reco: self.reco.is_some(),
+      state: VehicleState {
+        bms: self.bms.map(Bms::decompress).unwrap_or_default(),
+        fc_sensors: self
+          .fc_sensors
+          .map(FcSensors::decompress)
+          .unwrap_or_default(),
+        gps: self.gps.and_then(<Option<GpsState> as Compress>::decompress),
+        gps_valid: self.gps_valid.unwrap_or_default(),
+        reco: self.reco.map(RecoTriState::decompress).unwrap_or_default(),
+        reco_valid: self.reco_valid.unwrap_or_default(),
+        reco_disagreement: self
+          .reco_disagreement
+          .map(<[RecoMcuDisagreement; 3]>::decompress)
+          .unwrap_or_default(),
+        ..state
+      },
+    };
+
+    Ok(slice)
+  }
+}
+
+/// Part of a vehicle state, decoded from a frame which may have held only
+/// some of it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct VehicleStateSlice {
+  /// The valves and sensors held by the frame, along with whichever other
+  /// parts of the state it held. Every part it didn't hold is left default.
+  pub state: VehicleState,
+
+  /// Whether the frame held `bms`.
+  pub bms: bool,
+
+  /// Whether the frame held `fc_sensors`.
+  pub fc_sensors: bool,
+
+  /// Whether the frame held `gps` and `gps_valid`.
+  pub gps: bool,
+
+  /// Whether the frame held `reco`, `reco_valid`, and `reco_disagreement`.
+  pub reco: bool,
+}
+
+impl VehicleStateSlice {
+  /// Wraps a state decoded from a frame which held all of it.
+  pub fn complete(state: VehicleState) -> Self {
+    Self {
+      state,
+      bms: true,
+      fc_sensors: true,
+      gps: true,
+      reco: true,
+    }
+  }
+}
+
+/// How often each part of a state is sent when it is split across frames.
+/// Parts with a higher priority are sent more often, though every part is
+/// eventually sent. Valve and sensor priorities are in the order of the keys
+/// of the schema, and any missing priority is taken to be zero.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct SlicePriorities {
+  /// The priority of each valve.
+  pub valves: Vec<u8>,
+
+  /// The priority of each sensor.
+  pub sensors: Vec<u8>,
+
+  /// The priority of `bms`.
+  pub bms: u8,
+
+  /// The priority of `fc_sensors`.
+  pub fc_sensors: u8,
+
+  /// The priority of `gps` and `gps_valid`.
+  pub gps: u8,
+
+  /// The priority of `reco`, `reco_valid`, and `reco_disagreement`.
+  pub reco: u8,
+}
+
+/// A part of a state which is sent whole in a slice.
+#[derive(Clone, Copy, Debug)]
+enum Part {
+  Bms,
+  FcSensors,
+  Gps,
+  Reco,
+  Valve(usize),
+  Sensor(usize),
+}
+
+impl Part {
+  fn priority(self, priorities: &SlicePriorities) -> u8 {
+    match self {
+      Self::Bms => priorities.bms,
+      Self::FcSensors => priorities.fc_sensors,
+      Self::Gps => priorities.gps,
+      Self::Reco => priorities.reco,
+      Self::Valve(index) => {
+        priorities.valves.get(index).copied().unwrap_or_default()
+      }
+      Self::Sensor(index) => {
+        priorities.sensors.get(index).copied().unwrap_or_default()
+      }
+    }
+  }
+
+  /// Returns how many bytes adding this part makes a slice take.
+  fn size(
+    self,
+    state: &CompressedVehicleState,
+  ) -> Result<usize, VehicleStateCompaqError> {
+    fn size_of(value: &impl Serialize) -> Result<usize, VehicleStateCompaqError> {
+      postcard::experimental::serialized_size(value)
+        .map_err(VehicleStateCompaqError::Serialize)
+    }
+
+    // indices are at most 255, which postcard writes as a single byte.
+    match self {
+      Self::Bms => size_of(&state.bms),
+      Self::FcSensors => size_of(&state.fc_sensors),
+      Self::Gps => Ok(size_of(&state.gps)? + size_of(&state.gps_valid)?),
+      Self::Reco => Ok(
+        size_of(&state.reco)?
+          + size_of(&state.reco_valid)?
+          + size_of(&state.reco_disagreement)?,
+      ),
+      Self::Valve(index) => Ok(1 + size_of(&state.valve_states[index])?),
+      Self::Sensor(index) => Ok(1 + size_of(&state.sensor_readings[index])?),
+    }
+  }
+
+  fn add_to(
+    self,
+    slice: &mut CompressedVehicleStateSlice,
+    state: &CompressedVehicleState,
+  ) {
+    // schemas hold at most 255 valves and sensors, so every index fits.
+    match self {
+      Self::Bms => slice.bms = Some(state.bms),
+      Self::FcSensors => slice.fc_sensors = Some(state.fc_sensors),
+      Self::Gps => {
+        slice.gps = Some(state.gps.clone());
+        slice.gps_valid = Some(state.gps_valid);
+      }
+      Self::Reco => {
+        slice.reco = Some(state.reco.clone());
+        slice.reco_valid = Some(state.reco_valid);
+        slice.reco_disagreement = Some(state.reco_disagreement);
+      }
+      Self::Valve(index) => slice
+        .valve_states
+        .push((index as u8, state.valve_states[index].clone())),
+      Self::Sensor(index) => slice
+        .sensor_readings
+        .push((index as u8, state.sensor_readings[index].clone())),
+    }
+  }
+}
+
+/// Compresses vehicle states too large for one frame into frames which each
+/// hold as many parts of the state as fit.
+///
+/// Every part earns credit for each frame it waits, more quickly the higher
+/// its priority, and each frame takes the parts with the most credit. Each
+/// frame can be decoded on its own, so a lost frame only delays the parts it
+/// held until they are next sent.
+#[derive(Clone, Debug, Default)]
+pub struct VehicleStateSlicer {
+  fingerprint: Option<u32>,
+  credits: Vec<u64>,
+}
+
+impl VehicleStateSlicer {
+  /// Creates a slicer where every part of the state starts out equally due.
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Compresses the most overdue parts of a state which fit in the buffer
+  /// into a single frame, returning the number of bytes written.
+  pub fn encode(
+    &mut self,
+    state: &VehicleState,
+    buf: &mut [u8],
+    schema: &VehicleStateCompressionSchema,
+    priorities: &SlicePriorities,
+  ) -> Result<usize, VehicleStateCompaqError> {
+    let compressed = state.deflate_with_schema(schema)?;
+
+    let parts: Vec<Part> = [Part::Bms, Part::FcSensors, Part::Gps, Part::Reco]
+      .into_iter()
+      .chain((0..compressed.valve_states.len()).map(Part::Valve))
+      .chain((0..compressed.sensor_readings.len()).map(Part::Sensor))
+      .collect();
+
+    if self.fingerprint != Some(schema.fingerprint)
+      || self.credits.len() != parts.len()
+    {
+      self.fingerprint = Some(schema.fingerprint);
+      self.credits = vec![0; parts.len()];
+    }
+
+    for (credit, part) in self.credits.iter_mut().zip(&parts) {
+      *credit += u64::from(part.priority(priorities)) + 1;
+    }
+
+    let mut order: Vec<usize> = (0..parts.len()).collect();
+    order.sort_by(|a, b| self.credits[*b].cmp(&self.credits[*a]));
+
+    // the lengths of the valve and sensor lists may each take a second byte
+    // once they pass 127 entries.
+    let empty = CompaqFrame::Slice(CompressedVehicleStateSlice::default());
+    let mut size = SCHEMA_FINGERPRINT_SIZE
+      + 2
+      + postcard::experimental::serialized_size(&empty)
+        .map_err(VehicleStateCompaqError::Serialize)?;
+
+    let mut slice = CompressedVehicleStateSlice::default();
+    let mut sent = Vec::new();
+
+    for index in order {
+      let part_size = parts[index].size(&compressed)?;
+
+      if size + part_size <= buf.len() {
+        parts[index].add_to(&mut slice, &compressed);
+        sent.push(index);
+        size += part_size;
+      }
+    }
+
+    if sent.is_empty() {
+      return Err(VehicleStateCompaqError::Serialize(
+        postcard::Error::SerializeBufferFull,
+      ));
+    }
+
+    let size = write_frame(buf, schema.fingerprint, &CompaqFrame::Slice(slice))?;
+
+    for index in sent {
+      self.credits[index] = 0;
+    }
+
+    Ok(size)
+  }
+}
@@ -0,0 +1,357 @@
+//! Two-of-three voting across the RECO MCUs. Each field of the voted state is
+//! the median or majority of the MCUs which reported, so one diverged MCU
+//! can't pull the state sent over TEL away from the other two.
+
+use super::RecoTriState;
+use crate::comm::RecoState;
+use bytecheck;
+use compaq::compress;
+use rkyv;
+use serde::{Deserialize, Serialize};
+
+/// The mean radius of the Earth in meters, used to turn differences in
+/// latitude and longitude into distances.
+const EARTH_RADIUS: f32 = 6_371_000.0;
+
+/// How far an MCU may stray from the voted RECO state before it's flagged as
+/// disagreeing with it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RecoVoteThresholds {
+  /// The largest angle between an MCU's attitude and the voted attitude, in
+  /// radians.
+  pub attitude: f32,
+
+  /// The largest horizontal distance between an MCU's position and the voted
+  /// position, in meters.
+  pub position: f32,
+
+  /// The largest difference between an MCU's altitude and the voted altitude,
+  /// in meters.
+  pub altitude: f32,
+
+  /// The largest difference between an MCU's velocity and the voted velocity,
+  /// in m/s.
+  pub velocity: f32,
+}
+
+impl Default for RecoVoteThresholds {
+  fn default() -> Self {
+    Self {
+      attitude: 5.0_f32.to_radians(),
+      position: 50.0,
+      altitude: 25.0,
+      velocity: 5.0,
+    }
+  }
+}
+
+/// How the state reported by one RECO MCU disagrees with the vote across all
+/// three.
+#[compress(CompressedRecoMcuDisagreement, exact_size)]
+#[derive(
+  Clone,
+  Copy,
+  Debug,
+  Default,
+  Deserialize,
+  PartialEq,
+  Serialize,
+  rkyv::Archive,
+  rkyv::Serialize,
+  rkyv::Deserialize,
+)]
+#[archive_attr(derive(bytecheck::CheckBytes))]
+pub struct RecoMcuDisagreement {
+  /// The MCU didn't report a state, so it took no part in the vote.
+  #[pack]
+  pub missing: bool,
+
+  /// The MCU's attitude is further from the voted attitude than allowed.
+  #[pack]
+  pub attitude: bool,
+
+  /// The MCU's horizontal position is further from the voted position than
+  /// allowed.
+  #[pack]
+  pub position: bool,
+
+  /// The MCU's altitude is further from the voted altitude than allowed.
+  #[pack]
+  pub altitude: bool,
+
+  /// The MCU's velocity is further from the voted velocity than allowed.
+  #[pack]
+  pub velocity: bool,
+
+  /// At least one of the MCU's flags differs from the majority.
+  #[pack]
+  pub flags: bool,
+}
+
+impl RecoMcuDisagreement {
+  /// Whether the MCU is missing or disagrees with the vote in any way.
+  pub fn any(&self) -> bool {
+    self.missing
+      || self.attitude
+      || self.position
+      || self.altitude
+      || self.velocity
+      || self.flags
+  }
+}
+
+impl RecoTriState {
+  /// Votes across the MCUs which reported a state, taking the median of every
+  /// measurement and the majority of every flag, or returns `None` if none of
+  /// them did.
+  ///
+  /// With two MCUs, measurements are their mean and a flag is only set if
+  /// both set it, so a single MCU can never set a flag such as
+  /// `stage1_enabled` on its own.
+  pub fn vote(&self) -> Option<RecoState> {
+    let present: Vec<&RecoState> = self.0.iter().flatten().collect();
+    if present.is_empty() {
+      return None;
+    }
+
+    let mut voted = RecoState {
+      quaternion: vote_quaternion(&present),
+      lla_pos: median_array(&present, |reco| reco.lla_pos),
+      velocity: median_array(&present, |reco| reco.velocity),
+      g_bias: median_array(&present, |reco| reco.g_bias),
+      a_bias: median_array(&present, |reco| reco.a_bias),
+      g_sf: median_array(&present, |reco| reco.g_sf),
+      a_sf: median_array(&present, |reco| reco.a_sf),
+      lin_accel: median_array(&present, |reco| reco.lin_accel),
+      angular_rate: median_array(&present, |reco| reco.angular_rate),
+      mag_data: median_array(&present, |reco| reco.mag_data),
+      reco_driver_faults: majority_bits(&present, |reco| {
+        reco.reco_driver_faults
+      }),
+      ..RecoState::default()
+    };
+
+    macro_rules! median_scalars {
+      ($($field:ident),*) => {
+        $(voted.$field = median(present.iter().map(|reco| reco.$field));)*
+      };
+    }
+
+    median_scalars!(
+      temperature,
+      pressure,
+      vref_ch1_dr1,
+      vref_ch1_dr2,
+      vref_ch2_dr1,
+      vref_ch2_dr2,
+      sns1_current,
+      sns2_current,
+      v_rail_24v,
+      v_rail_3v3,
+      fading_memory_baro,
+      fading_memory_gps
+    );
+
+    macro_rules! majority_flags {
+      ($($field:ident),*) => {
+        $(voted.$field = majority(present.iter().map(|reco| reco.$field));)*
+      };
+    }
+
+    majority_flags!(
+      stage1_enabled,
+      stage2_enabled,
+      reco_recvd_launch,
+      ekf_blown_up,
+      drouge_timer_enable,
+      main_timer_enable,
+      rbf_enabled
+    );
+
+    Some(voted)
+  }
+
+  /// Compares the state reported by each MCU to the vote across all of them.
+  pub fn disagreement(
+    &self,
+    thresholds: &RecoVoteThresholds,
+  ) -> [RecoMcuDisagreement; 3] {
+    let Some(voted) = self.vote() else {
+      return [RecoMcuDisagreement {
+        missing: true,
+        ..RecoMcuDisagreement::default()
+      }; 3];
+    };
+
+    self.0.each_ref().map(|reco| match reco {
+      Some(reco) => compare(reco, &voted, thresholds),
+      None => RecoMcuDisagreement {
+        missing: true,
+        ..RecoMcuDisagreement::default()
+      },
+    })
+  }
+}
+
+/// Flags how far one MCU's state is from the voted state.
+fn compare(
+  reco: &RecoState,
+  voted: &RecoState,
+  thresholds: &RecoVoteThresholds,
+) -> RecoMcuDisagreement {
+  // a difference which can't be measured, such as one involving NaN, always
+  // counts as a disagreement.
+  let exceeds = |difference: f32, threshold: f32| {
+    difference > threshold || difference.is_nan()
+  };
+
+  let attitude = normalize(reco.quaternion).map_or(f32::NAN, |quaternion| {
+    angle_between(quaternion, voted.quaternion)
+  });
+
+  let [longitude, latitude, altitude] = reco.lla_pos;
+  let [voted_longitude, voted_latitude, voted_altitude] = voted.lla_pos;
+  let north = (latitude - voted_latitude).to_radians() * EARTH_RADIUS;
+  let east = (longitude - voted_longitude).to_radians()
+    * voted_latitude.to_radians().cos()
+    * EARTH_RADIUS;
+
+  let velocity = reco
+    .velocity
+    .iter()
+    .zip(voted.velocity)
+    .map(|(component, voted)| (component - voted).powi(2))
+    .sum::<f32>()
+    .sqrt();
+
+  let flags = [
+    reco.stage1_enabled != voted.stage1_enabled,
+    reco.stage2_enabled != voted.stage2_enabled,
+    reco.reco_recvd_launch != voted.reco_recvd_launch,
+    reco.ekf_blown_up != voted.ekf_blown_up,
+    reco.drouge_timer_enable != voted.drouge_timer_enable,
+    reco.main_timer_enable != voted.main_timer_enable,
+    reco.rbf_enabled != voted.rbf_enabled,
+  ];
+
+  RecoMcuDisagreement {
+    missing: false,
+    attitude: exceeds(attitude, thresholds.attitude),
+    position: exceeds(north.hypot(east), thresholds.position),
+    altitude: exceeds((altitude - voted_altitude).abs(), thresholds.altitude),
+    velocity: exceeds(velocity, thresholds.velocity),
+    flags: flags.contains(&true),
+  }
+}
+
+/// Returns the median of some values, or the mean of the middle two if there
+/// are an even number of them.
+fn median(values: impl Iterator<Item = f32>) -> f32 {
+  let mut values: Vec<f32> = values.collect();
+  values.sort_by(f32::total_cmp);
+
+  let middle = values.len() / 2;
+  if values.len().is_multiple_of(2) {
+    (values[middle - 1] + values[middle]) / 2.0
+  } else {
+    values[middle]
+  }
+}
+
+fn median_array<const N: usize>(
+  present: &[&RecoState],
+  select: impl Fn(&RecoState) -> [f32; N],
+) -> [f32; N] {
+  std::array::from_fn(|index| {
+    median(present.iter().map(|reco| select(reco)[index]))
+  })
+}
+
+/// Whether more than half of some flags are set.
+fn majority(flags: impl ExactSizeIterator<Item = bool>) -> bool {
+  let count = flags.len();
+  flags.filter(|flag| *flag).count() * 2 > count
+}
+
+/// Takes the majority of every bit of some bytes, such as fault bitfields.
+fn majority_bits<const N: usize>(
+  present: &[&RecoState],
+  select: impl Fn(&RecoState) -> [u8; N],
+) -> [u8; N] {
+  std::array::from_fn(|index| {
+    (0..8).fold(0, |byte, bit| {
+      let set = majority(
+        present
+          .iter()
+          .map(|reco| select(reco)[index] >> bit & 1 == 1),
+      );
+
+      byte | u8::from(set) << bit
+    })
+  })
+}
+
+/// Scales a quaternion to unit length, or returns `None` if it has no
+/// meaningful direction.
+fn normalize(quaternion: [f32; 4]) -> Option<[f32; 4]> {
+  let norm = quaternion.iter().map(|q| q * q).sum::<f32>().sqrt();
+
+  if norm.is_finite() && norm > f32::EPSILON {
+    Some(quaternion.map(|q| q / norm))
+  } else {
+    None
+  }
+}
+
+fn dot(a: [f32; 4], b: [f32; 4]) -> f32 {
+  a.iter().zip(b).map(|(a, b)| a * b).sum()
+}
+
+/// The angle of the rotation between two unit quaternions, in radians. A
+/// quaternion and its negation are the same rotation, so the sign of their
+/// product is ignored.
+fn angle_between(a: [f32; 4], b: [f32; 4]) -> f32 {
+  2.0 * dot(a, b).abs().min(1.0).acos()
+}
+
+/// Votes on attitude, which can't be done per component like the other
+/// measurements as the result must stay a unit quaternion. With three MCUs
+/// the attitude closest to the other two wins, and with two their attitudes
+/// are averaged once they have the same sign.
+fn vote_quaternion(present: &[&RecoState]) -> [f32; 4] {
+  let quaternions: Vec<[f32; 4]> = present
+    .iter()
+    .filter_map(|reco| normalize(reco.quaternion))
+    .collect();
+
+  let voted = match quaternions.as_slice() {
+    [] => None,
+    [only] => Some(*only),
+    [first, second] => {
+      let sign = if dot(*first, *second) < 0.0 {
+        -1.0
+      } else {
+        1.0
+      };
+      normalize(std::array::from_fn(|index| {
+        first[index] + sign * second[index]
+      }))
+      .or(Some(*first))
+    }
+    _ => quaternions
+      .iter()
+      .min_by(|a, b| {
+        let spread = |quaternion: &[f32; 4]| {
+          quaternions
+            .iter()
+            .map(|other| angle_between(*quaternion, *other))
+            .sum::<f32>()
+        };
+
+        spread(a).total_cmp(&spread(b))
+      })
+      .copied(),
+  };
+
+  voted.unwrap_or(RecoState::default().quaternion)
+}
@@ -0,0 +1,1279 @@
+use crate::comm::{NodeMapping, SensorType, ValveState, VehicleState};
+use std::{
+  cell::{Cell, RefCell},
+  collections::{HashSet, VecDeque},
+  fmt,
+  time::{Duration, Instant},
+};
+
+/// A parsed condition, written in the subset of Python that sequences already
+/// use for checks such as `PT1.read() > 500*psi and not BBV.is_open()`.
+///
+/// Names are resolved against the `VehicleState` when the condition is
+/// evaluated, in the same order the sequence prelude would shadow them:
+/// valves, then sensors, then the constants exported by `common`.
+///
+/// The one extension over Python is `<condition> for <duration>`, such as
+/// `PT1 > 500 for 200ms`, which only holds once the condition has been met
+/// continuously for that long. The start of the current streak is kept in the
+/// expression itself, so a parsed condition must be evaluated every loop.
+///
+/// Python's `min`, `max` and `abs` may also be called, along with
+/// `moving_average(<expr>, <duration>)`, which likewise keeps its samples in
+/// the expression.
+///
+/// Sensors also have `is_stale()`, which holds while the board a reading came
+/// from has stopped reporting, so conditions can avoid acting on old data.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Expr(Node);
+
+/// A node of a parsed `Expr`.
+#[derive(Clone, Debug, PartialEq)]
+enum Node {
+  Literal(Value),
+  Name(String),
+  Method(String, Method),
+  Not(Box<Node>),
+  And(Box<Node>, Box<Node>),
+  Or(Box<Node>, Box<Node>),
+  Compare(Box<Node>, Comparison, Box<Node>),
+  Arithmetic(Box<Node>, Operator, Box<Node>),
+  Negate(Box<Node>),
+  Sustained(Box<Node>, Duration, Cell<Option<Instant>>),
+  Call(Function, Vec<Node>),
+  MovingAverage(Box<Node>, Duration, RefCell<Samples>),
+}
+
+/// The value of an expression.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Value {
+  /// A number, such as a sensor reading in its base unit.
+  Number(f64),
+
+  /// The result of a comparison or of `True` or `False`.
+  Bool(bool),
+
+  /// The state of a valve, or `OPEN` or `CLOSED`.
+  Valve(ValveState),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Method {
+  Read,
+  IsStale,
+  IsOpen,
+  IsClosed,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Function {
+  Min,
+  Max,
+  Abs,
+}
+
+/// The values an expression took within the window of a moving average,
+/// oldest first, along with their running total.
+#[derive(Clone, Debug, Default, PartialEq)]
+struct Samples {
+  values: VecDeque<(Instant, f64)>,
+  total: f64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Comparison {
+  Less,
+  LessEqual,
+  Greater,
+  GreaterEqual,
+  Equal,
+  NotEqual,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Operator {
+  Add,
+  Subtract,
+  Multiply,
+  Divide,
+}
+
+type Result<T> = std::result::Result<T, ConditionError>;
+
+/// Why an expression couldn't be parsed, validated or evaluated.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ConditionError {
+  /// A character which can't start any token, and the column it's at.
+  UnexpectedCharacter(char, usize),
+
+  /// A token out of place, and the column it starts at.
+  UnexpectedToken(String, usize),
+
+  /// The expression ended before it was complete.
+  UnexpectedEnd,
+
+  /// A method other than those sensors and valves have.
+  UnknownMethod(String),
+
+  /// A call to a function which isn't supported.
+  UnknownFunction(String),
+
+  /// A supported function called with the wrong arguments.
+  WrongArguments(&'static str),
+
+  /// A name which isn't a valve, sensor or constant.
+  UnknownName(String),
+
+  /// A valve method called on something other than a valve.
+  NotAValve(String),
+
+  /// A sensor method called on something other than a sensor.
+  NotASensor(String),
+
+  /// A value used where one of another type was expected.
+  TypeMismatch(&'static str),
+
+  /// A chained comparison whose middle operand keeps state, such as a moving
+  /// average, which each of its comparisons would otherwise keep apart.
+  StatefulChain,
+}
+
+impl fmt::Display for ConditionError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::UnexpectedCharacter(c, at) => {
+        write!(f, "Unexpected character '{c}' at column {}.", at + 1)
+      }
+      Self::UnexpectedToken(token, at) => {
+        write!(f, "Unexpected '{token}' at column {}.", at + 1)
+      }
+      Self::UnexpectedEnd => write!(f, "The condition ended unexpectedly."),
+      Self::UnknownMethod(method) => write!(
+        f,
+        "'{method}' is not supported. Use read(), is_stale(), is_open() or \
+        is_closed()."
+      ),
+      Self::UnknownFunction(function) => write!(
+        f,
+        "'{function}' is not supported. Use min(), max(), abs() or \
+        moving_average()."
+      ),
+      Self::WrongArguments(message) => write!(f, "{message}"),
+      Self::UnknownName(name) => {
+        write!(f, "'{name}' is not a known valve, sensor or constant.")
+      }
+      Self::NotAValve(name) => write!(f, "'{name}' is not a valve."),
+      Self::NotASensor(name) => write!(f, "'{name}' is not a sensor."),
+      Self::TypeMismatch(message) => write!(f, "{message}"),
+      Self::StatefulChain => write!(
+        f,
+        "moving_average() and 'for' can't be in the middle of a chained \
+        comparison. Split it into comparisons joined by 'and' instead."
+      ),
+    }
+  }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+  Number(f64),
+  Ident(String),
+  Symbol(&'static str),
+}
+
+impl fmt::Display for Token {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::Number(n) => write!(f, "{n}"),
+      Self::Ident(i) => write!(f, "{i}"),
+      Self::Symbol(s) => write!(f, "{s}"),
+    }
+  }
+}
+
+const SYMBOLS: [&str; 14] = [
+  "<=", ">=", "==", "!=", "<", ">", "+", "-", "*", "/", "(", ")", ".", ",",
+];
+
+fn tokenize(source: &str) -> Result<Vec<(Token, usize)>> {
+  let mut tokens = Vec::new();
+  let mut chars = source.char_indices().peekable();
+
+  while let Some(&(start, c)) = chars.peek() {
+    if c.is_whitespace() {
+      chars.next();
+    } else if c.is_ascii_digit()
+      || c == '.' && source[start + 1..].starts_with(|d: char| d.is_ascii_digit())
+    {
+      let mut end = start;
+      while let Some(&(i, d)) = chars.peek() {
+        let exponent_sign = (d == '-' || d == '+')
+          && matches!(source[..i].chars().last(), Some('e' | 'E'));
+
+        if d.is_ascii_digit() || matches!(d, '.' | 'e' | 'E' | '_') || exponent_sign
+        {
+          end = i + d.len_utf8();
+          chars.next();
+        } else {
+          break;
+        }
+      }
+
+      let literal = source[start..end].replace('_', "");
+      let number = literal.parse::<f64>().map_err(|_| {
+        ConditionError::UnexpectedToken(source[start..end].to_owned(), start)
+      })?;
+      tokens.push((Token::Number(number), start));
+    } else if c.is_alphabetic() || c == '_' {
+      let mut end = start;
+      while let Some(&(i, d)) = chars.peek() {
+        if d.is_alphanumeric() || d == '_' {
+          end = i + d.len_utf8();
+          chars.next();
+        } else {
+          break;
+        }
+      }
+
+      tokens.push((Token::Ident(source[start..end].to_owned()), start));
+    } else {
+      let Some(symbol) = SYMBOLS
+        .iter()
+        .find(|symbol| source[start..].starts_with(**symbol))
+      else {
+        return Err(ConditionError::UnexpectedCharacter(c, start));
+      };
+
+      for _ in 0..symbol.len() {
+        chars.next();
+      }
+
+      tokens.push((Token::Symbol(symbol), start));
+    }
+  }
+
+  Ok(tokens)
+}
+
+/// Recursive descent parser following Python's operator precedence.
+struct Parser {
+  tokens: Vec<(Token, usize)>,
+  position: usize,
+}
+
+impl Parser {
+  fn peek(&self) -> Option<&Token> {
+    self.tokens.get(self.position).map(|(token, _)| token)
+  }
+
+  fn next(&mut self) -> Result<Token> {
+    let token = self
+      .tokens
+      .get(self.position)
+      .map(|(token, _)| token.clone())
+      .ok_or(ConditionError::UnexpectedEnd)?;
+
+    self.position += 1;
+    Ok(token)
+  }
+
+  fn unexpected(&self) -> ConditionError {
+    match self.tokens.get(self.position) {
+      Some((token, at)) => ConditionError::UnexpectedToken(token.to_string(), *at),
+      None => ConditionError::UnexpectedEnd,
+    }
+  }
+
+  fn eat_symbol(&mut self, symbol: &str) -> bool {
+    if matches!(self.peek(), Some(Token::Symbol(s)) if *s == symbol) {
+      self.position += 1;
+      true
+    } else {
+      false
+    }
+  }
+
+  fn eat_keyword(&mut self, keyword: &str) -> bool {
+    if matches!(self.peek(), Some(Token::Ident(i)) if i == keyword) {
+      self.position += 1;
+      true
+    } else {
+      false
+    }
+  }
+
+  fn expect_symbol(&mut self, symbol: &str) -> Result<()> {
+    if self.eat_symbol(symbol) {
+      Ok(())
+    } else {
+      Err(self.unexpected())
+    }
+  }
+
+  fn or(&mut self) -> Result<Node> {
+    let mut left = self.and()?;
+
+    while self.eat_keyword("or") {
+      left = Node::Or(Box::new(left), Box::new(self.and()?));
+    }
+
+    Ok(left)
+  }
+
+  fn and(&mut self) -> Result<Node> {
+    let mut left = self.not()?;
+
+    while self.eat_keyword("and") {
+      left = Node::And(Box::new(left), Box::new(self.not()?));
+    }
+
+    Ok(left)
+  }
+
+  fn not(&mut self) -> Result<Node> {
+    if self.eat_keyword("not") {
+      return Ok(Node::Not(Box::new(self.not()?)));
+    }
+
+    let comparison = self.comparison()?;
+
+    if self.eat_keyword("for") {
+      let duration = self.duration()?;
+      Ok(Node::Sustained(Box::new(comparison), duration, Cell::new(None)))
+    } else {
+      Ok(comparison)
+    }
+  }
+
+  /// Parses the duration after `for`, written as `200ms`, `200 * ms` or a bare
+  /// number of seconds.
+  fn duration(&mut self) -> Result<Duration> {
+    let at = self.position;
+
+    let Token::Number(count) = self.next()? else {
+      self.position = at;
+      return Err(self.unexpected());
+    };
+
+    let explicit_unit = self.eat_symbol("*");
+    let scale = match self.peek() {
+      Some(Token::Ident(unit)) if unit == "s" => 1.0,
+      Some(Token::Ident(unit)) if unit == "ms" => 0.001,
+      Some(Token::Ident(unit)) if unit == "us" => 0.000001,
+      _ if explicit_unit => return Err(self.unexpected()),
+      _ => return Ok(Duration::from_secs_f64(count.max(0.0))),
+    };
+
+    self.position += 1;
+    Ok(Duration::from_secs_f64((count * scale).max(0.0)))
+  }
+
+  /// Chained comparisons such as `0 < PT1 < 100` are expanded into a
+  /// conjunction, as Python does. Middle operands which keep state are
+  /// rejected, as each comparison would get a copy with its own state.
+  fn comparison(&mut self) -> Result<Node> {
+    let mut left = self.sum()?;
+    let mut chain: Option<Node> = None;
+
+    loop {
+      let comparison = match self.peek() {
+        Some(Token::Symbol("<")) => Comparison::Less,
+        Some(Token::Symbol("<=")) => Comparison::LessEqual,
+        Some(Token::Symbol(">")) => Comparison::Greater,
+        Some(Token::Symbol(">=")) => Comparison::GreaterEqual,
+        Some(Token::Symbol("==")) => Comparison::Equal,
+        Some(Token::Symbol("!=")) => Comparison::NotEqual,
+        _ => break,
+      };
+
+      if chain.is_some() && left.is_stateful() {
+        return Err(ConditionError::StatefulChain);
+      }
+
+      self.position += 1;
+      let right = self.sum()?;
+      let link =
+        Node::Compare(Box::new(left), comparison, Box::new(right.clone()));
+
+      chain = Some(match chain {
+        Some(previous) => Node::And(Box::new(previous), Box::new(link)),
+        None => link,
+      });
+      left = right;
+    }
+
+    Ok(chain.unwrap_or(left))
+  }
+
+  fn sum(&mut self) -> Result<Node> {
+    let mut left = self.term()?;
+
+    loop {
+      let operator = if self.eat_symbol("+") {
+        Operator::Add
+      } else if self.eat_symbol("-") {
+        Operator::Subtract
+      } else {
+        break;
+      };
+
+      left = Node::Arithmetic(Box::new(left), operator, Box::new(self.term()?));
+    }
+
+    Ok(left)
+  }
+
+  fn term(&mut self) -> Result<Node> {
+    let mut left = self.unary()?;
+
+    loop {
+      let operator = if self.eat_symbol("*") {
+        Operator::Multiply
+      } else if self.eat_symbol("/") {
+        Operator::Divide
+      } else {
+        break;
+      };
+
+      left =
+        Node::Arithmetic(Box::new(left), operator, Box::new(self.unary()?));
+    }
+
+    Ok(left)
+  }
+
+  fn unary(&mut self) -> Result<Node> {
+    if self.eat_symbol("-") {
+      Ok(Node::Negate(Box::new(self.unary()?)))
+    } else if self.eat_symbol("+") {
+      self.unary()
+    } else {
+      self.primary()
+    }
+  }
+
+  fn primary(&mut self) -> Result<Node> {
+    let at = self.position;
+
+    match self.next()? {
+      Token::Number(n) => Ok(Node::Literal(Value::Number(n))),
+      Token::Symbol("(") => {
+        let inner = self.or()?;
+        self.expect_symbol(")")?;
+        Ok(inner)
+      }
+      Token::Ident(i) if i == "True" => Ok(Node::Literal(Value::Bool(true))),
+      Token::Ident(i) if i == "False" => Ok(Node::Literal(Value::Bool(false))),
+      Token::Ident(i) if matches!(i.as_str(), "and" | "or" | "not" | "for") => {
+        self.position = at;
+        Err(self.unexpected())
+      }
+      Token::Ident(name) => {
+        if self.eat_symbol("(") {
+          return self.call(name);
+        }
+
+        if !self.eat_symbol(".") {
+          return Ok(Node::Name(name));
+        }
+
+        let method = match self.next()? {
+          Token::Ident(method) => method,
+          _ => {
+            self.position -= 1;
+            return Err(self.unexpected());
+          }
+        };
+
+        let method = match method.as_str() {
+          "read" => Method::Read,
+          "is_stale" => Method::IsStale,
+          "is_open" => Method::IsOpen,
+          "is_closed" => Method::IsClosed,
+          _ => return Err(ConditionError::UnknownMethod(method)),
+        };
+
+        self.expect_symbol("(")?;
+        self.expect_symbol(")")?;
+        Ok(Node::Method(name, method))
+      }
+      _ => {
+        self.position = at;
+        Err(self.unexpected())
+      }
+    }
+  }
+
+  /// Parses the arguments of a function call, after its opening parenthesis.
+  fn call(&mut self, name: String) -> Result<Node> {
+    if name == "moving_average" {
+      let inner = self.or()?;
+
+      if !self.eat_symbol(",") {
+        return Err(ConditionError::WrongArguments(
+          "moving_average() takes a value and a window, such as \
+          moving_average(PT1, 500ms).",
+        ));
+      }
+
+      let window = self.duration()?;
+      self.expect_symbol(")")?;
+
+      return Ok(Node::MovingAverage(
+        Box::new(inner),
+        window,
+        RefCell::new(Samples::default()),
+      ));
+    }
+
+    let function = match name.as_str() {
+      "min" => Function::Min,
+      "max" => Function::Max,
+      "abs" => Function::Abs,
+      _ => return Err(ConditionError::UnknownFunction(name)),
+    };
+
+    let mut arguments = Vec::new();
+
+    if !self.eat_symbol(")") {
+      loop {
+        arguments.push(self.or()?);
+
+        if self.eat_symbol(")") {
+          break;
+        }
+
+        self.expect_symbol(",")?;
+      }
+    }
+
+    match function {
+      Function::Abs if arguments.len() != 1 => Err(
+        ConditionError::WrongArguments("abs() takes exactly one argument."),
+      ),
+      Function::Min | Function::Max if arguments.len() < 2 => {
+        Err(ConditionError::WrongArguments(
+          "min() and max() take at least two arguments.",
+        ))
+      }
+      _ => Ok(Node::Call(function, arguments)),
+    }
+  }
+}
+
+/// Parses a condition string into an expression tree.
+pub fn parse(source: &str) -> Result<Expr> {
+  let mut parser = Parser {
+    tokens: tokenize(source)?,
+    position: 0,
+  };
+
+  let expr = parser.or()?;
+
+  if parser.position < parser.tokens.len() {
+    return Err(parser.unexpected());
+  }
+
+  Ok(Expr(expr))
+}
+
+/// The constants `common` exports into every sequence. Units are expressed in
+/// the same base units that `Measurement` values are stored in.
+fn constant(name: &str) -> Option<Value> {
+  let value = match name {
+    "OPEN" => return Some(Value::Valve(ValveState::Open)),
+    "CLOSED" => return Some(Value::Valve(ValveState::Closed)),
+    "A" | "s" | "V" | "lbf" | "psi" | "K" => 1.0,
+    "mA" | "ms" | "mV" => 0.001,
+    "us" => 0.000001,
+    _ => return None,
+  };
+
+  Some(Value::Number(value))
+}
+
+impl Value {
+  /// The value as a number, with `True` and `False` as 1 and 0.
+  pub fn number(self) -> Result<f64> {
+    match self {
+      Value::Number(n) => Ok(n),
+      Value::Bool(b) => Ok(b as u8 as f64),
+      Value::Valve(_) => Err(ConditionError::TypeMismatch(
+        "A valve state can only be compared with OPEN or CLOSED.",
+      )),
+    }
+  }
+
+  fn truthy(self) -> Result<bool> {
+    match self {
+      Value::Bool(b) => Ok(b),
+      Value::Number(n) => Ok(n != 0.0),
+      Value::Valve(_) => Err(ConditionError::TypeMismatch(
+        "A valve cannot be used as a condition on its own. Use is_open() or is_closed().",
+      )),
+    }
+  }
+}
+
+impl Expr {
+  /// Evaluates the expression against the given vehicle state.
+  pub fn evaluate(&self, state: &VehicleState) -> Result<Value> {
+    self.0.evaluate(state)
+  }
+
+  /// Evaluates the expression as a condition, following Python truthiness.
+  pub fn is_met(&self, state: &VehicleState) -> Result<bool> {
+    self.0.is_met(state)
+  }
+}
+
+impl Node {
+  fn evaluate(&self, state: &VehicleState) -> Result<Value> {
+    match self {
+      Node::Literal(value) => Ok(*value),
+      Node::Name(name) => {
+        if let Some(valve) = state.valve_states.get(name) {
+          Ok(Value::Valve(valve.actual))
+        } else if let Some(measurement) = state.sensor_readings.get(name) {
+          Ok(Value::Number(measurement.value))
+        } else {
+          constant(name).ok_or_else(|| ConditionError::UnknownName(name.clone()))
+        }
+      }
+      Node::Method(name, Method::Read) => state
+        .sensor_readings
+        .get(name)
+        .map(|measurement| Value::Number(measurement.value))
+        .ok_or_else(|| ConditionError::UnknownName(name.clone())),
+      Node::Method(name, Method::IsStale) => state
+        .sensor_readings
+        .get(name)
+        .map(|measurement| Value::Bool(measurement.stale))
+        .ok_or_else(|| ConditionError::UnknownName(name.clone())),
+      Node::Method(name, method) => {
+        let valve = state
+          .valve_states
+          .get(name)
+          .ok_or_else(|| ConditionError::UnknownName(name.clone()))?;
+
+        Ok(Value::Bool(match method {
+          Method::IsOpen => valve.actual == ValveState::Open,
+          _ => valve.actual == ValveState::Closed,
+        }))
+      }
+      Node::Not(inner) => Ok(Value::Bool(!inner.evaluate(state)?.truthy()?)),
+      // both sides are always evaluated so that any `for` timers within them
+      // stay up to date, but the result still short-circuits like Python.
+      Node::And(left, right) => {
+        let (left, right) = (left.is_met(state), right.is_met(state));
+        Ok(Value::Bool(left? && right?))
+      }
+      Node::Or(left, right) => {
+        let (left, right) = (left.is_met(state), right.is_met(state));
+        Ok(Value::Bool(left? || right?))
+      }
+      Node::Sustained(inner, duration, since) => {
+        let now = Instant::now();
+
+        if !inner.is_met(state).unwrap_or(false) {
+          since.set(None);
+          return Ok(Value::Bool(false));
+        }
+
+        let start = since.get().unwrap_or(now);
+        since.set(Some(start));
+        Ok(Value::Bool(now.duration_since(start) >= *duration))
+      }
+      Node::Negate(inner) => Ok(Value::Number(-inner.evaluate(state)?.number()?)),
+      Node::Call(function, arguments) => {
+        let values = arguments
+          .iter()
+          .map(|argument| argument.evaluate(state)?.number())
+          .collect::<Result<Vec<f64>>>()?;
+
+        Ok(Value::Number(match function {
+          Function::Min => values.into_iter().fold(f64::INFINITY, f64::min),
+          Function::Max => values.into_iter().fold(f64::NEG_INFINITY, f64::max),
+          Function::Abs => values[0].abs(),
+        }))
+      }
+      Node::MovingAverage(inner, window, samples) => {
+        let now = Instant::now();
+        let value = inner.evaluate(state)?.number()?;
+        let mut samples = samples.borrow_mut();
+
+        samples.values.push_back((now, value));
+        samples.total += value;
+
+        while let Some(&(at, old)) = samples.values.front() {
+          if now.duration_since(at) <= *window {
+            break;
+          }
+
+          samples.values.pop_front();
+          samples.total -= old;
+        }
+
+        Ok(Value::Number(samples.total / samples.values.len() as f64))
+      }
+      Node::Arithmetic(left, operator, right) => {
+        let left = left.evaluate(state)?.number()?;
+        let right = right.evaluate(state)?.number()?;
+
+        Ok(Value::Number(match operator {
+          Operator::Add => left + right,
+          Operator::Subtract => left - right,
+          Operator::Multiply => left * right,
+          Operator::Divide => left / right,
+        }))
+      }
+      Node::Compare(left, comparison, right) => {
+        let left = left.evaluate(state)?;
+        let right = right.evaluate(state)?;
+
+        let result = match (left, right) {
+          (Value::Valve(left), Value::Valve(right)) => match comparison {
+            Comparison::Equal => left == right,
+            Comparison::NotEqual => left != right,
+            _ => {
+              return Err(ConditionError::TypeMismatch(
+                "Valve states can only be compared with == or !=.",
+              ))
+            }
+          },
+          (left, right) => {
+            let (left, right) = (left.number()?, right.number()?);
+
+            match comparison {
+              Comparison::Less => left < right,
+              Comparison::LessEqual => left <= right,
+              Comparison::Greater => left > right,
+              Comparison::GreaterEqual => left >= right,
+              Comparison::Equal => left == right,
+              Comparison::NotEqual => left != right,
+            }
+          }
+        };
+
+        Ok(Value::Bool(result))
+      }
+    }
+  }
+
+  fn is_met(&self, state: &VehicleState) -> Result<bool> {
+    self.evaluate(state)?.truthy()
+  }
+
+  /// Whether evaluating the node updates state kept within it, as moving
+  /// averages and `for` do.
+  fn is_stateful(&self) -> bool {
+    match self {
+      Node::Literal(_) | Node::Name(_) | Node::Method(..) => false,
+      Node::Sustained(..) | Node::MovingAverage(..) => true,
+      Node::Not(inner) | Node::Negate(inner) => inner.is_stateful(),
+      Node::Call(_, arguments) => arguments.iter().any(Node::is_stateful),
+      Node::And(left, right)
+      | Node::Or(left, right)
+      | Node::Compare(left, _, right)
+      | Node::Arithmetic(left, _, right) => {
+        left.is_stateful() || right.is_stateful()
+      }
+    }
+  }
+}
+
+/// Checks that every valve and sensor named in the expression exists in the
+/// given mappings, so that typos are caught when a condition is configured
+/// instead of every time it is evaluated.
+pub fn validate(expr: &Expr, mappings: &[NodeMapping]) -> Result<()> {
+  validate_node(&expr.0, mappings)
+}
+
+fn validate_node(node: &Node, mappings: &[NodeMapping]) -> Result<()> {
+  let is_valve = |name: &str| {
+    mappings
+      .iter()
+      .any(|m| m.sensor_type == SensorType::Valve && m.text_id == name)
+  };
+
+  // valves also publish their voltage and current as `<valve>_V` and
+  // `<valve>_I` sensor readings.
+  let is_sensor = |name: &str| {
+    mappings.iter().any(|m| {
+      if m.sensor_type == SensorType::Valve {
+        name
+          .strip_prefix(m.text_id.as_str())
+          .is_some_and(|suffix| suffix == "_V" || suffix == "_I")
+      } else {
+        m.text_id == name
+      }
+    })
+  };
+
+  match node {
+    Node::Literal(_) => Ok(()),
+    Node::Name(name) => {
+      if is_valve(name) || is_sensor(name) || constant(name).is_some() {
+        Ok(())
+      } else {
+        Err(ConditionError::UnknownName(name.clone()))
+      }
+    }
+    Node::Method(name, Method::Read | Method::IsStale) if !is_sensor(name) => {
+      Err(if is_valve(name) {
+        ConditionError::NotASensor(name.clone())
+      } else {
+        ConditionError::UnknownName(name.clone())
+      })
+    }
+    Node::Method(name, Method::IsOpen | Method::IsClosed) if !is_valve(name) => {
+      Err(if is_sensor(name) {
+        ConditionError::NotAValve(name.clone())
+      } else {
+        ConditionError::UnknownName(name.clone())
+      })
+    }
+    Node::Method(..) => Ok(()),
+    Node::Not(inner)
+    | Node::Negate(inner)
+    | Node::Sustained(inner, ..)
+    | Node::MovingAverage(inner, ..) => validate_node(inner, mappings),
+    Node::Call(_, arguments) => arguments
+      .iter()
+      .try_for_each(|argument| validate_node(argument, mappings)),
+    Node::And(left, right)
+    | Node::Or(left, right)
+    | Node::Compare(left, _, right)
+    | Node::Arithmetic(left, _, right) => {
+      validate_node(left, mappings)?;
+      validate_node(right, mappings)
+    }
+  }
+}
+
+/// Collects the names of every valve, sensor and constant the expression
+/// refers to.
+pub fn names<'a>(expr: &'a Expr, found: &mut Vec<&'a str>) {
+  node_names(&expr.0, found);
+}
+
+fn node_names<'a>(node: &'a Node, found: &mut Vec<&'a str>) {
+  match node {
+    Node::Literal(_) => {}
+    Node::Name(name) | Node::Method(name, _) => found.push(name),
+    Node::Not(inner)
+    | Node::Negate(inner)
+    | Node::Sustained(inner, ..)
+    | Node::MovingAverage(inner, ..) => node_names(inner, found),
+    Node::Call(_, arguments) => {
+      for argument in arguments {
+        node_names(argument, found);
+      }
+    }
+    Node::And(left, right)
+    | Node::Or(left, right)
+    | Node::Compare(left, _, right)
+    | Node::Arithmetic(left, _, right) => {
+      node_names(left, found);
+      node_names(right, found);
+    }
+  }
+}
+
+/// Parses and validates the expression of every virtual sensor in a set of
+/// mappings, ordered so that each comes after any other virtual sensors it
+/// reads. Fails if any expression is invalid or if virtual sensors read each
+/// other in a cycle.
+pub fn order_virtual_sensors(
+  mappings: &[NodeMapping],
+) -> std::result::Result<Vec<(&NodeMapping, Expr)>, String> {
+  let mut pending = Vec::new();
+
+  for mapping in mappings {
+    let Some(source) = &mapping.expression else {
+      continue;
+    };
+
+    let expression = parse(source)
+      .and_then(|expression| {
+        validate(&expression, mappings)?;
+        Ok(expression)
+      })
+      .map_err(|e| {
+        format!(
+          "The expression of virtual sensor '{}' is invalid: {e}",
+          mapping.text_id
+        )
+      })?;
+
+    pending.push((mapping, expression));
+  }
+
+  // repeatedly move over every sensor which only reads virtual sensors that
+  // have already been ordered. if none can be moved, the rest form a cycle.
+  let mut ordered = Vec::with_capacity(pending.len());
+
+  while !pending.is_empty() {
+    let unordered: HashSet<&str> = pending
+      .iter()
+      .map(|(mapping, _)| mapping.text_id.as_str())
+      .collect();
+
+    let (ready, blocked): (Vec<_>, Vec<_>) =
+      pending.into_iter().partition(|(_, expression)| {
+        let mut inputs = Vec::new();
+        names(expression, &mut inputs);
+        !inputs.iter().any(|name| unordered.contains(name))
+      });
+
+    if ready.is_empty() {
+      let names: Vec<&str> = blocked
+        .iter()
+        .map(|(mapping, _)| mapping.text_id.as_str())
+        .collect();
+
+      return Err(format!(
+        "Virtual sensors {} read each other in a cycle.",
+        names.join(", ")
+      ));
+    }
+
+    ordered.extend(ready);
+    pending = blocked;
+  }
+
+  Ok(ordered)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::comm::{sam::Unit, CompositeValveState, Measurement};
+  use std::thread;
+
+  fn mapping(text_id: &str, sensor_type: SensorType) -> NodeMapping {
+    NodeMapping {
+      text_id: text_id.to_string(),
+      board_id: "sam-01".to_string(),
+      sensor_type,
+      ..Default::default()
+    }
+  }
+
+  fn mappings() -> Vec<NodeMapping> {
+    vec![
+      mapping("PT1", SensorType::Pt),
+      mapping("BBV", SensorType::Valve),
+    ]
+  }
+
+  fn read(state: &mut VehicleState, text_id: &str, value: f64, stale: bool) {
+    state.sensor_readings.insert(
+      text_id.to_string(),
+      Measurement {
+        value,
+        unit: Unit::Psi,
+        timestamp: 0.0,
+        board_id: Some("sam-01".to_string()),
+        stale,
+      },
+    );
+  }
+
+  fn evaluate(source: &str) -> Value {
+    parse(source)
+      .unwrap()
+      .evaluate(&VehicleState::new())
+      .unwrap()
+  }
+
+  #[test]
+  fn arithmetic_follows_python_precedence() {
+    assert_eq!(evaluate("1 + 2 * 3"), Value::Number(7.0));
+    assert_eq!(evaluate("(1 + 2) * 3"), Value::Number(9.0));
+    assert_eq!(evaluate("-2 * 3 + 1"), Value::Number(-5.0));
+    assert_eq!(evaluate("8 / 4 / 2"), Value::Number(1.0));
+    assert_eq!(evaluate("10 - 4 - 3"), Value::Number(3.0));
+    assert_eq!(evaluate("1_000 + 2e-3 * 500"), Value::Number(1001.0));
+  }
+
+  #[test]
+  fn boolean_operators_follow_python_precedence() {
+    assert_eq!(
+      parse("A or B and C").unwrap(),
+      parse("A or (B and C)").unwrap()
+    );
+    assert_eq!(
+      parse("not A and B").unwrap(),
+      parse("(not A) and B").unwrap()
+    );
+    assert_eq!(
+      parse("A + 1 > B * 2 and C").unwrap(),
+      parse("((A + 1) > (B * 2)) and C").unwrap()
+    );
+
+    assert_eq!(evaluate("True or False and False"), Value::Bool(true));
+    assert_eq!(evaluate("not False and False"), Value::Bool(false));
+    assert_eq!(evaluate("not 1 > 2"), Value::Bool(true));
+  }
+
+  #[test]
+  fn comparisons_chain_like_python() {
+    assert_eq!(
+      parse("0 < A < 10").unwrap(),
+      parse("0 < A and A < 10").unwrap()
+    );
+
+    assert_eq!(evaluate("0 < 5 < 10"), Value::Bool(true));
+    assert_eq!(evaluate("0 < 5 > 10"), Value::Bool(false));
+    assert_eq!(evaluate("1 <= 1 == 1 != 2"), Value::Bool(true));
+  }
+
+  #[test]
+  fn stateful_operands_cannot_be_shared_by_chained_comparisons() {
+    let error = |source| parse(source).unwrap_err();
+
+    assert_eq!(
+      error("0 < moving_average(A, 1s) < 10"),
+      ConditionError::StatefulChain
+    );
+    assert_eq!(
+      error("0 < 2 * abs(moving_average(A, 1s)) < 10"),
+      ConditionError::StatefulChain
+    );
+    assert_eq!(
+      error("0 < (A > 1 for 1s) < 2"),
+      ConditionError::StatefulChain
+    );
+
+    // they may still be at either end, or compared once.
+    assert!(parse("moving_average(A, 1s) < B < 10").is_ok());
+    assert!(parse("0 < B < moving_average(A, 1s)").is_ok());
+    assert!(parse("0 < moving_average(A, 1s)").is_ok());
+  }
+
+  #[test]
+  fn bad_input_is_rejected_with_its_position() {
+    let error = |source| parse(source).unwrap_err();
+
+    assert_eq!(error("PT1 >"), ConditionError::UnexpectedEnd);
+    assert_eq!(error("(PT1 > 1"), ConditionError::UnexpectedEnd);
+    assert_eq!(error(""), ConditionError::UnexpectedEnd);
+    assert_eq!(
+      error("PT1 > > 2"),
+      ConditionError::UnexpectedToken(">".to_string(), 6)
+    );
+    assert_eq!(
+      error("PT1 > 1 )"),
+      ConditionError::UnexpectedToken(")".to_string(), 8)
+    );
+    assert_eq!(
+      error("PT1 $ 2"),
+      ConditionError::UnexpectedCharacter('$', 4)
+    );
+    assert_eq!(
+      error("PT1 > 1 and or 2"),
+      ConditionError::UnexpectedToken("or".to_string(), 12)
+    );
+    assert_eq!(
+      error("PT1.foo()"),
+      ConditionError::UnknownMethod("foo".to_string())
+    );
+    assert_eq!(
+      error("sqrt(PT1)"),
+      ConditionError::UnknownFunction("sqrt".to_string())
+    );
+    assert!(matches!(
+      error("abs(1, 2)"),
+      ConditionError::WrongArguments(_)
+    ));
+    assert!(matches!(error("min(1)"), ConditionError::WrongArguments(_)));
+    assert!(matches!(
+      error("moving_average(PT1)"),
+      ConditionError::WrongArguments(_)
+    ));
+  }
+
+  #[test]
+  fn durations_accept_units_or_seconds() {
+    let sustained = |source| match parse(source).unwrap().0 {
+      Node::Sustained(_, duration, _) => duration,
+      other => panic!("{other:?} is not a `for` condition"),
+    };
+
+    assert_eq!(sustained("PT1 > 1 for 200ms"), Duration::from_millis(200));
+    assert_eq!(
+      sustained("PT1 > 1 for 200 * ms"),
+      Duration::from_millis(200)
+    );
+    assert_eq!(sustained("PT1 > 1 for 0.2"), Duration::from_millis(200));
+    assert_eq!(sustained("PT1 > 1 for 3s"), Duration::from_secs(3));
+    assert_eq!(sustained("PT1 > 1 for 50us"), Duration::from_micros(50));
+
+    assert_eq!(
+      parse("PT1 > 1 for").unwrap_err(),
+      ConditionError::UnexpectedEnd
+    );
+    assert_eq!(
+      parse("PT1 > 1 for 2 * kg").unwrap_err(),
+      ConditionError::UnexpectedToken("kg".to_string(), 16)
+    );
+    assert_eq!(
+      parse("PT1 > 1 for ms").unwrap_err(),
+      ConditionError::UnexpectedToken("ms".to_string(), 12)
+    );
+  }
+
+  #[test]
+  fn unknown_names_are_caught_by_validation() {
+    let mappings = mappings();
+    let validated = |source| validate(&parse(source).unwrap(), &mappings);
+
+    assert_eq!(validated("PT1 > 500 * psi and BBV.is_open()"), Ok(()));
+    assert_eq!(validated("BBV == OPEN and BBV_V > 20"), Ok(()));
+    assert_eq!(
+      validated("PT2 > 500"),
+      Err(ConditionError::UnknownName("PT2".to_string()))
+    );
+    assert_eq!(
+      validated("max(PT1, OX_PT) > 500"),
+      Err(ConditionError::UnknownName("OX_PT".to_string()))
+    );
+    assert_eq!(
+      validated("PT1.is_open()"),
+      Err(ConditionError::NotAValve("PT1".to_string()))
+    );
+    assert_eq!(
+      validated("BBV.read() > 1"),
+      Err(ConditionError::NotASensor("BBV".to_string()))
+    );
+    assert_eq!(
+      validated("BBV_X > 1"),
+      Err(ConditionError::UnknownName("BBV_X".to_string()))
+    );
+  }
+
+  #[test]
+  fn names_resolve_to_valves_then_sensors_then_constants() {
+    let mut state = VehicleState::new();
+    read(&mut state, "PT1", 600.0, false);
+    state.valve_states.insert(
+      "BBV".to_string(),
+      CompositeValveState {
+        commanded: ValveState::Open,
+        actual: ValveState::Closed,
+      },
+    );
+
+    let met = |source| parse(source).unwrap().is_met(&state).unwrap();
+
+    assert!(met("PT1 > 500 * psi"));
+    assert!(met("PT1.read() == PT1"));
+    assert!(met(
+      "BBV == CLOSED and BBV.is_closed() and not BBV.is_open()"
+    ));
+    assert!(met("100 * ms == 0.1"));
+
+    assert_eq!(
+      parse("BBV > 1").unwrap().is_met(&state),
+      Err(ConditionError::TypeMismatch(
+        "A valve state can only be compared with OPEN or CLOSED."
+      ))
+    );
+  }
+
+  #[test]
+  fn missing_readings_are_errors() {
+    let state = VehicleState::new();
+    let evaluated = |source| parse(source).unwrap().is_met(&state);
+
+    assert_eq!(
+      evaluated("PT1 > 500"),
+      Err(ConditionError::UnknownName("PT1".to_string()))
+    );
+    assert_eq!(
+      evaluated("PT1.is_stale()"),
+      Err(ConditionError::UnknownName("PT1".to_string()))
+    );
+    assert_eq!(
+      evaluated("BBV.is_open()"),
+      Err(ConditionError::UnknownName("BBV".to_string()))
+    );
+  }
+
+  #[test]
+  fn stale_readings_can_be_checked() {
+    let mut state = VehicleState::new();
+    let condition = parse("PT1 > 500 and not PT1.is_stale()").unwrap();
+
+    read(&mut state, "PT1", 600.0, false);
+    assert_eq!(condition.is_met(&state), Ok(true));
+
+    read(&mut state, "PT1", 600.0, true);
+    assert_eq!(condition.is_met(&state), Ok(false));
+  }
+
+  #[test]
+  fn sustained_conditions_hold_only_after_their_duration() {
+    let mut state = VehicleState::new();
+    let condition = parse("PT1 > 500 for 50ms").unwrap();
+
+    read(&mut state, "PT1", 600.0, false);
+    assert_eq!(condition.is_met(&state), Ok(false));
+
+    thread::sleep(Duration::from_millis(60));
+    assert_eq!(condition.is_met(&state), Ok(true));
+    assert_eq!(condition.is_met(&state), Ok(true));
+  }
+
+  #[test]
+  fn sustained_conditions_restart_when_broken() {
+    let mut state = VehicleState::new();
+    let condition = parse("PT1 > 500 for 50ms").unwrap();
+
+    read(&mut state, "PT1", 600.0, false);
+    assert_eq!(condition.is_met(&state), Ok(false));
+    thread::sleep(Duration::from_millis(60));
+
+    // a single sample within limits restarts the timer
+    read(&mut state, "PT1", 400.0, false);
+    assert_eq!(condition.is_met(&state), Ok(false));
+
+    read(&mut state, "PT1", 600.0, false);
+    assert_eq!(condition.is_met(&state), Ok(false));
+
+    thread::sleep(Duration::from_millis(60));
+    assert_eq!(condition.is_met(&state), Ok(true));
+  }
+
+  #[test]
+  fn sustained_conditions_restart_when_a_reading_is_missing() {
+    let mut state = VehicleState::new();
+    let condition = parse("PT1 > 500 for 50ms").unwrap();
+
+    read(&mut state, "PT1", 600.0, false);
+    assert_eq!(condition.is_met(&state), Ok(false));
+    thread::sleep(Duration::from_millis(60));
+
+    // a reading that can't be evaluated counts as the condition not holding
+    state.sensor_readings.remove("PT1");
+    assert_eq!(condition.is_met(&state), Ok(false));
+
+    read(&mut state, "PT1", 600.0, false);
+    assert_eq!(condition.is_met(&state), Ok(false));
+  }
+
+  #[test]
+  fn sustained_timers_keep_running_behind_a_false_conjunct() {
+    let mut state = VehicleState::new();
+    let condition = parse("ARMED and PT1 > 500 for 50ms").unwrap();
+
+    read(&mut state, "ARMED", 0.0, false);
+    read(&mut state, "PT1", 600.0, false);
+    assert_eq!(condition.is_met(&state), Ok(false));
+    thread::sleep(Duration::from_millis(60));
+
+    read(&mut state, "ARMED", 1.0, false);
+    assert_eq!(condition.is_met(&state), Ok(true));
+  }
+}
@@ -21,6 +21,11 @@ pub const LAYOUT_FINGERPRINT: &str =
 /// subsystems.
 pub mod comm;
 
+/// Conditions and expressions over the vehicle state, written in the subset of
+/// Python that sequences use, as evaluated by the flight computer for triggers,
+/// abort stages and virtual sensors.
+pub mod condition;
+
 /// All components necessary to run Python sequences.
 #[cfg(feature = "sequences")]
 pub mod sequence;
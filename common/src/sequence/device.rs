@@ -2,16 +2,18 @@ use crate::comm::{Measurement, ValveState, flight::SequenceDomainCommand};
 use pyo3::{
   pyclass,
   pyclass::CompareOp,
+  pyfunction,
   pymethods,
   IntoPy,
   PyAny,
   PyObject,
+  PyRef,
   PyResult,
   Python,
 };
 use rkyv::Deserialize;
 
-use super::{read_vehicle_state, synchronize, PostcardSerializationError, RkyvDeserializationError, SendCommandIpcError, SensorNotFoundError, ValveNotFoundError, SOCKET, SYNCHRONIZER};
+use super::{read_vehicle_state, send_command, synchronize, RkyvDeserializationError, SensorNotFoundError, StaleReadingError, ValveNotFoundError, SYNCHRONIZER};
 
 /// A Python-exposed class that allows for interacting with a sensor.
 #[pyclass]
@@ -20,17 +22,9 @@ pub struct Sensor {
   name: String,
 }
 
-#[pymethods]
 impl Sensor {
-  /// Creates a new sensor with the specified text identifier.
-  #[new]
-  pub fn new(name: String) -> Self {
-    Sensor { name }
-  }
-
-  /// Reads the latest sensor measurements by indexing into the global vehicle
-  /// state.
-  pub fn read(&self) -> PyResult<PyObject> {
+  /// Reads the latest measurement of the sensor from the global vehicle state.
+  fn measurement(&self) -> PyResult<Measurement> {
     let mut sync = synchronize(&SYNCHRONIZER)?;
     // this unwrap() should never fail as synchronize ensures the value is Some.
     let vehicle_state = read_vehicle_state(sync.as_mut().unwrap())?;
@@ -40,25 +34,56 @@ impl Sensor {
         "Couldn't find the sensor named '{}' in sensor_readings.", self.name
       )));
     };
-    
-    // TODO: logic can be isolated in a function
-    let measurement: Measurement = match measurement.deserialize(&mut rkyv::Infallible) {
-      Ok(m) => m,
-      Err(e) => return Err(RkyvDeserializationError::new_err(format!(
+
+    match measurement.deserialize(&mut rkyv::Infallible) {
+      Ok(m) => Ok(m),
+      Err(e) => Err(RkyvDeserializationError::new_err(format!(
         "rkyv couldn't deserialize the measurement from '{}': {e}", self.name
       ))),
-    };
+    }
+  }
+}
 
-    // done to ensure we aren't reading during the gil.
-    drop(vehicle_state);
+#[pymethods]
+impl Sensor {
+  /// Creates a new sensor with the specified text identifier.
+  #[new]
+  pub fn new(name: String) -> Self {
+    Sensor { name }
+  }
+
+  /// Reads the latest sensor measurements by indexing into the global vehicle
+  /// state. If `fresh` is set, a stale reading raises `StaleReadingError`
+  /// instead of being returned.
+  #[pyo3(signature = (fresh = false))]
+  pub fn read(&self, fresh: bool) -> PyResult<PyObject> {
+    let measurement = self.measurement()?;
+
+    if fresh && measurement.stale {
+      return Err(StaleReadingError::new_err(format!(
+        "The reading of '{}' is stale.", self.name
+      )));
+    }
 
     Ok(Python::with_gil(move |py| {
       measurement.into_py(py)
     }))
   }
 
+  /// Determines if the latest reading is stale, as its board has stopped
+  /// reporting or it has never been reported.
+  pub fn is_stale(&self) -> PyResult<bool> {
+    Ok(self.measurement()?.stale)
+  }
+
+  /// Returns the UNIX timestamp, in seconds, at which the latest reading was
+  /// sampled, or zero if the sensor has never reported.
+  pub fn timestamp(&self) -> PyResult<f64> {
+    Ok(self.measurement()?.timestamp)
+  }
+
   fn __richcmp__(&self, other: &PyAny, op: CompareOp) -> PyResult<bool> {
-    other.rich_compare(self.read()?, op)?.is_truthy()
+    other.rich_compare(self.read(false)?, op)?.is_truthy()
   }
 }
 
@@ -127,10 +152,11 @@ impl Valve {
     self.actuate(false)
   }
 
-  /// Instructs the SAM board to actuate a valve.
+  /// Instructs the SAM board to actuate a valve, raising
+  /// `ValveNotFoundError` if the FC has no mapping for it. If the FC doesn't
+  /// respond in time, `CommandTimeoutError` is raised, though the valve may
+  /// still actuate.
   pub fn actuate(&self, open: bool) -> PyResult<()> {
-    let mut buf: [u8; 1024] = [0; 1024];
-
     let state = if open {
       ValveState::Open
     } else {
@@ -141,19 +167,71 @@ impl Valve {
       valve: self.name.clone(),
       state
     };
-    
-    match postcard::to_slice(&command, &mut buf) {
-      Ok(s) => s,
-      Err(e) => return Err(PostcardSerializationError::new_err(format!(
-        "Postcard error in serializing an actuate valve command: {e}"
-      ))),
+
+    send_command(command, &format!("ActuateValve {}", self.name))
+  }
+}
+
+/// A Python-exposed claim on valves, which stops any other sequence from
+/// actuating them while it is held. Used as a context manager, the valves are
+/// claimed on entry and released on exit.
+#[pyclass]
+#[derive(Clone, Debug)]
+pub struct ValveClaim {
+  valves: Vec<String>,
+}
+
+impl ValveClaim {
+  fn describe(&self, action: &str) -> String {
+    format!("{action} {}", self.valves.join(", "))
+  }
+}
+
+#[pymethods]
+impl ValveClaim {
+  /// Claims the valves, raising `ValveClaimedError` without claiming any of
+  /// them if another sequence already claims one.
+  pub fn acquire(&self) -> PyResult<()> {
+    let command = SequenceDomainCommand::ClaimValves {
+      valves: self.valves.clone(),
     };
 
-    SOCKET.send(&buf).map_or_else(
-      |e| Err(SendCommandIpcError::new_err(format!(
-        "Error in sending actuate command to FC process via domain socket: {e}"
-      ))),
-      |_| Ok(())
-    )
+    send_command(command, &self.describe("ClaimValves"))
+  }
+
+  /// Releases the valves so that other sequences may actuate them.
+  pub fn release(&self) -> PyResult<()> {
+    let command = SequenceDomainCommand::ReleaseValves {
+      valves: self.valves.clone(),
+    };
+
+    send_command(command, &self.describe("ReleaseValves"))
+  }
+
+  fn __enter__(slf: PyRef<'_, Self>) -> PyResult<PyRef<'_, Self>> {
+    slf.acquire()?;
+    Ok(slf)
+  }
+
+  fn __exit__(
+    &self,
+    _exc_type: &PyAny,
+    _exc_value: &PyAny,
+    _traceback: &PyAny,
+  ) -> PyResult<bool> {
+    self.release()?;
+
+    // exceptions raised inside the block are never suppressed.
+    Ok(false)
+  }
+}
+
+/// A Python-exposed function which creates a claim on the given valves, to be
+/// used as `with claim(BBV, SWV):`.
+#[pyfunction]
+#[pyo3(signature = (*valves))]
+pub fn claim(valves: Vec<Valve>) -> ValveClaim {
+  ValveClaim {
+    valves: valves.iter().map(Valve::get_name).collect(),
   }
 }
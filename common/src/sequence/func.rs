@@ -1,36 +1,149 @@
-use super::{PostcardSerializationError, SendCommandIpcError, SOCKET};
-use crate::{comm::{flight::{SequenceDomainCommand, ValveSafeState}, ValveState}, sequence::{unit::Duration, Valve}};
+use super::send_command;
+use crate::{comm::{flight::{SequenceDomainCommand, ValveSafeState}, ArchivedSequenceStatus, ValveState, VehicleState}, sequence::{unit::Duration, Valve}};
 
 use pyo3::{pyclass, pyfunction, pymethods, PyAny, PyRef, PyRefMut, PyResult, types::PyDict, Python, PyObject, IntoPy};
-use std::{thread, time::Instant, collections::HashMap};
-use super::{read_vehicle_state, synchronize, SYNCHRONIZER};
+use std::{thread, time::Instant, collections::HashMap, process};
+use super::{read_vehicle_state, synchronize, MMAP_PATH, SYNCHRONIZER};
 use crate::comm::reco::SequenceCommand;
+use mmap_sync::synchronizer::Synchronizer;
+
+/// How often a waiting sequence checks whether it has been paused, and so the
+/// longest it can overrun a pause or resume by.
+const PAUSE_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(10);
+
+/// Returns how many times this sequence has been stepped if it's paused, or
+/// `None` if it's running or wasn't started by the FC.
+///
+/// A missing or unreadable vehicle state, as for scripts run outside the FC or
+/// before the FC has first written it, counts as running so that waits still
+/// work there. The synchronizer is read directly rather than through
+/// `synchronize`, as its errors would only be thrown away.
+///
+/// This is polled by every waiting sequence, so only the statuses of runs are
+/// read, without validating the rest of the `VehicleState`.
+fn paused_step() -> Option<u32> {
+  let mut sync = SYNCHRONIZER.lock().ok()?;
+  let synchronizer =
+    sync.get_or_insert_with(|| Synchronizer::new(MMAP_PATH.as_ref()));
+
+  // the FC only runs sequences against the `common` module it was built with,
+  // so the layout always matches and needn't be validated.
+  let vehicle_state =
+    unsafe { synchronizer.read::<VehicleState>(false) }.ok()?;
+
+  let pid = process::id();
+  vehicle_state
+    .sequences
+    .values()
+    .find(|run| run.pid == pid)
+    .and_then(|run| match run.status {
+      ArchivedSequenceStatus::Paused { step } => Some(step),
+      _ => None,
+    })
+}
+
+/// Follows whether a single wait is held by a pause. The wait holds from when
+/// the pause is first seen until the sequence is resumed or stepped.
+#[derive(Default)]
+struct PauseGate {
+  /// The step count of the pause holding the wait.
+  held_at: Option<u32>,
+
+  /// Whether a step has released the wait from the pause.
+  stepped: bool,
+}
+
+impl PauseGate {
+  fn is_held(&mut self) -> bool {
+    self.observe(paused_step())
+  }
+
+  /// Updates the gate with the step count of the current pause, or `None` if
+  /// the sequence is running, and returns whether the wait is held.
+  fn observe(&mut self, paused_step: Option<u32>) -> bool {
+    let Some(step) = paused_step else {
+      *self = PauseGate::default();
+      return false;
+    };
+
+    if self.stepped {
+      return false;
+    }
+
+    match self.held_at {
+      Some(held_at) if held_at != step => {
+        self.stepped = true;
+        false
+      }
+      Some(_) => true,
+      None => {
+        self.held_at = Some(step);
+        true
+      }
+    }
+  }
+}
+
+/// Sleeps until `duration` has passed while not held by `gate`, so that time
+/// spent paused doesn't count towards it. Returns how long it was held for.
+fn sleep_unpaused(
+  duration: std::time::Duration,
+  gate: &mut PauseGate,
+) -> std::time::Duration {
+  let mut remaining = duration;
+  let mut held = std::time::Duration::ZERO;
+
+  loop {
+    let start = Instant::now();
+
+    if gate.is_held() {
+      thread::sleep(PAUSE_POLL_INTERVAL);
+      held += start.elapsed();
+    } else if remaining.is_zero() {
+      return held;
+    } else {
+      thread::sleep(remaining.min(PAUSE_POLL_INTERVAL));
+      remaining = remaining.saturating_sub(start.elapsed());
+    }
+  }
+}
 
-/// A Python-exposed function which waits the thread for the given duration.
+/// A Python-exposed function which waits the thread for the given duration,
+/// not counting any time the sequence spends paused.
 #[pyfunction]
-pub fn wait_for(duration: Duration) {
+pub fn wait_for(duration: Duration) -> PyResult<()> {
   // TODO: considering using a different way to sleep, possibly sleeping only
   // the GIL?
-  thread::sleep(duration.into());
+  sleep_unpaused(duration.into(), &mut PauseGate::default());
+  Ok(())
 }
 
 /// A Python-exposed function which waits until a condition function is true,
 /// given an optional timeout and interval between checking.
+///
+/// While the sequence is paused the condition isn't checked and the timeout
+/// doesn't run down.
 #[pyfunction]
 pub fn wait_until(
   condition: &PyAny,
   timeout: Option<Duration>,
   poll_interval: Option<Duration>,
 ) -> PyResult<()> {
-  let timeout = timeout.map_or(std::time::Duration::MAX, Into::into);
+  let mut remaining = timeout.map_or(std::time::Duration::MAX, Into::into);
 
   let interval =
     poll_interval.map_or(std::time::Duration::from_millis(10), Into::into);
 
-  let end_time = Instant::now() + timeout;
+  let mut gate = PauseGate::default();
+
+  // holds before every check so that a paused sequence can't move on because
+  // its condition became true.
+  sleep_unpaused(std::time::Duration::ZERO, &mut gate);
 
-  while !condition.call0()?.is_truthy()? && Instant::now() < end_time {
-    thread::sleep(interval);
+  while !condition.call0()?.is_truthy()? && !remaining.is_zero() {
+    let slice = interval.min(remaining);
+    sleep_unpaused(slice, &mut gate);
+    remaining -= slice;
   }
 
   Ok(())
@@ -59,21 +172,8 @@ pub fn create_abort_stage(stage_name: String, abort_condition: String, safe_valv
     valve_safe_states: rust_valve_states,
   };
 
-  // serialize command to send to FC
-  let command = match postcard::to_allocvec(&command) {
-    Ok(m) => m,
-    Err(e) => return Err(PostcardSerializationError::new_err(
-      format!("Couldn't serialize the new abort stage creation message for stage {stage_name}: {e}")
-    )),
-  };
-
-  // send command to FC
-  match SOCKET.send(&command) {
-    Ok(_) => println!("New abort stage configuration for stage {stage_name} sent successfully to FC for processing."),
-    Err(e) => return Err(SendCommandIpcError::new_err(
-      format!("Couldn't send the new abort stage configuration for stage {stage_name} to the FC process: {e}")
-    ))
-  }
+  send_command(command, &format!("CreateAbortStage {stage_name}"))?;
+  println!("New abort stage configuration for stage {stage_name} accepted by the FC.");
 
   Ok(())
 }
@@ -86,20 +186,8 @@ pub fn set_abort_stage(stage_name: String) -> PyResult<()> {
     stage_name: stage_name.clone(),
   };
 
-  let command = match postcard::to_allocvec(&command) {
-    Ok(m) => m,
-    Err(e) => return Err(PostcardSerializationError::new_err(
-      format!("Couldn't serialize the desired abort stage name: {e}")
-    )),
-  };
-
-  // send command to FC
-  match SOCKET.send(&command) {
-    Ok(_) => println!("Set abort stage to {stage_name} command sent successfully to FC for processing."),
-    Err(e) => return Err(SendCommandIpcError::new_err(
-      format!("Couldn't send the set-abort-stage change to the FC process: {e}")
-    ))
-  }
+  send_command(command, &format!("SetAbortStage {stage_name}"))?;
+  println!("Abort stage set to {stage_name} by the FC.");
 
   Ok(())
 }
@@ -110,19 +198,8 @@ pub fn send_sams_abort() -> PyResult<()> {
   // we need to change vehiclestate.abort_stage.aborted from false to true since we have now aborted (fc side)
   // also make sure to kill all sequences besides the abort stage sequence before we abort. (fc side)
   // in the abort stage seq itself, we don't abort if we are in a "FLIGHT" abort stage.
-  let abort_command = match postcard::to_allocvec(&SequenceDomainCommand::AbortViaStage) {
-    Ok(m) => m,
-    Err(e) => return Err(PostcardSerializationError::new_err(
-      format!("Couldn't serialize the AbortViaStage command: {e}")
-    )),
-  };
-
-  match SOCKET.send(&abort_command) {
-    Ok(_) => println!("AbortViaStage sent successfully to FC for processing."),
-    Err(e) => return Err(SendCommandIpcError::new_err(
-      format!("Couldn't send the AbortViaStage command to the FC process: {e}")
-    )),
-  }
+  send_command(SequenceDomainCommand::AbortViaStage, "AbortViaStage")?;
+  println!("AbortViaStage carried out by the FC.");
 
   Ok(())
 }
@@ -175,48 +252,23 @@ pub fn aborted_in_this_stage() -> PyResult<bool> {
 /// A Python-exposed function which runs the abort sequence if we are in the default stage, else the abort via stage.
 #[pyfunction]
 pub fn abort() -> PyResult<()> {
-  let mut abort_command = match postcard::to_allocvec(&SequenceDomainCommand::Abort) {
-      Ok(m) => m,
-      Err(e) => return Err(PostcardSerializationError::new_err(
-        format!("Couldn't serialize the Abort command: {e}")
-      )),
-    };
+  let (abort_command, name) = if curr_abort_condition().unwrap() != "DEFAULT" {
+    (SequenceDomainCommand::AbortViaStage, "AbortViaStage")
+  } else {
+    (SequenceDomainCommand::Abort, "Abort")
+  };
 
-  if curr_abort_condition().unwrap() != "DEFAULT" {
-    abort_command = match postcard::to_allocvec(&SequenceDomainCommand::AbortViaStage) {
-      Ok(m) => m,
-      Err(e) => return Err(PostcardSerializationError::new_err(
-        format!("Couldn't serialize the AbortViaStage command: {e}")
-      )),
-    };
-  }
+  send_command(abort_command, name)?;
+  println!("Abort sent successfully.");
 
-  match SOCKET.send(&abort_command) {
-    Ok(_) => println!("Abort sent successfully."),
-    Err(e) => return Err(SendCommandIpcError::new_err(
-      format!("Couldn't send the Abort command to the FC process: {e}")
-    )),
-  }
-  
   Ok(())
 }
 
 /// Python exposed function that sends a message to the RECO board that we have launched the rocket.
 #[pyfunction]
 pub fn send_reco_launch() -> PyResult<()> {
-  let command = match postcard::to_allocvec(&SequenceDomainCommand::RecoCommand(SequenceCommand::Launch)) {
-    Ok(m) => m,
-    Err(e) => return Err(PostcardSerializationError::new_err(
-      format!("Couldn't serialize the RecoLaunch command: {e}")
-    )),
-  };
-
-  match SOCKET.send(&command) {
-    Ok(_) => println!("RecoLaunch sent successfully to FC for processing."),
-    Err(e) => return Err(SendCommandIpcError::new_err(
-      format!("Couldn't send the RecoLaunch command to the FC process: {e}")
-    )),
-  }
+  send_command(SequenceDomainCommand::RecoCommand(SequenceCommand::Launch), "RecoLaunch")?;
+  println!("RecoLaunch queued by the FC for the RECO worker.");
 
   Ok(())
 }
@@ -224,19 +276,8 @@ pub fn send_reco_launch() -> PyResult<()> {
 /// Python exposed function that sends the EKF-initialization message to the RECO board.
 #[pyfunction]
 pub fn reco_init_ekf() -> PyResult<()> {
-  let command = match postcard::to_allocvec(&SequenceDomainCommand::RecoCommand(SequenceCommand::InitEKF)) {
-    Ok(m) => m,
-    Err(e) => return Err(PostcardSerializationError::new_err(
-      format!("Couldn't serialize the RecoInitEKF command: {e}")
-    )),
-  };
-
-  match SOCKET.send(&command) {
-    Ok(_) => println!("RecoInitEKF command sent successfully to FC for processing."),
-    Err(e) => return Err(SendCommandIpcError::new_err(
-      format!("Couldn't send the RecoInitEKF command to the FC process: {e}")
-    )),
-  }
+  send_command(SequenceDomainCommand::RecoCommand(SequenceCommand::InitEKF), "RecoInitEKF")?;
+  println!("RecoInitEKF command queued by the FC for the RECO worker.");
 
   Ok(())
 }
@@ -278,22 +319,17 @@ pub fn reco_recvd_launch() -> PyResult<bool> {
 /// A Python-exposed function which sends a message to the FC to arm the launch lug for the given SAM hostname.
 #[pyfunction]
 pub fn launch_lug_arm(sam_hostname: String, should_enable: bool) -> PyResult<()> {
-  let message = match postcard::to_allocvec(&SequenceDomainCommand::LaunchLugArm {
+  let command = SequenceDomainCommand::LaunchLugArm {
     sam_hostname: sam_hostname.clone(),
     should_enable,
-  }) {
-    Ok(m) => m,
-    Err(e) => return Err(PostcardSerializationError::new_err(
-      format!("Couldn't serialize the LaunchLugArm {} command for {sam_hostname}: {e}", if should_enable { "enable" } else { "disable" })
-    )),
   };
+  let name = format!(
+    "LaunchLugArm {} for {sam_hostname}",
+    if should_enable { "enable" } else { "disable" }
+  );
 
-  match SOCKET.send(&message) {
-    Ok(_) => println!("LaunchLugArm {} command for {sam_hostname} sent successfully to FC for processing.", if should_enable { "enable" } else { "disable" }),
-    Err(e) => return Err(SendCommandIpcError::new_err(
-      format!("Couldn't send the LaunchLugArm {} command for {sam_hostname} to the FC process: {e}", if should_enable { "enable" } else { "disable" })
-    )),
-  }
+  send_command(command, &name)?;
+  println!("{name} command carried out by the FC.");
 
   Ok(())
 }
@@ -301,22 +337,17 @@ pub fn launch_lug_arm(sam_hostname: String, should_enable: bool) -> PyResult<()>
 /// A Python-exposed function which sends a message to the FC to detonate the launch lug for the given SAM hostname.
 #[pyfunction]
 pub fn launch_lug_detonate(sam_hostname: String, should_enable: bool) -> PyResult<()> {
-  let message = match postcard::to_allocvec(&SequenceDomainCommand::LaunchLugDetonate {
+  let command = SequenceDomainCommand::LaunchLugDetonate {
     sam_hostname: sam_hostname.clone(),
     should_enable,
-  }) {
-    Ok(m) => m,
-    Err(e) => return Err(PostcardSerializationError::new_err(
-      format!("Couldn't serialize the LaunchLugDetonate {} command for {sam_hostname}: {e}", if should_enable { "enable" } else { "disable" })
-    )),
   };
+  let name = format!(
+    "LaunchLugDetonate {} for {sam_hostname}",
+    if should_enable { "enable" } else { "disable" }
+  );
 
-  match SOCKET.send(&message) {
-    Ok(_) => println!("LaunchLugDetonate {} command for {sam_hostname} sent successfully to FC for processing.", if should_enable { "enable" } else { "disable" }),
-    Err(e) => return Err(SendCommandIpcError::new_err(
-      format!("Couldn't send the LaunchLugDetonate {} command for {sam_hostname} to the FC process: {e}", if should_enable { "enable" } else { "disable" })
-    )),
-  }
+  send_command(command, &name)?;
+  println!("{name} command carried out by the FC.");
 
   Ok(())
 }
@@ -324,21 +355,13 @@ pub fn launch_lug_detonate(sam_hostname: String, should_enable: bool) -> PyResul
 /// A Python-exposed function which sends a message to the FC to [should_enable] the cameras on.
 #[pyfunction]
 pub fn sam_camera_toggle(should_enable: bool) -> PyResult<()> {
-  let message = match postcard::to_allocvec(&SequenceDomainCommand::CameraEnable {
-    should_enable,
-  }) {
-    Ok(m) => m,
-    Err(e) => return Err(PostcardSerializationError::new_err(
-      format!("Couldn't serialize the SamCameraToggle {} command: {e}", if should_enable { "enable" } else { "disable" })
-    )),
-  };
+  let name = format!(
+    "SamCameraToggle {}",
+    if should_enable { "enable" } else { "disable" }
+  );
 
-  match SOCKET.send(&message) {
-    Ok(_) => println!("SamCameraToggle {} command sent successfully to FC for processing.", if should_enable { "enable" } else { "disable" }),
-    Err(e) => return Err(SendCommandIpcError::new_err(
-      format!("Couldn't send the SamCameraToggle {} command to the FC process: {e}", if should_enable { "enable" } else { "disable" })
-    )),
-  }
+  send_command(SequenceDomainCommand::CameraEnable { should_enable }, &name)?;
+  println!("{name} command carried out by the FC.");
   
   Ok(())
 }
@@ -346,29 +369,13 @@ pub fn sam_camera_toggle(should_enable: bool) -> PyResult<()> {
 /// enabled is false, else to monitor servo disconnects.
 #[pyfunction]
 pub fn set_servo_disconnect_monitoring(enabled: bool) -> PyResult<()> {
-  let command = match postcard::to_allocvec(
-    &SequenceDomainCommand::SetServoDisconnectMonitoring { enabled },
-  ) {
-    Ok(m) => m,
-    Err(e) => {
-      return Err(PostcardSerializationError::new_err(
-        format!(
-          "Couldn't serialize the SetServoDisconnectMonitoring({}) command: {e}", enabled
-        ),
-      ))
-    }
-  };
+  let name = format!("SetServoDisconnectMonitoring({enabled})");
 
-  match SOCKET.send(&command) {
-    Ok(_) => println!("SetServoDisconnectMonitoring({}) sent successfully to FC for processing.", enabled),
-    Err(e) => {
-      return Err(SendCommandIpcError::new_err(
-        format!(
-          "Couldn't send the SetServoDisconnectMonitoring({}) command to the FC process: {e}", enabled
-        ),
-      ))
-    }
-  }
+  send_command(
+    SequenceDomainCommand::SetServoDisconnectMonitoring { enabled },
+    &name,
+  )?;
+  println!("{name} carried out by the FC.");
 
   Ok(())
 }
@@ -389,21 +396,24 @@ impl IntervalIterator {
     _self
   }
 
-  fn __next__(mut _self: PyRefMut<'_, Self>) -> Option<i64> {
+  fn __next__(mut _self: PyRefMut<'_, Self>) -> PyResult<Option<i64>> {
     if _self.iteration >= _self.total {
-      return None;
+      return Ok(None);
     }
 
-    let wait = _self.next_tick - Instant::now();
-    thread::sleep(wait);
+    let wait = _self.next_tick.saturating_duration_since(Instant::now());
+
+    // later ticks are pushed back by however long this one was held, so a
+    // pause delays the rest of the interval rather than skipping part of it.
+    let held = sleep_unpaused(wait, &mut PauseGate::default());
 
     let iteration = _self.iteration;
-    let next_tick = _self.next_tick + _self.period;
+    let next_tick = _self.next_tick + held + _self.period;
 
     _self.next_tick = next_tick;
     _self.iteration += 1;
 
-    Some(iteration)
+    Ok(Some(iteration))
   }
 }
 
@@ -418,3 +428,79 @@ pub fn interval(count: i64, period: Duration) -> IntervalIterator {
     total: count,
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn running_sequences_are_never_held() {
+    let mut gate = PauseGate::default();
+
+    assert!(!gate.observe(None));
+    assert!(!gate.observe(None));
+  }
+
+  #[test]
+  fn pauses_hold_until_stepped() {
+    let mut gate = PauseGate::default();
+
+    assert!(gate.observe(Some(0)));
+    assert!(gate.observe(Some(0)));
+    assert!(!gate.observe(Some(1)));
+  }
+
+  #[test]
+  fn a_step_releases_exactly_one_wait() {
+    let mut first = PauseGate::default();
+    assert!(first.observe(Some(0)));
+    assert!(!first.observe(Some(1)));
+
+    // the released wait stays released, even through further steps
+    assert!(!first.observe(Some(1)));
+    assert!(!first.observe(Some(2)));
+
+    // but the next wait is held again
+    let mut second = PauseGate::default();
+    assert!(second.observe(Some(2)));
+    assert!(second.observe(Some(2)));
+    assert!(!second.observe(Some(3)));
+  }
+
+  #[test]
+  fn steps_seen_outside_a_wait_are_ignored() {
+    // the sequence was stepped several times before this wait began, which
+    // must not release it.
+    let mut gate = PauseGate::default();
+    assert!(gate.observe(Some(5)));
+    assert!(gate.observe(Some(5)));
+  }
+
+  #[test]
+  fn resuming_clears_the_gate() {
+    let mut gate = PauseGate::default();
+    assert!(gate.observe(Some(0)));
+    assert!(!gate.observe(Some(1)));
+
+    assert!(!gate.observe(None));
+    assert!(gate.held_at.is_none());
+    assert!(!gate.stepped);
+
+    // pausing again holds the same wait once more
+    assert!(gate.observe(Some(1)));
+  }
+
+  #[test]
+  fn waits_outside_the_fc_are_never_held() {
+    // this test isn't a run the FC started, whether or not its vehicle state
+    // has been written.
+    assert_eq!(paused_step(), None);
+
+    let wait = std::time::Duration::from_millis(20);
+    let start = Instant::now();
+    let held = sleep_unpaused(wait, &mut PauseGate::default());
+
+    assert_eq!(held, std::time::Duration::ZERO);
+    assert!(start.elapsed() >= wait);
+  }
+}
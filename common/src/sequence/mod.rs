@@ -1,20 +1,42 @@
 mod device;
 mod func;
 mod unit;
+mod vehicle;
 
 pub use device::*;
 pub use exceptions::*;
 pub use func::*;
 pub use unit::*;
+pub use vehicle::*;
 
-use std::{os::unix::net::UnixDatagram, sync::{LazyLock, Mutex, MutexGuard}};
+use std::{
+  collections::HashMap,
+  env,
+  io,
+  os::{linux::net::SocketAddrExt, unix::net::{SocketAddr, UnixDatagram}},
+  process,
+  sync::{LazyLock, Mutex, MutexGuard},
+  time::Duration as StdDuration,
+};
 use mmap_sync::{guard::ReadResult, synchronizer::Synchronizer};
 
 use pyo3::{
-  pymodule, types::PyModule, wrap_pyfunction, Py, PyResult, Python
+  exceptions::PyValueError, pymodule, types::{PyDict, PyModule}, wrap_pyfunction, IntoPy,
+  Py, PyErr, PyResult, Python,
 };
 
-use crate::comm::{ValveState, VehicleState, flight::ValveSafeState};
+use crate::comm::{
+  SequenceArgument,
+  ValveState,
+  VehicleState,
+  flight::{
+    SequenceCommandError,
+    SequenceDomainCommand,
+    SequenceDomainRequest,
+    SequenceDomainResponse,
+    ValveSafeState,
+  },
+};
 
 /// A module containing all exception types declared for sequences.
 ///
@@ -29,10 +51,16 @@ mod exceptions {
   create_exception!(sequences, AbortError, pyo3::exceptions::PyException);
   create_exception!(sequences, ReadVehicleStateIpcError, pyo3::exceptions::PyException);
   create_exception!(sequences, SensorNotFoundError, pyo3::exceptions::PyException);
+  create_exception!(sequences, StaleReadingError, pyo3::exceptions::PyException);
   create_exception!(sequences, ValveNotFoundError, pyo3::exceptions::PyException);
   create_exception!(sequences, SendCommandIpcError, pyo3::exceptions::PyException);
+  create_exception!(sequences, CommandTimeoutError, SendCommandIpcError);
   create_exception!(sequences, PostcardSerializationError, pyo3::exceptions::PyException);
   create_exception!(sequences, RkyvDeserializationError, pyo3::exceptions::PyException);
+  create_exception!(sequences, AbortStageNotFoundError, pyo3::exceptions::PyException);
+  create_exception!(sequences, BoardNotFoundError, pyo3::exceptions::PyException);
+  create_exception!(sequences, CommandRejectedError, pyo3::exceptions::PyException);
+  create_exception!(sequences, ValveClaimedError, pyo3::exceptions::PyException);
 }
 
 /// Memory mapped file path where we "send" commands from sequences to the FC
@@ -40,6 +68,16 @@ mod exceptions {
 pub const SOCKET_PATH: &str = "/tmp/fc_sam_commands";
 /// Memory mapped file path where we read the VehicleState from the FC.
 pub const MMAP_PATH: &str = "/dev/shm/fc_vehicle_state";
+/// Environment variable through which the FC hands a sequence the JSON-encoded
+/// values of its parameters, so they never have to be spliced into the script.
+pub const ARGUMENTS_VAR: &str = "SEQUENCE_ARGUMENTS";
+/// Attribute of the `common` module holding a dict of the decoded arguments of
+/// the sequence, which the FC's prelude binds as globals of the script.
+pub const ARGUMENTS_ATTRIBUTE: &str = "__sequence_arguments__";
+/// Prefix of the abstract address each sequence binds its command socket to,
+/// followed by its process ID. The FC identifies the sender of a command by
+/// this address rather than trusting anything in the command itself.
+pub const COMMAND_ADDRESS_PREFIX: &str = "fc_sequence_";
 
 // let's break this one down:
 // Mutex<...> - required because this is a global variable, and a mutable
@@ -53,14 +91,132 @@ pub const MMAP_PATH: &str = "/dev/shm/fc_vehicle_state";
 pub(crate) static SYNCHRONIZER: Mutex<Option<Synchronizer>> =
   Mutex::new(None);
 
-pub(crate) static SOCKET: LazyLock<UnixDatagram> = LazyLock::new(|| {
-  let socket = UnixDatagram::unbound()
-    .expect("Can't initialize socket for ");
+/// How long a sequence waits for the FC to respond to a command before giving
+/// up on it.
+pub const RESPONSE_TIMEOUT: StdDuration = StdDuration::from_secs(2);
+
+/// The socket over which a sequence sends commands to the FC and receives its
+/// responses, along with the ID of the next command.
+pub(crate) struct CommandChannel {
+  socket: UnixDatagram,
+  next_id: u32,
+}
+
+// the socket is bound to an abstract address unique to this process so that
+// the FC has somewhere to send its responses, without leaving a file behind.
+pub(crate) static CHANNEL: LazyLock<Mutex<CommandChannel>> = LazyLock::new(|| {
+  let address = SocketAddr::from_abstract_name(
+    format!("{COMMAND_ADDRESS_PREFIX}{}", process::id())
+  ).expect("Can't create an address for the sequence command socket.");
+  let socket = UnixDatagram::bind_addr(&address)
+    .expect("Can't initialize socket for sending commands via IPC.");
   socket.connect(SOCKET_PATH)
     .expect("Can't connect to FC for sending commands via IPC.");
-  socket
+  socket.set_read_timeout(Some(RESPONSE_TIMEOUT))
+    .expect("Can't set the timeout of the sequence command socket.");
+
+  Mutex::new(CommandChannel { socket, next_id: 0 })
 });
 
+/// Sends a command to the FC and waits for it to be carried out, raising the
+/// exception matching the reason the FC gives if it wasn't. `name` describes
+/// the command in error messages.
+///
+/// If the FC doesn't respond within `RESPONSE_TIMEOUT`, `CommandTimeoutError`
+/// is raised instead. The FC may still have carried the command out, so unlike
+/// the other exceptions it doesn't mean the command was rejected.
+pub(crate) fn send_command(command: SequenceDomainCommand, name: &str) -> PyResult<()> {
+  let Ok(mut channel) = CHANNEL.lock() else {
+    return Err(SendCommandIpcError::new_err(format!(
+      "Couldn't send the {name} command: the command socket's mutex is poisoned."
+    )));
+  };
+
+  let id = channel.next_id;
+  channel.next_id = id.wrapping_add(1);
+
+  let request = match postcard::to_allocvec(&SequenceDomainRequest { id, command }) {
+    Ok(r) => r,
+    Err(e) => return Err(PostcardSerializationError::new_err(
+      format!("Couldn't serialize the {name} command: {e}")
+    )),
+  };
+
+  if let Err(e) = channel.socket.send(&request) {
+    return Err(SendCommandIpcError::new_err(
+      format!("Couldn't send the {name} command to the FC process: {e}")
+    ));
+  }
+
+  match receive_response(&channel.socket, id) {
+    Ok(result) => result.map_err(command_error),
+    Err(ResponseError::TimedOut) => Err(CommandTimeoutError::new_err(format!(
+      "The FC process didn't respond to the {name} command within \
+      {RESPONSE_TIMEOUT:?}. It may still carry the command out."
+    ))),
+    Err(ResponseError::Receive(e)) => Err(SendCommandIpcError::new_err(
+      format!("The FC process didn't respond to the {name} command: {e}")
+    )),
+    Err(ResponseError::Deserialize(e)) => Err(PostcardSerializationError::new_err(
+      format!("Couldn't deserialize the FC's response to the {name} command: {e}")
+    )),
+  }
+}
+
+/// Why no response to a command could be read from the FC.
+#[derive(Debug)]
+pub(crate) enum ResponseError {
+  /// The FC didn't respond before the socket's read timeout.
+  TimedOut,
+
+  /// The socket couldn't be read.
+  Receive(io::Error),
+
+  /// The response couldn't be deserialized.
+  Deserialize(postcard::Error),
+}
+
+/// Waits on `socket` for the FC's response to the command with the given ID,
+/// returning whether the FC carried it out.
+pub(crate) fn receive_response(
+  socket: &UnixDatagram,
+  id: u32,
+) -> Result<Result<(), SequenceCommandError>, ResponseError> {
+  let mut buf = [0; 1024];
+
+  loop {
+    let size = match socket.recv(&mut buf) {
+      Ok(s) => s,
+      Err(e) if matches!(e.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => {
+        return Err(ResponseError::TimedOut)
+      }
+      Err(e) => return Err(ResponseError::Receive(e)),
+    };
+
+    let response = postcard::from_bytes::<SequenceDomainResponse>(&buf[..size])
+      .map_err(ResponseError::Deserialize)?;
+
+    // responses to earlier commands which timed out are no longer awaited.
+    if response.id == id {
+      return Ok(response.result);
+    }
+  }
+}
+
+/// Converts the reason the FC gave for not carrying out a command into the
+/// matching Python exception.
+fn command_error(error: SequenceCommandError) -> PyErr {
+  let message = error.to_string();
+
+  match error {
+    SequenceCommandError::ValveNotFound(_) => ValveNotFoundError::new_err(message),
+    SequenceCommandError::AbortStageNotFound(_) => AbortStageNotFoundError::new_err(message),
+    SequenceCommandError::BoardNotFound(_) => BoardNotFoundError::new_err(message),
+    SequenceCommandError::ValveClaimed { .. } => ValveClaimedError::new_err(message),
+    SequenceCommandError::Rejected(_) => CommandRejectedError::new_err(message),
+  }
+}
+
 fn synchronize(synchronizer: &Mutex<Option<Synchronizer>>) -> PyResult<MutexGuard<'_, Option<Synchronizer>>> {
   let Ok(mut sync) = synchronizer.lock() else {
     eprintln!("Failed to lock global synchronizer: Mutex is poisoned.");
@@ -107,16 +263,51 @@ fn sequences(py: Python<'_>, module: &PyModule) -> PyResult<()> {
   module.add("K", Py::new(py, Temperature::new(1.0))?)?;
   module.add("__layout_fingerprint__", crate::LAYOUT_FINGERPRINT)?;
 
+  // decoded here and bound explicitly by the prelude, as `from common import *`
+  // would skip any argument whose name starts with an underscore.
+  let sequence_arguments = PyDict::new(py);
+
+  if let Ok(arguments) = env::var(ARGUMENTS_VAR) {
+    let arguments: HashMap<String, SequenceArgument> =
+      serde_json::from_str(&arguments).map_err(|e| {
+        PyValueError::new_err(format!("Couldn't decode the sequence arguments: {e}"))
+      })?;
+
+    for (name, argument) in arguments {
+      sequence_arguments.set_item(name, argument.into_py(py))?;
+    }
+  }
+
+  module.add(ARGUMENTS_ATTRIBUTE, sequence_arguments)?;
+
+  module.add("StaleReadingError", py.get_type::<StaleReadingError>())?;
+  module.add("ValveNotFoundError", py.get_type::<ValveNotFoundError>())?;
+  module.add("AbortStageNotFoundError", py.get_type::<AbortStageNotFoundError>())?;
+  module.add("BoardNotFoundError", py.get_type::<BoardNotFoundError>())?;
+  module.add("CommandRejectedError", py.get_type::<CommandRejectedError>())?;
+  module.add("ValveClaimedError", py.get_type::<ValveClaimedError>())?;
+  module.add("SendCommandIpcError", py.get_type::<SendCommandIpcError>())?;
+  module.add("CommandTimeoutError", py.get_type::<CommandTimeoutError>())?;
+
   module.add_class::<Sensor>()?;
   module.add_class::<Valve>()?;
+  module.add_class::<ValveClaim>()?;
   module.add_class::<ValveState>()?;
   module.add_class::<ValveSafeState>()?;
   module.add_class::<IntervalIterator>()?;
+  module.add_class::<Vector3>()?;
+  module.add_class::<ImuReading>()?;
+  module.add_class::<BarometerReading>()?;
+  module.add_class::<GpsReading>()?;
+  module.add_class::<BusReading>()?;
+  module.add_class::<BmsReading>()?;
+  module.add_class::<RecoReading>()?;
 
   module.add_function(wrap_pyfunction!(wait_for, module)?)?;
   module.add_function(wrap_pyfunction!(wait_until, module)?)?;
   module.add_function(wrap_pyfunction!(abort, module)?)?;
   module.add_function(wrap_pyfunction!(interval, module)?)?;
+  module.add_function(wrap_pyfunction!(claim, module)?)?;
   module.add_function(wrap_pyfunction!(create_abort_stage, module)?)?;
   module.add_function(wrap_pyfunction!(set_abort_stage, module)?)?;
   module.add_function(wrap_pyfunction!(send_sams_abort, module)?)?;
@@ -127,6 +318,12 @@ fn sequences(py: Python<'_>, module: &PyModule) -> PyResult<()> {
   module.add_function(wrap_pyfunction!(reco_init_ekf, module)?)?;
   module.add_function(wrap_pyfunction!(read_umbilical_voltage, module)?)?;
   module.add_function(wrap_pyfunction!(reco_recvd_launch, module)?)?;
+  module.add_function(wrap_pyfunction!(read_imu, module)?)?;
+  module.add_function(wrap_pyfunction!(read_magnetometer, module)?)?;
+  module.add_function(wrap_pyfunction!(read_barometer, module)?)?;
+  module.add_function(wrap_pyfunction!(read_gps, module)?)?;
+  module.add_function(wrap_pyfunction!(read_bms, module)?)?;
+  module.add_function(wrap_pyfunction!(read_reco, module)?)?;
   module.add_function(wrap_pyfunction!(launch_lug_arm, module)?)?;
   module.add_function(wrap_pyfunction!(launch_lug_detonate, module)?)?;
   module.add_function(wrap_pyfunction!(set_servo_disconnect_monitoring, module)?)?;
@@ -134,3 +331,82 @@ fn sequences(py: Python<'_>, module: &PyModule) -> PyResult<()> {
 
   Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// A sequence's command socket and the FC's end of it.
+  fn channel() -> (UnixDatagram, UnixDatagram) {
+    let (sequence, fc) = UnixDatagram::pair().unwrap();
+    sequence
+      .set_read_timeout(Some(StdDuration::from_millis(50)))
+      .unwrap();
+    (sequence, fc)
+  }
+
+  fn respond(
+    fc: &UnixDatagram,
+    id: u32,
+    result: Result<(), SequenceCommandError>,
+  ) {
+    let response = SequenceDomainResponse { id, result };
+    fc.send(&postcard::to_allocvec(&response).unwrap()).unwrap();
+  }
+
+  #[test]
+  fn responses_to_other_commands_are_skipped() {
+    let (sequence, fc) = channel();
+
+    // late responses to commands that already timed out
+    respond(
+      &fc,
+      3,
+      Err(SequenceCommandError::Rejected("late".to_string())),
+    );
+    respond(&fc, 4, Ok(()));
+    respond(
+      &fc,
+      5,
+      Err(SequenceCommandError::ValveNotFound("BBV".to_string())),
+    );
+
+    assert_eq!(
+      receive_response(&sequence, 5).unwrap(),
+      Err(SequenceCommandError::ValveNotFound("BBV".to_string()))
+    );
+  }
+
+  #[test]
+  fn a_missing_response_times_out() {
+    let (sequence, fc) = channel();
+
+    assert!(matches!(
+      receive_response(&sequence, 0),
+      Err(ResponseError::TimedOut)
+    ));
+
+    // only a response to another command arrives
+    respond(&fc, 1, Ok(()));
+    assert!(matches!(
+      receive_response(&sequence, 2),
+      Err(ResponseError::TimedOut)
+    ));
+
+    // the response to the timed out command is skipped once it arrives
+    respond(&fc, 2, Ok(()));
+    respond(&fc, 3, Ok(()));
+    assert_eq!(receive_response(&sequence, 3).unwrap(), Ok(()));
+  }
+
+  #[test]
+  fn malformed_responses_are_reported() {
+    let (sequence, fc) = channel();
+    fc.send(&[0xff, 0xff, 0xff, 0xff, 0xff, 0xff]).unwrap();
+
+    assert!(matches!(
+      receive_response(&sequence, 0),
+      Err(ResponseError::Deserialize(_))
+    ));
+  }
+}
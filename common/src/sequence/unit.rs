@@ -1,7 +1,9 @@
-use crate::comm::{sam::Unit, Measurement};
+use crate::comm::{sam::Unit, Measurement, SequenceArgument};
 
 use pyo3::{pyclass, IntoPy, PyObject, Python};
 
+use super::Valve;
+
 macro_rules! create_unit {
   ($name:ident, $abbrev:literal) => {
     /// A unit struct representing a continuous physical property.
@@ -108,3 +110,22 @@ impl IntoPy<PyObject> for Measurement {
     }
   }
 }
+
+impl IntoPy<PyObject> for SequenceArgument {
+  fn into_py(self, py: Python<'_>) -> PyObject {
+    match self {
+      SequenceArgument::Number(value) => value.into_py(py),
+      SequenceArgument::Current(value) => Current::new(value).into_py(py),
+      SequenceArgument::Duration(value) => Duration::new(value).into_py(py),
+      SequenceArgument::ElectricPotential(value) => {
+        ElectricPotential::new(value).into_py(py)
+      }
+      SequenceArgument::Force(value) => Force::new(value).into_py(py),
+      SequenceArgument::Pressure(value) => Pressure::new(value).into_py(py),
+      SequenceArgument::Temperature(value) => {
+        Temperature::new(value).into_py(py)
+      }
+      SequenceArgument::Valve(name) => Valve::new(name).into_py(py),
+    }
+  }
+}
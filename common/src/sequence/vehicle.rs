@@ -0,0 +1,681 @@
+use crate::comm::{
+  bms::{Bms, Bus},
+  fc_sensors::{Barometer, Imu, Vector},
+  vehicle::RecoMcuDisagreement,
+  GpsState,
+  RecoState,
+  VehicleState,
+};
+use pyo3::{pyclass, pyfunction, pymethods, PyResult};
+use rkyv::{Archived, Deserialize};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::{
+  read_vehicle_state,
+  synchronize,
+  Current,
+  ElectricPotential,
+  Pressure,
+  RkyvDeserializationError,
+  StaleReadingError,
+  Temperature,
+  SYNCHRONIZER,
+};
+
+/// The number of pascals in one pound per square inch.
+const PASCALS_PER_PSI: f64 = 6_894.757;
+
+/// The temperature of 0 °C in kelvin.
+const ZERO_CELSIUS: f64 = 273.15;
+
+/// How long after the receiver takes a GPS fix that it remains fresh, in
+/// seconds.
+const GPS_TIME_TO_LIVE: f64 = 0.5;
+
+/// How long after the flight computer receives RECO states that they remain
+/// fresh, in seconds.
+const RECO_TIME_TO_LIVE: f64 = 0.35;
+
+/// Reads part of the global vehicle state, releasing it before returning so
+/// that the result is never converted while still reading.
+fn read_state<T>(
+  read: impl FnOnce(&Archived<VehicleState>) -> PyResult<T>,
+) -> PyResult<T> {
+  let mut sync = synchronize(&SYNCHRONIZER)?;
+  // this unwrap() should never fail as synchronize ensures the value is Some.
+  let vehicle_state = read_vehicle_state(sync.as_mut().unwrap())?;
+
+  read(&vehicle_state)
+}
+
+fn deserialize<T>(
+  archived: &impl Deserialize<T, rkyv::Infallible>,
+  what: &str,
+) -> PyResult<T> {
+  archived.deserialize(&mut rkyv::Infallible).map_err(|e| {
+    RkyvDeserializationError::new_err(format!(
+      "rkyv couldn't deserialize the {what}: {e}"
+    ))
+  })
+}
+
+fn pressure(pascals: f64) -> Pressure {
+  Pressure::new(pascals / PASCALS_PER_PSI)
+}
+
+/// The current UNIX timestamp, in seconds.
+fn current_timestamp() -> f64 {
+  SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .map_or(0.0, |elapsed| elapsed.as_secs_f64())
+}
+
+/// Determines if something taken at `timestamp` is still fresh at `now`. Having
+/// no timestamp at all is never fresh.
+fn is_fresh(timestamp: Option<f64>, now: f64, time_to_live: f64) -> bool {
+  timestamp.is_some_and(|timestamp| now - timestamp <= time_to_live)
+}
+
+/// A Python-exposed reading of a three-axis sensor.
+#[pyclass]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Vector3 {
+  /// The X component.
+  #[pyo3(get)]
+  pub x: f64,
+
+  /// The Y component.
+  #[pyo3(get)]
+  pub y: f64,
+
+  /// The Z component.
+  #[pyo3(get)]
+  pub z: f64,
+}
+
+#[pymethods]
+impl Vector3 {
+  fn __repr__(&self) -> String {
+    format!("({}, {}, {})", self.x, self.y, self.z)
+  }
+}
+
+impl From<Vector> for Vector3 {
+  fn from(Vector { x, y, z }: Vector) -> Self {
+    Vector3 { x, y, z }
+  }
+}
+
+impl From<[f32; 3]> for Vector3 {
+  fn from([x, y, z]: [f32; 3]) -> Self {
+    Vector3 {
+      x: x.into(),
+      y: y.into(),
+      z: z.into(),
+    }
+  }
+}
+
+/// A Python-exposed reading of the flight computer's IMU.
+#[pyclass]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ImuReading {
+  /// The acceleration, in m/s².
+  #[pyo3(get)]
+  pub accelerometer: Vector3,
+
+  /// The angular rate, in degrees per second.
+  #[pyo3(get)]
+  pub gyroscope: Vector3,
+}
+
+impl From<Imu> for ImuReading {
+  fn from(imu: Imu) -> Self {
+    ImuReading {
+      accelerometer: imu.accelerometer.into(),
+      gyroscope: imu.gyroscope.into(),
+    }
+  }
+}
+
+/// A Python-exposed reading of the flight computer's barometer.
+#[pyclass]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BarometerReading {
+  /// The temperature of the barometer.
+  #[pyo3(get)]
+  pub temperature: Temperature,
+
+  /// The ambient pressure.
+  #[pyo3(get)]
+  pub pressure: Pressure,
+}
+
+impl From<Barometer> for BarometerReading {
+  fn from(barometer: Barometer) -> Self {
+    BarometerReading {
+      temperature: Temperature::new(barometer.temperature + ZERO_CELSIUS),
+      pressure: pressure(barometer.pressure),
+    }
+  }
+}
+
+/// A Python-exposed GPS fix.
+#[pyclass]
+#[derive(Clone, Debug, PartialEq)]
+pub struct GpsReading {
+  /// Latitude in degrees, positive north.
+  #[pyo3(get)]
+  pub latitude: f64,
+
+  /// Longitude in degrees, positive east.
+  #[pyo3(get)]
+  pub longitude: f64,
+
+  /// Altitude above mean sea level, in meters.
+  #[pyo3(get)]
+  pub altitude: f64,
+
+  /// Velocity as (north, east, down), in m/s.
+  #[pyo3(get)]
+  pub velocity: Vector3,
+
+  /// The UNIX timestamp, in seconds, of the fix if the receiver reported one.
+  #[pyo3(get)]
+  pub timestamp: Option<f64>,
+
+  /// Whether the receiver has a valid fix.
+  #[pyo3(get)]
+  pub has_fix: bool,
+
+  /// The number of satellites used in the fix.
+  #[pyo3(get)]
+  pub num_satellites: u8,
+
+  /// Whether the receiver took this fix within the last half second. A fix
+  /// without a timestamp is never fresh.
+  #[pyo3(get)]
+  pub fresh: bool,
+}
+
+impl GpsReading {
+  fn new(gps: GpsState, fresh: bool) -> Self {
+    GpsReading {
+      latitude: gps.latitude_deg,
+      longitude: gps.longitude_deg,
+      altitude: gps.altitude_m,
+      velocity: Vector3 {
+        x: gps.north_mps,
+        y: gps.east_mps,
+        z: gps.down_mps,
+      },
+      timestamp: gps.timestamp_unix_ms.map(|ms| ms as f64 / 1000.0),
+      has_fix: gps.has_fix,
+      num_satellites: gps.num_satellites,
+      fresh,
+    }
+  }
+}
+
+/// A Python-exposed reading of a power bus or rail on BMS.
+#[pyclass]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BusReading {
+  /// The voltage of the bus.
+  #[pyo3(get)]
+  pub voltage: ElectricPotential,
+
+  /// The current drawn through the bus.
+  #[pyo3(get)]
+  pub current: Current,
+}
+
+impl From<Bus> for BusReading {
+  fn from(bus: Bus) -> Self {
+    BusReading {
+      voltage: ElectricPotential::new(bus.voltage),
+      current: Current::new(bus.current),
+    }
+  }
+}
+
+/// A Python-exposed reading of every device on BMS.
+#[pyclass]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BmsReading {
+  /// The battery bus.
+  #[pyo3(get)]
+  pub battery_bus: BusReading,
+
+  /// The umbilical bus.
+  #[pyo3(get)]
+  pub umbilical_bus: BusReading,
+
+  /// The SAM power bus.
+  #[pyo3(get)]
+  pub sam_power_bus: BusReading,
+
+  /// The ethernet load switch bus.
+  #[pyo3(get)]
+  pub ethernet_bus: BusReading,
+
+  /// The TEL load switch bus.
+  #[pyo3(get)]
+  pub tel_bus: BusReading,
+
+  /// The flight computer board bus.
+  #[pyo3(get)]
+  pub fcb_bus: BusReading,
+
+  /// The 5V rail.
+  #[pyo3(get)]
+  pub five_volt_rail: BusReading,
+
+  /// The current drawn by the charger.
+  #[pyo3(get)]
+  pub charger: Current,
+
+  /// The chassis voltage.
+  #[pyo3(get)]
+  pub chassis: ElectricPotential,
+
+  /// The estop voltage.
+  #[pyo3(get)]
+  pub e_stop: ElectricPotential,
+
+  /// The RBF tag voltage.
+  #[pyo3(get)]
+  pub rbf_tag: ElectricPotential,
+
+  /// The voltage of RECO load switch 1.
+  #[pyo3(get)]
+  pub reco_load_switch_1: ElectricPotential,
+
+  /// The voltage of RECO load switch 2.
+  #[pyo3(get)]
+  pub reco_load_switch_2: ElectricPotential,
+}
+
+impl From<Bms> for BmsReading {
+  fn from(bms: Bms) -> Self {
+    BmsReading {
+      battery_bus: bms.battery_bus.into(),
+      umbilical_bus: bms.umbilical_bus.into(),
+      sam_power_bus: bms.sam_power_bus.into(),
+      ethernet_bus: bms.ethernet_bus.into(),
+      tel_bus: bms.tel_bus.into(),
+      fcb_bus: bms.fcb_bus.into(),
+      five_volt_rail: bms.five_volt_rail.into(),
+      charger: Current::new(bms.charger),
+      chassis: ElectricPotential::new(bms.chassis),
+      e_stop: ElectricPotential::new(bms.e_stop),
+      rbf_tag: ElectricPotential::new(bms.rbf_tag),
+      reco_load_switch_1: ElectricPotential::new(bms.reco_load_switch_1),
+      reco_load_switch_2: ElectricPotential::new(bms.reco_load_switch_2),
+    }
+  }
+}
+
+/// A Python-exposed reading of the state reported by one RECO MCU.
+#[pyclass]
+#[derive(Clone, Debug, PartialEq)]
+pub struct RecoReading {
+  /// The index of the MCU which reported this state, where 0, 1 and 2 are
+  /// MCUs A, B and C.
+  #[pyo3(get)]
+  pub mcu: usize,
+
+  /// The attitude of the vehicle as a quaternion [w, x, y, z].
+  #[pyo3(get)]
+  pub quaternion: [f32; 4],
+
+  /// Latitude in degrees.
+  #[pyo3(get)]
+  pub latitude: f64,
+
+  /// Longitude in degrees.
+  #[pyo3(get)]
+  pub longitude: f64,
+
+  /// Altitude in meters.
+  #[pyo3(get)]
+  pub altitude: f64,
+
+  /// Velocity as (north, east, down), in m/s.
+  #[pyo3(get)]
+  pub velocity: Vector3,
+
+  /// Linear acceleration, in m/s².
+  #[pyo3(get)]
+  pub linear_acceleration: Vector3,
+
+  /// Angular rates as (pitch, yaw, roll), in rad/s.
+  #[pyo3(get)]
+  pub angular_rate: Vector3,
+
+  /// Magnetometer data.
+  #[pyo3(get)]
+  pub magnetometer: Vector3,
+
+  /// Gyroscope bias offset.
+  #[pyo3(get)]
+  pub gyroscope_bias: Vector3,
+
+  /// Accelerometer bias offset.
+  #[pyo3(get)]
+  pub accelerometer_bias: Vector3,
+
+  /// Gyroscope scale factor.
+  #[pyo3(get)]
+  pub gyroscope_scale: Vector3,
+
+  /// Accelerometer scale factor.
+  #[pyo3(get)]
+  pub accelerometer_scale: Vector3,
+
+  /// The temperature of the MCU.
+  #[pyo3(get)]
+  pub temperature: Temperature,
+
+  /// The ambient pressure.
+  #[pyo3(get)]
+  pub pressure: Pressure,
+
+  /// Channel 1 driver 1 voltage.
+  #[pyo3(get)]
+  pub vref_ch1_dr1: ElectricPotential,
+
+  /// Channel 1 driver 2 voltage.
+  #[pyo3(get)]
+  pub vref_ch1_dr2: ElectricPotential,
+
+  /// Channel 2 driver 1 voltage.
+  #[pyo3(get)]
+  pub vref_ch2_dr1: ElectricPotential,
+
+  /// Channel 2 driver 2 voltage.
+  #[pyo3(get)]
+  pub vref_ch2_dr2: ElectricPotential,
+
+  /// Recovery driver 1 current.
+  #[pyo3(get)]
+  pub sns1_current: Current,
+
+  /// Recovery driver 2 current.
+  #[pyo3(get)]
+  pub sns2_current: Current,
+
+  /// 24V rail voltage.
+  #[pyo3(get)]
+  pub v_rail_24v: ElectricPotential,
+
+  /// 3.3V rail voltage.
+  #[pyo3(get)]
+  pub v_rail_3v3: ElectricPotential,
+
+  /// Barometer value from the fading memory filter.
+  #[pyo3(get)]
+  pub fading_memory_baro: f32,
+
+  /// GPS value from the fading memory filter.
+  #[pyo3(get)]
+  pub fading_memory_gps: f32,
+
+  /// Whether stage 1 is enabled.
+  #[pyo3(get)]
+  pub stage1_enabled: bool,
+
+  /// Whether stage 2 is enabled.
+  #[pyo3(get)]
+  pub stage2_enabled: bool,
+
+  /// Whether the MCU has received the launch command.
+  #[pyo3(get)]
+  pub reco_recvd_launch: bool,
+
+  /// Whether the EKF has blown up.
+  #[pyo3(get)]
+  pub ekf_blown_up: bool,
+
+  /// Whether a timer is used instead of the EKF for drogue.
+  #[pyo3(get)]
+  pub drouge_timer_enable: bool,
+
+  /// Whether a timer is used instead of the altimeter for main.
+  #[pyo3(get)]
+  pub main_timer_enable: bool,
+
+  /// Whether RBF is installed.
+  #[pyo3(get)]
+  pub rbf_enabled: bool,
+
+  /// Fault status bytes for the RECO drivers and channels.
+  #[pyo3(get)]
+  pub driver_faults: [u8; 10],
+
+  /// Whether this MCU disagrees with the vote across all three.
+  #[pyo3(get)]
+  pub disagrees: bool,
+
+  /// Whether the flight computer received this state within the last 350
+  /// milliseconds.
+  #[pyo3(get)]
+  pub fresh: bool,
+}
+
+impl RecoReading {
+  fn new(mcu: usize, reco: RecoState, disagrees: bool, fresh: bool) -> Self {
+    let [longitude, latitude, altitude] = reco.lla_pos;
+
+    RecoReading {
+      mcu,
+      quaternion: reco.quaternion,
+      latitude: latitude.into(),
+      longitude: longitude.into(),
+      altitude: altitude.into(),
+      velocity: reco.velocity.into(),
+      linear_acceleration: reco.lin_accel.into(),
+      angular_rate: reco.angular_rate.into(),
+      magnetometer: reco.mag_data.into(),
+      gyroscope_bias: reco.g_bias.into(),
+      accelerometer_bias: reco.a_bias.into(),
+      gyroscope_scale: reco.g_sf.into(),
+      accelerometer_scale: reco.a_sf.into(),
+      temperature: Temperature::new(reco.temperature.into()),
+      pressure: pressure(reco.pressure.into()),
+      vref_ch1_dr1: ElectricPotential::new(reco.vref_ch1_dr1.into()),
+      vref_ch1_dr2: ElectricPotential::new(reco.vref_ch1_dr2.into()),
+      vref_ch2_dr1: ElectricPotential::new(reco.vref_ch2_dr1.into()),
+      vref_ch2_dr2: ElectricPotential::new(reco.vref_ch2_dr2.into()),
+      sns1_current: Current::new(reco.sns1_current.into()),
+      sns2_current: Current::new(reco.sns2_current.into()),
+      v_rail_24v: ElectricPotential::new(reco.v_rail_24v.into()),
+      v_rail_3v3: ElectricPotential::new(reco.v_rail_3v3.into()),
+      fading_memory_baro: reco.fading_memory_baro,
+      fading_memory_gps: reco.fading_memory_gps,
+      stage1_enabled: reco.stage1_enabled,
+      stage2_enabled: reco.stage2_enabled,
+      reco_recvd_launch: reco.reco_recvd_launch,
+      ekf_blown_up: reco.ekf_blown_up,
+      drouge_timer_enable: reco.drouge_timer_enable,
+      main_timer_enable: reco.main_timer_enable,
+      rbf_enabled: reco.rbf_enabled,
+      driver_faults: reco.reco_driver_faults,
+      disagrees,
+      fresh,
+    }
+  }
+}
+
+/// Python exposed function that reads the flight computer's IMU.
+#[pyfunction]
+pub fn read_imu() -> PyResult<ImuReading> {
+  read_state(|vehicle_state| {
+    deserialize::<Imu>(&vehicle_state.fc_sensors.imu, "IMU").map(Into::into)
+  })
+}
+
+/// Python exposed function that reads the flight computer's magnetometer, in
+/// Gauss.
+#[pyfunction]
+pub fn read_magnetometer() -> PyResult<Vector3> {
+  read_state(|vehicle_state| {
+    deserialize::<Vector>(
+      &vehicle_state.fc_sensors.magnetometer,
+      "magnetometer",
+    )
+    .map(Into::into)
+  })
+}
+
+/// Python exposed function that reads the flight computer's barometer.
+#[pyfunction]
+pub fn read_barometer() -> PyResult<BarometerReading> {
+  read_state(|vehicle_state| {
+    deserialize::<Barometer>(&vehicle_state.fc_sensors.barometer, "barometer")
+      .map(Into::into)
+  })
+}
+
+/// Python exposed function that reads the state of every device on BMS.
+#[pyfunction]
+pub fn read_bms() -> PyResult<BmsReading> {
+  read_state(|vehicle_state| {
+    deserialize::<Bms>(&vehicle_state.bms, "BMS state").map(Into::into)
+  })
+}
+
+/// Python exposed function that reads the latest GPS fix, or `None` if the
+/// flight computer has never received one. If `fresh` is set, a fix which the
+/// receiver didn't take within `GPS_TIME_TO_LIVE` raises `StaleReadingError`
+/// instead.
+#[pyfunction]
+#[pyo3(signature = (fresh = false))]
+pub fn read_gps(fresh: bool) -> PyResult<Option<GpsReading>> {
+  let gps: Option<GpsState> =
+    read_state(|vehicle_state| deserialize(&vehicle_state.gps, "GPS state"))?;
+
+  let gps_fresh = gps.as_ref().is_some_and(|gps| {
+    is_fresh(
+      gps.timestamp_unix_ms.map(|ms| ms as f64 / 1000.0),
+      current_timestamp(),
+      GPS_TIME_TO_LIVE,
+    )
+  });
+
+  if fresh && !gps_fresh {
+    return Err(StaleReadingError::new_err("The GPS fix is stale."));
+  }
+
+  Ok(gps.map(|gps| GpsReading::new(gps, gps_fresh)))
+}
+
+/// Python exposed function that reads the state reported by each RECO MCU, in
+/// the order A, B, C, with `None` for an MCU which hasn't reported. If `fresh`
+/// is set, states which the flight computer didn't receive within
+/// `RECO_TIME_TO_LIVE` raise `StaleReadingError` instead.
+#[pyfunction]
+#[pyo3(signature = (fresh = false))]
+pub fn read_reco(fresh: bool) -> PyResult<[Option<RecoReading>; 3]> {
+  let (mut reco, disagreement, reco_timestamp) = read_state(|vehicle_state| {
+    let reco: [Option<RecoState>; 3] =
+      deserialize(&vehicle_state.reco.0, "RECO state")?;
+    let disagreement: [RecoMcuDisagreement; 3] = deserialize(
+      &vehicle_state.reco_disagreement,
+      "RECO disagreement",
+    )?;
+
+    Ok((reco, disagreement, vehicle_state.reco_timestamp))
+  })?;
+
+  // a timestamp of zero means the flight computer has never received any
+  let reco_fresh = is_fresh(
+    (reco_timestamp > 0.0).then_some(reco_timestamp),
+    current_timestamp(),
+    RECO_TIME_TO_LIVE,
+  );
+
+  if fresh && !reco_fresh {
+    return Err(StaleReadingError::new_err("The RECO state is stale."));
+  }
+
+  Ok(std::array::from_fn(|mcu| {
+    reco[mcu].take().map(|reco| {
+      RecoReading::new(mcu, reco, disagreement[mcu].any(), reco_fresh)
+    })
+  }))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn assert_close(actual: f64, expected: f64) {
+    assert!(
+      (actual - expected).abs() < 1e-6,
+      "expected {expected}, got {actual}"
+    );
+  }
+
+  #[test]
+  fn pascals_are_converted_to_psi() {
+    assert_close(pressure(0.0).raw, 0.0);
+    assert_close(pressure(6_894.757).raw, 1.0);
+    assert_close(pressure(101_325.0).raw, 14.695_949);
+    assert_close(pressure(-6_894.757).raw, -1.0);
+  }
+
+  #[test]
+  fn barometer_celsius_is_converted_to_kelvin() {
+    let reading = BarometerReading::from(Barometer {
+      temperature: 25.0,
+      pressure: 101_325.0,
+    });
+
+    assert_close(reading.temperature.raw, 298.15);
+    assert_close(reading.pressure.raw, 14.695_949);
+
+    let freezing = BarometerReading::from(Barometer {
+      temperature: 0.0,
+      pressure: 0.0,
+    });
+    assert_close(freezing.temperature.raw, ZERO_CELSIUS);
+
+    let absolute_zero = BarometerReading::from(Barometer {
+      temperature: -273.15,
+      pressure: 0.0,
+    });
+    assert_close(absolute_zero.temperature.raw, 0.0);
+  }
+
+  #[test]
+  fn reco_pressure_is_converted_but_temperature_is_already_kelvin() {
+    let reco = RecoState {
+      temperature: 290.0,
+      pressure: 101_325.0,
+      ..RecoState::default()
+    };
+
+    let reading = RecoReading::new(0, reco, false, true);
+    assert_close(reading.temperature.raw, 290.0);
+    assert_close(reading.pressure.raw, 14.695_949);
+  }
+
+  #[test]
+  fn freshness_follows_the_age_of_the_timestamp() {
+    let now = 1_742_313_600.0;
+
+    assert!(is_fresh(Some(now), now, GPS_TIME_TO_LIVE));
+    assert!(is_fresh(Some(now - 0.4), now, GPS_TIME_TO_LIVE));
+    assert!(is_fresh(
+      Some(now - GPS_TIME_TO_LIVE),
+      now,
+      GPS_TIME_TO_LIVE
+    ));
+    assert!(!is_fresh(Some(now - 0.6), now, GPS_TIME_TO_LIVE));
+    assert!(!is_fresh(None, now, GPS_TIME_TO_LIVE));
+
+    // a receiver clock slightly ahead of ours still counts as fresh
+    assert!(is_fresh(Some(now + 0.05), now, GPS_TIME_TO_LIVE));
+  }
+}
@@ -1,2 +1,75 @@
-while True:
-    if 
+use common::{
+  comm::VehicleState,
+  condition::{self, Expr},
+};
+
+/// The abort stage in which abort conditions are never evaluated.
+const FLIGHT_STAGE: &str = "FLIGHT";
+
+/// Evaluates the abort condition of the current abort stage every loop.
+///
+/// The condition is re-parsed whenever the current stage or its condition
+/// changes, which also restarts any `for` timers within it.
+#[derive(Default)]
+pub(crate) struct AbortConditionMonitor {
+  stage: String,
+  source: String,
+  condition: Option<Expr>,
+  last_error: Option<String>,
+}
+
+impl AbortConditionMonitor {
+  /// Returns true if the current stage's abort condition has been met and the
+  /// vehicle has not yet aborted in this stage.
+  pub(crate) fn check(&mut self, state: &VehicleState) -> bool {
+    let stage = &state.abort_stage;
+
+    if stage.name != self.stage || stage.abort_condition != self.source {
+      self.stage = stage.name.clone();
+      self.source = stage.abort_condition.clone();
+      self.last_error = None;
+
+      // conditions are validated when the stage is created, so this should
+      // only fail if the stage was set some other way.
+      self.condition = match condition::parse(&self.source) {
+        Ok(condition) => Some(condition),
+        Err(e) => {
+          eprintln!(
+            "The abort condition of stage '{}' is invalid and will never abort: {e}",
+            self.stage
+          );
+          None
+        }
+      };
+    }
+
+    if stage.name == FLIGHT_STAGE || stage.aborted {
+      return false;
+    }
+
+    let Some(ref condition) = self.condition else {
+      return false;
+    };
+
+    match condition.is_met(state) {
+      Ok(is_met) => {
+        self.last_error = None;
+        is_met
+      }
+      Err(e) => {
+        // only report an error when it changes rather than every loop.
+        let message = e.to_string();
+
+        if self.last_error.as_ref() != Some(&message) {
+          eprintln!(
+            "Couldn't evaluate the abort condition of stage '{}': {message}",
+            self.stage
+          );
+          self.last_error = Some(message);
+        }
+
+        false
+      }
+    }
+  }
+}
@@ -1,37 +1,41 @@
 use common::comm::{
   bms,
   fc_sensors,
-  flight::{DataMessage, SequenceDomainCommand},
-  sam::{SamControlMessage, Unit},
+  flight::{DataMessage, SequenceCommandError, SequenceDomainCommand},
+  sam::{SamControlMessage, SamControlRequest, Unit},
   AbortStage,
   AbortStageConfig,
+  AlarmSeverity,
   CompositeValveState,
   GpsState,
   Measurement,
   NodeMapping,
   reco::{GuiCommand as SharedRecoCommand, SequenceCommand as RecoSequenceCommand, TargetedGuiCommand},
   RecoState,
+  RecoVoteThresholds,
   SensorType,
   Statistics,
   ValveAction,
+  ValveCommander,
   ValveState,
   VehicleState,
 };
+use common::condition;
 use lis2mdl::MagnetometerData;
 use std::{
-  collections::HashMap,
+  collections::{HashMap, HashSet},
   fmt,
   io,
   net::{IpAddr, SocketAddr, UdpSocket},
-  ops::Deref,
   sync::mpsc,
-  time::{Duration, Instant},
+  time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 
 use crate::{
+  file_logger::current_timestamp,
   gps::{GpsHandle, RecoControlMessage},
   sensors::{BarometerData, ImuAdcSample},
-  sequence::Sequences,
+  sequence::{self, ReceivedCommand, Sequences},
   state::process_flight_pt_data,
   Ingestible,
   DECAY,
@@ -42,6 +46,24 @@ use crate::{
 pub(crate) type Mappings = Vec<NodeMapping>;
 pub(crate) type AbortStages = Vec<AbortStage>;
 
+/// How long to wait for a SAM to acknowledge a command before sending it again.
+const SAM_COMMAND_RETRY_INTERVAL: Duration = Duration::from_millis(50);
+
+/// How many times a command is sent, including the first, before giving up.
+const SAM_COMMAND_MAX_ATTEMPTS: u32 = 6;
+
+/// How long, in seconds, a sensor must stay beyond a critical redline before
+/// aborting when its mapping doesn't set a persistence.
+const DEFAULT_CRITICAL_PERSISTENCE: f64 = 0.1;
+
+/// A command sent to a SAM board which hasn't been acknowledged yet.
+struct PendingSamCommand {
+  board_id: String,
+  request: SamControlRequest,
+  last_sent: Instant,
+  attempts: u32,
+}
+
 #[derive(Clone)]
 pub(crate) struct Device {
   id: String,
@@ -153,6 +175,16 @@ pub(crate) struct Devices {
   /// Whether we are still actively communicating with servo (pulling data and
   /// pushing telemetry).
   servo_communication_enabled: bool,
+  /// The sequence number given to the next command sent to a SAM board.
+  next_sam_sequence: u32,
+  /// Commands sent to SAM boards that are still awaiting acknowledgement.
+  pending_sam_commands: Vec<PendingSamCommand>,
+  /// For every valve disagreeing with its command, the state it was
+  /// commanded to and when it was first seen disagreeing with that command.
+  mismatched_since: HashMap<String, (ValveState, Instant)>,
+  /// The sensors that had been beyond a critical redline for their whole
+  /// persistence when last checked.
+  critical_redlines: HashSet<String>,
 }
 
 impl Devices {
@@ -164,6 +196,12 @@ impl Devices {
       last_updates: HashMap::new(),
       monitor_servo_disconnects: true,
       servo_communication_enabled: true,
+      // start somewhere random so that a restarted flight computer doesn't
+      // reuse sequence numbers a SAM board has recently executed.
+      next_sam_sequence: rand::random(),
+      pending_sam_commands: Vec::new(),
+      mismatched_since: HashMap::new(),
+      critical_redlines: HashSet::new(),
     }
   }
 
@@ -250,6 +288,10 @@ impl Devices {
     for (address, message) in telemetry {
       match message {
         DataMessage::FlightHeartbeat => continue,
+        DataMessage::SamAck(ref id, sequence) => {
+          self.acknowledge_sam_command(id, sequence);
+          continue;
+        }
         DataMessage::Bms(ref id, _) | DataMessage::Sam(ref id, _) => {
           let Some(device) = self.devices.iter_mut().find(|d| d.id == *id)
           else {
@@ -321,6 +363,13 @@ impl Devices {
       .state
       .valve_states
       .retain(|valve_name, _| configured_valves.contains(valve_name));
+    self
+      .state
+      .valve_mismatches
+      .retain(|valve_name, _| configured_valves.contains(valve_name));
+    self
+      .mismatched_since
+      .retain(|valve_name, _| configured_valves.contains(valve_name));
 
     for valve_name in configured_valves {
       self.state.valve_states.entry(valve_name).or_insert(
@@ -342,14 +391,161 @@ impl Devices {
       .state
       .sensor_readings
       .retain(|sensor_name, _| configured_sensors.contains_key(sensor_name));
+    self
+      .state
+      .raw_readings
+      .retain(|sensor_name, _| configured_sensors.contains_key(sensor_name));
+    self
+      .state
+      .redline_alarms
+      .retain(|sensor_name, _| configured_sensors.contains_key(sensor_name));
 
+    // sensors that haven't reported yet are stale until they do
     for (sensor_name, unit) in configured_sensors {
-      self
+      self.state.sensor_readings.entry(sensor_name).or_insert(Measurement {
+        value: 0.0,
+        unit,
+        timestamp: 0.0,
+        board_id: None,
+        stale: true,
+      });
+    }
+  }
+
+  /// Marks every reading from a board that has been silent for longer than
+  /// `TIME_TO_LIVE` as stale. A reading only becomes fresh again once its
+  /// board reports it anew.
+  pub(crate) fn mark_stale_readings(&mut self) {
+    for (sensor_name, measurement) in &mut self.state.sensor_readings {
+      let Some(board_id) = &measurement.board_id else {
+        continue;
+      };
+
+      if !measurement.stale
+        && self
+          .devices
+          .iter()
+          .find(|device| &device.id == board_id)
+          .is_some_and(Device::is_disconnected)
+      {
+        measurement.stale = true;
+
+        // a stale reading says nothing about whether the sensor is still
+        // beyond its limits.
+        self.state.redline_alarms.remove(sensor_name);
+      }
+    }
+  }
+
+  /// Tracks how long each valve's actual state has disagreed with its
+  /// commanded state, faulting the valve once that outlasts the fault timeout
+  /// in its mapping. A new command restarts the timer. Returns the valves
+  /// that have just faulted and whose mappings call for an abort.
+  pub(crate) fn update_valve_mismatches(
+    &mut self,
+    mappings: &Mappings,
+  ) -> Vec<String> {
+    let now = Instant::now();
+    let mut aborting = Vec::new();
+
+    for mapping in mappings.iter().filter(|m| m.sensor_type == SensorType::Valve)
+    {
+      let valve_name = &mapping.text_id;
+
+      let commanded = match self.state.valve_states.get(valve_name) {
+        Some(valve) if is_mismatched(valve) => valve.commanded,
+        _ => {
+          self.mismatched_since.remove(valve_name);
+          self.state.valve_mismatches.remove(valve_name);
+          continue;
+        }
+      };
+
+      let since = match self.mismatched_since.get(valve_name) {
+        Some(&(previous, since)) if previous == commanded => since,
+        _ => {
+          self.mismatched_since.insert(valve_name.clone(), (commanded, now));
+          now
+        }
+      };
+
+      let duration = now.duration_since(since).as_secs_f64();
+      let faulted =
+        mapping.fault_timeout.is_some_and(|timeout| duration >= timeout);
+
+      let mismatch = self
         .state
-        .sensor_readings
-        .entry(sensor_name)
-        .or_insert(Measurement { value: 0.0, unit });
+        .valve_mismatches
+        .entry(valve_name.clone())
+        .or_default();
+
+      if faulted && !mismatch.faulted {
+        eprintln!(
+          "Valve '{valve_name}' hasn't been {commanded} for {duration:.2} s after being commanded to. Marking it as faulted."
+        );
+
+        if mapping.abort_on_fault == Some(true) {
+          aborting.push(valve_name.clone());
+        }
+      }
+
+      mismatch.duration = duration;
+      mismatch.faulted = faulted;
+    }
+
+    // a valve that faults as a result of an abort shouldn't abort again.
+    if self.state.abort_stage.aborted {
+      aborting.clear();
+    }
+
+    aborting
+  }
+
+  /// Returns the sensors that have stayed beyond a critical redline for the
+  /// persistence in their mappings since this was last run, and whose mappings
+  /// call for an abort.
+  pub(crate) fn update_critical_redlines(
+    &mut self,
+    mappings: &Mappings,
+  ) -> Vec<String> {
+    let now = current_timestamp();
+
+    let critical: HashSet<String> = mappings
+      .iter()
+      .filter(|mapping| {
+        let persistence = mapping
+          .critical_persistence
+          .unwrap_or(DEFAULT_CRITICAL_PERSISTENCE);
+
+        self
+          .state
+          .redline_alarms
+          .get(&mapping.text_id)
+          .is_some_and(|alarm| {
+            alarm.severity == AlarmSeverity::Critical
+              && now - alarm.first_exceeded >= persistence
+          })
+      })
+      .map(|mapping| mapping.text_id.clone())
+      .collect();
+
+    let mut aborting: Vec<String> = mappings
+      .iter()
+      .filter(|mapping| {
+        mapping.abort_on_critical == Some(true)
+          && critical.contains(&mapping.text_id)
+          && !self.critical_redlines.contains(&mapping.text_id)
+      })
+      .map(|mapping| mapping.text_id.clone())
+      .collect();
+
+    self.critical_redlines = critical;
+
+    if self.state.abort_stage.aborted {
+      aborting.clear();
     }
+
+    aborting
   }
 
   /// Sends a message on a socket to a board with id `destination`
@@ -380,132 +576,291 @@ impl Devices {
     return Ok(());
   }
 
-  ///
-  pub(crate) fn send_sam_commands(
+  /// Sends a control message to the SAM board with id `board_id`, tagged with
+  /// a new sequence number. Messages that require acknowledgement are kept
+  /// and retransmitted by `retransmit_sam_commands` until the board
+  /// acknowledges them, replacing any pending message they make obsolete.
+  fn send_sam_command(
+    &mut self,
+    socket: &UdpSocket,
+    board_id: &str,
+    message: SamControlMessage,
+  ) -> std::result::Result<(), String> {
+    let request = SamControlRequest {
+      sequence: self.next_sam_sequence,
+      message,
+    };
+
+    self.next_sam_sequence = self.next_sam_sequence.wrapping_add(1);
+
+    let result = self.serialize_and_send(socket, board_id, &request);
+
+    self.track_sam_command(board_id, request);
+    result
+  }
+
+  /// Drops the pending commands to `board_id` that `request` makes obsolete,
+  /// whether or not `request` itself needs acknowledging, then keeps
+  /// `request` for retransmission if it does. A command whose first send
+  /// failed is still retried, as the board may only be momentarily
+  /// unreachable.
+  fn track_sam_command(&mut self, board_id: &str, request: SamControlRequest) {
+    let delivery = self
+      .state
+      .command_delivery
+      .entry(board_id.to_string())
+      .or_default();
+
+    self.pending_sam_commands.retain(|pending| {
+      let superseded = pending.board_id == board_id
+        && supersedes(&request.message, &pending.request.message);
+
+      if superseded {
+        delivery.superseded += 1;
+        delivery.pending -= 1;
+      }
+
+      !superseded
+    });
+
+    if !request.message.requires_acknowledgement() {
+      return;
+    }
+
+    delivery.sent += 1;
+    delivery.pending += 1;
+
+    self.pending_sam_commands.push(PendingSamCommand {
+      board_id: board_id.to_string(),
+      request,
+      last_sent: Instant::now(),
+      attempts: 1,
+    });
+  }
+
+  /// Marks the command with the given sequence number as received by a board.
+  fn acknowledge_sam_command(&mut self, board_id: &str, sequence: u32) {
+    // acknowledgements of commands that aren't tracked, or that were already
+    // acknowledged, need no action.
+    let Some(index) = self.pending_sam_commands.iter().position(|pending| {
+      pending.board_id == board_id && pending.request.sequence == sequence
+    }) else {
+      return;
+    };
+
+    self.pending_sam_commands.swap_remove(index);
+
+    let delivery = self
+      .state
+      .command_delivery
+      .entry(board_id.to_string())
+      .or_default();
+
+    delivery.acknowledged += 1;
+    delivery.pending -= 1;
+  }
+
+  /// Resends every SAM command that hasn't been acknowledged within
+  /// `SAM_COMMAND_RETRY_INTERVAL`, giving up on those that have been sent
+  /// `SAM_COMMAND_MAX_ATTEMPTS` times. Should be run every loop iteration.
+  pub(crate) fn retransmit_sam_commands(&mut self, socket: &UdpSocket) {
+    let now = Instant::now();
+    let mut index = 0;
+
+    while index < self.pending_sam_commands.len() {
+      let pending = &self.pending_sam_commands[index];
+
+      if now.duration_since(pending.last_sent) < SAM_COMMAND_RETRY_INTERVAL {
+        index += 1;
+        continue;
+      }
+
+      if pending.attempts >= SAM_COMMAND_MAX_ATTEMPTS {
+        let pending = self.pending_sam_commands.swap_remove(index);
+
+        eprintln!(
+          "{} never acknowledged {:?} after {} attempts. Giving up on it.",
+          pending.board_id, pending.request.message, pending.attempts
+        );
+
+        let delivery =
+          self.state.command_delivery.entry(pending.board_id).or_default();
+        delivery.failed += 1;
+        delivery.pending -= 1;
+        continue;
+      }
+
+      if let Err(msg) =
+        self.serialize_and_send(socket, &pending.board_id, &pending.request)
+      {
+        println!("{}", msg);
+      }
+
+      let pending = &mut self.pending_sam_commands[index];
+      pending.attempts += 1;
+      pending.last_sent = now;
+
+      self
+        .state
+        .command_delivery
+        .entry(pending.board_id.clone())
+        .or_default()
+        .retransmitted += 1;
+
+      index += 1;
+    }
+  }
+
+  /// Carries out a command sent from a sequence, returning why it couldn't be
+  /// if it wasn't, such as the valve it actuates being claimed by another
+  /// sequence. `SequenceDomainCommand::Abort` is left to the caller, as
+  /// the abort sequence should only be run once every command has been
+  /// handled.
+  pub(crate) fn handle_sequence_command(
     &mut self,
     socket: &UdpSocket,
     mappings: &Mappings,
-    commands: Vec<SequenceDomainCommand>,
+    request: ReceivedCommand,
     abort_stages: &mut AbortStages,
     sequences: &mut Sequences,
     gps_handle: Option<&GpsHandle>,
-  ) -> bool {
-    let mut should_abort = false;
-
-    for command in commands {
-      match command {
-        SequenceDomainCommand::ActuateValve { valve, state } => {
-          let Some(mapping) = mappings.iter().find(|m| m.text_id == valve)
-          else {
-            eprintln!(
-              "Failed to actuate valve: mapping '{valve}' is not defined."
-            );
-            continue;
-          };
-
-          let closed = state == ValveState::Closed;
-          let normally_closed = mapping.normally_closed.unwrap_or(true);
-          let powered = closed != normally_closed;
-
-          if let Some(existing) = self.state.valve_states.get_mut(&valve) {
-            existing.commanded = state;
-          } else {
-            self.state.valve_states.insert(
-              valve,
-              CompositeValveState {
-                commanded: state,
-                actual: ValveState::Undetermined,
-              },
-            );
-          }
+  ) -> std::result::Result<(), SequenceCommandError> {
+    let ReceivedCommand { pid, command, .. } = request;
+
+    match command {
+      SequenceDomainCommand::ActuateValve { valve, state } => {
+        let Some(mapping) = mappings.iter().find(|m| m.text_id == valve)
+        else {
+          return Err(SequenceCommandError::ValveNotFound(valve));
+        };
 
-          let command = SamControlMessage::ActuateValve {
-            channel: mapping.channel,
-            powered,
-          };
+        sequence::check_claim(sequences, pid, &valve)?;
+        self.state.valve_commanders.insert(
+          valve.clone(),
+          ValveCommander {
+            sequence: sequence::name_of(sequences, pid).map(str::to_string),
+            pid,
+            commanded_at: SystemTime::now()
+              .duration_since(UNIX_EPOCH)
+              .map_or(0.0, |time| time.as_secs_f64()),
+          },
+        );
 
-          if let Err(msg) =
-            self.serialize_and_send(socket, &mapping.board_id, &command)
-          {
-            println!("{}", msg);
-          }
-        }
+        let closed = state == ValveState::Closed;
+        let normally_closed = mapping.normally_closed.unwrap_or(true);
+        let powered = closed != normally_closed;
 
-        SequenceDomainCommand::CreateAbortStage {
-          stage_name,
-          abort_condition,
-          valve_safe_states,
-        } => {
-          self.create_abort_stage(
-            mappings,
-            abort_stages,
-            AbortStageConfig {
-              stage_name,
-              abort_condition,
-              valve_safe_states,
+        if let Some(existing) = self.state.valve_states.get_mut(&valve) {
+          existing.commanded = state;
+        } else {
+          self.state.valve_states.insert(
+            valve,
+            CompositeValveState {
+              commanded: state,
+              actual: ValveState::Undetermined,
             },
           );
         }
 
-        // TODO: should we not allow setting an abort stage if we already in
-        // that abort stage?
-        SequenceDomainCommand::SetAbortStage { stage_name } => {
-          self.handle_setting_abort_stage(socket, stage_name, abort_stages);
-        }
+        let command = SamControlMessage::ActuateValve {
+          channel: mapping.channel,
+          powered,
+        };
 
-        SequenceDomainCommand::AbortViaStage => {
-          //println!("Sending abort message to sams");
-          self.send_sams_abort(socket, mappings, abort_stages, sequences, true);
-          // command from a sequence, so yes we want to use stage timers
+        // the command is retried until the board acknowledges it, so a failed
+        // first send doesn't fail the request.
+        if let Err(msg) =
+          self.send_sam_command(socket, &mapping.board_id, command)
+        {
+          println!("{}", msg);
         }
+      }
 
-        SequenceDomainCommand::RecoCommand(reco_command) => {
-            self.handle_sequence_reco_message(gps_handle, reco_command);
-        }
+      SequenceDomainCommand::CreateAbortStage {
+        stage_name,
+        abort_condition,
+        valve_safe_states,
+      } => {
+        self.create_abort_stage(
+          mappings,
+          abort_stages,
+          AbortStageConfig {
+            stage_name,
+            abort_condition,
+            valve_safe_states,
+          },
+        )?;
+      }
+
+      // TODO: should we not allow setting an abort stage if we already in
+      // that abort stage?
+      SequenceDomainCommand::SetAbortStage { stage_name } => {
+        self.handle_setting_abort_stage(socket, stage_name, abort_stages)?;
+      }
+
+      SequenceDomainCommand::AbortViaStage => {
+        //println!("Sending abort message to sams");
+        self.send_sams_abort(socket, mappings, abort_stages, sequences, true);
+        // command from a sequence, so yes we want to use stage timers
+      }
 
-        SequenceDomainCommand::LaunchLugArm {
+      SequenceDomainCommand::RecoCommand(reco_command) => {
+        self.handle_sequence_reco_message(gps_handle, reco_command)?;
+      }
+
+      SequenceDomainCommand::LaunchLugArm {
+        sam_hostname,
+        should_enable,
+      } => {
+        self.send_sams_toggle_launch_lug_arm(
+          socket,
           sam_hostname,
           should_enable,
-        } => {
-          self.send_sams_toggle_launch_lug_arm(
-            socket,
-            sam_hostname,
-            should_enable,
-          );
-        }
-        SequenceDomainCommand::LaunchLugDetonate {
+        )?;
+      }
+      SequenceDomainCommand::LaunchLugDetonate {
+        sam_hostname,
+        should_enable,
+      } => {
+        self.send_sams_toggle_launch_lug_detonate(
+          socket,
           sam_hostname,
           should_enable,
-        } => {
-          self.send_sams_toggle_launch_lug_detonate(
-            socket,
-            sam_hostname,
-            should_enable,
-          );
-        }
-        SequenceDomainCommand::CameraEnable { should_enable } => {
-          self.send_sams_toggle_camera(socket, should_enable);
+        )?;
+      }
+      SequenceDomainCommand::CameraEnable { should_enable } => {
+        self.send_sams_toggle_camera(socket, should_enable);
+      }
+      SequenceDomainCommand::SetServoDisconnectMonitoring { enabled } => {
+        self.monitor_servo_disconnects = enabled;
+        if enabled {
+          // When monitoring is re-enabled, also allow the FC to resume
+          // communicating with servo. The main loop will handle
+          // reconnecting on the next pull attempt if needed.
+          self.servo_communication_enabled = true;
         }
-        SequenceDomainCommand::SetServoDisconnectMonitoring { enabled } => {
-          self.monitor_servo_disconnects = enabled;
-          if enabled {
-            // When monitoring is re-enabled, also allow the FC to resume
-            // communicating with servo. The main loop will handle
-            // reconnecting on the next pull attempt if needed.
-            self.servo_communication_enabled = true;
-          }
-          println!(
-            "Servo disconnect monitoring {}.",
-            if enabled { "enabled" } else { "disabled" }
-          );
+        println!(
+          "Servo disconnect monitoring {}.",
+          if enabled { "enabled" } else { "disabled" }
+        );
+      }
+      SequenceDomainCommand::ClaimValves { valves } => {
+        if let Some(valve) = valves
+          .iter()
+          .find(|valve| !mappings.iter().any(|m| &m.text_id == *valve))
+        {
+          return Err(SequenceCommandError::ValveNotFound(valve.clone()));
         }
-        // TODO: shouldn't we break out of the loop here? if we receive an abort
-        // command why are we not flushing commands that come in after
-        SequenceDomainCommand::Abort => should_abort = true,
+
+        sequence::claim(sequences, pid, valves)?;
+      }
+      SequenceDomainCommand::ReleaseValves { valves } => {
+        sequence::release(sequences, pid, &valves);
       }
+      SequenceDomainCommand::Abort => {}
     }
 
-    should_abort
+    Ok(())
   }
 
   /// Enqueues a RECO command for the RECO worker to process.
@@ -581,7 +936,7 @@ impl Devices {
     &self,
     gps_handle: Option<&GpsHandle>,
     command: RecoSequenceCommand,
-  ) {
+  ) -> std::result::Result<(), SequenceCommandError> {
       let (reco_command, label) = match command {
         RecoSequenceCommand::Launch => (
             RecoControlMessage::Launch,
@@ -593,16 +948,40 @@ impl Devices {
         ),
       };
 
-      self.enqueue_reco_command(gps_handle, reco_command, label);
+      let Some(gps_handle) = gps_handle else {
+        return Err(SequenceCommandError::Rejected(format!(
+          "Received {label} command, but RECO worker is not initialized."
+        )));
+      };
+
+      gps_handle.send_reco_control(reco_command).map_err(|_| {
+        SequenceCommandError::Rejected(format!(
+          "Failed to enqueue {label} command for RECO worker."
+        ))
+      })
     }
 
 
+  /// Builds an abort stage from its config and the current mappings,
+  /// replacing any stage of the same name. Returns why the stage was rejected
+  /// if it was.
   pub(crate) fn create_abort_stage(
     &mut self,
     mappings: &Mappings,
     abort_stages: &mut AbortStages,
     stage_config: AbortStageConfig,
-  ) {
+  ) -> std::result::Result<(), SequenceCommandError> {
+    // reject a condition that can't be parsed or names an unknown valve or
+    // sensor now, rather than failing every time it is evaluated.
+    if let Err(e) = condition::parse(&stage_config.abort_condition)
+      .and_then(|expr| condition::validate(&expr, mappings))
+    {
+      return Err(SequenceCommandError::Rejected(format!(
+        "Rejected abort stage '{}' because its abort condition is invalid: {e}",
+        stage_config.stage_name
+      )));
+    }
+
     let mut valve_lookup: HashMap<String, (&str, u32, bool)> = HashMap::new();
     for mapping in mappings {
       if mapping.sensor_type == SensorType::Valve {
@@ -622,11 +1001,7 @@ impl Devices {
       let Some(&(board_id, channel, normally_closed)) =
         valve_lookup.get(&valve_name)
       else {
-        eprintln!(
-          "Abort valve '{}' not found in mappings. Skipping command.",
-          valve_name
-        );
-        continue;
+        return Err(SequenceCommandError::ValveNotFound(valve_name));
       };
 
       // determine if we want to give power to this valve
@@ -660,6 +1035,8 @@ impl Devices {
       aborted: false,
       valve_safe_states: board_valves,
     });
+
+    Ok(())
   }
 
   pub(crate) fn handle_setting_abort_stage(
@@ -667,24 +1044,24 @@ impl Devices {
     socket: &UdpSocket,
     stage_name: String,
     abort_stages: &mut AbortStages,
-  ) {
+  ) -> std::result::Result<(), SequenceCommandError> {
     // change the abort stage in vehicle state by looking through saved abort
     // stage configs. if name doesn't match up throw an error
     if let Some(stage) = abort_stages.iter().find(|m| m.name == stage_name) {
       self.set_abort_stage(&stage);
     } else {
-      eprintln!("Tried to set abort stage to {stage_name} but could not find the stage.");
-      return;
+      return Err(SequenceCommandError::AbortStageNotFound(stage_name));
     }
 
     self.send_sams_abort_stage(socket, &None);
+    Ok(())
   }
 
   // sends all sams the current abort stage's safe valve states. if "None"
   // board_id is passed, message is sent to all sams. else, a message is sent
   // to the board id passed in (if it is valid)
   pub(crate) fn send_sams_abort_stage(
-    &self,
+    &mut self,
     socket: &UdpSocket,
     board_id: &Option<&String>,
   ) {
@@ -693,52 +1070,44 @@ impl Devices {
     // in whatever state they are in already
 
     // individual board
-    if board_id.is_some() {
-      if let Some(device) = self.devices.iter().find(|d| {
-        d.get_board_id().deref() == board_id.unwrap()
-          && board_id.unwrap().starts_with("sam")
+    if let Some(board_id) = *board_id {
+      if !self.devices.iter().any(|d| {
+        d.get_board_id() == board_id && board_id.starts_with("sam")
       }) {
-        if let Some(valve_states_to_send) = self
-          .state
-          .abort_stage
-          .valve_safe_states
-          .get(device.get_board_id())
-        {
-          let command = SamControlMessage::AbortStageValveStates {
-            valve_states: valve_states_to_send.clone(),
-          };
+        eprintln!("Invalid board id passed in when trying to send sams abort stage: Either your board does not exist or is not a sam.");
+        return;
+      }
 
-          // send message to this sam board
-          if let Err(msg) =
-            self.serialize_and_send(socket, board_id.unwrap(), &command)
-          {
-            println!("{}", msg);
-          } else {
-            println!(
-              "Sent {} abort stage's valve safe states to SAM: {}",
-              self.state.abort_stage.name,
-              board_id.unwrap()
-            );
-          }
+      if let Some(valve_states_to_send) =
+        self.state.abort_stage.valve_safe_states.get(board_id)
+      {
+        let command = SamControlMessage::AbortStageValveStates {
+          valve_states: valve_states_to_send.clone(),
+        };
+
+        // send message to this sam board
+        if let Err(msg) = self.send_sam_command(socket, board_id, command) {
+          println!("{}", msg);
         } else {
           println!(
-            "No abort stage configuration to send to {}",
-            device.get_board_id()
+            "Sent {} abort stage's valve safe states to SAM: {}",
+            self.state.abort_stage.name, board_id
           );
         }
       } else {
-        eprintln!("Invalid board id passed in when trying to send sams abort stage: Either your board does not exist or is not a sam.");
+        println!("No abort stage configuration to send to {}", board_id);
       }
     } else {
-      for (board_id, valves) in self.state.abort_stage.valve_safe_states.iter()
-      {
+      let valve_safe_states = self.state.abort_stage.valve_safe_states.clone();
+
+      for (board_id, valves) in valve_safe_states {
         // create message for this sam board
         let command = SamControlMessage::AbortStageValveStates {
-          valve_states: valves.clone(),
+          valve_states: valves,
         };
 
         // send message to this sam board
-        if let Err(msg) = self.serialize_and_send(socket, &board_id, &command) {
+        if let Err(msg) = self.send_sam_command(socket, &board_id, command) {
           println!("{}", msg);
         } else {
           println!(
@@ -757,30 +1126,20 @@ impl Devices {
     sequences: &mut Sequences,
     use_stage_timers: bool,
   ) {
-    // kill all sequences besides the abort stage sequence
-    for (name, sequence) in &mut *sequences {
-      if name != "AbortStage" {
-        if let Err(e) = sequence.kill() {
-          println!("Couldn't kill a sequence in preperation for abort, continuing normally: {e}");
-        }
-      }
-    }
+    // kill all running sequences
+    sequence::kill_all(sequences);
 
     // send message to sams
-    for device in self.devices.iter() {
-      if device.get_board_id().starts_with("sam") {
-        let command = SamControlMessage::Abort {
-          use_stage_timers: use_stage_timers,
-        };
-        // send message to this sam board
-        if let Err(msg) =
-          self.serialize_and_send(socket, device.get_board_id(), &command)
-        {
-          println!("{}", msg);
-        } else {
-          println!("Sent abort message to SAM: {}, which will use {} stage's safe valves.", 
-                        device.get_board_id(), self.state.abort_stage.name);
-        }
+    for board_id in self.sam_board_ids(|_| true) {
+      let command = SamControlMessage::Abort {
+        use_stage_timers: use_stage_timers,
+      };
+      // send message to this sam board
+      if let Err(msg) = self.send_sam_command(socket, &board_id, command) {
+        println!("{}", msg);
+      } else {
+        println!("Sent abort message to SAM: {}, which will use {} stage's safe valves.", 
+                      board_id, self.state.abort_stage.name);
       }
     }
 
@@ -789,90 +1148,85 @@ impl Devices {
   }
 
   // Clears any stored abort stages on sams
-  pub(crate) fn send_sam_clear_abort_stage(&self, socket: &UdpSocket) {
-    for device in self.devices.iter() {
-      if device.get_board_id().starts_with("sam") {
-        let command = SamControlMessage::ClearStoredAbortStage {};
-        if let Err(msg) =
-          self.serialize_and_send(socket, device.get_board_id(), &command)
-        {
-          println!("{}", msg);
-        } else {
-          println!("Cleared abort stage from {} memory", device.get_board_id());
-        }
+  pub(crate) fn send_sam_clear_abort_stage(&mut self, socket: &UdpSocket) {
+    for board_id in self.sam_board_ids(|_| true) {
+      let command = SamControlMessage::ClearStoredAbortStage {};
+      if let Err(msg) = self.send_sam_command(socket, &board_id, command) {
+        println!("{}", msg);
+      } else {
+        println!("Cleared abort stage from {} memory", board_id);
       }
     }
   }
 
   pub(crate) fn send_sams_toggle_camera(
-    &self,
+    &mut self,
     socket: &UdpSocket,
     should_enable: bool,
   ) {
-    for device in self.devices.iter() {
-      // only send camera enable message to flight sams
-      if device.get_board_id().starts_with("sam-2")
-        || device.get_board_id().starts_with("sam-3")
-      {
-        // create message for this sam board
-        let command = SamControlMessage::CameraEnable(should_enable);
+    // only send camera enable message to flight sams
+    for board_id in self.sam_board_ids(|id| {
+      id.starts_with("sam-2") || id.starts_with("sam-3")
+    }) {
+      // create message for this sam board
+      let command = SamControlMessage::CameraEnable(should_enable);
 
-        // send message to this sam board
-        if let Err(msg) =
-          self.serialize_and_send(socket, device.get_board_id(), &command)
-        {
-          println!("{}", msg);
-        }
+      // send message to this sam board
+      if let Err(msg) = self.send_sam_command(socket, &board_id, command) {
+        println!("{}", msg);
       }
     }
   }
 
   pub(crate) fn send_sams_toggle_launch_lug_arm(
-    &self,
+    &mut self,
     socket: &UdpSocket,
     sam_hostname: String,
     should_enable: bool,
-  ) {
-    if let Some(device) = self
-      .devices
-      .iter()
-      .find(|d| d.get_board_id() == &sam_hostname)
-    {
-      let command = SamControlMessage::LaunchLugArm(should_enable);
+  ) -> std::result::Result<(), SequenceCommandError> {
+    if !self.devices.iter().any(|d| d.get_board_id() == &sam_hostname) {
+      return Err(SequenceCommandError::BoardNotFound(sam_hostname));
+    }
 
-      // send message to this sam board
-      if let Err(msg) =
-        self.serialize_and_send(socket, device.get_board_id(), &command)
-      {
-        println!("{}", msg);
-      }
-    } else {
-      eprintln!("Invalid board id passed in when trying to send sams launch lug arm: Either your board does not exist or is not a sam.");
+    let command = SamControlMessage::LaunchLugArm(should_enable);
+
+    // send message to this sam board
+    if let Err(msg) = self.send_sam_command(socket, &sam_hostname, command) {
+      println!("{}", msg);
     }
+
+    Ok(())
   }
 
   pub(crate) fn send_sams_toggle_launch_lug_detonate(
-    &self,
+    &mut self,
     socket: &UdpSocket,
     sam_hostname: String,
     should_enable: bool,
-  ) {
-    if let Some(device) = self
-      .devices
-      .iter()
-      .find(|d| d.get_board_id() == &sam_hostname)
-    {
-      let command = SamControlMessage::LaunchLugDetonate(should_enable);
+  ) -> std::result::Result<(), SequenceCommandError> {
+    if !self.devices.iter().any(|d| d.get_board_id() == &sam_hostname) {
+      return Err(SequenceCommandError::BoardNotFound(sam_hostname));
+    }
 
-      // send message to this sam board
-      if let Err(msg) =
-        self.serialize_and_send(socket, device.get_board_id(), &command)
-      {
-        println!("{}", msg);
-      }
-    } else {
-      eprintln!("Invalid board id passed in when trying to send sams launch lug detonate: Either your board does not exist or is not a sam.");
+    let command = SamControlMessage::LaunchLugDetonate(should_enable);
+
+    // send message to this sam board
+    if let Err(msg) = self.send_sam_command(socket, &sam_hostname, command) {
+      println!("{}", msg);
     }
+
+    Ok(())
+  }
+
+  /// Returns the ids of every connected SAM board whose id matches `filter`.
+  fn sam_board_ids(&self, filter: impl Fn(&str) -> bool) -> Vec<String> {
+    self
+      .devices
+      .iter()
+      .map(|device| device.get_board_id())
+      .filter(|id| id.starts_with("sam") && filter(id))
+      .cloned()
+      .collect()
   }
 
   pub(crate) fn send_bms_command(
@@ -908,10 +1262,16 @@ impl Devices {
   /// Update RECO-related fields on the vehicle state with new samples from all
   /// three MCUs. The array should contain: [MCU A (spidev1.2), MCU B
   /// (spidev1.1), MCU C (spidev1.0)]
-  pub(crate) fn update_reco(&mut self, samples: [Option<RecoState>; 3]) {
+  pub(crate) fn update_reco(
+    &mut self,
+    samples: [Option<RecoState>; 3],
+    thresholds: &RecoVoteThresholds,
+  ) {
     self.state.rbf.reco = get_reco_rbf_values(&samples);
     self.state.reco = samples.into();
+    self.state.reco_disagreement = self.state.reco.disagreement(thresholds);
     self.state.reco_valid = true;
+    self.state.reco_timestamp = current_timestamp();
   }
 
   /// Mark RECO data as invalid for the current control-loop iteration.
@@ -926,6 +1286,10 @@ impl Devices {
     &self.state
   }
 
+  pub(crate) fn get_state_mut(&mut self) -> &mut VehicleState {
+    &mut self.state
+  }
+
   /// Returns whether the FC should monitor servo disconnects.
   pub(crate) fn monitor_servo_disconnects(&self) -> bool {
     self.monitor_servo_disconnects
@@ -974,6 +1338,36 @@ pub(crate) fn get_reco_rbf_values(
   ]
 }
 
+/// Whether a valve has been commanded open or closed but is estimated to be
+/// in some other state. A valve without readings yet isn't mismatched.
+fn is_mismatched(valve: &CompositeValveState) -> bool {
+  matches!(valve.commanded, ValveState::Open | ValveState::Closed)
+    && valve.actual != ValveState::Undetermined
+    && valve.actual != valve.commanded
+}
+
+/// Whether sending `new` to a board makes a pending `old` sent to the same
+/// board obsolete, such that retransmitting `old` could undo `new`.
+fn supersedes(new: &SamControlMessage, old: &SamControlMessage) -> bool {
+  match (new, old) {
+    (
+      SamControlMessage::ActuateValve { channel: new, .. },
+      SamControlMessage::ActuateValve { channel: old, .. },
+    ) => new == old,
+    // an abort must not be followed by a stale actuation reopening a valve.
+    (
+      SamControlMessage::Abort { .. },
+      SamControlMessage::ActuateValve { .. } | SamControlMessage::Abort { .. },
+    ) => true,
+    (
+      SamControlMessage::AbortStageValveStates { .. }
+      | SamControlMessage::ClearStoredAbortStage {},
+      SamControlMessage::AbortStageValveStates { .. },
+    ) => true,
+    _ => false,
+  }
+}
+
 /// performs a flight handshake with the board.
 pub(crate) fn handshake(
   address: &SocketAddr,
@@ -1040,3 +1434,345 @@ impl fmt::Display for Error {
     }
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::state::process_sam_data;
+  use common::comm::{
+    sam::{ChannelType, SamDataPoint, SensorDataPoint},
+    AlarmBound, CommandDelivery, RedlineAlarm,
+  };
+
+  fn actuate(channel: u32, powered: bool) -> SamControlMessage {
+    SamControlMessage::ActuateValve { channel, powered }
+  }
+
+  fn abort() -> SamControlMessage {
+    SamControlMessage::Abort {
+      use_stage_timers: true,
+    }
+  }
+
+  fn abort_stage(channel_num: u32) -> SamControlMessage {
+    SamControlMessage::AbortStageValveStates {
+      valve_states: vec![ValveAction {
+        channel_num,
+        powered: false,
+        timer: Duration::from_secs(1),
+      }],
+    }
+  }
+
+  fn socket() -> UdpSocket {
+    UdpSocket::bind("127.0.0.1:0").unwrap()
+  }
+
+  /// Commands to unconnected boards fail to send but are still tracked, which
+  /// is all these tests need.
+  fn send(devices: &mut Devices, board_id: &str, message: SamControlMessage) {
+    let _ = devices.send_sam_command(&socket(), board_id, message);
+  }
+
+  fn pending_messages(devices: &Devices) -> Vec<(&str, &SamControlMessage)> {
+    devices
+      .pending_sam_commands
+      .iter()
+      .map(|pending| (pending.board_id.as_str(), &pending.request.message))
+      .collect()
+  }
+
+  fn delivery<'a>(devices: &'a Devices, board_id: &str) -> &'a CommandDelivery {
+    &devices.state.command_delivery[board_id]
+  }
+
+  /// Pretends every pending command was last sent long enough ago to be due
+  /// for retransmission.
+  fn age_pending(devices: &mut Devices) {
+    for pending in &mut devices.pending_sam_commands {
+      pending.last_sent -= SAM_COMMAND_RETRY_INTERVAL;
+    }
+  }
+
+  #[test]
+  fn actuations_supersede_only_the_same_channel() {
+    assert!(supersedes(&actuate(1, true), &actuate(1, false)));
+    assert!(!supersedes(&actuate(1, true), &actuate(2, false)));
+    assert!(!supersedes(&actuate(1, true), &abort()));
+    assert!(!supersedes(&actuate(1, true), &abort_stage(1)));
+  }
+
+  #[test]
+  fn aborts_supersede_actuations_and_aborts() {
+    assert!(supersedes(&abort(), &actuate(1, true)));
+    assert!(supersedes(&abort(), &abort()));
+    assert!(!supersedes(&abort(), &abort_stage(1)));
+  }
+
+  #[test]
+  fn abort_stages_are_superseded_by_new_stages_and_clears() {
+    let clear = SamControlMessage::ClearStoredAbortStage {};
+
+    assert!(supersedes(&abort_stage(2), &abort_stage(1)));
+    assert!(supersedes(&clear, &abort_stage(1)));
+    assert!(!supersedes(&clear, &actuate(1, true)));
+    assert!(!supersedes(&abort_stage(1), &actuate(1, true)));
+    assert!(!supersedes(
+      &SamControlMessage::CameraEnable(true),
+      &actuate(1, true)
+    ));
+  }
+
+  #[test]
+  fn only_commands_requiring_acknowledgement_are_kept() {
+    let mut devices = Devices::new();
+
+    send(&mut devices, "sam-01", actuate(1, true));
+    send(
+      &mut devices,
+      "sam-01",
+      SamControlMessage::CameraEnable(true),
+    );
+
+    assert_eq!(
+      pending_messages(&devices),
+      vec![("sam-01", &actuate(1, true))]
+    );
+    assert_eq!(delivery(&devices, "sam-01").sent, 1);
+    assert_eq!(delivery(&devices, "sam-01").pending, 1);
+  }
+
+  #[test]
+  fn clearing_the_abort_stage_drops_a_pending_stage() {
+    let mut devices = Devices::new();
+
+    send(&mut devices, "sam-01", abort_stage(1));
+    send(
+      &mut devices,
+      "sam-01",
+      SamControlMessage::ClearStoredAbortStage {},
+    );
+
+    // the cleared stage must not be retransmitted and stored again.
+    assert!(pending_messages(&devices).is_empty());
+    assert_eq!(delivery(&devices, "sam-01").superseded, 1);
+    assert_eq!(delivery(&devices, "sam-01").pending, 0);
+  }
+
+  #[test]
+  fn superseding_is_limited_to_the_same_board() {
+    let mut devices = Devices::new();
+
+    send(&mut devices, "sam-01", actuate(1, true));
+    send(&mut devices, "sam-02", actuate(1, false));
+    send(&mut devices, "sam-02", abort());
+
+    assert_eq!(
+      pending_messages(&devices),
+      vec![("sam-01", &actuate(1, true)), ("sam-02", &abort())]
+    );
+    assert_eq!(delivery(&devices, "sam-01").superseded, 0);
+    assert_eq!(delivery(&devices, "sam-02").superseded, 1);
+    assert_eq!(delivery(&devices, "sam-02").pending, 1);
+  }
+
+  #[test]
+  fn acknowledgements_match_board_and_sequence() {
+    let mut devices = Devices::new();
+
+    send(&mut devices, "sam-01", actuate(1, true));
+    send(&mut devices, "sam-01", actuate(2, true));
+    let first = devices.pending_sam_commands[0].request.sequence;
+
+    // the right sequence number from the wrong board is not an acknowledgement.
+    devices.acknowledge_sam_command("sam-02", first);
+    assert_eq!(devices.pending_sam_commands.len(), 2);
+
+    devices.acknowledge_sam_command("sam-01", first);
+    assert_eq!(
+      pending_messages(&devices),
+      vec![("sam-01", &actuate(2, true))]
+    );
+
+    // acknowledging again changes nothing.
+    devices.acknowledge_sam_command("sam-01", first);
+    assert_eq!(devices.pending_sam_commands.len(), 1);
+    assert_eq!(delivery(&devices, "sam-01").acknowledged, 1);
+    assert_eq!(delivery(&devices, "sam-01").pending, 1);
+  }
+
+  #[test]
+  fn commands_are_retransmitted_until_attempts_run_out() {
+    let mut devices = Devices::new();
+    let socket = socket();
+
+    send(&mut devices, "sam-01", actuate(1, true));
+
+    // nothing is resent before the retry interval has passed.
+    devices.retransmit_sam_commands(&socket);
+    assert_eq!(devices.pending_sam_commands[0].attempts, 1);
+
+    for attempts in 2..=SAM_COMMAND_MAX_ATTEMPTS {
+      age_pending(&mut devices);
+      devices.retransmit_sam_commands(&socket);
+      assert_eq!(devices.pending_sam_commands[0].attempts, attempts);
+    }
+
+    age_pending(&mut devices);
+    devices.retransmit_sam_commands(&socket);
+
+    assert!(devices.pending_sam_commands.is_empty());
+    let delivery = delivery(&devices, "sam-01");
+    assert_eq!(delivery.sent, 1);
+    assert_eq!(delivery.retransmitted, SAM_COMMAND_MAX_ATTEMPTS - 1);
+    assert_eq!(delivery.failed, 1);
+    assert_eq!(delivery.pending, 0);
+  }
+
+  fn redlined(critical_persistence: Option<f64>) -> NodeMapping {
+    NodeMapping {
+      text_id: "FUEL_PT".to_string(),
+      board_id: "sam-01".to_string(),
+      critical_high: Some(750.0),
+      abort_on_critical: Some(true),
+      critical_persistence,
+      ..Default::default()
+    }
+  }
+
+  /// Raises a critical alarm on FUEL_PT which was first exceeded `ago`
+  /// seconds ago.
+  fn exceed_for(devices: &mut Devices, ago: f64) {
+    devices.state.redline_alarms.insert(
+      "FUEL_PT".to_string(),
+      RedlineAlarm {
+        severity: AlarmSeverity::Critical,
+        bound: AlarmBound::High,
+        limit: 750.0,
+        value: 800.0,
+        first_exceeded: current_timestamp() - ago,
+      },
+    );
+  }
+
+  #[test]
+  fn critical_redlines_abort_only_once_they_persist() {
+    let mut devices = Devices::new();
+    let mappings = vec![redlined(Some(0.5))];
+
+    exceed_for(&mut devices, 0.2);
+    assert!(devices.update_critical_redlines(&mappings).is_empty());
+
+    exceed_for(&mut devices, 0.6);
+    assert_eq!(devices.update_critical_redlines(&mappings), vec!["FUEL_PT"]);
+
+    // it stays beyond the redline, which doesn't abort again
+    assert!(devices.update_critical_redlines(&mappings).is_empty());
+  }
+
+  #[test]
+  fn critical_redlines_persist_briefly_by_default() {
+    let mut devices = Devices::new();
+    let mappings = vec![redlined(None)];
+
+    exceed_for(&mut devices, 0.0);
+    assert!(devices.update_critical_redlines(&mappings).is_empty());
+
+    exceed_for(&mut devices, DEFAULT_CRITICAL_PERSISTENCE);
+    assert_eq!(devices.update_critical_redlines(&mappings), vec!["FUEL_PT"]);
+  }
+
+  #[test]
+  fn critical_redlines_abort_again_after_clearing() {
+    let mut devices = Devices::new();
+    let mappings = vec![redlined(Some(0.0))];
+
+    exceed_for(&mut devices, 0.0);
+    assert_eq!(devices.update_critical_redlines(&mappings), vec!["FUEL_PT"]);
+
+    devices.state.redline_alarms.clear();
+    assert!(devices.update_critical_redlines(&mappings).is_empty());
+
+    exceed_for(&mut devices, 0.0);
+    assert_eq!(devices.update_critical_redlines(&mappings), vec!["FUEL_PT"]);
+  }
+
+  /// A board which last reported `ago` before now.
+  fn board(id: &str, ago: Duration) -> Device {
+    let mut device =
+      Device::new(id.to_string(), "127.0.0.1:0".parse().unwrap());
+    device.last_recieved = Instant::now().checked_sub(ago).unwrap();
+    device
+  }
+
+  fn reported(devices: &mut Devices, text_id: &str, board_id: Option<&str>) {
+    devices.state.sensor_readings.insert(
+      text_id.to_string(),
+      Measurement {
+        value: 800.0,
+        unit: Unit::Psi,
+        timestamp: current_timestamp(),
+        board_id: board_id.map(str::to_string),
+        stale: false,
+      },
+    );
+  }
+
+  fn is_stale(devices: &Devices, text_id: &str) -> bool {
+    devices.state.sensor_readings[text_id].stale
+  }
+
+  #[test]
+  fn readings_go_stale_once_their_board_is_silent() {
+    let mut devices = Devices::new();
+    devices.devices = vec![
+      board("sam-01", TIME_TO_LIVE + Duration::from_millis(50)),
+      board("sam-02", Duration::ZERO),
+    ];
+
+    reported(&mut devices, "FUEL_PT", Some("sam-01"));
+    reported(&mut devices, "OX_PT", Some("sam-02"));
+    reported(&mut devices, "INJ_DP", None);
+    exceed_for(&mut devices, 1.0);
+
+    devices.mark_stale_readings();
+
+    assert!(is_stale(&devices, "FUEL_PT"));
+    assert!(!is_stale(&devices, "OX_PT"));
+    assert!(!is_stale(&devices, "INJ_DP"));
+
+    // a stale reading can't be trusted to be beyond its redline
+    assert!(devices.state.redline_alarms.is_empty());
+  }
+
+  #[test]
+  fn readings_stay_stale_until_reported_anew() {
+    let mut devices = Devices::new();
+    devices.devices =
+      vec![board("sam-01", TIME_TO_LIVE + Duration::from_millis(50))];
+    let mappings = vec![redlined(None)];
+
+    reported(&mut devices, "FUEL_PT", Some("sam-01"));
+    devices.mark_stale_readings();
+    assert!(is_stale(&devices, "FUEL_PT"));
+
+    // the board reconnecting doesn't make its last reading any newer
+    devices.devices[0].reset_timer();
+    devices.mark_stale_readings();
+    assert!(is_stale(&devices, "FUEL_PT"));
+
+    process_sam_data(
+      "sam-01",
+      &mut devices.state,
+      vec![SamDataPoint::Sensor(SensorDataPoint {
+        value: 2.0,
+        timestamp: current_timestamp(),
+        channel: 0,
+        channel_type: ChannelType::CurrentLoop,
+      })],
+      &mappings,
+    );
+    devices.mark_stale_readings();
+    assert!(!is_stale(&devices, "FUEL_PT"));
+  }
+}
@@ -8,7 +8,13 @@ use std::{
   time::{Duration, Instant},
 };
 
-use common::comm::{reco::TargetMCU, GpsState, RecoState, VehicleState};
+use common::comm::{
+  reco::TargetMCU,
+  GpsState,
+  RecoState,
+  RecoVoteThresholds,
+  VehicleState,
+};
 use reco::{FcGpsBody, RecoBody, RecoDriver, 
   ProcessNoiseMatrix,
   MeasurementNoiseMatrix,
@@ -235,12 +241,15 @@ impl GpsManager {
   /// `vehicle_state_receiver` is used to receive vehicle state updates for
   /// logging. `file_logger_sender` is the sender for logging vehicle state at
   /// 200Hz. `print_gps` enables printing GPS data to terminal at ~1Hz.
+  /// `reco_vote_thresholds` decides when a RECO MCU in the logged state is
+  /// flagged as disagreeing with the vote across all three.
   pub fn spawn(
     i2c_bus: u8,
     address: Option<u16>,
     vehicle_state_receiver: mpsc::Receiver<VehicleState>,
     file_logger_sender: Option<mpsc::SyncSender<TimestampedVehicleState>>,
     print_gps: bool,
+    reco_vote_thresholds: RecoVoteThresholds,
   ) -> Result<GpsHandle, GPSError> {
     let (writer, reader) = create_gps_reco_mailbox();
     let running = Arc::new(AtomicBool::new(true));
@@ -287,6 +296,7 @@ impl GpsManager {
           vehicle_state_receiver,
           file_logger_sender,
           reco_control_receiver,
+          reco_vote_thresholds,
         );
 
         // Mark the worker as no longer running, regardless of success or error.
@@ -447,6 +457,7 @@ fn gps_worker_loop(
   vehicle_state_receiver: mpsc::Receiver<VehicleState>,
   file_logger_sender: Option<mpsc::SyncSender<TimestampedVehicleState>>,
   reco_control_receiver: mpsc::Receiver<RecoControlMessage>,
+  reco_vote_thresholds: RecoVoteThresholds,
 ) -> Result<(), GPSError> {
   // Optional performance debug logging for GPS/RECO worker.
   let perf_debug = std::env::var("GPS_RECO_PERF_DEBUG").is_ok();
@@ -689,6 +700,8 @@ fn gps_worker_loop(
           let mut updated_state = state.clone();
           updated_state.gps = last_gps_state.clone();
           updated_state.reco = reco_states.clone().into();
+          updated_state.reco_disagreement =
+            updated_state.reco.disagreement(&reco_vote_thresholds);
           updated_state.gps_valid = gps_valid;
           updated_state.reco_valid = true;
           updated_state.rbf.reco = get_reco_rbf_values(&reco_states);
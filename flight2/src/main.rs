@@ -1,30 +1,47 @@
+mod abort_stage;
 mod common_so;
 mod device;
 mod file_logger;
 mod gps;
 mod imu_logger;
+mod persistence;
 mod sensors;
 mod sequence;
 mod servo;
 mod state;
+mod trigger;
+mod virtual_sensor;
 
 use crate::{
+  abort_stage::AbortConditionMonitor,
   common_so::{materialize_common_so, python_path_for},
   device::{AbortStages, Mappings, Devices},
   file_logger::{FileLogger, LoggerConfig},
+  persistence::FlightStateDir,
   sensors::spawn_imu_adc_worker,
   sequence::Sequences,
   servo::ServoError,
   state::Ingestible,
+  trigger::Triggers,
+  virtual_sensor::VirtualSensors,
 };
 use clap::{Parser, Subcommand};
 use common::{
-  comm::{bms, AbortStage, FlightControlMessage, Sequence},
+  comm::{
+    bms,
+    flight::SequenceDomainCommand,
+    AbortStage,
+    DeltaEncoding,
+    FlightControlMessage,
+    FlightControlRequest,
+    RecoVoteThresholds,
+    Sequence,
+    RADIO_PAYLOAD_MTU,
+  },
   sequence::{MMAP_PATH, SOCKET_PATH},
 };
 use mmap_sync::{locks::LockDisabled, synchronizer::Synchronizer};
 use std::{
-  collections::HashMap,
   env,
   ffi::OsStr,
   net::{SocketAddr, TcpStream, UdpSocket},
@@ -47,20 +64,6 @@ const FC_SOCKET_ADDRESS: (&str, u16) = ("0.0.0.0", 4573);
 const DEVICE_COMMAND_PORT: u16 = 8378;
 const SERVO_DATA_PORT: u16 = 7201;
 
-/// Total MTU enforced by the TEL radio link, counting the IP packet as a
-/// whole. Radio telemetry must fit within this bound because the TEL path does
-/// not fragment frames for us.
-const RADIO_MTU: usize = 255;
-
-/// IPv4 plus UDP header overhead for radio telemetry packets. This leaves the
-/// remainder of the TEL MTU available for the compressed `VehicleState`
-/// payload.
-const RADIO_IPV4_UDP_OVERHEAD: usize = 20 + 8;
-
-/// Maximum compressed radio telemetry payload that can be serialized into one
-/// TEL UDP datagram without exceeding the radio MTU.
-const RADIO_PAYLOAD_MTU: usize = RADIO_MTU - RADIO_IPV4_UDP_OVERHEAD;
-
 /// How quickly a sequence must read from the shared VehicleState before the
 /// data becomes corrupted.
 const MMAP_GRACE_PERIOD: Duration = Duration::from_millis(20);
@@ -121,6 +124,11 @@ struct Args {
   #[arg(long, global = true)]
   log_dir: Option<PathBuf>,
 
+  /// Directory for state persisted across reboots, such as the abort
+  /// sequence (default: $HOME/flight_state)
+  #[arg(long, global = true)]
+  state_dir: Option<PathBuf>,
+
   /// Buffer size in samples (default: 100)
   #[arg(long, default_value_t = 100, global = true)]
   log_buffer_size: usize,
@@ -136,6 +144,36 @@ struct Args {
   /// Disable GPS and RECO worker initialization entirely.
   #[arg(long, default_value_t = false, global = true)]
   disable_gps: bool,
+
+  /// Send radio telemetry as a keyframe every N frames with delta frames in
+  /// between (disabled by default)
+  #[arg(long, global = true)]
+  radio_keyframe_interval: Option<u32>,
+
+  /// How far a sensor must move from the last keyframe before delta frames
+  /// include it (default: 0.5)
+  #[arg(long, default_value_t = 0.5, global = true)]
+  radio_deadband: f64,
+
+  /// How far a RECO MCU's attitude may be from the vote across all three
+  /// before it's flagged as disagreeing, in degrees (default: 5)
+  #[arg(long, default_value_t = 5.0, global = true)]
+  reco_attitude_threshold: f32,
+
+  /// How far a RECO MCU's horizontal position may be from the vote before
+  /// it's flagged as disagreeing, in meters (default: 50)
+  #[arg(long, default_value_t = 50.0, global = true)]
+  reco_position_threshold: f32,
+
+  /// How far a RECO MCU's altitude may be from the vote before it's flagged
+  /// as disagreeing, in meters (default: 25)
+  #[arg(long, default_value_t = 25.0, global = true)]
+  reco_altitude_threshold: f32,
+
+  /// How far a RECO MCU's velocity may be from the vote before it's flagged
+  /// as disagreeing, in m/s (default: 5)
+  #[arg(long, default_value_t = 5.0, global = true)]
+  reco_velocity_threshold: f32,
 }
 
 fn main() -> ! {
@@ -216,11 +254,46 @@ fn main() -> ! {
   // TODO: HAVE THIS IN A STRUCT CALLED MAIN LOOP DATA
   let mut mappings: Mappings = Vec::new();
   let mut devices: Devices = Devices::new();
-  let mut sequences: Sequences = HashMap::new();
+  let mut sequences = Sequences::new();
+  let mut triggers = Triggers::default();
+  let mut virtual_sensors = VirtualSensors::default();
   let mut synchronizer: Synchronizer<WyHash, LockDisabled, 1024, 500_000> =
     Synchronizer::with_params(MMAP_PATH.as_ref());
   let mut abort_sequence: Option<Sequence> = None;
   let mut abort_stages: AbortStages = Vec::new();
+  let mut abort_condition = AbortConditionMonitor::default();
+
+  // reload what is needed to abort without servo, in case it never connects
+  // after a reboot.
+  let state_dir_path = args.state_dir.unwrap_or_else(|| {
+    env::var("HOME")
+      .map(PathBuf::from)
+      .unwrap_or_else(|_| PathBuf::from("."))
+      .join("flight_state")
+  });
+
+  let state_dir = match FlightStateDir::open(state_dir_path.clone()) {
+    Ok(state_dir) => {
+      println!("Persisting flight state to {state_dir_path:?}");
+      Some(state_dir)
+    }
+    Err(e) => {
+      eprintln!("Warning: Failed to open flight state directory {state_dir_path:?}: {e}. Continuing without persisted state.");
+      None
+    }
+  };
+
+  if let Some(ref state_dir) = state_dir {
+    abort_sequence = reload_state(
+      state_dir,
+      &mut mappings,
+      &mut virtual_sensors,
+      &mut devices,
+      &mut abort_stages,
+    );
+  }
+
+  let mut persisted_abort_stage = devices.get_state().abort_stage.name.clone();
 
   // Create channel for sending vehicle state to GPS worker for logging (bounded
   // for try_send)
@@ -230,6 +303,13 @@ fn main() -> ! {
   let file_logger_sender =
     file_logger.as_ref().map(|logger| logger.clone_sender());
 
+  let reco_vote_thresholds = RecoVoteThresholds {
+    attitude: args.reco_attitude_threshold.to_radians(),
+    position: args.reco_position_threshold,
+    altitude: args.reco_altitude_threshold,
+    velocity: args.reco_velocity_threshold,
+  };
+
   // Spawn GPS worker thread. If initialization fails, continue without GPS/RECO.
   let gps_handle = if args.disable_gps {
     println!("GPS/RECO worker disabled by command-line flag.");
@@ -241,6 +321,7 @@ fn main() -> ! {
       vehicle_state_receiver,
       file_logger_sender,
       args.print_gps,
+      reco_vote_thresholds,
     ) {
       Ok(handle) => {
         println!("GPS worker started successfully on I2C bus 1.");
@@ -317,32 +398,23 @@ fn main() -> ! {
   }
 
   let mut last_received_from_servo = Instant::now(); // last time that we had an established connection with servo
-  let (mut servo_stream, mut servo_address) = loop {
-    match servo::establish(
-      &SERVO_SOCKET_ADDRESSES,
-      None,
-      3,
-      Duration::from_secs(2),
-    ) {
-      Ok(s) => {
-        println!(
-          "Connected to servo successfully. Beginning control cycle...\n"
-        );
-        last_received_from_servo = Instant::now();
-        break s;
-      }
-      Err(e) => {
-        println!("Couldn't connect due to error: {e}\n");
-        thread::sleep(Duration::from_secs(2));
-      }
-    }
-  };
+
+  // the control loop starts without servo so that aborts, triggers, and the
+  // stored abort sequence work even if it never connects after a reboot.
+  let servo_connector = servo::connect_in_background(&SERVO_SOCKET_ADDRESSES);
+  let mut servo: Option<(TcpStream, SocketAddr)> = None;
+  let mut servo_outbox = servo::StatusOutbox::default();
 
   // TODO: put this information into a struct, maybe call it main_loop_info or
   // something?
   let mut last_sent_to_servo = Instant::now(); // for sending messages to servo
   let mut last_sent_radio_to_servo = Instant::now();
-  let mut radio_encoder = servo::RadioTelemetryEncoder::default();
+  let mut radio_encoder = servo::RadioTelemetryEncoder::new(
+    args.radio_keyframe_interval.map(|keyframe_interval| DeltaEncoding {
+      keyframe_interval,
+      deadband: args.radio_deadband,
+    }),
+  );
   let mut radio_buffer = [0u8; RADIO_PAYLOAD_MTU];
   let mut last_heartbeat_sent = Instant::now(); // for sending messages to boards
   let mut aborted = false;
@@ -362,8 +434,9 @@ fn main() -> ! {
     // Pull any new message from servo if we are still communicating with it.
     let servo_message = if devices.servo_communication_enabled() {
       get_servo_data(
-        &mut servo_stream,
-        &mut servo_address,
+        &mut servo,
+        &mut servo_outbox,
+        &servo_connector,
         &mut last_received_from_servo,
         &mut aborted,
         &mut devices,
@@ -374,8 +447,10 @@ fn main() -> ! {
 
     let servo_disconnect_abort_active = devices.monitor_servo_disconnects();
 
+    // servo can only be lost once it has connected.
     if !aborted
       && servo_disconnect_abort_active
+      && servo.is_some()
       && (Instant::now().duration_since(last_received_from_servo)
         > SERVO_TO_FC_TIME_TO_LIVE)
     {
@@ -386,76 +461,112 @@ fn main() -> ! {
       aborted = true;
       // On servo loss-of-communication while on the ground, we immediately
       // abort after SERVO_TO_FC_TIME_TO_LIVE seconds.
-      devices.send_sams_abort(
+      if let Err(e) = fire_abort(
         &socket,
         &mappings,
+        &mut devices,
         &mut abort_stages,
         &mut sequences,
-        true,
-      );
+        &abort_sequence,
+      ) {
+        eprintln!("{e}");
+      }
     }
 
     // decoding servo message, if it was received
-    if let Some(command) = servo_message {
+    if let Some(FlightControlRequest { id, message: command }) = servo_message {
       println!("Recieved a FlightControlMessage: {command:#?}");
 
-      match command {
-        FlightControlMessage::Abort => {
-          // check which type of abort should happen, abort stage or abort seq
-          if devices.get_state().abort_stage.name != "DEFAULT" {
-            devices.send_sams_abort(
-              &socket,
-              &mappings,
-              &mut abort_stages,
-              &mut sequences,
-              true,
-            ); // abort message means we use stage timers
-          } else {
-            abort(&mappings, &mut sequences, &abort_sequence);
+      let result = match command {
+        FlightControlMessage::Abort => fire_abort(
+          &socket,
+          &mappings,
+          &mut devices,
+          &mut abort_stages,
+          &mut sequences,
+          &abort_sequence,
+        ),
+        FlightControlMessage::AbortStageConfig(config) => {
+          let result = devices
+            .create_abort_stage(&mappings, &mut abort_stages, config.clone())
+            .map_err(|e| e.to_string());
+
+          if let (Ok(()), Some(state_dir)) = (&result, &state_dir) {
+            state_dir.save_abort_stage_config(&config);
           }
-        },
-        FlightControlMessage::AbortStageConfig(config) => devices.create_abort_stage(&mappings, &mut abort_stages, config),
-        FlightControlMessage::SetAbortStage(stage_name) => devices.handle_setting_abort_stage(&socket, stage_name, &mut abort_stages),
-        FlightControlMessage::BmsCommand(c) => devices.send_bms_command(&socket, c),
+
+          result
+        }
+        FlightControlMessage::SetAbortStage(stage_name) => devices
+          .handle_setting_abort_stage(&socket, stage_name, &mut abort_stages)
+          .map_err(|e| e.to_string()),
+        FlightControlMessage::BmsCommand(c) => {
+          devices.send_bms_command(&socket, c);
+          Ok(())
+        }
         FlightControlMessage::RecoCommand(reco_command) => {
           devices.handle_gui_reco_command(gps_handle.as_ref(), reco_command);
+          Ok(())
         }
-        FlightControlMessage::Trigger(_) => todo!(),
-        FlightControlMessage::Mappings(m) => {
-          mappings = m;
-          devices.sync_configured_valves(&mappings);
-
-          // send clear message to sams. this is needed as with new mappings we
-          // restart the abort stage sequence and are in the default
-          // stage again.
-          devices.send_sam_clear_abort_stage(&socket);
-
-          // restart the abort stage sequence
-          start_abort_stage_process(
-            &mut abort_stages,
-            &mappings,
-            &mut sequences,
-            &mut devices,
-          );
+        FlightControlMessage::Trigger(t) => {
+          triggers.register(t, devices.get_state_mut())
+        }
+        FlightControlMessage::DeleteTrigger(name) => {
+          triggers.delete(&name, devices.get_state_mut())
         }
+        FlightControlMessage::Mappings(m) => update_mappings(
+          &socket,
+          m,
+          &mut mappings,
+          &mut virtual_sensors,
+          &mut devices,
+          &mut abort_stages,
+          state_dir.as_ref(),
+        ),
         FlightControlMessage::Sequence(s) if s.name == "abort" => {
-          abort_sequence = Some(s)
+          if let Some(ref state_dir) = state_dir {
+            state_dir.save_abort_sequence(&s);
+          }
+
+          abort_sequence = Some(s);
+          Ok(())
         }
         FlightControlMessage::Sequence(ref s) => {
           sequence::execute(&mappings, s, &mut sequences)
         }
         FlightControlMessage::StopSequence(n) => {
-          if let Err(e) = sequence::kill(&mut sequences, &n) {
-            eprintln!("There was an issue in stopping sequence '{n}': {e}");
-          }
+          sequence::kill(&mut sequences, &n).map_err(|e| {
+            format!("There was an issue in stopping sequence '{n}': {e}")
+          })
+        }
+        FlightControlMessage::PauseSequence(n) => {
+          sequence::pause(&mut sequences, &n)
+        }
+        FlightControlMessage::ResumeSequence(n) => {
+          sequence::resume(&mut sequences, &n)
+        }
+        FlightControlMessage::StepSequence(n) => {
+          sequence::step(&mut sequences, &n)
         }
         FlightControlMessage::CameraEnable(should_enable) => {
-          devices.send_sams_toggle_camera(&socket, should_enable)
+          devices.send_sams_toggle_camera(&socket, should_enable);
+          Ok(())
         }
-        _ => eprintln!(
+        _ => Err(format!(
           "Received a FlightControlMessage that is not supported: {command:#?}"
-        ),
+        )),
       };
+
+      if let Err(ref e) = result {
+        eprintln!("{e}");
+      }
+
+      // let servo know how the request went, so that it can tell the operator.
+      if devices.servo_communication_enabled() && servo.is_some() {
+        if let Err(e) = servo_outbox.reply(id, result) {
+          eprintln!("Issue in replying to servo: {e}");
+        }
+      }
     }
 
     // updates records
@@ -469,7 +580,7 @@ fn main() -> ! {
           devices.update_gps(gps);
         }
         // Update all three RECO MCU states
-        devices.update_reco(gps_reco_sample.reco);
+        devices.update_reco(gps_reco_sample.reco, &reco_vote_thresholds);
       }
     }
 
@@ -508,11 +619,14 @@ fn main() -> ! {
 
     let now = Instant::now();
     let servo_comm_enabled = devices.servo_communication_enabled();
+    let servo_address = servo.as_ref().map(|(_, address)| *address);
     let send_umbilical = servo_comm_enabled
+      && servo_address.is_some()
       && now.duration_since(last_sent_to_servo) > FC_TO_SERVO_RATE;
-    let send_radio = now.duration_since(last_sent_radio_to_servo) > FC_TO_SERVO_RADIO_RATE;
+    let send_radio = servo_address.is_some()
+      && now.duration_since(last_sent_radio_to_servo) > FC_TO_SERVO_RADIO_RATE;
 
-    if send_umbilical {
+    if let (true, Some(servo_address)) = (send_umbilical, servo_address) {
       // send servo the current umbilical telemetry (file logging removed - now
       // done in GPS worker)
       if let Err(e) =
@@ -524,7 +638,7 @@ fn main() -> ! {
       last_sent_to_servo = now;
     }
 
-    if send_radio {
+    if let (true, Some(servo_address)) = (send_radio, servo_address) {
       if let Err(e) = servo::push_radio(
         &radio_socket,
         servo_address,
@@ -552,6 +666,33 @@ fn main() -> ! {
     // process telemetry from boards
     devices.update_state(telemetry, &mappings, &socket);
 
+    // readings from boards that have gone quiet are no longer trustworthy
+    devices.mark_stale_readings();
+
+    // compute virtual sensors from everything ingested so far this loop
+    virtual_sensors.evaluate(devices.get_state_mut());
+
+    // resend safety-relevant commands that boards haven't acknowledged
+    devices.retransmit_sam_commands(&socket);
+
+    abort_on_valve_faults(
+      &socket,
+      &mappings,
+      &mut devices,
+      &mut abort_stages,
+      &mut sequences,
+      &abort_sequence,
+    );
+
+    abort_on_critical_redlines(
+      &socket,
+      &mappings,
+      &mut devices,
+      &mut abort_stages,
+      &mut sequences,
+      &abort_sequence,
+    );
+
     update_goldfish_system_safe_timer(
       &mut devices,
       &socket,
@@ -560,6 +701,22 @@ fn main() -> ! {
       &mut seen_valid_umbilical_voltage,
     );
 
+    // abort if the current stage's abort condition is met, using the stage
+    // timers just as an abort from a sequence would.
+    if abort_condition.check(devices.get_state()) {
+      println!(
+        "Abort condition of stage '{}' was met. Sending abort messages to boards.",
+        devices.get_state().abort_stage.name
+      );
+      devices.send_sams_abort(
+        &socket,
+        &mappings,
+        &mut abort_stages,
+        &mut sequences,
+        true,
+      );
+    }
+
     // updates all running sequences with the newest received data
     if let Err(e) =
       state::sync_sequences(&mut synchronizer, devices.get_state())
@@ -600,11 +757,14 @@ fn main() -> ! {
     }
 
     // TODO: this is not really optimal, figure out a better way to do this
-    for device in devices.iter() {
-      //println!("{}", device.get_num_heartbeats());
-      if device.get_num_heartbeats() == 20 {
-        devices.send_sams_abort_stage(&socket, &Some(device.get_board_id()));
-      }
+    let boards_due_abort_stage: Vec<String> = devices
+      .iter()
+      .filter(|device| device.get_num_heartbeats() == 20)
+      .map(|device| device.get_board_id().clone())
+      .collect();
+
+    for board_id in &boards_due_abort_stage {
+      devices.send_sams_abort_stage(&socket, &Some(board_id));
     }
 
     for device in devices.iter_mut() {
@@ -614,32 +774,70 @@ fn main() -> ! {
     }
 
     // sequences and triggers
-    let sam_commands = sequence::pull_commands(&command_socket);
-    let should_abort = devices.send_sam_commands(
-      &socket,
-      &mappings,
-      sam_commands,
-      &mut abort_stages,
-      &mut sequences,
-      gps_handle.as_ref(),
-    );
+    let mut should_abort = false;
+    for request in sequence::pull_commands(&command_socket) {
+      should_abort |= matches!(request.command, SequenceDomainCommand::Abort);
+      let (id, reply_to) = (request.id, request.reply_to.clone());
+
+      let result = devices.handle_sequence_command(
+        &socket,
+        &mappings,
+        request,
+        &mut abort_stages,
+        &mut sequences,
+        gps_handle.as_ref(),
+      );
+
+      sequence::respond(&command_socket, id, &reply_to, result);
+    }
 
     if should_abort {
-      // check which type of abort should happen, abort stage or abort seq
-      if devices.get_state().abort_stage.name != "DEFAULT" {
-        devices.send_sams_abort(
-          &socket,
-          &mappings,
-          &mut abort_stages,
-          &mut sequences,
-          true,
-        ); // not servo LOC, abort with stage timers
-      } else {
-        abort(&mappings, &mut sequences, &abort_sequence);
+      if let Err(e) = fire_abort(
+        &socket,
+        &mappings,
+        &mut devices,
+        &mut abort_stages,
+        &mut sequences,
+        &abort_sequence,
+      ) {
+        eprintln!("{e}");
       }
     }
 
     // triggers
+    triggers.evaluate(&mappings, devices.get_state_mut(), &mut sequences);
+
+    // forward sequence output and lifecycle changes to servo
+    let sequence_messages =
+      sequence::poll(&mut sequences, devices.get_state_mut());
+
+    if devices.servo_communication_enabled() && servo.is_some() {
+      for message in sequence_messages {
+        if let Err(e) = servo_outbox.push(&message) {
+          eprintln!("Issue in sending servo a sequence update: {e}");
+        }
+      }
+    }
+
+    // write whatever servo will take right now; the rest waits for the next
+    // iteration rather than holding up the control loop.
+    if let (true, Some((servo_stream, _))) =
+      (devices.servo_communication_enabled(), &mut servo)
+    {
+      if let Err(e) = servo_outbox.flush(servo_stream) {
+        eprintln!("Issue in sending servo status messages: {e}");
+      }
+    }
+
+    // persist the abort stage whenever it changes so a reboot doesn't silently
+    // fall back to DEFAULT.
+    if devices.get_state().abort_stage.name != persisted_abort_stage {
+      persisted_abort_stage = devices.get_state().abort_stage.name.clone();
+
+      if let Some(ref state_dir) = state_dir {
+        state_dir.save_active_abort_stage(&persisted_abort_stage);
+      }
+    }
 
     // Optional performance diagnostics for the main loop.
     if fc_perf_debug {
@@ -654,34 +852,109 @@ fn main() -> ! {
   }
 }
 
-fn abort(
+/// Aborts through the current abort stage, using its stage timers, or runs the
+/// abort sequence if no stage other than the default has been set.
+fn fire_abort(
+  socket: &UdpSocket,
+  mappings: &Mappings,
+  devices: &mut Devices,
+  abort_stages: &mut AbortStages,
+  sequences: &mut Sequences,
+  abort_sequence: &Option<Sequence>,
+) -> Result<(), String> {
+  if devices.get_state().abort_stage.name != "DEFAULT" {
+    devices.send_sams_abort(socket, mappings, abort_stages, sequences, true);
+    Ok(())
+  } else {
+    abort(mappings, sequences, abort_sequence)
+  }
+}
+
+/// Aborts if a valve which should abort on fault has just faulted, just as an
+/// abort from a sequence would. In the default stage this runs the abort
+/// sequence, as the SAMs have no safe states to go to.
+fn abort_on_valve_faults(
+  socket: &UdpSocket,
+  mappings: &Mappings,
+  devices: &mut Devices,
+  abort_stages: &mut AbortStages,
+  sequences: &mut Sequences,
+  abort_sequence: &Option<Sequence>,
+) {
+  let faulted_valves = devices.update_valve_mismatches(mappings);
+  if faulted_valves.is_empty() {
+    return;
+  }
+
+  println!("Valves {} faulted. Aborting.", faulted_valves.join(", "));
+
+  if let Err(e) = fire_abort(
+    socket,
+    mappings,
+    devices,
+    abort_stages,
+    sequences,
+    abort_sequence,
+  ) {
+    eprintln!("{e}");
+  }
+}
+
+/// Likewise aborts if a sensor which should abort on a critical redline has
+/// stayed beyond it for long enough.
+fn abort_on_critical_redlines(
+  socket: &UdpSocket,
   mappings: &Mappings,
+  devices: &mut Devices,
+  abort_stages: &mut AbortStages,
   sequences: &mut Sequences,
   abort_sequence: &Option<Sequence>,
 ) {
+  let redlined_sensors = devices.update_critical_redlines(mappings);
+  if redlined_sensors.is_empty() {
+    return;
+  }
+
+  println!(
+    "Sensors {} exceeded a critical redline. Aborting.",
+    redlined_sensors.join(", ")
+  );
+
+  if let Err(e) = fire_abort(
+    socket,
+    mappings,
+    devices,
+    abort_stages,
+    sequences,
+    abort_sequence,
+  ) {
+    eprintln!("{e}");
+  }
+}
+
+fn abort(
+  mappings: &Mappings,
+  sequences: &mut Sequences,
+  abort_sequence: &Option<Sequence>,
+) -> Result<(), String> {
   if let Some(ref sequence) = abort_sequence {
-    for (name, sequence) in &mut *sequences {
-      if name != "AbortStage" {
-        if let Err(e) = sequence.kill() {
-          println!("Couldn't kill a sequence in preperation for abort, continuing normally: {e}");
-        }
-      }
-    }
+    sequence::kill_all(sequences);
 
-    sequence::execute(&mappings, sequence, sequences);
+    sequence::execute(mappings, sequence, sequences)
   } else {
-    println!("Received an abort command, but no abort sequence has been set. Continuing normally...");
+    Err("Received an abort command, but no abort sequence has been set. Continuing normally...".to_string())
   }
 }
 
-/// Pulls data from Servo, if available.
+/// Pulls data from Servo, if available, first taking the connection made by
+/// `connector` if there isn't one yet.
 /// # Error Handling
 ///
 /// ## FC-Servo Connection Dropped
 /// If the connection between the FC and Servo was severed, the connection
 /// will tried to be re-established. If a new connection is successfully
-/// established, servo_stream and servo_address will be set to mirror the
-/// change. Otherwise, a notification will be printed to the terminal and None
+/// established, `servo` will be set to mirror the change and any status message
+/// left half-written in `outbox` is discarded. Otherwise, a notification will be printed to the terminal and None
 /// will be returned.
 ///
 /// ## Servo Message Deserialization Fails
@@ -691,18 +964,29 @@ fn abort(
 /// ## Transport Layer failed
 /// If reading from servo_stream is not possible, None will be returned.
 fn get_servo_data(
-  servo_stream: &mut TcpStream,
-  servo_address: &mut SocketAddr,
+  servo: &mut Option<(TcpStream, SocketAddr)>,
+  outbox: &mut servo::StatusOutbox,
+  connector: &mpsc::Receiver<(TcpStream, SocketAddr)>,
   last_received_from_servo: &mut Instant,
   aborted: &mut bool,
   devices: &mut Devices,
-) -> Option<FlightControlMessage> {
+) -> Option<FlightControlRequest> {
   // If we've been instructed to permanently stop communicating with servo after
   // a disconnect, short-circuit immediately.
   if !devices.servo_communication_enabled() {
     return None;
   }
 
+  let Some((servo_stream, servo_address)) = servo else {
+    if let Ok(connection) = connector.try_recv() {
+      println!("Connected to servo successfully.\n");
+      *servo = Some(connection);
+      *last_received_from_servo = Instant::now();
+    }
+
+    return None;
+  };
+
   let monitor_servo_disconnects = devices.monitor_servo_disconnects();
 
   match servo::pull(servo_stream) {
@@ -726,6 +1010,7 @@ fn get_servo_data(
             ) {
               Ok(s) => {
                 (*servo_stream, *servo_address) = s;
+                outbox.reconnected();
                 *last_received_from_servo = Instant::now();
                 *aborted = false;
                 eprintln!("Connection successfully re-established.");
@@ -822,51 +1107,105 @@ fn update_goldfish_system_safe_timer(
   }
 }
 
-fn start_abort_stage_process(
-  abort_stages: &mut AbortStages,
-  mappings: &Mappings,
-  sequences: &mut Sequences,
+/// Reloads the mappings, abort stages and active abort stage persisted to
+/// `state_dir`, returning the persisted abort sequence.
+fn reload_state(
+  state_dir: &FlightStateDir,
+  mappings: &mut Mappings,
+  virtual_sensors: &mut VirtualSensors,
   devices: &mut Devices,
-) {
-  // if any abort stage sequences exist, kill them
-  for (name, sequence) in &mut *sequences {
-    if name == "AbortStage" {
-      if let Err(e) = sequence.kill() {
-        println!("Couldn't kill AbortStage sequence in preperation for starting new AbortStage sequence: {e}");
-        return;
-      }
+  abort_stages: &mut AbortStages,
+) -> Option<Sequence> {
+  if let Some(persisted) = state_dir.load_mappings() {
+    *mappings = persisted;
+    devices.sync_configured_valves(mappings);
+    *virtual_sensors = VirtualSensors::new(mappings).unwrap_or_else(|e| {
+      eprintln!("{e}");
+      VirtualSensors::default()
+    });
+  }
+
+  reset_abort_stage(abort_stages, devices);
+  for config in state_dir.load_abort_stage_configs() {
+    if let Err(e) = devices.create_abort_stage(mappings, abort_stages, config) {
+      eprintln!("{e}");
     }
   }
-  sequences.remove_entry("AbortStage");
-
-  let abort_stage_body = r#"
-import time
-while True:
-    try:
-        if curr_abort_stage() != "FLIGHT" and aborted_in_this_stage() == False and eval(curr_abort_condition()) == True:
-            #print("ABORTING")
-            abort()
-    except Exception as e:
-        print("ERROR:", e)
-    wait_for(10*ms)
-"#;
-
-  // create abort stage and store in abort_stages
-  let default_stage = AbortStage {
-    name: "DEFAULT".to_string(),
-    abort_condition: "False".to_string(), // never abort in this situation?
-    aborted: false,
-    valve_safe_states: HashMap::new(),
-  };
+
+  // boards are sent the stage's safe states once they connect, so only the
+  // vehicle state needs to be put back here.
+  if let Some(stage_name) = state_dir.load_active_abort_stage() {
+    match abort_stages.iter().find(|stage| stage.name == stage_name) {
+      Some(stage) => devices.set_abort_stage(stage),
+      None => eprintln!(
+        "Persisted abort stage '{stage_name}' no longer exists. Staying in DEFAULT."
+      ),
+    }
+  }
+
+  let abort_sequence = state_dir.load_abort_sequence();
+  println!(
+    "Reloaded {} mappings, {} abort stages, and {} abort sequence. In abort stage '{}'.",
+    mappings.len(),
+    abort_stages.len() - 1,
+    if abort_sequence.is_some() { "an" } else { "no" },
+    devices.get_state().abort_stage.name,
+  );
+
+  abort_sequence
+}
+
+/// Replaces the mappings with those sent by Servo. Mappings with a virtual
+/// sensor that can't be evaluated are rejected outright, keeping the previous
+/// mappings in place.
+///
+/// Servo sends its mappings every time it connects, so mappings identical to
+/// the current ones, such as those reloaded after a reboot, change nothing.
+/// Only new mappings return the vehicle to the default abort stage.
+fn update_mappings(
+  socket: &UdpSocket,
+  new_mappings: Mappings,
+  mappings: &mut Mappings,
+  virtual_sensors: &mut VirtualSensors,
+  devices: &mut Devices,
+  abort_stages: &mut AbortStages,
+  state_dir: Option<&FlightStateDir>,
+) -> Result<(), String> {
+  if new_mappings == *mappings {
+    println!(
+      "Received the current mappings again. Staying in abort stage '{}'.",
+      devices.get_state().abort_stage.name
+    );
+    return Ok(());
+  }
+
+  let sensors = VirtualSensors::new(&new_mappings)
+    .map_err(|e| format!("Rejected new mappings. {e}"))?;
+
+  *mappings = new_mappings;
+  *virtual_sensors = sensors;
+  devices.sync_configured_valves(mappings);
+
+  if let Some(state_dir) = state_dir {
+    state_dir.save_mappings(mappings);
+  }
+
+  // send clear message to sams. this is needed as with new mappings we are in
+  // the default stage again.
+  devices.send_sam_clear_abort_stage(socket);
+  reset_abort_stage(abort_stages, devices);
+  Ok(())
+}
+
+/// Returns the flight computer to the default abort stage, in which the vehicle
+/// never aborts on its own.
+fn reset_abort_stage(abort_stages: &mut AbortStages, devices: &mut Devices) {
+  let default_stage = AbortStage::default();
+
+  abort_stages.retain(|stage| stage.name != default_stage.name);
   abort_stages.push(default_stage.clone());
 
   devices.set_abort_stage(&default_stage);
-
-  let abort_stage_seq = Sequence {
-    name: "AbortStage".to_string(),
-    script: abort_stage_body.to_string(),
-  };
-  sequence::execute(mappings, &abort_stage_seq, sequences);
 }
 
 /// Checks if python3 and the passed python modules exist.
@@ -917,3 +1256,236 @@ fn check_python_dependencies(
     Err(missing_imports)
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use common::comm::{
+    AbortStageConfig,
+    AlarmBound,
+    AlarmSeverity,
+    CompositeValveState,
+    FlightStatusMessage,
+    NodeMapping,
+    RedlineAlarm,
+    SensorType,
+    SequenceStatus,
+    ValveSafeState,
+    ValveState,
+    VehicleState,
+  };
+  use std::collections::HashMap;
+
+  fn socket() -> UdpSocket {
+    UdpSocket::bind("127.0.0.1:0").unwrap()
+  }
+
+  /// A sequence that does nothing for long enough to outlive the test.
+  fn idle(name: &str) -> Sequence {
+    Sequence {
+      name: name.to_string(),
+      script: "import time; time.sleep(30)".to_string(),
+      arguments: HashMap::new(),
+    }
+  }
+
+  /// Polls `sequences` until the sequence named `name` has finished, returning
+  /// how it did.
+  fn finished(
+    sequences: &mut Sequences,
+    state: &mut VehicleState,
+    name: &str,
+  ) -> SequenceStatus {
+    let deadline = Instant::now() + Duration::from_secs(5);
+
+    while Instant::now() < deadline {
+      for message in sequence::poll(sequences, state) {
+        if let FlightStatusMessage::SequenceFinished(run) = message {
+          if run.name == name {
+            return run.status;
+          }
+        }
+      }
+
+      thread::sleep(Duration::from_millis(10));
+    }
+
+    panic!("the '{name}' sequence never finished");
+  }
+
+  #[test]
+  fn valve_faults_in_the_default_stage_run_the_abort_sequence() {
+    materialize_common_so().unwrap();
+
+    let mappings = vec![NodeMapping {
+      text_id: "BBV".to_string(),
+      board_id: "sam-01".to_string(),
+      sensor_type: SensorType::Valve,
+      fault_timeout: Some(0.0),
+      abort_on_fault: Some(true),
+      ..Default::default()
+    }];
+
+    let mut devices = Devices::new();
+    let mut abort_stages = AbortStages::new();
+    reset_abort_stage(&mut abort_stages, &mut devices);
+
+    let mut sequences = Sequences::new();
+    let mut state = VehicleState::new();
+    sequence::execute(&mappings, &idle("fill"), &mut sequences).unwrap();
+
+    // BBV was commanded open but stays closed
+    devices.get_state_mut().valve_states.insert(
+      "BBV".to_string(),
+      CompositeValveState {
+        commanded: ValveState::Open,
+        actual: ValveState::Closed,
+      },
+    );
+
+    abort_on_valve_faults(
+      &socket(),
+      &mappings,
+      &mut devices,
+      &mut abort_stages,
+      &mut sequences,
+      &Some(idle("abort")),
+    );
+
+    assert!(matches!(
+      finished(&mut sequences, &mut state, "fill"),
+      SequenceStatus::Killed { .. }
+    ));
+    assert_eq!(state.sequences["abort"].status, SequenceStatus::Running);
+    assert_eq!(devices.get_state().abort_stage.name, "DEFAULT");
+
+    sequence::kill_all(&mut sequences);
+  }
+
+  #[test]
+  fn critical_redlines_in_the_default_stage_run_the_abort_sequence() {
+    materialize_common_so().unwrap();
+
+    let mappings = vec![NodeMapping {
+      text_id: "FUEL_PT".to_string(),
+      board_id: "sam-01".to_string(),
+      critical_high: Some(750.0),
+      abort_on_critical: Some(true),
+      critical_persistence: Some(0.0),
+      ..Default::default()
+    }];
+
+    let mut devices = Devices::new();
+    let mut abort_stages = AbortStages::new();
+    reset_abort_stage(&mut abort_stages, &mut devices);
+
+    let mut sequences = Sequences::new();
+    let mut state = VehicleState::new();
+    sequence::execute(&mappings, &idle("fill"), &mut sequences).unwrap();
+
+    devices.get_state_mut().redline_alarms.insert(
+      "FUEL_PT".to_string(),
+      RedlineAlarm {
+        severity: AlarmSeverity::Critical,
+        bound: AlarmBound::High,
+        limit: 750.0,
+        value: 800.0,
+        first_exceeded: file_logger::current_timestamp(),
+      },
+    );
+
+    abort_on_critical_redlines(
+      &socket(),
+      &mappings,
+      &mut devices,
+      &mut abort_stages,
+      &mut sequences,
+      &Some(idle("abort")),
+    );
+
+    assert!(matches!(
+      finished(&mut sequences, &mut state, "fill"),
+      SequenceStatus::Killed { .. }
+    ));
+    assert_eq!(state.sequences["abort"].status, SequenceStatus::Running);
+
+    sequence::kill_all(&mut sequences);
+  }
+
+  #[test]
+  fn identical_mappings_keep_the_reloaded_abort_stage() {
+    let root = tempfile::tempdir().unwrap();
+    let state_dir = FlightStateDir::open(root.path().join("state")).unwrap();
+
+    let persisted = vec![NodeMapping {
+      text_id: "BBV".to_string(),
+      board_id: "sam-01".to_string(),
+      sensor_type: SensorType::Valve,
+      ..Default::default()
+    }];
+
+    state_dir.save_mappings(&persisted);
+    state_dir.save_abort_stage_config(&AbortStageConfig {
+      stage_name: "FIRE".to_string(),
+      abort_condition: "False".to_string(),
+      valve_safe_states: HashMap::from([(
+        "BBV".to_string(),
+        ValveSafeState {
+          desired_state: ValveState::Closed,
+          safing_timer: 250,
+        },
+      )]),
+    });
+    state_dir.save_active_abort_stage("FIRE");
+
+    // the flight computer reboots
+    let mut mappings = Vec::new();
+    let mut virtual_sensors = VirtualSensors::default();
+    let mut devices = Devices::new();
+    let mut abort_stages = AbortStages::new();
+
+    reload_state(
+      &state_dir,
+      &mut mappings,
+      &mut virtual_sensors,
+      &mut devices,
+      &mut abort_stages,
+    );
+    assert_eq!(devices.get_state().abort_stage.name, "FIRE");
+
+    // then servo reconnects and sends the same mappings
+    update_mappings(
+      &socket(),
+      persisted.clone(),
+      &mut mappings,
+      &mut virtual_sensors,
+      &mut devices,
+      &mut abort_stages,
+      Some(&state_dir),
+    )
+    .unwrap();
+
+    let stage = &devices.get_state().abort_stage;
+    assert_eq!(stage.name, "FIRE");
+    assert!(stage.valve_safe_states.contains_key("sam-01"));
+
+    // new mappings still return to the default stage
+    let mut changed = persisted.clone();
+    changed[0].channel = 2;
+
+    update_mappings(
+      &socket(),
+      changed.clone(),
+      &mut mappings,
+      &mut virtual_sensors,
+      &mut devices,
+      &mut abort_stages,
+      Some(&state_dir),
+    )
+    .unwrap();
+
+    assert_eq!(devices.get_state().abort_stage.name, "DEFAULT");
+    assert_eq!(mappings, changed);
+    assert_eq!(state_dir.load_mappings(), Some(changed));
+  }
+}
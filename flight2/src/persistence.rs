@@ -0,0 +1,291 @@
+use crate::device::Mappings;
+use common::comm::{AbortStageConfig, Sequence};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{
+  collections::HashMap,
+  fs::{self, File},
+  io::{self, Write},
+  path::PathBuf,
+};
+
+const MAPPINGS_FILE: &str = "mappings.postcard";
+const ABORT_SEQUENCE_FILE: &str = "abort_sequence.postcard";
+const ABORT_STAGES_FILE: &str = "abort_stages.postcard";
+const ACTIVE_ABORT_STAGE_FILE: &str = "active_abort_stage.postcard";
+
+/// The layout of an abort sequence persisted before sequences took arguments.
+#[derive(Deserialize)]
+struct LegacySequence {
+  name: String,
+  script: String,
+}
+
+/// A directory holding everything the flight computer needs to abort safely
+/// after a power cycle without waiting on Servo: the mappings, the abort
+/// sequence, the abort stage configs it was last sent, and the abort stage it
+/// was last in.
+///
+/// Every file is replaced atomically, so a power loss mid-write leaves the
+/// previous version intact rather than a truncated one.
+pub(crate) struct FlightStateDir {
+  path: PathBuf,
+}
+
+impl FlightStateDir {
+  /// Opens the state directory at `path`, creating it if needed.
+  pub(crate) fn open(path: PathBuf) -> io::Result<Self> {
+    fs::create_dir_all(&path)?;
+    Ok(FlightStateDir { path })
+  }
+
+  pub(crate) fn load_mappings(&self) -> Option<Mappings> {
+    self.load(MAPPINGS_FILE)
+  }
+
+  pub(crate) fn save_mappings(&self, mappings: &Mappings) {
+    self.save(MAPPINGS_FILE, mappings);
+  }
+
+  pub(crate) fn load_abort_sequence(&self) -> Option<Sequence> {
+    let bytes = self.read(ABORT_SEQUENCE_FILE)?;
+
+    if let Ok(sequence) = postcard::from_bytes(&bytes) {
+      return Some(sequence);
+    }
+
+    match postcard::from_bytes::<LegacySequence>(&bytes) {
+      Ok(legacy) => Some(Sequence {
+        name: legacy.name,
+        script: legacy.script,
+        arguments: HashMap::new(),
+      }),
+      Err(e) => {
+        eprintln!(
+          "Couldn't deserialize persisted state from {:?}: {e}",
+          self.path.join(ABORT_SEQUENCE_FILE)
+        );
+        None
+      }
+    }
+  }
+
+  pub(crate) fn save_abort_sequence(&self, sequence: &Sequence) {
+    self.save(ABORT_SEQUENCE_FILE, sequence);
+  }
+
+  pub(crate) fn load_abort_stage_configs(&self) -> Vec<AbortStageConfig> {
+    self.load(ABORT_STAGES_FILE).unwrap_or_default()
+  }
+
+  /// Stores an abort stage config, replacing any stored config of the same
+  /// stage name.
+  pub(crate) fn save_abort_stage_config(&self, config: &AbortStageConfig) {
+    let mut configs = self.load_abort_stage_configs();
+    configs.retain(|existing| existing.stage_name != config.stage_name);
+    configs.push(config.clone());
+
+    self.save(ABORT_STAGES_FILE, &configs);
+  }
+
+  /// The name of the abort stage the flight computer was last in.
+  pub(crate) fn load_active_abort_stage(&self) -> Option<String> {
+    self.load(ACTIVE_ABORT_STAGE_FILE)
+  }
+
+  pub(crate) fn save_active_abort_stage(&self, stage_name: &str) {
+    self.save(ACTIVE_ABORT_STAGE_FILE, stage_name);
+  }
+
+  /// Reads a file, returning `None` if it doesn't exist or can't be read.
+  fn read(&self, file_name: &str) -> Option<Vec<u8>> {
+    let path = self.path.join(file_name);
+
+    match fs::read(&path) {
+      Ok(bytes) => Some(bytes),
+      Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+      Err(e) => {
+        eprintln!("Couldn't read persisted state from {path:?}: {e}");
+        None
+      }
+    }
+  }
+
+  /// Reads and deserializes a file, returning `None` if it doesn't exist or
+  /// can't be read.
+  fn load<T: DeserializeOwned>(&self, file_name: &str) -> Option<T> {
+    let path = self.path.join(file_name);
+    let bytes = self.read(file_name)?;
+
+    match postcard::from_bytes(&bytes) {
+      Ok(value) => Some(value),
+      Err(e) => {
+        eprintln!("Couldn't deserialize persisted state from {path:?}: {e}");
+        None
+      }
+    }
+  }
+
+  /// Serializes and writes a file by writing a temporary file next to it and
+  /// renaming it into place once it has been flushed to disk.
+  fn save<T: Serialize + ?Sized>(&self, file_name: &str, value: &T) {
+    let path = self.path.join(file_name);
+    let temporary_path = self.path.join(format!("{file_name}.tmp"));
+
+    let result = postcard::to_allocvec(value)
+      .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+      .and_then(|bytes| {
+        let mut file = File::create(&temporary_path)?;
+        file.write_all(&bytes)?;
+        file.sync_all()?;
+
+        fs::rename(&temporary_path, &path)?;
+
+        // sync the directory so that the rename itself survives power loss.
+        File::open(&self.path)?.sync_all()
+      });
+
+    if let Err(e) = result {
+      eprintln!("Couldn't persist state to {path:?}: {e}");
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use common::comm::{NodeMapping, ValveSafeState, ValveState};
+
+  fn state_dir() -> (tempfile::TempDir, FlightStateDir) {
+    let root = tempfile::tempdir().unwrap();
+    let dir = FlightStateDir::open(root.path().join("state")).unwrap();
+    (root, dir)
+  }
+
+  fn pt_mapping(text_id: &str) -> NodeMapping {
+    NodeMapping {
+      text_id: text_id.to_string(),
+      board_id: "sam-01".to_string(),
+      channel: 1,
+      max: Some(1000.0),
+      min: Some(0.0),
+      calibrated_offset: 2.5,
+      warning_high: Some(800.0),
+      critical_high: Some(900.0),
+      abort_on_critical: Some(true),
+      radio: true,
+      radio_priority: 3,
+      ..Default::default()
+    }
+  }
+
+  fn stage(stage_name: &str, abort_condition: &str) -> AbortStageConfig {
+    AbortStageConfig {
+      stage_name: stage_name.to_string(),
+      abort_condition: abort_condition.to_string(),
+      valve_safe_states: HashMap::from([(
+        "BBV".to_string(),
+        ValveSafeState {
+          desired_state: ValveState::Closed,
+          safing_timer: 250,
+        },
+      )]),
+    }
+  }
+
+  #[test]
+  fn missing_files_load_as_nothing() {
+    let (_root, dir) = state_dir();
+
+    assert_eq!(dir.load_mappings(), None);
+    assert_eq!(dir.load_abort_sequence().map(|s| s.name), None);
+    assert!(dir.load_abort_stage_configs().is_empty());
+    assert_eq!(dir.load_active_abort_stage(), None);
+  }
+
+  #[test]
+  fn saved_state_round_trips() {
+    let (_root, dir) = state_dir();
+
+    let mappings = vec![pt_mapping("PT_FUEL"), pt_mapping("PT_OX")];
+    dir.save_mappings(&mappings);
+    assert_eq!(dir.load_mappings(), Some(mappings));
+
+    let sequence = Sequence {
+      name: "abort".to_string(),
+      script: "BBV.close()".to_string(),
+      arguments: HashMap::new(),
+    };
+    dir.save_abort_sequence(&sequence);
+    let loaded = dir.load_abort_sequence().unwrap();
+    assert_eq!(loaded.name, sequence.name);
+    assert_eq!(loaded.script, sequence.script);
+
+    dir.save_active_abort_stage("FIRE");
+    assert_eq!(dir.load_active_abort_stage().as_deref(), Some("FIRE"));
+
+    // nothing temporary is left behind once a save completes.
+    let leftovers = fs::read_dir(&dir.path)
+      .unwrap()
+      .filter(|entry| {
+        entry.as_ref().unwrap().path().extension() == Some("tmp".as_ref())
+      })
+      .count();
+    assert_eq!(leftovers, 0);
+  }
+
+  #[test]
+  fn saving_an_abort_stage_replaces_one_of_the_same_name() {
+    let (_root, dir) = state_dir();
+
+    dir.save_abort_stage_config(&stage("FILL", "PT_OX > 700"));
+    dir.save_abort_stage_config(&stage("FIRE", "PT_FUEL > 900"));
+    dir.save_abort_stage_config(&stage("FILL", "PT_OX > 750"));
+
+    let configs = dir.load_abort_stage_configs();
+    assert_eq!(
+      configs,
+      vec![stage("FIRE", "PT_FUEL > 900"), stage("FILL", "PT_OX > 750")]
+    );
+  }
+
+  #[test]
+  fn corrupt_files_load_as_nothing() {
+    let (_root, dir) = state_dir();
+
+    for file_name in [
+      MAPPINGS_FILE,
+      ABORT_SEQUENCE_FILE,
+      ABORT_STAGES_FILE,
+      ACTIVE_ABORT_STAGE_FILE,
+    ] {
+      fs::write(dir.path.join(file_name), [0xff; 3]).unwrap();
+    }
+
+    assert_eq!(dir.load_mappings(), None);
+    assert_eq!(dir.load_abort_sequence().map(|s| s.name), None);
+    assert!(dir.load_abort_stage_configs().is_empty());
+    assert_eq!(dir.load_active_abort_stage(), None);
+  }
+
+  #[test]
+  fn abort_sequence_saved_before_arguments_still_loads() {
+    #[derive(Serialize)]
+    struct OldSequence<'a> {
+      name: &'a str,
+      script: &'a str,
+    }
+
+    let (_root, dir) = state_dir();
+    let bytes = postcard::to_allocvec(&OldSequence {
+      name: "abort",
+      script: "BBV.close()",
+    })
+    .unwrap();
+    fs::write(dir.path.join(ABORT_SEQUENCE_FILE), bytes).unwrap();
+
+    let sequence = dir.load_abort_sequence().unwrap();
+    assert_eq!(sequence.name, "abort");
+    assert_eq!(sequence.script, "BBV.close()");
+    assert!(sequence.arguments.is_empty());
+  }
+}
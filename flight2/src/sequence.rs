@@ -2,21 +2,162 @@ use crate::{
   common_so::{common_so_dir, python_path_for},
   Mappings,
 };
-use common::comm::{flight::SequenceDomainCommand, SensorType, Sequence};
+use common::comm::{
+  flight::{
+    SequenceCommandError,
+    SequenceDomainCommand,
+    SequenceDomainRequest,
+    SequenceDomainResponse,
+  },
+  FlightStatusMessage,
+  SensorType,
+  Sequence,
+  SequenceOutputStream,
+  SequenceRun,
+  SequenceStatus,
+  VehicleState,
+};
+use common::sequence::{
+  ARGUMENTS_ATTRIBUTE,
+  ARGUMENTS_VAR,
+  COMMAND_ADDRESS_PREFIX,
+};
 use std::{
   collections::HashMap,
-  io,
-  os::unix::net::UnixDatagram,
-  process::{Child, Command},
+  io::{self, BufRead, BufReader, Read},
+  os::{
+    linux::net::SocketAddrExt,
+    unix::{
+      net::{SocketAddr, UnixDatagram},
+      process::ExitStatusExt,
+    },
+  },
+  process::{Child, Command, ExitStatus, Stdio},
+  sync::mpsc::{self, Receiver, Sender},
+  thread,
+  time::{SystemTime, UNIX_EPOCH},
 };
 
-pub(crate) type Sequences = HashMap<String, Child>;
+/// Lines longer than this are truncated before being forwarded to Servo so
+/// that a single message always fits in the length-prefixed TCP framing.
+const MAX_OUTPUT_LINE_LENGTH: usize = 4096;
+
+/// The largest command a sequence can send, in bytes.
+const MAX_SEQUENCE_REQUEST_SIZE: usize = 16 * 1024;
+
+/// Sent by the threads which read the output of each sequence process.
+enum OutputEvent {
+  Line {
+    pid: u32,
+    stream: SequenceOutputStream,
+    line: String,
+  },
+  Closed {
+    pid: u32,
+  },
+}
+
+/// A running sequence process and the run it is reported as.
+struct SequenceProcess {
+  child: Child,
+  run: SequenceRun,
+
+  /// How many of stdout and stderr have not yet reached EOF. A run is only
+  /// reported as finished once all of its output has been forwarded.
+  open_streams: u8,
+
+  /// How many times the run has been stepped over all of its pauses, so that
+  /// the sequence can tell a new step from one it was already released by.
+  steps: u32,
+}
+
+/// Every sequence process started by the flight computer, keyed by sequence
+/// name, along with the output they have written but that hasn't been
+/// forwarded yet.
+pub(crate) struct Sequences {
+  processes: HashMap<String, SequenceProcess>,
+  output_sender: Sender<OutputEvent>,
+  output_receiver: Receiver<OutputEvent>,
+  pending: Vec<FlightStatusMessage>,
+
+  /// The process ID of the run which claims each valve.
+  claims: HashMap<String, u32>,
+  changed: bool,
+}
+
+impl Sequences {
+  pub(crate) fn new() -> Self {
+    let (output_sender, output_receiver) = mpsc::channel();
+
+    Sequences {
+      processes: HashMap::new(),
+      output_sender,
+      output_receiver,
+      pending: Vec::new(),
+      claims: HashMap::new(),
+      changed: false,
+    }
+  }
+}
+
+fn unix_now() -> f64 {
+  SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .map(|time| time.as_secs_f64())
+    .unwrap_or(0.0)
+}
+
+fn status_of(status: ExitStatus) -> SequenceStatus {
+  match (status.code(), status.signal()) {
+    (Some(code), _) => SequenceStatus::Exited { code },
+    (None, Some(signal)) => SequenceStatus::Killed { signal },
+    (None, None) => SequenceStatus::Exited { code: -1 },
+  }
+}
+
+/// Forwards each line of `pipe` to `sender` on a separate thread.
+fn forward_output(
+  pipe: impl Read + Send + 'static,
+  pid: u32,
+  stream: SequenceOutputStream,
+  sender: Sender<OutputEvent>,
+) {
+  thread::spawn(move || {
+    for line in BufReader::new(pipe).lines() {
+      let Ok(mut line) = line else {
+        break;
+      };
+
+      if line.len() > MAX_OUTPUT_LINE_LENGTH {
+        let mut end = MAX_OUTPUT_LINE_LENGTH;
+        while !line.is_char_boundary(end) {
+          end -= 1;
+        }
+
+        line.truncate(end);
+      }
+
+      if sender.send(OutputEvent::Line { pid, stream, line }).is_err() {
+        return;
+      }
+    }
+
+    let _ = sender.send(OutputEvent::Closed { pid });
+  });
+}
 
 /// Spawns a new Python process that runs `sequence` with `mappings`.
 fn run(mappings: &Mappings, sequence: &Sequence) -> io::Result<Child> {
   let python_path = python_path_for(common_so_dir())?;
 
   let mut script = String::from("from common import *;");
+
+  // arguments are bound explicitly, as the import above skips names starting
+  // with an underscore.
+  script.push_str(&format!(
+    "from common import {ARGUMENTS_ATTRIBUTE};\
+    globals().update({ARGUMENTS_ATTRIBUTE});"
+  ));
   script.push_str("OPEN = ValveState.Open;");
   script.push_str("CLOSED = ValveState.Closed;");
   for mapping in mappings {
@@ -29,70 +170,384 @@ fn run(mappings: &Mappings, sequence: &Sequence) -> io::Result<Child> {
   }
 
   script.push_str(&sequence.script);
+
+  // arguments are decoded by the common module itself, so no value supplied by
+  // an operator ever becomes part of the script.
+  let arguments = serde_json::to_string(&sequence.arguments)
+    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
   Command::new("python3")
     .env("PYTHONPATH", python_path)
+    .env(ARGUMENTS_VAR, arguments)
+    // output is piped, so Python would otherwise buffer it until exit.
+    .env("PYTHONUNBUFFERED", "1")
     .args(["-c", &script])
+    .stdout(Stdio::piped())
+    .stderr(Stdio::piped())
     .spawn()
 }
 
+/// Reports a finished run and removes it from the set of sequences.
+fn finish(sequences: &mut Sequences, name: &str, status: ExitStatus) {
+  let Some(mut process) = sequences.processes.remove(name) else {
+    return;
+  };
+
+  process.run.status = status_of(status);
+  process.run.finished_at = Some(unix_now());
+
+  println!(
+    "The '{name}' sequence (pid {}) finished: {:?}",
+    process.run.pid, process.run.status
+  );
+
+  // a sequence which exits without releasing its valves doesn't keep them.
+  sequences.claims.retain(|_, pid| *pid != process.run.pid);
+
+  sequences
+    .pending
+    .push(FlightStatusMessage::SequenceFinished(process.run));
+  sequences.changed = true;
+}
+
+/// Starts running `sequence`, returning why it couldn't be started if it
+/// wasn't.
 pub(crate) fn execute(
   mappings: &Mappings,
   sequence: &Sequence,
   sequences: &mut Sequences,
-) {
-  if let Some(running) = sequences.get_mut(&sequence.name) {
-    match running.try_wait() {
-      Ok(Some(_)) => {}
+) -> Result<(), String> {
+  if let Some(running) = sequences.processes.get_mut(&sequence.name) {
+    match running.child.try_wait() {
+      // the previous run has exited but some of its output may not have been
+      // forwarded yet. report it now so the new run can take its place.
+      Ok(Some(status)) => finish(sequences, &sequence.name, status),
       Ok(None) => {
-        println!("The '{}' sequence is already running. Stop it before re-attempting execution.", sequence.name);
-        return;
+        return Err(format!("The '{}' sequence is already running. Stop it before re-attempting execution.", sequence.name));
       }
       Err(e) => {
-        eprintln!("Another '{}' sequence was previously ran, but it's status couldn't be determined: {e}", sequence.name);
-        return;
+        return Err(format!("Another '{}' sequence was previously ran, but it's status couldn't be determined: {e}", sequence.name));
       }
     }
   }
 
-  let process = match run(mappings, &sequence) {
+  let mut child = match run(mappings, sequence) {
     Ok(c) => c,
-    Err(e) => {
-      eprintln!("Error in running python3: {e}");
-      return;
-    }
+    Err(e) => return Err(format!("Error in running python3: {e}")),
   };
 
-  sequences.insert(sequence.name.clone(), process);
+  let pid = child.id();
+  let mut open_streams = 0;
+
+  if let Some(stdout) = child.stdout.take() {
+    forward_output(
+      stdout,
+      pid,
+      SequenceOutputStream::Stdout,
+      sequences.output_sender.clone(),
+    );
+    open_streams += 1;
+  }
+
+  if let Some(stderr) = child.stderr.take() {
+    forward_output(
+      stderr,
+      pid,
+      SequenceOutputStream::Stderr,
+      sequences.output_sender.clone(),
+    );
+    open_streams += 1;
+  }
+
+  let run = SequenceRun {
+    name: sequence.name.clone(),
+    pid,
+    started_at: unix_now(),
+    finished_at: None,
+    status: SequenceStatus::Running,
+  };
+
+  sequences
+    .pending
+    .push(FlightStatusMessage::SequenceStarted(run.clone()));
+  sequences.processes.insert(
+    sequence.name.clone(),
+    SequenceProcess {
+      child,
+      run,
+      open_streams,
+      steps: 0,
+    },
+  );
+  sequences.changed = true;
+
+  Ok(())
 }
 
+/// Stops a running sequence, failing with `NotFound` if it isn't running.
 pub(crate) fn kill(sequences: &mut Sequences, name: &String) -> io::Result<()> {
-  let sequence = match sequences.get_mut(name) {
-    Some(c) => {
-      if let Ok(Some(_)) = c.try_wait() {
-        println!("A sequence named '{name}' isn't running.");
-        return Ok(());
+  let not_running = || {
+    io::Error::new(
+      io::ErrorKind::NotFound,
+      format!("A sequence named '{name}' isn't running."),
+    )
+  };
+
+  let sequence = match sequences.processes.get_mut(name) {
+    Some(p) => {
+      if let Ok(Some(_)) = p.child.try_wait() {
+        return Err(not_running());
       }
 
-      c
-    }
-    None => {
-      println!("A sequence named '{name}' isn't running.");
-      return Ok(());
+      p
     }
+    None => return Err(not_running()),
   };
 
-  sequence.kill()
+  sequence.child.kill()
 }
 
-pub(crate) fn pull_commands<'a>(
-  socket: &UnixDatagram,
-) -> Vec<SequenceDomainCommand> {
-  let mut buf: [u8; 1024] = [0; 1024];
+/// Returns the process of a running sequence, failing if it isn't running.
+fn running<'a>(
+  sequences: &'a mut Sequences,
+  name: &str,
+) -> Result<&'a mut SequenceProcess, String> {
+  let not_running = || format!("A sequence named '{name}' isn't running.");
+  let process = sequences.processes.get_mut(name).ok_or_else(not_running)?;
+
+  match process.child.try_wait() {
+    Ok(None) => Ok(process),
+    _ => Err(not_running()),
+  }
+}
+
+/// Pauses a running sequence, which holds at its current or next wait until
+/// it's resumed. Pausing a paused sequence does nothing.
+pub(crate) fn pause(
+  sequences: &mut Sequences,
+  name: &str,
+) -> Result<(), String> {
+  let process = running(sequences, name)?;
+
+  if process.run.status == SequenceStatus::Running {
+    process.run.status = SequenceStatus::Paused {
+      step: process.steps,
+    };
+    sequences.changed = true;
+  }
+
+  Ok(())
+}
+
+/// Resumes a paused sequence. Resuming a running sequence does nothing.
+pub(crate) fn resume(
+  sequences: &mut Sequences,
+  name: &str,
+) -> Result<(), String> {
+  let process = running(sequences, name)?;
+
+  if process.run.status != SequenceStatus::Running {
+    process.run.status = SequenceStatus::Running;
+    sequences.changed = true;
+  }
+
+  Ok(())
+}
+
+/// Lets a paused sequence past the wait it's held at, after which it holds at
+/// the next one. A step has no effect if the sequence isn't held at a wait
+/// when the step is seen.
+pub(crate) fn step(
+  sequences: &mut Sequences,
+  name: &str,
+) -> Result<(), String> {
+  let process = running(sequences, name)?;
+
+  if process.run.status == SequenceStatus::Running {
+    return Err(format!(
+      "The '{name}' sequence must be paused before it can be stepped."
+    ));
+  }
+
+  process.steps = process.steps.wrapping_add(1);
+  process.run.status = SequenceStatus::Paused {
+    step: process.steps,
+  };
+  sequences.changed = true;
+
+  Ok(())
+}
+
+/// Kills every running sequence, such as in preparation for an abort, and
+/// releases every valve they claim.
+pub(crate) fn kill_all(sequences: &mut Sequences) {
+  sequences.claims.clear();
+  sequences.changed = true;
+
+  for process in sequences.processes.values_mut() {
+    if let Ok(Some(_)) = process.child.try_wait() {
+      continue;
+    }
+
+    if let Err(e) = process.child.kill() {
+      println!("Couldn't kill a sequence in preperation for abort, continuing normally: {e}");
+    }
+  }
+}
+
+/// Collects the output written by sequences and reaps those which have exited,
+/// keeping the running sequences in `state` up to date.
+///
+/// Returns the messages that should be forwarded to Servo, in order.
+pub(crate) fn poll(
+  sequences: &mut Sequences,
+  state: &mut VehicleState,
+) -> Vec<FlightStatusMessage> {
+  while let Ok(event) = sequences.output_receiver.try_recv() {
+    match event {
+      OutputEvent::Line { pid, stream, line } => {
+        let name = sequences
+          .processes
+          .values()
+          .find(|p| p.run.pid == pid)
+          .map_or("unknown", |p| p.run.name.as_str());
+
+        match stream {
+          SequenceOutputStream::Stdout => println!("[{name}] {line}"),
+          SequenceOutputStream::Stderr => eprintln!("[{name}] {line}"),
+        }
+
+        sequences.pending.push(FlightStatusMessage::SequenceOutput {
+          pid,
+          stream,
+          line,
+        });
+      }
+      OutputEvent::Closed { pid } => {
+        if let Some(process) =
+          sequences.processes.values_mut().find(|p| p.run.pid == pid)
+        {
+          process.open_streams = process.open_streams.saturating_sub(1);
+        }
+      }
+    }
+  }
+
+  let mut exited = Vec::new();
+  for (name, process) in &mut sequences.processes {
+    if process.open_streams > 0 {
+      continue;
+    }
+
+    match process.child.try_wait() {
+      Ok(Some(status)) => exited.push((name.clone(), status)),
+      Ok(None) => {}
+      Err(e) => {
+        eprintln!("The status of the '{name}' sequence couldn't be determined: {e}")
+      }
+    }
+  }
+
+  for (name, status) in exited {
+    finish(sequences, &name, status);
+  }
+
+  if sequences.changed {
+    state.sequences = sequences
+      .processes
+      .iter()
+      .map(|(name, process)| (name.clone(), process.run.clone()))
+      .collect();
+    state.valve_claims = sequences
+      .claims
+      .iter()
+      .filter_map(|(valve, pid)| {
+        Some((valve.clone(), name_of(sequences, *pid)?.to_string()))
+      })
+      .collect();
+    sequences.changed = false;
+  }
+
+  std::mem::take(&mut sequences.pending)
+}
+
+/// Returns the name of the sequence whose run has the given process ID, if
+/// the flight computer started it.
+pub(crate) fn name_of(sequences: &Sequences, pid: u32) -> Option<&str> {
+  sequences
+    .processes
+    .iter()
+    .find(|(_, process)| process.run.pid == pid)
+    .map(|(name, _)| name.as_str())
+}
+
+/// Claims `valves` for the run with process ID `pid`, or claims none of them
+/// if another run claims one.
+pub(crate) fn claim(
+  sequences: &mut Sequences,
+  pid: u32,
+  valves: Vec<String>,
+) -> Result<(), SequenceCommandError> {
+  if name_of(sequences, pid).is_none() {
+    return Err(SequenceCommandError::Rejected(format!(
+      "Process {pid} tried to claim valves, but only sequences started by the \
+      flight computer can."
+    )));
+  }
+
+  for valve in &valves {
+    check_claim(sequences, pid, valve)?;
+  }
+
+  for valve in valves {
+    sequences.claims.insert(valve, pid);
+  }
+
+  sequences.changed = true;
+  Ok(())
+}
+
+/// Releases those of `valves` claimed by the run with process ID `pid`.
+pub(crate) fn release(sequences: &mut Sequences, pid: u32, valves: &[String]) {
+  sequences
+    .claims
+    .retain(|valve, owner| *owner != pid || !valves.contains(valve));
+  sequences.changed = true;
+}
+
+/// Checks that `valve` isn't claimed by a run other than the one with process
+/// ID `pid`.
+pub(crate) fn check_claim(
+  sequences: &Sequences,
+  pid: u32,
+  valve: &str,
+) -> Result<(), SequenceCommandError> {
+  match sequences.claims.get(valve) {
+    Some(&owner) if owner != pid => Err(SequenceCommandError::ValveClaimed {
+      valve: valve.to_string(),
+      sequence: name_of(sequences, owner).unwrap_or("unknown").to_string(),
+    }),
+    _ => Ok(()),
+  }
+}
+
+/// A command received from a sequence process, along with where to send the
+/// response to it.
+pub(crate) struct ReceivedCommand {
+  pub(crate) id: u32,
+  /// The process ID of the sender, taken from the address it sent from.
+  pub(crate) pid: u32,
+  pub(crate) command: SequenceDomainCommand,
+  pub(crate) reply_to: SocketAddr,
+}
+
+pub(crate) fn pull_commands(socket: &UnixDatagram) -> Vec<ReceivedCommand> {
+  let mut buf = [0; MAX_SEQUENCE_REQUEST_SIZE];
   let mut commands = Vec::new();
 
   loop {
-    let size = match socket.recv(&mut buf) {
-      Ok(s) => s,
+    let (size, reply_to) = match socket.recv_from(&mut buf) {
+      Ok(received) => received,
       Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => break,
       Err(e) => {
         eprintln!("Error in receiving from sequence command socket: {e}");
@@ -100,19 +555,415 @@ pub(crate) fn pull_commands<'a>(
       }
     };
 
-    let command =
-      match postcard::from_bytes::<SequenceDomainCommand>(&buf[..size]) {
-        Ok(c) => c,
-        Err(e) => {
-          eprintln!(
-            "Error in deserializing SequenceDomainCommand from sequence: {e}"
-          );
+    // a datagram which fills the buffer may have been truncated, but its ID
+    // can still be read so that the sequence learns why it was dropped.
+    let error = if size == buf.len() {
+      format!(
+        "Sequence command exceeded the maximum size of \
+        {MAX_SEQUENCE_REQUEST_SIZE} bytes."
+      )
+    } else if let Some(pid) = sender_pid(&reply_to) {
+      match postcard::from_bytes::<SequenceDomainRequest>(&buf[..size]) {
+        Ok(SequenceDomainRequest { id, command }) => {
+          commands.push(ReceivedCommand {
+            id,
+            pid,
+            command,
+            reply_to,
+          });
           continue;
         }
-      };
+        Err(e) => format!(
+          "Error in deserializing SequenceDomainCommand from sequence: {e}"
+        ),
+      }
+    } else {
+      format!(
+        "Sequence command was sent from {reply_to:?}, which isn't the address \
+        of a sequence."
+      )
+    };
 
-    commands.push(command);
+    match postcard::take_from_bytes::<u32>(&buf[..size]) {
+      Ok((id, _)) => respond(
+        socket,
+        id,
+        &reply_to,
+        Err(SequenceCommandError::Rejected(error)),
+      ),
+      Err(_) => eprintln!("{error}"),
+    }
   }
 
   commands
 }
+
+/// The process ID of the sequence which sent a command from `address`, which
+/// the `common` module binds to `COMMAND_ADDRESS_PREFIX` and its process ID.
+fn sender_pid(address: &SocketAddr) -> Option<u32> {
+  let name = address.as_abstract_name()?;
+  let pid = name.strip_prefix(COMMAND_ADDRESS_PREFIX.as_bytes())?;
+
+  std::str::from_utf8(pid).ok()?.parse().ok()
+}
+
+/// Tells the sequence process at `reply_to` how its request with the given ID
+/// went.
+pub(crate) fn respond(
+  socket: &UnixDatagram,
+  id: u32,
+  reply_to: &SocketAddr,
+  result: Result<(), SequenceCommandError>,
+) {
+  if let Err(ref e) = result {
+    eprintln!("{e}");
+  }
+
+  let response = match postcard::to_allocvec(&SequenceDomainResponse {
+    id,
+    result,
+  }) {
+    Ok(response) => response,
+    Err(e) => {
+      eprintln!("Error in serializing SequenceDomainResponse: {e}");
+      return;
+    }
+  };
+
+  // the sequence may have exited without waiting for its response.
+  if let Err(e) = socket.send_to_addr(&response, reply_to) {
+    eprintln!("Error in responding to sequence command {id}: {e}");
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::common_so::materialize_common_so;
+  use common::comm::SequenceArgument;
+  use std::{
+    os::linux::net::SocketAddrExt,
+    process,
+    sync::atomic::{AtomicU32, Ordering},
+    time::Duration,
+  };
+
+  /// Binds a socket to the abstract address `name`.
+  fn bind(name: String) -> UnixDatagram {
+    let socket =
+      UnixDatagram::bind_addr(&SocketAddr::from_abstract_name(name).unwrap())
+        .unwrap();
+    socket
+      .set_read_timeout(Some(Duration::from_secs(1)))
+      .unwrap();
+    socket
+  }
+
+  /// A process ID no other test uses, large enough not to be a real one.
+  fn unused_pid() -> u32 {
+    static NEXT: AtomicU32 = AtomicU32::new(0);
+
+    3_000_000_000
+      + process::id() % 100_000 * 100
+      + NEXT.fetch_add(1, Ordering::Relaxed)
+  }
+
+  /// The FC's command socket and a sequence with the given process ID
+  /// connected to it.
+  fn command_sockets(pid: u32) -> (UnixDatagram, UnixDatagram) {
+    let fc = bind(format!("fc_test_commands_{}", unused_pid()));
+    fc.set_nonblocking(true).unwrap();
+
+    let sequence = bind(format!("{COMMAND_ADDRESS_PREFIX}{pid}"));
+    sequence.connect_addr(&fc.local_addr().unwrap()).unwrap();
+
+    (fc, sequence)
+  }
+
+  fn request(id: u32) -> Vec<u8> {
+    postcard::to_allocvec(&SequenceDomainRequest {
+      id,
+      command: SequenceDomainCommand::AbortViaStage,
+    })
+    .unwrap()
+  }
+
+  /// Registers a run named `name`, backed by a real process so that it can be
+  /// looked up by its process ID.
+  fn start(sequences: &mut Sequences, name: &str) -> u32 {
+    let child = Command::new("sleep").arg("30").spawn().unwrap();
+    let pid = child.id();
+
+    sequences.processes.insert(
+      name.to_string(),
+      SequenceProcess {
+        child,
+        run: SequenceRun {
+          name: name.to_string(),
+          pid,
+          started_at: 0.0,
+          finished_at: None,
+          status: SequenceStatus::Running,
+        },
+        open_streams: 0,
+        steps: 0,
+      },
+    );
+
+    pid
+  }
+
+  fn valves(names: &[&str]) -> Vec<String> {
+    names.iter().map(|name| name.to_string()).collect()
+  }
+
+  fn claims(sequences: &Sequences) -> Vec<(&str, u32)> {
+    let mut claims: Vec<(&str, u32)> = sequences
+      .claims
+      .iter()
+      .map(|(valve, pid)| (valve.as_str(), *pid))
+      .collect();
+    claims.sort();
+    claims
+  }
+
+  fn response(sequence: &UnixDatagram) -> SequenceDomainResponse {
+    let mut buf = [0; 1024];
+    let size = sequence.recv(&mut buf).unwrap();
+    postcard::from_bytes(&buf[..size]).unwrap()
+  }
+
+  fn rejection(response: SequenceDomainResponse) -> String {
+    match response.result {
+      Err(SequenceCommandError::Rejected(reason)) => reason,
+      other => panic!("{other:?} is not a rejection"),
+    }
+  }
+
+  #[test]
+  fn arguments_are_bound_in_the_spawned_script() {
+    materialize_common_so().unwrap();
+
+    let sequence = Sequence {
+      name: String::from("arguments"),
+      script: String::from(
+        "assert _hold == 3.0, _hold\n\
+        assert count == 2.0, count\n\
+        assert isinstance(target, Pressure), target",
+      ),
+      arguments: HashMap::from([
+        (String::from("_hold"), SequenceArgument::Number(3.0)),
+        (String::from("count"), SequenceArgument::Number(2.0)),
+        (String::from("target"), SequenceArgument::Pressure(450.0)),
+      ]),
+    };
+
+    let output = run(&Vec::new(), &sequence)
+      .unwrap()
+      .wait_with_output()
+      .unwrap();
+
+    assert!(
+      output.status.success(),
+      "{}",
+      String::from_utf8_lossy(&output.stderr)
+    );
+  }
+
+  #[test]
+  fn commands_are_pulled_with_their_sender() {
+    let pid = unused_pid();
+    let (fc, sequence) = command_sockets(pid);
+    sequence.send(&request(4)).unwrap();
+    sequence.send(&request(5)).unwrap();
+
+    let commands = pull_commands(&fc);
+    assert_eq!(
+      commands
+        .iter()
+        .map(|command| command.id)
+        .collect::<Vec<_>>(),
+      vec![4, 5]
+    );
+    assert!(commands.iter().all(|command| command.pid == pid));
+    assert!(matches!(
+      commands[0].command,
+      SequenceDomainCommand::AbortViaStage
+    ));
+
+    respond(&fc, 4, &commands[0].reply_to, Ok(()));
+    let response = response(&sequence);
+    assert_eq!(response.id, 4);
+    assert_eq!(response.result, Ok(()));
+
+    // nothing is left to pull
+    assert!(pull_commands(&fc).is_empty());
+  }
+
+  #[test]
+  fn malformed_commands_are_rejected_by_id() {
+    let (fc, sequence) = command_sockets(unused_pid());
+
+    let mut malformed = request(7);
+    malformed.truncate(1);
+    malformed.extend_from_slice(&[0xff; 8]);
+    sequence.send(&malformed).unwrap();
+
+    assert!(pull_commands(&fc).is_empty());
+
+    let response = response(&sequence);
+    assert_eq!(response.id, 7);
+    assert!(rejection(response).contains("deserializing"));
+  }
+
+  #[test]
+  fn oversized_commands_are_rejected_by_id() {
+    let (fc, sequence) = command_sockets(unused_pid());
+
+    let mut oversized = request(300);
+    oversized.resize(MAX_SEQUENCE_REQUEST_SIZE + 100, 0);
+    sequence.send(&oversized).unwrap();
+    sequence.send(&request(301)).unwrap();
+
+    // the command after the oversized one is still pulled
+    let commands = pull_commands(&fc);
+    assert_eq!(commands.len(), 1);
+    assert_eq!(commands[0].id, 301);
+
+    let response = response(&sequence);
+    assert_eq!(response.id, 300);
+    assert!(rejection(response).contains("maximum size"));
+  }
+
+  #[test]
+  fn commands_without_an_id_are_dropped() {
+    let (fc, sequence) = command_sockets(unused_pid());
+    sequence.send(&[0xff; 6]).unwrap();
+
+    assert!(pull_commands(&fc).is_empty());
+
+    sequence.set_nonblocking(true).unwrap();
+    let mut buf = [0; 64];
+    assert_eq!(
+      sequence.recv(&mut buf).unwrap_err().kind(),
+      io::ErrorKind::WouldBlock
+    );
+  }
+
+  #[test]
+  fn commands_from_other_addresses_are_rejected_by_id() {
+    let fc = bind(format!("fc_test_commands_{}", unused_pid()));
+    fc.set_nonblocking(true).unwrap();
+
+    let impostor = bind(format!("fc_test_impostor_{}", unused_pid()));
+    impostor.connect_addr(&fc.local_addr().unwrap()).unwrap();
+    impostor.send(&request(2)).unwrap();
+
+    assert!(pull_commands(&fc).is_empty());
+
+    let response = response(&impostor);
+    assert_eq!(response.id, 2);
+    assert!(rejection(response).contains("isn't the address of a sequence"));
+  }
+
+  #[test]
+  fn sender_pids_are_read_from_sequence_addresses() {
+    let address = |name: &str| SocketAddr::from_abstract_name(name).unwrap();
+
+    assert_eq!(sender_pid(&address("fc_sequence_1234")), Some(1234));
+    assert_eq!(sender_pid(&address("fc_sequence_")), None);
+    assert_eq!(sender_pid(&address("fc_sequence_12a")), None);
+    assert_eq!(sender_pid(&address("other_1234")), None);
+  }
+
+  #[test]
+  fn claims_are_all_or_nothing() {
+    let mut sequences = Sequences::new();
+    let fill = start(&mut sequences, "fill");
+    let vent = start(&mut sequences, "vent");
+
+    claim(&mut sequences, fill, valves(&["FUEL", "OX"])).unwrap();
+    assert_eq!(claims(&sequences), vec![("FUEL", fill), ("OX", fill)]);
+
+    // claiming again is harmless
+    claim(&mut sequences, fill, valves(&["FUEL"])).unwrap();
+
+    let error = claim(&mut sequences, vent, valves(&["VENT", "OX"]));
+    assert_eq!(
+      error,
+      Err(SequenceCommandError::ValveClaimed {
+        valve: "OX".to_string(),
+        sequence: "fill".to_string(),
+      })
+    );
+    assert_eq!(claims(&sequences), vec![("FUEL", fill), ("OX", fill)]);
+
+    kill_all(&mut sequences);
+  }
+
+  #[test]
+  fn only_sequences_started_by_the_fc_can_claim() {
+    let mut sequences = Sequences::new();
+
+    assert!(matches!(
+      claim(&mut sequences, unused_pid(), valves(&["FUEL"])),
+      Err(SequenceCommandError::Rejected(_))
+    ));
+    assert!(sequences.claims.is_empty());
+  }
+
+  #[test]
+  fn claimed_valves_can_only_be_commanded_by_their_owner() {
+    let mut sequences = Sequences::new();
+    let fill = start(&mut sequences, "fill");
+    let vent = start(&mut sequences, "vent");
+
+    claim(&mut sequences, fill, valves(&["FUEL"])).unwrap();
+
+    assert_eq!(check_claim(&sequences, fill, "FUEL"), Ok(()));
+    assert!(check_claim(&sequences, vent, "FUEL").is_err());
+    assert_eq!(check_claim(&sequences, vent, "OX"), Ok(()));
+
+    kill_all(&mut sequences);
+  }
+
+  #[test]
+  fn runs_release_only_their_own_claims() {
+    let mut sequences = Sequences::new();
+    let fill = start(&mut sequences, "fill");
+    let vent = start(&mut sequences, "vent");
+
+    claim(&mut sequences, fill, valves(&["FUEL", "OX"])).unwrap();
+    claim(&mut sequences, vent, valves(&["VENT"])).unwrap();
+
+    release(&mut sequences, vent, &valves(&["FUEL", "OX"]));
+    assert_eq!(
+      claims(&sequences),
+      vec![("FUEL", fill), ("OX", fill), ("VENT", vent)]
+    );
+
+    release(&mut sequences, fill, &valves(&["OX"]));
+    assert_eq!(claims(&sequences), vec![("FUEL", fill), ("VENT", vent)]);
+
+    kill_all(&mut sequences);
+  }
+
+  #[test]
+  fn finishing_a_run_releases_its_claims() {
+    let mut sequences = Sequences::new();
+    let fill = start(&mut sequences, "fill");
+    let vent = start(&mut sequences, "vent");
+
+    claim(&mut sequences, fill, valves(&["FUEL", "OX"])).unwrap();
+    claim(&mut sequences, vent, valves(&["VENT"])).unwrap();
+
+    let child = &mut sequences.processes.get_mut("fill").unwrap().child;
+    child.kill().unwrap();
+    let status = child.wait().unwrap();
+
+    finish(&mut sequences, "fill", status);
+    assert_eq!(claims(&sequences), vec![("VENT", vent)]);
+
+    kill_all(&mut sequences);
+  }
+}
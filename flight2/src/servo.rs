@@ -1,19 +1,26 @@
 use common::comm::{
-  include_in_radio_telemetry,
+  sam::Unit,
   Computer,
-  FlightControlMessage,
+  DeltaEncoding,
+  FlightControlRequest,
+  FlightStatusMessage,
   SensorType,
+  SlicePriorities,
   VehicleState,
   VehicleStateCompaqError,
   VehicleStateCompressionSchema,
+  VehicleStateDeltaEncoder,
   VehicleStateSchemaError,
+  VehicleStateSlicer,
 };
 use postcard::experimental::max_size::MaxSize;
 use socket2::{Domain, Protocol, Socket, TcpKeepalive, Type};
 use std::{
+  collections::{HashMap, VecDeque},
   fmt,
   io::{self, Read, Write},
   net::{SocketAddr, TcpStream, ToSocketAddrs, UdpSocket},
+  sync::mpsc::{self, Receiver},
   thread,
   time::Duration,
 };
@@ -25,6 +32,13 @@ pub const servo_keep_alive_delay: Duration = Duration::from_secs(1);
 /// them from the uncompressed umbilical telemetry stream on the same UDP port.
 pub const RADIO_TELEMETRY_DSCP: u8 = 0x2e;
 
+/// How long to wait between attempts to make the first connection to servo.
+const CONNECT_RETRY_DELAY: Duration = Duration::from_secs(2);
+
+/// The most status bytes that may wait to be written to servo before new
+/// status messages are refused, so a stalled servo can't exhaust memory.
+const MAX_QUEUED_STATUS_BYTES: usize = 1 << 20;
+
 type Result<T> = std::result::Result<T, ServoError>;
 
 #[derive(Debug)]
@@ -60,14 +74,39 @@ impl fmt::Display for ServoError {
   }
 }
 
+/// The priority of the parts of radio telemetry which don't come from a
+/// mapping, such as GPS and RECO, when it is split across frames. These are
+/// scheduled alongside the most important mappings.
+const RADIO_SECTION_PRIORITY: u8 = u8::MAX;
+
 #[derive(Default)]
 pub(crate) struct RadioTelemetryEncoder {
   schema: Option<VehicleStateCompressionSchema>,
   valve_keys: Vec<String>,
-  sensor_keys: Vec<String>,
+  sensor_metadata: Vec<(String, Unit)>,
+
+  /// The most bytes a frame holding all of the schema can take. Telemetry is
+  /// split across several frames when this is larger than the buffer.
+  max_compressed_size: usize,
+
+  /// How often each part of the state is sent when it is split across
+  /// frames, with valves and sensors taking the priority in their mapping.
+  priorities: SlicePriorities,
+  slicer: VehicleStateSlicer,
+
+  /// Sends keyframes and delta frames instead of a full state every frame
+  /// when set.
+  delta: Option<VehicleStateDeltaEncoder>,
 }
 
 impl RadioTelemetryEncoder {
+  pub(crate) fn new(delta_encoding: Option<DeltaEncoding>) -> Self {
+    Self {
+      delta: delta_encoding.map(VehicleStateDeltaEncoder::new),
+      ..Self::default()
+    }
+  }
+
   pub(crate) fn encode<'a>(
     &'a mut self,
     state: &VehicleState,
@@ -79,10 +118,14 @@ impl RadioTelemetryEncoder {
       .schema
       .as_ref()
       .ok_or(ServoError::CompressionFailed("missing radio schema"))?;
-    let size =
-      state
-        .compress_compaq_with_schema(buffer, schema)
-        .map_err(|error| match error {
+    let size = if self.max_compressed_size > buffer.len() {
+      self.slicer.encode(state, buffer, schema, &self.priorities)
+    } else if let Some(delta) = &mut self.delta {
+      delta.encode(state, buffer, schema)
+    } else {
+      state.compress_compaq_with_schema(buffer, schema)
+    }
+    .map_err(|error| match error {
           VehicleStateCompaqError::Schema(
             VehicleStateSchemaError::TooManyValves,
           ) => {
@@ -106,7 +149,10 @@ impl RadioTelemetryEncoder {
               "failed to serialize compaq radio telemetry",
             )
           }
-          VehicleStateCompaqError::Deserialize(_) => {
+          VehicleStateCompaqError::Deserialize(_)
+          | VehicleStateCompaqError::SchemaMismatch { .. }
+          | VehicleStateCompaqError::MissingKeyframe
+          | VehicleStateCompaqError::IncompleteState => {
             ServoError::CompressionFailed(
               "unexpected compaq deserialize during radio encode",
             )
@@ -122,15 +168,15 @@ impl RadioTelemetryEncoder {
   ) -> Result<()> {
     let mut valve_keys: Vec<_> = mappings
       .iter()
-      .filter(|mapping| include_in_radio_telemetry(mapping))
+      .filter(|mapping| mapping.radio)
       .filter(|mapping| mapping.sensor_type == SensorType::Valve)
       .map(|mapping| mapping.text_id.clone())
       .collect();
     valve_keys.sort_unstable();
 
-    let mut sensor_keys: Vec<_> = mappings
+    let mut sensor_metadata: Vec<_> = mappings
       .iter()
-      .filter(|mapping| include_in_radio_telemetry(mapping))
+      .filter(|mapping| mapping.radio)
       .filter(|mapping| mapping.sensor_type != SensorType::Valve)
       .filter(|mapping| {
         !mapping
@@ -141,27 +187,54 @@ impl RadioTelemetryEncoder {
             valve_keys.iter().any(|key| key == valve_name)
           })
       })
-      .map(|mapping| mapping.text_id.clone())
+      .filter_map(|mapping| {
+        Some((mapping.text_id.clone(), mapping.sensor_type.unit()?))
+      })
       .collect();
-    sensor_keys.sort_unstable();
+    sensor_metadata.sort_unstable_by(|(left, _), (right, _)| left.cmp(right));
 
     if self.schema.is_none()
       || self.valve_keys != valve_keys
-      || self.sensor_keys != sensor_keys
+      || self.sensor_metadata != sensor_metadata
     {
       self.schema = Some(
         VehicleStateCompressionSchema::new(
           valve_keys.iter().cloned(),
-          sensor_keys.iter().cloned(),
+          sensor_metadata.iter().cloned(),
         )
         .map_err(|_| {
           ServoError::CompressionFailed("failed to build radio schema")
         })?,
       );
       self.valve_keys = valve_keys;
-      self.sensor_keys = sensor_keys;
+      self.sensor_metadata = sensor_metadata;
+      self.max_compressed_size = self
+        .schema
+        .as_ref()
+        .map_or(0, VehicleStateCompressionSchema::max_compressed_size);
     }
 
+    let radio_priorities: HashMap<&str, u8> = mappings
+      .iter()
+      .filter(|mapping| mapping.radio)
+      .map(|mapping| (mapping.text_id.as_str(), mapping.radio_priority))
+      .collect();
+    let priority_of =
+      |key: &String| radio_priorities.get(key.as_str()).copied().unwrap_or(0);
+
+    self.priorities = SlicePriorities {
+      valves: self.valve_keys.iter().map(priority_of).collect(),
+      sensors: self
+        .sensor_metadata
+        .iter()
+        .map(|(key, _)| priority_of(key))
+        .collect(),
+      bms: RADIO_SECTION_PRIORITY,
+      fc_sensors: RADIO_SECTION_PRIORITY,
+      gps: RADIO_SECTION_PRIORITY,
+      reco: RADIO_SECTION_PRIORITY,
+    };
+
     Ok(())
   }
 }
@@ -277,10 +350,34 @@ pub(crate) fn establish(
   Err(ServoError::TransportFailed(fatal_error))
 }
 
+/// Makes the first connection to servo on a separate thread, retrying until it
+/// succeeds, so that resolving and connecting to its addresses never holds up
+/// the control loop. The connection is sent over the returned channel.
+pub(crate) fn connect_in_background(
+  servo_addresses: &'static [(&'static str, u16)],
+) -> Receiver<(TcpStream, SocketAddr)> {
+  let (sender, receiver) = mpsc::channel();
+
+  thread::spawn(move || loop {
+    match establish(servo_addresses, None, 3, Duration::from_secs(2)) {
+      Ok(connection) => {
+        let _ = sender.send(connection);
+        return;
+      }
+      Err(e) => {
+        println!("Couldn't connect due to error: {e}\n");
+        thread::sleep(CONNECT_RETRY_DELAY);
+      }
+    }
+  });
+
+  receiver
+}
+
 // "pull" new information from servo
 pub(crate) fn pull(
   servo_stream: &mut TcpStream,
-) -> Result<Option<FlightControlMessage>> {
+) -> Result<Option<FlightControlRequest>> {
   let mut buffer = vec![0; u16::MAX as usize + 2];
   let mut index: usize = 0;
 
@@ -308,12 +405,102 @@ pub(crate) fn pull(
     };
   }
 
-  match postcard::from_bytes::<FlightControlMessage>(&buffer[2..]) {
+  match postcard::from_bytes::<FlightControlRequest>(&buffer[2..]) {
     Ok(m) => Ok(Some(m)),
     Err(e) => Err(ServoError::DeserializationFailed(e)),
   }
 }
 
+/// Status messages waiting to be written to servo over the same stream that
+/// messages are pulled from, using the same length-prefixed framing.
+///
+/// The stream is non-blocking, so a frame may only be partially written on a
+/// given loop iteration. The rest of it is kept and written first on the next
+/// flush, so the framing stays intact without the control loop ever blocking.
+#[derive(Default)]
+pub(crate) struct StatusOutbox {
+  frames: VecDeque<Vec<u8>>,
+  /// How much of the front frame has already been written.
+  written: usize,
+  queued_bytes: usize,
+}
+
+impl StatusOutbox {
+  /// Frames a status message and queues it to be written on the next flush.
+  pub(crate) fn push(&mut self, message: &FlightStatusMessage) -> Result<()> {
+    let serialized = match postcard::to_allocvec(message) {
+      Ok(v) => v,
+      Err(e) => return Err(ServoError::DeserializationFailed(e)),
+    };
+
+    let Ok(length) = u16::try_from(serialized.len()) else {
+      return Err(ServoError::BufferTooSmall);
+    };
+
+    if self.queued_bytes + serialized.len() + 2 > MAX_QUEUED_STATUS_BYTES {
+      return Err(ServoError::BufferTooSmall);
+    }
+
+    let mut framed = Vec::with_capacity(serialized.len() + 2);
+    framed.extend_from_slice(&length.to_be_bytes());
+    framed.extend_from_slice(&serialized);
+
+    self.queued_bytes += framed.len();
+    self.frames.push_back(framed);
+    Ok(())
+  }
+
+  /// Queues the reply telling servo how the request with the given ID went,
+  /// carrying the reason along if it failed.
+  pub(crate) fn reply(
+    &mut self,
+    id: u32,
+    result: std::result::Result<(), String>,
+  ) -> Result<()> {
+    let reply = match result {
+      Ok(()) => FlightStatusMessage::Ack { id },
+      Err(reason) => FlightStatusMessage::Nack { id, reason },
+    };
+
+    self.push(&reply)
+  }
+
+  /// Writes as much of the queue as `servo_stream` accepts without blocking.
+  /// Anything left over stays queued for the next flush.
+  pub(crate) fn flush(&mut self, servo_stream: &mut impl Write) -> Result<()> {
+    while let Some(frame) = self.frames.front() {
+      match servo_stream.write(&frame[self.written..]) {
+        Ok(0) => return Err(ServoError::ServoDisconnected),
+        Ok(n) => self.written += n,
+        Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(()),
+        Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+        Err(e) => return Err(ServoError::TransportFailed(e)),
+      }
+
+      if self.written == frame.len() {
+        self.queued_bytes -= frame.len();
+        self.written = 0;
+        self.frames.pop_front();
+      }
+    }
+
+    Ok(())
+  }
+
+  /// Prepares the queue for a new connection. A frame that was partially
+  /// written to the old connection can't be finished on the new one, so it is
+  /// discarded, while frames that weren't started are kept and sent.
+  pub(crate) fn reconnected(&mut self) {
+    if self.written > 0 {
+      if let Some(frame) = self.frames.pop_front() {
+        self.queued_bytes -= frame.len();
+      }
+
+      self.written = 0;
+    }
+  }
+}
+
 pub(crate) fn make_radio_socket() -> Result<UdpSocket> {
   let socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP))
     .map_err(ServoError::TransportFailed)?;
@@ -357,3 +544,153 @@ pub(crate) fn push_radio(
     .send_to(message, (servo_socket.ip(), SERVO_DATA_PORT))
     .map_err(ServoError::TransportFailed)
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// A non-blocking stream that accepts up to `capacity` more bytes before it
+  /// would block.
+  struct Limited {
+    written: Vec<u8>,
+    capacity: usize,
+  }
+
+  impl Write for Limited {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+      if self.capacity == 0 {
+        return Err(io::ErrorKind::WouldBlock.into());
+      }
+
+      let n = buf.len().min(self.capacity);
+      self.written.extend_from_slice(&buf[..n]);
+      self.capacity -= n;
+      Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+      Ok(())
+    }
+  }
+
+  fn frame(message: &FlightStatusMessage) -> Vec<u8> {
+    let serialized = postcard::to_allocvec(message).unwrap();
+    let mut framed = (serialized.len() as u16).to_be_bytes().to_vec();
+    framed.extend_from_slice(&serialized);
+    framed
+  }
+
+  fn ack(id: u32) -> FlightStatusMessage {
+    FlightStatusMessage::Ack { id }
+  }
+
+  #[test]
+  fn partial_writes_are_finished_on_later_flushes() {
+    let mut outbox = StatusOutbox::default();
+    outbox.push(&ack(1)).unwrap();
+    outbox.push(&ack(2)).unwrap();
+
+    let mut stream = Limited {
+      written: Vec::new(),
+      capacity: 3,
+    };
+    outbox.flush(&mut stream).unwrap();
+    assert_eq!(stream.written.len(), 3);
+
+    stream.capacity = usize::MAX;
+    outbox.flush(&mut stream).unwrap();
+
+    let mut expected = frame(&ack(1));
+    expected.extend(frame(&ack(2)));
+    assert_eq!(stream.written, expected);
+  }
+
+  #[test]
+  fn messages_stay_queued_while_the_stream_would_block() {
+    let mut outbox = StatusOutbox::default();
+    outbox.push(&ack(1)).unwrap();
+
+    let mut stream = Limited {
+      written: Vec::new(),
+      capacity: 0,
+    };
+    outbox.flush(&mut stream).unwrap();
+    outbox.push(&ack(2)).unwrap();
+
+    stream.capacity = usize::MAX;
+    outbox.flush(&mut stream).unwrap();
+
+    let mut expected = frame(&ack(1));
+    expected.extend(frame(&ack(2)));
+    assert_eq!(stream.written, expected);
+  }
+
+  #[test]
+  fn reconnecting_drops_only_a_partially_written_frame() {
+    let mut outbox = StatusOutbox::default();
+    outbox.push(&ack(1)).unwrap();
+    outbox.push(&ack(2)).unwrap();
+
+    let mut old = Limited {
+      written: Vec::new(),
+      capacity: 1,
+    };
+    outbox.flush(&mut old).unwrap();
+    outbox.reconnected();
+
+    let mut new = Limited {
+      written: Vec::new(),
+      capacity: usize::MAX,
+    };
+    outbox.flush(&mut new).unwrap();
+    assert_eq!(new.written, frame(&ack(2)));
+
+    // nothing was partially written this time, so nothing is dropped.
+    outbox.push(&ack(3)).unwrap();
+    outbox.reconnected();
+    outbox.flush(&mut new).unwrap();
+
+    let mut expected = frame(&ack(2));
+    expected.extend(frame(&ack(3)));
+    assert_eq!(new.written, expected);
+  }
+
+  #[test]
+  fn a_stalled_servo_cannot_grow_the_queue_without_bound() {
+    let mut outbox = StatusOutbox::default();
+    let message = FlightStatusMessage::Nack {
+      id: 0,
+      reason: "x".repeat(1000),
+    };
+
+    let mut accepted = 0;
+    while outbox.push(&message).is_ok() {
+      accepted += 1;
+    }
+
+    assert!(accepted > 0);
+    assert!(outbox.queued_bytes <= MAX_QUEUED_STATUS_BYTES);
+  }
+
+  #[test]
+  fn replies_carry_their_request_id_and_reason() {
+    let mut outbox = StatusOutbox::default();
+    outbox.reply(4, Ok(())).unwrap();
+    outbox
+      .reply(5, Err(String::from("no sequence named 'fill'")))
+      .unwrap();
+
+    let mut stream = Limited {
+      written: Vec::new(),
+      capacity: usize::MAX,
+    };
+    outbox.flush(&mut stream).unwrap();
+
+    let mut expected = frame(&ack(4));
+    expected.extend(frame(&FlightStatusMessage::Nack {
+      id: 5,
+      reason: String::from("no sequence named 'fill'"),
+    }));
+    assert_eq!(stream.written, expected);
+  }
+}
@@ -2,13 +2,16 @@ use common::comm::{
   bms, 
   flight::DataMessage, 
   sam::{ChannelType, SamDataPoint, Unit}, CompositeValveState, 
+  AlarmBound,
+  AlarmSeverity,
   Measurement, 
   NodeMapping,
+  RedlineAlarm,
   SensorType, 
   ValveState, 
   VehicleState
 };
-use crate::{Mappings, MMAP_GRACE_PERIOD};
+use crate::{file_logger::current_timestamp, Mappings, MMAP_GRACE_PERIOD};
 use mmap_sync::locks::LockDisabled;
 use mmap_sync::synchronizer::{Synchronizer, SynchronizerError};
 use wyhash::WyHash;
@@ -45,7 +48,9 @@ impl<'a> Ingestible for DataMessage<'a> {
 
           process_bms_data(vehicle_state, **datapoint);
       },
-      DataMessage::FlightHeartbeat | DataMessage::Identity(_) => {},
+      DataMessage::FlightHeartbeat
+      | DataMessage::Identity(_)
+      | DataMessage::SamAck(..) => {},
     }
   }
 }
@@ -73,7 +78,8 @@ pub(crate) fn process_sam_data(board_id: &str, state: &mut VehicleState, datapoi
               .sensor_type
               .channel_types()
               .contains(&data_point.channel_type)
-            && board_id == mapping.board_id;
+            && board_id == mapping.board_id
+            && !mapping.is_virtual();
 
           if !corresponds {
             continue;
@@ -83,7 +89,9 @@ pub(crate) fn process_sam_data(board_id: &str, state: &mut VehicleState, datapoi
             mapping,
             data_point.channel_type,
             data_point.value,
+            data_point.timestamp,
           );
+          evaluate_redlines(state, mapping);
         }
       }
     }
@@ -95,19 +103,29 @@ pub(crate) fn process_flight_pt_data(
   sample_value: f64,
   mappings: &Mappings,
 ) {
+  let timestamp = current_timestamp();
+
   for mapping in mappings {
     let corresponds = mapping.channel == FLIGHT_PT_MAPPING_CHANNEL
       && mapping
         .sensor_type
         .channel_types()
         .contains(&ChannelType::CurrentLoop)
-      && mapping.board_id == FLIGHT_PT_BOARD_ID;
+      && mapping.board_id == FLIGHT_PT_BOARD_ID
+      && !mapping.is_virtual();
 
     if !corresponds {
       continue;
     }
 
-    apply_sensor_mapping(state, mapping, ChannelType::CurrentLoop, sample_value);
+    apply_sensor_mapping(
+      state,
+      mapping,
+      ChannelType::CurrentLoop,
+      sample_value,
+      timestamp,
+    );
+    evaluate_redlines(state, mapping);
   }
 }
 
@@ -116,22 +134,50 @@ fn apply_sensor_mapping(
   mapping: &NodeMapping,
   channel_type: ChannelType,
   sample_value: f64,
+  timestamp: f64,
 ) {
   let mut text_id = mapping.text_id.clone();
 
+  // some channels, such as the rails, aren't timestamped by their board, so
+  // they are considered sampled when received.
+  let timestamp = if timestamp > 0.0 {
+    timestamp
+  } else {
+    current_timestamp()
+  };
+  let reading = |value, unit| Measurement {
+    value,
+    unit,
+    timestamp,
+    board_id: Some(mapping.board_id.clone()),
+    stale: false,
+  };
+
+  if mapping.sensor_type != SensorType::Valve {
+    if let Some(raw) = state.raw_readings.get_mut(&text_id) {
+      *raw = sample_value;
+    } else {
+      state.raw_readings.insert(text_id.clone(), sample_value);
+    }
+  }
+
+  // a calibration curve replaces the default conversion of any sensor
+  if let Some((calibration, unit)) =
+    mapping.calibration.as_ref().zip(mapping.sensor_type.unit())
+  {
+    let measurement = reading(
+      calibration.apply(sample_value) - mapping.calibrated_offset,
+      unit,
+    );
+
+    store_reading(state, text_id, measurement);
+    return;
+  }
+
   let measurement = match mapping.sensor_type {
-    SensorType::RailVoltage => Measurement {
-      value: sample_value,
-      unit: Unit::Volts,
-    },
-    SensorType::Rtd | SensorType::Tc => Measurement {
-      value: sample_value,
-      unit: Unit::Kelvin,
-    },
-    SensorType::RailCurrent => Measurement {
-      value: sample_value,
-      unit: Unit::Amps,
-    },
+    SensorType::RailVoltage => reading(sample_value, Unit::Volts),
+    SensorType::Rtd | SensorType::Tc => reading(sample_value, Unit::Kelvin),
+    SensorType::RailCurrent => reading(sample_value, Unit::Amps),
     SensorType::Pt => {
       let value;
       let unit;
@@ -151,7 +197,7 @@ fn apply_sensor_mapping(
         unit = Unit::Volts;
       }
 
-      Measurement { value, unit }
+      reading(value, unit)
     }
     SensorType::LoadCell => {
       // if no load cell mappings are set, default to these values
@@ -167,7 +213,7 @@ fn apply_sensor_mapping(
         unit = Unit::Pounds;
       }
 
-      Measurement { value, unit }
+      reading(value, unit)
     }
     SensorType::Valve => {
       let voltage;
@@ -183,10 +229,7 @@ fn apply_sensor_mapping(
             .map(|measurement| measurement.value)
             .unwrap_or(0.0);
 
-          measurement = Measurement {
-            value: sample_value,
-            unit: Unit::Volts,
-          };
+          measurement = reading(sample_value, Unit::Volts);
           text_id = format!("{text_id}_V");
         }
         ChannelType::ValveCurrent => {
@@ -197,10 +240,7 @@ fn apply_sensor_mapping(
             .map(|measurement| measurement.value)
             .unwrap_or(0.0);
 
-          measurement = Measurement {
-            value: sample_value,
-            unit: Unit::Amps,
-          };
+          measurement = reading(sample_value, Unit::Amps);
           text_id = format!("{text_id}_I");
         }
         channel_type => {
@@ -234,6 +274,14 @@ fn apply_sensor_mapping(
     }
   };
 
+  store_reading(state, text_id, measurement);
+}
+
+pub(crate) fn store_reading(
+  state: &mut VehicleState,
+  text_id: String,
+  measurement: Measurement,
+) {
   // replace item without cloning string if already present
   if let Some(existing) = state.sensor_readings.get_mut(&text_id) {
     *existing = measurement;
@@ -242,6 +290,65 @@ fn apply_sensor_mapping(
   }
 }
 
+/// Raises, updates, or clears the redline alarm of a sensor according to its
+/// latest reading and the limits in its mapping. Critical limits take
+/// precedence over warning limits. Stale readings clear the alarm, as they
+/// can't show whether the sensor is within its limits.
+pub(crate) fn evaluate_redlines(state: &mut VehicleState, mapping: &NodeMapping) {
+  let Some(measurement) = state.sensor_readings.get(&mapping.text_id) else {
+    return;
+  };
+
+  if measurement.stale {
+    state.redline_alarms.remove(&mapping.text_id);
+    return;
+  }
+
+  let value = measurement.value;
+
+  let limits = [
+    (AlarmSeverity::Critical, AlarmBound::Low, mapping.critical_low),
+    (AlarmSeverity::Critical, AlarmBound::High, mapping.critical_high),
+    (AlarmSeverity::Warning, AlarmBound::Low, mapping.warning_low),
+    (AlarmSeverity::Warning, AlarmBound::High, mapping.warning_high),
+  ];
+
+  let exceeded = limits.into_iter().find_map(|(severity, bound, limit)| {
+    let limit = limit?;
+    let outside = match bound {
+      AlarmBound::Low => value < limit,
+      AlarmBound::High => value > limit,
+    };
+
+    outside.then_some((severity, bound, limit))
+  });
+
+  let Some((severity, bound, limit)) = exceeded else {
+    state.redline_alarms.remove(&mapping.text_id);
+    return;
+  };
+
+  match state.redline_alarms.get_mut(&mapping.text_id) {
+    Some(alarm) if alarm.severity == severity && alarm.bound == bound => {
+      alarm.value = value;
+    }
+    _ => {
+      let first_exceeded = current_timestamp();
+
+      state.redline_alarms.insert(
+        mapping.text_id.clone(),
+        RedlineAlarm {
+          severity,
+          bound,
+          limit,
+          value,
+          first_exceeded,
+        },
+      );
+    }
+  }
+}
+
 /// Estimates the state of a valve given its voltage, current, and the current
 /// threshold at which it is considered powered.
 fn estimate_valve_state(
@@ -282,3 +389,181 @@ fn estimate_valve_state(
 
   estimated
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn sensor() -> NodeMapping {
+    NodeMapping {
+      text_id: "FUEL_PT".to_string(),
+      board_id: "sam-01".to_string(),
+      warning_low: Some(100.0),
+      warning_high: Some(600.0),
+      critical_low: Some(50.0),
+      critical_high: Some(750.0),
+      abort_on_critical: Some(true),
+      ..Default::default()
+    }
+  }
+
+  fn read(state: &mut VehicleState, value: f64, stale: bool) {
+    state.sensor_readings.insert(
+      "FUEL_PT".to_string(),
+      Measurement {
+        value,
+        unit: Unit::Psi,
+        timestamp: 100.0,
+        board_id: Some("sam-01".to_string()),
+        stale,
+      },
+    );
+    evaluate_redlines(state, &sensor());
+  }
+
+  fn alarm(state: &VehicleState) -> Option<&RedlineAlarm> {
+    state.redline_alarms.get("FUEL_PT")
+  }
+
+  #[test]
+  fn alarms_are_raised_at_the_most_severe_limit_exceeded() {
+    let mut state = VehicleState::new();
+
+    read(&mut state, 300.0, false);
+    assert!(alarm(&state).is_none());
+
+    read(&mut state, 700.0, false);
+    let raised = alarm(&state).unwrap();
+    assert_eq!(raised.severity, AlarmSeverity::Warning);
+    assert_eq!(raised.bound, AlarmBound::High);
+    assert_eq!(raised.limit, 600.0);
+
+    read(&mut state, 20.0, false);
+    let raised = alarm(&state).unwrap();
+    assert_eq!(raised.severity, AlarmSeverity::Critical);
+    assert_eq!(raised.bound, AlarmBound::Low);
+    assert_eq!(raised.limit, 50.0);
+    assert_eq!(raised.value, 20.0);
+  }
+
+  #[test]
+  fn alarms_clear_once_back_within_limits() {
+    let mut state = VehicleState::new();
+
+    read(&mut state, 800.0, false);
+    assert!(alarm(&state).is_some());
+
+    read(&mut state, 300.0, false);
+    assert!(alarm(&state).is_none());
+  }
+
+  #[test]
+  fn first_exceeded_is_kept_until_the_limit_changes() {
+    let mut state = VehicleState::new();
+
+    read(&mut state, 800.0, false);
+    state
+      .redline_alarms
+      .get_mut("FUEL_PT")
+      .unwrap()
+      .first_exceeded = 10.0;
+
+    // still beyond the same limit, so only the value is updated
+    read(&mut state, 900.0, false);
+    assert_eq!(alarm(&state).unwrap().first_exceeded, 10.0);
+    assert_eq!(alarm(&state).unwrap().value, 900.0);
+
+    // dropping to the warning limit restarts the alarm
+    read(&mut state, 700.0, false);
+    assert!(alarm(&state).unwrap().first_exceeded > 10.0);
+
+    // as does leaving the limits and exceeding them again
+    state
+      .redline_alarms
+      .get_mut("FUEL_PT")
+      .unwrap()
+      .first_exceeded = 10.0;
+    read(&mut state, 300.0, false);
+    read(&mut state, 700.0, false);
+    assert!(alarm(&state).unwrap().first_exceeded > 10.0);
+  }
+
+  #[test]
+  fn stale_readings_clear_alarms() {
+    let mut state = VehicleState::new();
+
+    read(&mut state, 800.0, false);
+    assert!(alarm(&state).is_some());
+
+    read(&mut state, 800.0, true);
+    assert!(alarm(&state).is_none());
+  }
+
+  fn sample(value: f64, timestamp: f64) -> SamDataPoint {
+    SamDataPoint::Sensor(common::comm::sam::SensorDataPoint {
+      value,
+      timestamp,
+      channel: 0,
+      channel_type: ChannelType::CurrentLoop,
+    })
+  }
+
+  #[test]
+  fn readings_keep_the_time_their_board_sampled_them() {
+    let mut state = VehicleState::new();
+
+    process_sam_data(
+      "sam-01",
+      &mut state,
+      vec![sample(2.0, 1234.5)],
+      &vec![sensor()],
+    );
+
+    let measurement = &state.sensor_readings["FUEL_PT"];
+    assert_eq!(measurement.value, 2.0);
+    assert_eq!(measurement.timestamp, 1234.5);
+    assert_eq!(measurement.board_id.as_deref(), Some("sam-01"));
+    assert!(!measurement.stale);
+  }
+
+  #[test]
+  fn untimestamped_readings_are_sampled_when_received() {
+    let mut state = VehicleState::new();
+
+    let before = current_timestamp();
+    process_sam_data(
+      "sam-01",
+      &mut state,
+      vec![sample(2.0, 0.0)],
+      &vec![sensor()],
+    );
+    let after = current_timestamp();
+
+    let timestamp = state.sensor_readings["FUEL_PT"].timestamp;
+    assert!(before <= timestamp && timestamp <= after);
+  }
+
+  #[test]
+  fn reporting_again_makes_a_stale_reading_fresh() {
+    let mut state = VehicleState::new();
+    read(&mut state, 2.0, true);
+
+    process_sam_data(
+      "sam-01",
+      &mut state,
+      vec![sample(3.0, 1234.5)],
+      &vec![sensor()],
+    );
+    assert!(!state.sensor_readings["FUEL_PT"].stale);
+
+    // samples from another board leave it alone
+    read(&mut state, 2.0, true);
+    process_sam_data(
+      "sam-02",
+      &mut state,
+      vec![sample(3.0, 1234.5)],
+      &vec![sensor()],
+    );
+    assert!(state.sensor_readings["FUEL_PT"].stale);
+  }
+}
@@ -0,0 +1,330 @@
+use crate::{
+  sequence::{self, Sequences},
+  Mappings,
+};
+use common::{
+  comm::{Sequence, Trigger, TriggerState, VehicleState},
+  condition::{self, Expr},
+};
+use std::{
+  collections::HashMap,
+  time::{SystemTime, UNIX_EPOCH},
+};
+
+/// A trigger received from Servo along with its parsed condition and the
+/// result of its last evaluation, used for rising-edge detection.
+struct RegisteredTrigger {
+  trigger: Trigger,
+  condition: Result<Expr, condition::ConditionError>,
+  was_met: bool,
+}
+
+/// The set of triggers known to the flight computer, keyed by name.
+#[derive(Default)]
+pub(crate) struct Triggers {
+  registry: HashMap<String, RegisteredTrigger>,
+}
+
+impl Triggers {
+  /// Registers a new trigger or replaces an existing trigger of the same name.
+  ///
+  /// Replacing a trigger resets its edge detection, so a trigger that is
+  /// re-activated while its condition already holds will not fire until the
+  /// condition becomes false and then true again.
+  ///
+  /// A trigger with an invalid condition is still registered, so that the
+  /// error shows in telemetry, but the error is also returned.
+  pub(crate) fn register(
+    &mut self,
+    trigger: Trigger,
+    state: &mut VehicleState,
+  ) -> Result<(), String> {
+    let condition = condition::parse(&trigger.condition);
+
+    let result = condition.as_ref().map(|_| ()).map_err(|e| {
+      format!(
+        "The condition of trigger '{}' is invalid and it will never fire: {e}",
+        trigger.name
+      )
+    });
+
+    let history = state
+      .triggers
+      .get(&trigger.name)
+      .map(|status| (status.fire_count, status.last_fired));
+
+    state.triggers.insert(
+      trigger.name.clone(),
+      TriggerState {
+        active: trigger.active,
+        condition_met: false,
+        fire_count: history.map_or(0, |(count, _)| count),
+        last_fired: history.and_then(|(_, last)| last),
+        error: condition.as_ref().err().map(ToString::to_string),
+      },
+    );
+
+    // assume the condition was already met so that the first evaluation only
+    // establishes a baseline. triggers are re-sent every time Servo connects,
+    // and that must not fire a trigger whose condition has held all along.
+    self.registry.insert(
+      trigger.name.clone(),
+      RegisteredTrigger {
+        trigger,
+        condition,
+        was_met: true,
+      },
+    );
+
+    result
+  }
+
+  /// Forgets a trigger entirely, removing it from telemetry as well.
+  pub(crate) fn delete(
+    &mut self,
+    name: &str,
+    state: &mut VehicleState,
+  ) -> Result<(), String> {
+    state.triggers.remove(name);
+
+    match self.registry.remove(name) {
+      Some(_) => Ok(()),
+      None => Err(format!("A trigger named '{name}' isn't registered.")),
+    }
+  }
+
+  /// Evaluates every active trigger against the current vehicle state and
+  /// runs the script of each trigger whose condition has just become true.
+  pub(crate) fn evaluate(
+    &mut self,
+    mappings: &Mappings,
+    state: &mut VehicleState,
+    sequences: &mut Sequences,
+  ) {
+    for (name, registered) in &mut self.registry {
+      if !registered.trigger.active {
+        continue;
+      }
+
+      let Ok(ref expr) = registered.condition else {
+        continue;
+      };
+
+      let (is_met, error) = match expr.is_met(state) {
+        Ok(is_met) => (is_met, None),
+        Err(e) => (false, Some(e.to_string())),
+      };
+
+      let rising_edge = is_met && !registered.was_met;
+
+      // a condition that can't be evaluated right now (e.g. a sensor that
+      // hasn't reported yet) neither arms nor disarms the trigger.
+      if error.is_none() {
+        registered.was_met = is_met;
+      }
+
+      if rising_edge {
+        println!("Trigger '{name}' fired, running its script.");
+
+        let sequence = Sequence {
+          name: format!("trigger:{name}"),
+          script: registered.trigger.script.clone(),
+          arguments: HashMap::new(),
+        };
+        if let Err(e) = sequence::execute(mappings, &sequence, sequences) {
+          eprintln!("Couldn't run the script of trigger '{name}': {e}");
+        }
+      }
+
+      if let Some(status) = state.triggers.get_mut(name) {
+        status.condition_met = is_met;
+        status.error = error;
+
+        if rising_edge {
+          status.fire_count += 1;
+          status.last_fired = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .ok()
+            .map(|time| time.as_secs_f64());
+        }
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::common_so::materialize_common_so;
+  use common::comm::{sam::Unit, FlightStatusMessage, Measurement};
+
+  fn setup() -> (Triggers, VehicleState, Sequences) {
+    // scripts are really run when triggers fire
+    materialize_common_so().unwrap();
+    (Triggers::default(), VehicleState::new(), Sequences::new())
+  }
+
+  fn trigger(condition: &str, active: bool) -> Trigger {
+    Trigger {
+      name: "vent".to_string(),
+      condition: condition.to_string(),
+      script: "pass".to_string(),
+      active,
+    }
+  }
+
+  fn read(state: &mut VehicleState, value: f64) {
+    state.sensor_readings.insert(
+      "PT1".to_string(),
+      Measurement {
+        value,
+        unit: Unit::Psi,
+        timestamp: 0.0,
+        board_id: Some("sam-01".to_string()),
+        stale: false,
+      },
+    );
+  }
+
+  /// Sets PT1 and evaluates every trigger, returning how many times the vent
+  /// trigger has fired so far.
+  fn step(
+    triggers: &mut Triggers,
+    state: &mut VehicleState,
+    sequences: &mut Sequences,
+    value: f64,
+  ) -> u32 {
+    read(state, value);
+    triggers.evaluate(&Vec::new(), state, sequences);
+    state.triggers["vent"].fire_count
+  }
+
+  #[test]
+  fn triggers_fire_once_per_rising_edge() {
+    let (mut triggers, mut state, mut sequences) = setup();
+
+    triggers
+      .register(trigger("PT1 > 500", true), &mut state)
+      .unwrap();
+
+    assert_eq!(step(&mut triggers, &mut state, &mut sequences, 0.0), 0);
+    assert_eq!(step(&mut triggers, &mut state, &mut sequences, 600.0), 1);
+    assert!(state.triggers["vent"].condition_met);
+    assert!(state.triggers["vent"].last_fired.is_some());
+
+    let started = sequence::poll(&mut sequences, &mut state);
+    assert!(started.iter().any(|message| matches!(
+      message,
+      FlightStatusMessage::SequenceStarted(run) if run.name == "trigger:vent"
+    )));
+
+    // staying above the limit doesn't fire again
+    assert_eq!(step(&mut triggers, &mut state, &mut sequences, 700.0), 1);
+    assert_eq!(step(&mut triggers, &mut state, &mut sequences, 600.0), 1);
+
+    // but dropping below and rising again does
+    assert_eq!(step(&mut triggers, &mut state, &mut sequences, 100.0), 1);
+    assert!(!state.triggers["vent"].condition_met);
+    assert_eq!(step(&mut triggers, &mut state, &mut sequences, 600.0), 2);
+
+    sequence::kill_all(&mut sequences);
+  }
+
+  #[test]
+  fn triggers_whose_condition_already_holds_wait_for_the_next_edge() {
+    let (mut triggers, mut state, mut sequences) = setup();
+
+    read(&mut state, 600.0);
+    triggers
+      .register(trigger("PT1 > 500", true), &mut state)
+      .unwrap();
+
+    assert_eq!(step(&mut triggers, &mut state, &mut sequences, 600.0), 0);
+    assert_eq!(step(&mut triggers, &mut state, &mut sequences, 0.0), 0);
+    assert_eq!(step(&mut triggers, &mut state, &mut sequences, 600.0), 1);
+
+    sequence::kill_all(&mut sequences);
+  }
+
+  #[test]
+  fn deactivated_triggers_stop_firing() {
+    let (mut triggers, mut state, mut sequences) = setup();
+
+    triggers
+      .register(trigger("PT1 > 500", true), &mut state)
+      .unwrap();
+    step(&mut triggers, &mut state, &mut sequences, 0.0);
+    assert_eq!(step(&mut triggers, &mut state, &mut sequences, 600.0), 1);
+
+    triggers
+      .register(trigger("PT1 > 500", false), &mut state)
+      .unwrap();
+    assert!(!state.triggers["vent"].active);
+
+    for value in [0.0, 600.0, 0.0, 600.0] {
+      assert_eq!(step(&mut triggers, &mut state, &mut sequences, value), 1);
+    }
+
+    // reactivating keeps the history and waits for a fresh edge
+    triggers
+      .register(trigger("PT1 > 500", true), &mut state)
+      .unwrap();
+    assert_eq!(step(&mut triggers, &mut state, &mut sequences, 600.0), 1);
+    assert_eq!(step(&mut triggers, &mut state, &mut sequences, 0.0), 1);
+    assert_eq!(step(&mut triggers, &mut state, &mut sequences, 600.0), 2);
+
+    sequence::kill_all(&mut sequences);
+  }
+
+  #[test]
+  fn deleted_triggers_leave_telemetry() {
+    let (mut triggers, mut state, mut sequences) = setup();
+
+    triggers
+      .register(trigger("PT1 > 500", true), &mut state)
+      .unwrap();
+    assert!(triggers.delete("vent", &mut state).is_ok());
+    assert!(!state.triggers.contains_key("vent"));
+    assert!(triggers.delete("vent", &mut state).is_err());
+
+    read(&mut state, 0.0);
+    triggers.evaluate(&Vec::new(), &mut state, &mut sequences);
+    read(&mut state, 600.0);
+    triggers.evaluate(&Vec::new(), &mut state, &mut sequences);
+    assert!(sequence::poll(&mut sequences, &mut state).is_empty());
+  }
+
+  #[test]
+  fn invalid_conditions_are_reported_and_never_fire() {
+    let (mut triggers, mut state, mut sequences) = setup();
+
+    assert!(triggers
+      .register(trigger("PT1 >", true), &mut state)
+      .is_err());
+    assert!(state.triggers["vent"].error.is_some());
+
+    assert_eq!(step(&mut triggers, &mut state, &mut sequences, 0.0), 0);
+    assert_eq!(step(&mut triggers, &mut state, &mut sequences, 600.0), 0);
+  }
+
+  #[test]
+  fn conditions_that_cannot_be_evaluated_neither_arm_nor_disarm() {
+    let (mut triggers, mut state, mut sequences) = setup();
+
+    triggers
+      .register(trigger("PT1 > 500", true), &mut state)
+      .unwrap();
+    step(&mut triggers, &mut state, &mut sequences, 0.0);
+
+    // PT1 disappears, so the trigger reports the error but stays armed
+    state.sensor_readings.remove("PT1");
+    triggers.evaluate(&Vec::new(), &mut state, &mut sequences);
+    assert!(state.triggers["vent"].error.is_some());
+    assert_eq!(state.triggers["vent"].fire_count, 0);
+
+    assert_eq!(step(&mut triggers, &mut state, &mut sequences, 600.0), 1);
+    assert!(state.triggers["vent"].error.is_none());
+
+    sequence::kill_all(&mut sequences);
+  }
+}
@@ -0,0 +1,264 @@
+use crate::{file_logger::current_timestamp, state, Mappings};
+use common::{
+  comm::{sam::Unit, Measurement, NodeMapping, VehicleState},
+  condition::{self, Expr},
+};
+
+/// A sensor computed every loop from an expression over other sensors.
+struct VirtualSensor {
+  mapping: NodeMapping,
+  unit: Unit,
+  expression: Expr,
+
+  /// Every name the expression refers to, used to find the sensors it is
+  /// computed from.
+  inputs: Vec<String>,
+
+  /// Why the expression last failed to evaluate, so that a persistent failure
+  /// is only printed once.
+  error: Option<String>,
+}
+
+/// The virtual sensors in the current mappings, ordered so that each one is
+/// evaluated after any other virtual sensors it reads.
+#[derive(Default)]
+pub(crate) struct VirtualSensors {
+  sensors: Vec<VirtualSensor>,
+}
+
+impl VirtualSensors {
+  /// Parses the expressions of the virtual sensors in a set of mappings,
+  /// failing if any is invalid or if virtual sensors read each other in a
+  /// cycle.
+  pub(crate) fn new(mappings: &Mappings) -> Result<Self, String> {
+    let mut sensors = Vec::new();
+
+    for (mapping, expression) in condition::order_virtual_sensors(mappings)? {
+      let Some(unit) = mapping.sensor_type.unit() else {
+        return Err(format!(
+          "Virtual sensor '{}' can't be a valve.",
+          mapping.text_id
+        ));
+      };
+
+      let mut inputs = Vec::new();
+      condition::names(&expression, &mut inputs);
+      let inputs = inputs.into_iter().map(str::to_owned).collect();
+
+      sensors.push(VirtualSensor {
+        mapping: mapping.clone(),
+        unit,
+        expression,
+        inputs,
+        error: None,
+      });
+    }
+
+    Ok(VirtualSensors { sensors })
+  }
+
+  /// Computes every virtual sensor from the latest readings, publishing each
+  /// as a sensor reading and checking it against its redlines. A virtual
+  /// sensor is as old as the oldest sensor it is computed from, and is stale
+  /// if any of them are.
+  pub(crate) fn evaluate(&mut self, state: &mut VehicleState) {
+    for sensor in &mut self.sensors {
+      let value = sensor
+        .expression
+        .evaluate(state)
+        .and_then(|value| value.number());
+
+      match value {
+        Ok(value) => {
+          sensor.error = None;
+
+          let inputs: Vec<&Measurement> = sensor
+            .inputs
+            .iter()
+            .filter_map(|name| state.sensor_readings.get(name))
+            .collect();
+
+          let measurement = Measurement {
+            value: value - sensor.mapping.calibrated_offset,
+            unit: sensor.unit,
+            timestamp: inputs
+              .iter()
+              .map(|input| input.timestamp)
+              .reduce(f64::min)
+              .unwrap_or_else(current_timestamp),
+            board_id: None,
+            stale: inputs.iter().any(|input| input.stale),
+          };
+
+          state::store_reading(
+            state,
+            sensor.mapping.text_id.clone(),
+            measurement,
+          );
+          state::evaluate_redlines(state, &sensor.mapping);
+        }
+        Err(e) => {
+          let e = e.to_string();
+
+          if sensor.error.as_ref() != Some(&e) {
+            eprintln!(
+              "Virtual sensor '{}' couldn't be evaluated: {e}",
+              sensor.mapping.text_id
+            );
+          }
+
+          sensor.error = Some(e);
+        }
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::{thread, time::Duration};
+
+  fn sensor(text_id: &str, expression: Option<&str>) -> NodeMapping {
+    NodeMapping {
+      text_id: text_id.to_string(),
+      board_id: "sam-01".to_string(),
+      expression: expression.map(str::to_string),
+      ..Default::default()
+    }
+  }
+
+  fn read(state: &mut VehicleState, text_id: &str, value: f64) {
+    state.sensor_readings.insert(
+      text_id.to_string(),
+      Measurement {
+        value,
+        unit: Unit::Psi,
+        timestamp: 100.0,
+        board_id: Some("sam-01".to_string()),
+        stale: false,
+      },
+    );
+  }
+
+  fn value(state: &VehicleState, text_id: &str) -> f64 {
+    state.sensor_readings[text_id].value
+  }
+
+  #[test]
+  fn computes_min_max_and_abs() {
+    let mappings = vec![
+      sensor("FUEL_PT", None),
+      sensor("OX_PT", None),
+      sensor("HIGHEST", Some("max(FUEL_PT, OX_PT, 50)")),
+      sensor("LOWEST", Some("min(FUEL_PT, OX_PT)")),
+      sensor("SPREAD", Some("abs(FUEL_PT - OX_PT)")),
+    ];
+    let mut sensors = VirtualSensors::new(&mappings).unwrap();
+    let mut state = VehicleState::new();
+
+    read(&mut state, "FUEL_PT", 30.0);
+    read(&mut state, "OX_PT", 45.0);
+    sensors.evaluate(&mut state);
+
+    assert_eq!(value(&state, "HIGHEST"), 50.0);
+    assert_eq!(value(&state, "LOWEST"), 30.0);
+    assert_eq!(value(&state, "SPREAD"), 15.0);
+  }
+
+  #[test]
+  fn moving_average_covers_its_window() {
+    let mappings = vec![
+      sensor("FUEL_PT", None),
+      sensor("SMOOTHED", Some("moving_average(FUEL_PT, 10s)")),
+      sensor("RECENT", Some("moving_average(FUEL_PT, 20ms)")),
+    ];
+    let mut sensors = VirtualSensors::new(&mappings).unwrap();
+    let mut state = VehicleState::new();
+
+    for reading in [100.0, 200.0, 300.0] {
+      read(&mut state, "FUEL_PT", reading);
+      sensors.evaluate(&mut state);
+    }
+
+    assert_eq!(value(&state, "SMOOTHED"), 200.0);
+    assert_eq!(value(&state, "RECENT"), 200.0);
+
+    // samples older than the window are dropped.
+    thread::sleep(Duration::from_millis(30));
+    read(&mut state, "FUEL_PT", 600.0);
+    sensors.evaluate(&mut state);
+
+    assert_eq!(value(&state, "SMOOTHED"), 300.0);
+    assert_eq!(value(&state, "RECENT"), 600.0);
+  }
+
+  #[test]
+  fn virtual_sensors_are_evaluated_after_those_they_read() {
+    let mappings = vec![
+      sensor("DOUBLE_DP", Some("2 * INJ_DP")),
+      sensor("INJ_DP", Some("FUEL_PT - INJ_PT")),
+      sensor("FUEL_PT", None),
+      sensor("INJ_PT", None),
+    ];
+    let mut sensors = VirtualSensors::new(&mappings).unwrap();
+    let mut state = VehicleState::new();
+
+    read(&mut state, "FUEL_PT", 500.0);
+    read(&mut state, "INJ_PT", 420.0);
+    sensors.evaluate(&mut state);
+
+    assert_eq!(value(&state, "INJ_DP"), 80.0);
+    assert_eq!(value(&state, "DOUBLE_DP"), 160.0);
+  }
+
+  #[test]
+  fn virtual_sensors_are_as_stale_as_their_inputs() {
+    let mappings = vec![
+      sensor("FUEL_PT", None),
+      sensor("INJ_PT", None),
+      sensor("INJ_DP", Some("FUEL_PT - INJ_PT")),
+    ];
+    let mut sensors = VirtualSensors::new(&mappings).unwrap();
+    let mut state = VehicleState::new();
+
+    read(&mut state, "FUEL_PT", 500.0);
+    read(&mut state, "INJ_PT", 420.0);
+    let injector = state.sensor_readings.get_mut("INJ_PT").unwrap();
+    injector.stale = true;
+    injector.timestamp = 90.0;
+    sensors.evaluate(&mut state);
+
+    let measurement = &state.sensor_readings["INJ_DP"];
+    assert!(measurement.stale);
+    assert_eq!(measurement.timestamp, 90.0);
+    assert_eq!(measurement.board_id, None);
+  }
+
+  #[test]
+  fn cycles_between_virtual_sensors_are_rejected() {
+    let mappings = vec![
+      sensor("FUEL_PT", None),
+      sensor("A", Some("FUEL_PT + B")),
+      sensor("B", Some("A / 2")),
+    ];
+    let error = VirtualSensors::new(&mappings).err().unwrap();
+    assert!(error.contains("cycle"), "{error}");
+
+    let mappings = vec![sensor("A", Some("A + 1"))];
+    assert!(VirtualSensors::new(&mappings).is_err());
+  }
+
+  #[test]
+  fn invalid_expressions_are_rejected() {
+    for expression in ["FUEL_PT +", "FUEL_TP * 2", "moving_average(FUEL_PT)"] {
+      let mappings =
+        vec![sensor("FUEL_PT", None), sensor("DERIVED", Some(expression))];
+
+      assert!(
+        VirtualSensors::new(&mappings).is_err(),
+        "'{expression}' should be rejected"
+      );
+    }
+  }
+}
@@ -1,11 +1,12 @@
 use common::comm::{
   flight::DataMessage,
-  sam::{SamControlMessage, SamDataPoint}, ValveAction,
+  sam::{SamControlRequest, SamDataPoint}, ValveAction,
 };
 use hostname::get;
 use jeflog::{pass, warn};
 use std::{
   borrow::Cow,
+  collections::VecDeque,
   net::{SocketAddr, ToSocketAddrs, UdpSocket},
   time::{Duration, Instant},
 };
@@ -18,6 +19,9 @@ use std::net::Ipv4Addr;
 // const FC_ADDR: &str = "flight";
 const COMMAND_PORT: u16 = 8378;
 pub const HEARTBEAT_TIME_LIMIT: Duration = Duration::from_millis(1000);
+// how many recently executed request sequence numbers are remembered so that
+// retransmissions of them are acknowledged without being executed again
+const RECENT_REQUESTS_KEPT: usize = 32;
 
 pub fn get_hostname() -> String {
   loop {
@@ -294,7 +298,7 @@ pub fn check_heartbeat(data_socket: &UdpSocket, command_socket: &UdpSocket, time
   // for when it must be executed later on
   match command_socket.peek_from(&mut command_buffer) {
     Ok((size, _)) => {
-      match postcard::from_bytes::<SamControlMessage>(&command_buffer[..size]) {
+      match postcard::from_bytes::<SamControlRequest>(&command_buffer[..size]) {
         Ok(_) => return (Instant::now(), false), // don't care about contents
 
         Err(e) => warn!("Could not deserialize command from FC ({e}), continuing...")
@@ -310,7 +314,15 @@ pub fn check_heartbeat(data_socket: &UdpSocket, command_socket: &UdpSocket, time
   (timer, false)
 }
 
-pub fn check_and_execute(command_socket: &UdpSocket, abort_info: &mut AbortInfo, abort_valve_states: &mut Vec<(ValveAction, bool)>) {
+pub fn check_and_execute(
+  command_socket: &UdpSocket,
+  data_socket: &UdpSocket,
+  fc_address: &SocketAddr,
+  hostname: &str,
+  recent_requests: &mut VecDeque<u32>,
+  abort_info: &mut AbortInfo,
+  abort_valve_states: &mut Vec<(ValveAction, bool)>,
+) {
   // where to store the commands recieved from the FC
   let mut buf: [u8; 1024] = [0; 1024];
 
@@ -335,16 +347,48 @@ pub fn check_and_execute(command_socket: &UdpSocket, abort_info: &mut AbortInfo,
       Err(_) => break, // no data in buffer
     };
 
-    let command = match postcard::from_bytes::<SamControlMessage>(&buf[..size]) {
-      Ok(command) => command,
+    let request = match postcard::from_bytes::<SamControlRequest>(&buf[..size]) {
+      Ok(request) => request,
       Err(e) => {
         warn!("Command was recieved but could not be deserialized ({e}).");
         break;
       }
     };
 
+    // acknowledge every request, even a repeated one, as the FC only
+    // retransmits when it missed our previous acknowledgement
+    send_ack(data_socket, fc_address, hostname, request.sequence);
+
+    // executing a retransmitted abort again would restart its stage timers
+    if recent_requests.contains(&request.sequence) {
+      continue;
+    }
+
+    if recent_requests.len() == RECENT_REQUESTS_KEPT {
+      recent_requests.pop_front();
+    }
+    recent_requests.push_back(request.sequence);
+
     pass!("Executing command...");
     // execute the command
-    execute(command, abort_info, abort_valve_states);
+    execute(request.message, abort_info, abort_valve_states);
+  }
+}
+
+// tells the FC that the request with the given sequence number was received
+fn send_ack(socket: &UdpSocket, address: &SocketAddr, hostname: &str, sequence: u32) {
+  let mut buffer: [u8; 64] = [0; 64];
+  let ack = DataMessage::SamAck(hostname.to_string(), sequence);
+
+  let serialized = match postcard::to_slice(&ack, &mut buffer) {
+    Ok(serialized) => serialized,
+    Err(e) => {
+      warn!("Could not serialize acknowledgement ({e}), continuing...");
+      return;
+    }
+  };
+
+  if let Err(e) = socket.send_to(serialized, address) {
+    warn!("Could not send acknowledgement ({e}), continuing...");
   }
 }
@@ -20,6 +20,7 @@ use ads124s06::ADC as ADC_24_bit;
 use common::comm::{gpio::PinValue, sam::SamDataPoint, ADCFamily, ValveAction};
 use jeflog::fail;
 use std::{
+  collections::VecDeque,
   net::{SocketAddr, UdpSocket},
   time::Instant,
 };
@@ -56,6 +57,8 @@ pub struct MainLoopData {
   ambient_temps: Option<Vec<f64>>,
   abort_info: AbortInfo,
   pub abort_valve_states: Vec<(ValveAction, bool)>,
+  // sequence numbers of the most recently executed requests from the FC
+  recent_requests: VecDeque<u32>,
 }
 
 /// when we enter the abort state. only stay in this state once, and immediately attempt to reconnect. 
@@ -166,6 +169,7 @@ fn connect(mut data: ConnectData) -> State {
     },
     abort_info,
     abort_valve_states: data.abort_valve_states,
+    recent_requests: VecDeque::new(),
   })
 }
 
@@ -189,7 +193,15 @@ fn main_loop(mut data: MainLoopData) -> State {
   }
 
   // if there are commands, do them!
-  check_and_execute(&data.my_command_socket, &mut data.abort_info, &mut data.abort_valve_states);
+  check_and_execute(
+    &data.my_command_socket,
+    &data.my_data_socket,
+    &data.fc_address,
+    &data.hostname,
+    &mut data.recent_requests,
+    &mut data.abort_info,
+    &mut data.abort_valve_states,
+  );
 
   // check up on abort valve timers if we have received an abort an all valves have not been aborted
   if data.abort_info.received_abort && !data.abort_info.all_valves_aborted {
@@ -111,7 +111,14 @@ fn main() -> anyhow::Result<()> {
     .subcommand(
       Command::new("run")
         .about("Sends a Python sequence to be run on the flight computer.")
-        .arg(Arg::new("path").required(true)),
+        .arg(Arg::new("path").required(true))
+        .arg(
+          Arg::new("arg")
+            .long("arg")
+            .short('a')
+            .value_name("NAME=VALUE")
+            .action(ArgAction::Append),
+        ),
     )
     .subcommand(
       Command::new("serve")
@@ -140,6 +147,13 @@ fn main() -> anyhow::Result<()> {
           Arg::new("sequence_path")
             .value_parser(clap::value_parser!(PathBuf))
             .required(true),
+        )
+        .arg(
+          Arg::new("parameter")
+            .long("parameter")
+            .short('p')
+            .value_name("NAME:KIND[=DEFAULT]")
+            .action(ArgAction::Append),
         ),
     )
     .get_matches();
@@ -157,14 +171,18 @@ fn main() -> anyhow::Result<()> {
       )?;
     }
     Some(("locate", args)) => tool::locate(args)?,
-    Some(("run", args)) => tool::run(args.get_one::<String>("path").unwrap())?,
+    Some(("run", args)) => tool::run(
+      args.get_one::<String>("path").unwrap(),
+      args.get_many::<String>("arg").unwrap_or_default(),
+    )?,
     Some(("serve", args)) => tool::serve(&servo_dir, args)?,
     Some(("sql", args)) => {
       tool::sql(args.get_one::<String>("raw_sql").unwrap())?
     }
-    Some(("upload", args)) => {
-      tool::upload(args.get_one::<PathBuf>("sequence_path").unwrap())?
-    }
+    Some(("upload", args)) => tool::upload(
+      args.get_one::<PathBuf>("sequence_path").unwrap(),
+      args.get_many::<String>("parameter").unwrap_or_default(),
+    )?,
     _ => {
       fail!("Invalid command. Please check the command you entered.");
       process::exit(1);
@@ -0,0 +1,2 @@
+ALTER TABLE NodeMappings DROP fault_timeout;
+ALTER TABLE NodeMappings DROP abort_on_fault;
@@ -0,0 +1,2 @@
+ALTER TABLE NodeMappings ADD fault_timeout REAL;
+ALTER TABLE NodeMappings ADD abort_on_fault INTEGER;
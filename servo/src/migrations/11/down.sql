@@ -0,0 +1,5 @@
+ALTER TABLE NodeMappings DROP warning_low;
+ALTER TABLE NodeMappings DROP warning_high;
+ALTER TABLE NodeMappings DROP critical_low;
+ALTER TABLE NodeMappings DROP critical_high;
+ALTER TABLE NodeMappings DROP abort_on_critical;
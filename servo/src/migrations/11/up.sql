@@ -0,0 +1,5 @@
+ALTER TABLE NodeMappings ADD warning_low REAL;
+ALTER TABLE NodeMappings ADD warning_high REAL;
+ALTER TABLE NodeMappings ADD critical_low REAL;
+ALTER TABLE NodeMappings ADD critical_high REAL;
+ALTER TABLE NodeMappings ADD abort_on_critical INTEGER;
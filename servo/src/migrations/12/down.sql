@@ -0,0 +1 @@
+ALTER TABLE NodeMappings DROP calibration;
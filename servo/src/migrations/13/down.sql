@@ -0,0 +1,2 @@
+DROP TABLE CalibrationOffsets;
+DROP TABLE Calibrations;
@@ -0,0 +1,19 @@
+CREATE TABLE Calibrations (
+	calibration_id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+	window_seconds REAL NOT NULL CHECK(window_seconds > 0),
+	recorded_at REAL NOT NULL DEFAULT(unixepoch('now', 'subsec')) CHECK(recorded_at > 0),
+	reverted_at REAL
+);
+
+CREATE TABLE CalibrationOffsets (
+	calibration_id INTEGER NOT NULL REFERENCES Calibrations(calibration_id) ON DELETE CASCADE,
+	configuration_id TEXT NOT NULL,
+	text_id TEXT NOT NULL,
+	previous_offset REAL NOT NULL,
+	new_offset REAL NOT NULL,
+	mean REAL NOT NULL,
+	standard_deviation REAL NOT NULL,
+	samples INTEGER NOT NULL,
+
+	PRIMARY KEY (calibration_id, text_id)
+);
@@ -0,0 +1 @@
+ALTER TABLE NodeMappings ADD expression TEXT;
@@ -0,0 +1,2 @@
+ALTER TABLE NodeMappings DROP radio;
+ALTER TABLE NodeMappings DROP radio_priority;
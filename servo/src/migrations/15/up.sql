@@ -0,0 +1,8 @@
+ALTER TABLE NodeMappings ADD radio BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE NodeMappings ADD radio_priority INTEGER NOT NULL DEFAULT 0;
+
+-- radio telemetry used to include the flight computer and every SAM mounted
+-- on the vehicle, whose hostnames begin with sam-2 or sam-3.
+UPDATE NodeMappings
+SET radio = TRUE
+WHERE board_id = 'flight' OR board_id LIKE 'sam-2%' OR board_id LIKE 'sam-3%';
@@ -0,0 +1 @@
+ALTER TABLE Sequences DROP parameters;
@@ -0,0 +1,2 @@
+-- the typed parameters each sequence declares, as a JSON array.
+ALTER TABLE Sequences ADD parameters TEXT NOT NULL DEFAULT '[]';
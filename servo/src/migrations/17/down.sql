@@ -0,0 +1 @@
+ALTER TABLE NodeMappings DROP critical_persistence;
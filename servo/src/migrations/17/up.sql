@@ -0,0 +1 @@
+ALTER TABLE NodeMappings ADD critical_persistence REAL;
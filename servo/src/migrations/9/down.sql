@@ -0,0 +1 @@
+DROP TABLE SequenceRuns;
\ No newline at end of file
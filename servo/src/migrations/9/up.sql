@@ -0,0 +1,11 @@
+CREATE TABLE SequenceRuns (
+	run_id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+	name TEXT NOT NULL,
+	pid INTEGER NOT NULL,
+	started_at REAL NOT NULL,
+	finished_at REAL,
+	exit_code INTEGER,
+	signal INTEGER,
+	output BLOB NOT NULL,
+	truncated BOOLEAN NOT NULL DEFAULT FALSE
+);
@@ -0,0 +1,771 @@
+use common::comm::Calibration;
+use rusqlite::{params, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, error::Error, fmt};
+
+use super::Database;
+
+/// The highest degree of polynomial that may be fit. Higher degrees fit the
+/// reference points more closely but oscillate wildly between them.
+pub const MAX_POLYNOMIAL_DEGREE: usize = 5;
+
+/// A raw reading of a sensor paired with the true value of the quantity it
+/// measured at the time, such as the pressure on a reference gauge.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub struct CalibrationPoint {
+  /// The value reported by the board, before any conversion.
+  pub raw: f64,
+
+  /// The value the sensor should have read.
+  pub reference: f64,
+}
+
+/// The kind of curve to fit through calibration points.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case", tag = "kind")]
+pub enum CalibrationModel {
+  /// A least-squares polynomial of the given degree.
+  Polynomial {
+    /// The degree of the polynomial, where 1 is a straight line.
+    degree: usize,
+  },
+
+  /// Straight lines through every point.
+  PiecewiseLinear,
+}
+
+/// Why a curve couldn't be fit through a set of calibration points.
+#[derive(Clone, Debug, PartialEq)]
+pub enum FitError {
+  /// There are fewer points with distinct raw values than the model needs.
+  NotEnoughPoints {
+    /// The number of points with distinct raw values needed.
+    needed: usize,
+
+    /// The number of points with distinct raw values captured.
+    found: usize,
+  },
+
+  /// The requested polynomial degree is above `MAX_POLYNOMIAL_DEGREE`.
+  DegreeTooHigh(usize),
+
+  /// A point has a raw or reference value that isn't a finite number.
+  NotFinite,
+}
+
+impl fmt::Display for FitError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::NotEnoughPoints { needed, found } => write!(
+        f,
+        "fitting needs {needed} points with distinct raw values, but only \
+        {found} were captured"
+      ),
+      Self::DegreeTooHigh(degree) => write!(
+        f,
+        "polynomials of degree {degree} can't be fit, the highest allowed is \
+        {MAX_POLYNOMIAL_DEGREE}"
+      ),
+      Self::NotFinite => write!(f, "a captured point is not a finite number"),
+    }
+  }
+}
+
+impl Error for FitError {}
+
+/// The calibration points captured so far for each sensor, keyed by name.
+#[derive(Clone, Debug, Default)]
+pub struct CalibrationPoints {
+  points: HashMap<String, Vec<CalibrationPoint>>,
+}
+
+impl CalibrationPoints {
+  /// Adds a point for a sensor, returning every point captured for it.
+  pub fn add(
+    &mut self,
+    sensor: &str,
+    point: CalibrationPoint,
+  ) -> &[CalibrationPoint] {
+    let points = self.points.entry(sensor.to_string()).or_default();
+    points.push(point);
+    points
+  }
+
+  /// Returns the points captured for a sensor.
+  pub fn get(&self, sensor: &str) -> &[CalibrationPoint] {
+    self.points.get(sensor).map(Vec::as_slice).unwrap_or_default()
+  }
+
+  /// Returns the points captured for every sensor.
+  pub fn all(&self) -> &HashMap<String, Vec<CalibrationPoint>> {
+    &self.points
+  }
+
+  /// Discards the points captured for a sensor.
+  pub fn clear(&mut self, sensor: &str) {
+    self.points.remove(sensor);
+  }
+}
+
+/// The spread of the readings of a sensor over a zeroing window.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub struct SampleStatistics {
+  /// The average of the readings.
+  pub mean: f64,
+
+  /// The sample standard deviation of the readings, or zero if there was only
+  /// a single reading.
+  pub standard_deviation: f64,
+
+  /// The number of readings taken.
+  pub samples: u32,
+}
+
+impl SampleStatistics {
+  /// Summarizes a set of readings, returning `None` if there are none.
+  pub fn of(readings: &[f64]) -> Option<Self> {
+    if readings.is_empty() {
+      return None;
+    }
+
+    let count = readings.len() as f64;
+    let mean = readings.iter().sum::<f64>() / count;
+
+    let standard_deviation = if readings.len() < 2 {
+      0.0
+    } else {
+      let squares: f64 =
+        readings.iter().map(|reading| (reading - mean).powi(2)).sum();
+
+      (squares / (count - 1.0)).sqrt()
+    };
+
+    Some(SampleStatistics {
+      mean,
+      standard_deviation,
+      samples: readings.len() as u32,
+    })
+  }
+}
+
+/// The change a zero calibration made to the offset of a single sensor.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub struct ZeroedSensor {
+  /// The offset of the sensor before the calibration.
+  pub previous_offset: f64,
+
+  /// The offset of the sensor after the calibration.
+  pub offset: f64,
+
+  /// The readings the new offset was computed from, which were taken with the
+  /// previous offset applied.
+  #[serde(flatten)]
+  pub statistics: SampleStatistics,
+}
+
+/// A zero calibration of one or more sensors, as kept in history.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct ZeroCalibration {
+  /// The unique ID of the calibration.
+  pub calibration_id: i64,
+
+  /// The configuration whose mappings were calibrated.
+  pub configuration_id: String,
+
+  /// The number of seconds readings were averaged over.
+  pub window: f64,
+
+  /// When the calibration was made, in seconds since the Unix epoch.
+  pub recorded_at: f64,
+
+  /// When the calibration was reverted, if it has been.
+  pub reverted_at: Option<f64>,
+
+  /// The offset changes made to each calibrated sensor.
+  pub sensors: HashMap<String, ZeroedSensor>,
+}
+
+/// Fits a calibration curve of the given model through `points`.
+pub fn fit(
+  points: &[CalibrationPoint],
+  model: CalibrationModel,
+) -> Result<Calibration, FitError> {
+  if points
+    .iter()
+    .any(|point| !point.raw.is_finite() || !point.reference.is_finite())
+  {
+    return Err(FitError::NotFinite);
+  }
+
+  match model {
+    CalibrationModel::Polynomial { degree } => fit_polynomial(points, degree),
+    CalibrationModel::PiecewiseLinear => fit_piecewise_linear(points),
+  }
+}
+
+/// Fits a least-squares polynomial by solving its normal equations.
+fn fit_polynomial(
+  points: &[CalibrationPoint],
+  degree: usize,
+) -> Result<Calibration, FitError> {
+  if degree > MAX_POLYNOMIAL_DEGREE {
+    return Err(FitError::DegreeTooHigh(degree));
+  }
+
+  let needed = degree + 1;
+  let found = distinct_raw_values(points);
+
+  if found < needed {
+    return Err(FitError::NotEnoughPoints { needed, found });
+  }
+
+  // build the augmented matrix [XᵀX | Xᵀy], where X is the Vandermonde matrix
+  // of the raw values.
+  let mut matrix = vec![vec![0.0; needed + 1]; needed];
+
+  for point in points {
+    let powers: Vec<f64> =
+      (0..needed).map(|power| point.raw.powi(power as i32)).collect();
+
+    for row in 0..needed {
+      for column in 0..needed {
+        matrix[row][column] += powers[row] * powers[column];
+      }
+
+      matrix[row][needed] += powers[row] * point.reference;
+    }
+  }
+
+  // gaussian elimination with partial pivoting. the matrix is nonsingular as
+  // there are at least as many distinct raw values as coefficients.
+  for pivot in 0..needed {
+    let best = (pivot..needed)
+      .max_by(|&a, &b| matrix[a][pivot].abs().total_cmp(&matrix[b][pivot].abs()))
+      .unwrap_or(pivot);
+
+    matrix.swap(pivot, best);
+
+    let (upper, lower) = matrix.split_at_mut(pivot + 1);
+    let pivot_row = &upper[pivot];
+
+    for row in lower {
+      let factor = row[pivot] / pivot_row[pivot];
+
+      for (value, above) in row.iter_mut().zip(pivot_row).skip(pivot) {
+        *value -= factor * above;
+      }
+    }
+  }
+
+  let mut coefficients = vec![0.0; needed];
+
+  for row in (0..needed).rev() {
+    let known: f64 = (row + 1..needed)
+      .map(|column| matrix[row][column] * coefficients[column])
+      .sum();
+
+    coefficients[row] = (matrix[row][needed] - known) / matrix[row][row];
+  }
+
+  Ok(Calibration::Polynomial(coefficients))
+}
+
+/// Sorts the points by raw value, averaging the references of points sharing
+/// a raw value so that no segment is vertical.
+fn fit_piecewise_linear(
+  points: &[CalibrationPoint],
+) -> Result<Calibration, FitError> {
+  let found = distinct_raw_values(points);
+
+  if found < 2 {
+    return Err(FitError::NotEnoughPoints { needed: 2, found });
+  }
+
+  let mut sorted = points.to_vec();
+  sorted.sort_by(|a, b| a.raw.total_cmp(&b.raw));
+
+  let mut table: Vec<(f64, f64, usize)> = Vec::with_capacity(found);
+
+  for point in sorted {
+    match table.last_mut() {
+      Some((raw, sum, count)) if *raw == point.raw => {
+        *sum += point.reference;
+        *count += 1;
+      }
+      _ => table.push((point.raw, point.reference, 1)),
+    }
+  }
+
+  Ok(Calibration::PiecewiseLinear(
+    table
+      .into_iter()
+      .map(|(raw, sum, count)| (raw, sum / count as f64))
+      .collect(),
+  ))
+}
+
+fn distinct_raw_values(points: &[CalibrationPoint]) -> usize {
+  let mut raw: Vec<f64> = points.iter().map(|point| point.raw).collect();
+  raw.sort_by(f64::total_cmp);
+  raw.dedup();
+  raw.len()
+}
+
+impl Database {
+  /// Stores new zero offsets in the active mappings and records the change in
+  /// history, returning the ID of the calibration.
+  pub async fn apply_zero_calibration(
+    &self,
+    window: f64,
+    sensors: &HashMap<String, ZeroedSensor>,
+  ) -> rusqlite::Result<i64> {
+    let mut connection = self.connection.lock().await;
+    let transaction = connection.transaction()?;
+
+    let configuration_id = transaction.query_row(
+      "SELECT configuration_id FROM NodeMappings WHERE active = TRUE",
+      [],
+      |row| row.get::<_, String>(0),
+    )?;
+
+    transaction.execute(
+      "INSERT INTO Calibrations (window_seconds) VALUES (?1)",
+      params![window],
+    )?;
+
+    let calibration_id = transaction.last_insert_rowid();
+
+    for (text_id, sensor) in sensors {
+      transaction.execute(
+        "
+          UPDATE NodeMappings
+          SET calibrated_offset = ?1
+          WHERE configuration_id = ?2 AND text_id = ?3
+        ",
+        params![sensor.offset, configuration_id, text_id],
+      )?;
+
+      transaction.execute(
+        "
+          INSERT INTO CalibrationOffsets (
+            calibration_id,
+            configuration_id,
+            text_id,
+            previous_offset,
+            new_offset,
+            mean,
+            standard_deviation,
+            samples
+          ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+        ",
+        params![
+          calibration_id,
+          configuration_id,
+          text_id,
+          sensor.previous_offset,
+          sensor.offset,
+          sensor.statistics.mean,
+          sensor.statistics.standard_deviation,
+          sensor.statistics.samples,
+        ],
+      )?;
+    }
+
+    transaction.commit()?;
+    Ok(calibration_id)
+  }
+
+  /// Returns the most recent zero calibrations, newest first.
+  pub async fn zero_calibrations(
+    &self,
+    limit: u32,
+  ) -> rusqlite::Result<Vec<ZeroCalibration>> {
+    let connection = self.connection.lock().await;
+
+    let mut calibrations = connection
+      .prepare(
+        "
+          SELECT calibration_id, window_seconds, recorded_at, reverted_at
+          FROM Calibrations
+          ORDER BY calibration_id DESC
+          LIMIT ?1
+        ",
+      )?
+      .query_and_then(params![limit], |row| {
+        Ok::<_, rusqlite::Error>(ZeroCalibration {
+          calibration_id: row.get(0)?,
+          configuration_id: String::new(),
+          window: row.get(1)?,
+          recorded_at: row.get(2)?,
+          reverted_at: row.get(3)?,
+          sensors: HashMap::new(),
+        })
+      })?
+      .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    let mut statement = connection.prepare(
+      "
+        SELECT
+          configuration_id,
+          text_id,
+          previous_offset,
+          new_offset,
+          mean,
+          standard_deviation,
+          samples
+        FROM CalibrationOffsets
+        WHERE calibration_id = ?1
+      ",
+    )?;
+
+    for calibration in &mut calibrations {
+      let mut rows = statement.query(params![calibration.calibration_id])?;
+
+      while let Some(row) = rows.next()? {
+        calibration.configuration_id = row.get(0)?;
+        calibration.sensors.insert(
+          row.get(1)?,
+          ZeroedSensor {
+            previous_offset: row.get(2)?,
+            offset: row.get(3)?,
+            statistics: SampleStatistics {
+              mean: row.get(4)?,
+              standard_deviation: row.get(5)?,
+              samples: row.get(6)?,
+            },
+          },
+        );
+      }
+    }
+
+    Ok(calibrations)
+  }
+
+  /// Reverts the most recent zero calibration that hasn't been reverted yet,
+  /// restoring the offsets it replaced. Returns the reverted calibration, or
+  /// `None` if there was nothing to revert.
+  ///
+  /// Calibrations are only ever undone newest first so that reverting one can
+  /// never clobber the offsets set by a later calibration.
+  pub async fn revert_zero_calibration(
+    &self,
+  ) -> rusqlite::Result<Option<ZeroCalibration>> {
+    let calibration_id = {
+      let mut connection = self.connection.lock().await;
+      let transaction = connection.transaction()?;
+
+      let pending = transaction
+        .query_row(
+          "
+            SELECT calibration_id
+            FROM Calibrations
+            WHERE reverted_at IS NULL
+            ORDER BY calibration_id DESC
+            LIMIT 1
+          ",
+          [],
+          |row| row.get::<_, i64>(0),
+        )
+        .optional()?;
+
+      let Some(calibration_id) = pending else {
+        return Ok(None);
+      };
+
+      transaction.execute(
+        "
+          UPDATE NodeMappings
+          SET calibrated_offset = (
+            SELECT previous_offset
+            FROM CalibrationOffsets
+            WHERE
+              CalibrationOffsets.calibration_id = ?1
+              AND CalibrationOffsets.configuration_id = NodeMappings.configuration_id
+              AND CalibrationOffsets.text_id = NodeMappings.text_id
+          )
+          WHERE EXISTS (
+            SELECT 1
+            FROM CalibrationOffsets
+            WHERE
+              CalibrationOffsets.calibration_id = ?1
+              AND CalibrationOffsets.configuration_id = NodeMappings.configuration_id
+              AND CalibrationOffsets.text_id = NodeMappings.text_id
+          )
+        ",
+        params![calibration_id],
+      )?;
+
+      transaction.execute(
+        "
+          UPDATE Calibrations
+          SET reverted_at = unixepoch('now', 'subsec')
+          WHERE calibration_id = ?1
+        ",
+        params![calibration_id],
+      )?;
+
+      transaction.commit()?;
+      calibration_id
+    };
+
+    let calibration = self
+      .zero_calibrations(u32::MAX)
+      .await?
+      .into_iter()
+      .find(|calibration| calibration.calibration_id == calibration_id);
+
+    Ok(calibration)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::future::Future;
+
+  fn points(pairs: &[(f64, f64)]) -> Vec<CalibrationPoint> {
+    pairs
+      .iter()
+      .map(|&(raw, reference)| CalibrationPoint { raw, reference })
+      .collect()
+  }
+
+  #[test]
+  fn polynomial_fit_recovers_exact_curve() {
+    // reference = 2 + 3x - 0.5x^2
+    let points = points(&[
+      (0.8, 4.08),
+      (1.6, 5.52),
+      (2.4, 6.32),
+      (3.2, 6.48),
+      (4.0, 6.0),
+    ]);
+
+    let calibration =
+      fit(&points, CalibrationModel::Polynomial { degree: 2 }).unwrap();
+
+    let Calibration::Polynomial(ref coefficients) = calibration else {
+      panic!("expected a polynomial, got {calibration:?}");
+    };
+
+    for (found, expected) in coefficients.iter().zip([2.0, 3.0, -0.5]) {
+      assert!((found - expected).abs() < 1e-9, "{coefficients:?}");
+    }
+
+    assert!((calibration.apply(2.0) - 6.0).abs() < 1e-9);
+  }
+
+  #[test]
+  fn polynomial_fit_needs_enough_distinct_points() {
+    let points = points(&[(1.0, 10.0), (1.0, 11.0), (2.0, 20.0)]);
+
+    assert_eq!(
+      fit(&points, CalibrationModel::Polynomial { degree: 2 }),
+      Err(FitError::NotEnoughPoints {
+        needed: 3,
+        found: 2,
+      })
+    );
+  }
+
+  #[test]
+  fn piecewise_linear_interpolates_and_extrapolates() {
+    let points = points(&[(3.0, 300.0), (1.0, 0.0), (2.0, 100.0), (2.0, 140.0)]);
+
+    let calibration = fit(&points, CalibrationModel::PiecewiseLinear).unwrap();
+
+    assert_eq!(
+      calibration,
+      Calibration::PiecewiseLinear(vec![
+        (1.0, 0.0),
+        (2.0, 120.0),
+        (3.0, 300.0)
+      ])
+    );
+
+    assert_eq!(calibration.apply(1.5), 60.0);
+    assert_eq!(calibration.apply(0.0), -120.0);
+    assert_eq!(calibration.apply(4.0), 480.0);
+  }
+
+  #[test]
+  fn statistics_use_sample_standard_deviation() {
+    assert_eq!(SampleStatistics::of(&[]), None);
+
+    let single = SampleStatistics::of(&[4.2]).unwrap();
+    assert_eq!(single.mean, 4.2);
+    assert_eq!(single.standard_deviation, 0.0);
+
+    let spread =
+      SampleStatistics::of(&[2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0]).unwrap();
+
+    assert_eq!(spread.mean, 5.0);
+    assert_eq!(spread.samples, 8);
+    assert!((spread.standard_deviation - (32.0f64 / 7.0).sqrt()).abs() < 1e-12);
+  }
+
+  /// Runs a future to completion. The database can't be migrated from within
+  /// a runtime, so tests drive their own.
+  fn block_on<F: Future>(future: F) -> F::Output {
+    tokio::runtime::Builder::new_current_thread()
+      .build()
+      .unwrap()
+      .block_on(future)
+  }
+
+  /// An in-memory database with PT1, PT2 and PT3 mapped in the active
+  /// configuration and PT1 also mapped in an inactive one.
+  fn database() -> Database {
+    let database = Database::volatile().unwrap();
+    database.migrate().unwrap();
+
+    let connection = database.connection.blocking_lock();
+
+    for (configuration_id, text_id, offset, active) in [
+      ("active", "PT1", 0.5, true),
+      ("active", "PT2", 1.0, true),
+      ("active", "PT3", 2.0, true),
+      ("inactive", "PT1", 9.0, false),
+    ] {
+      connection
+        .execute(
+          "
+            INSERT INTO NodeMappings (
+              configuration_id,
+              text_id,
+              board_id,
+              sensor_type,
+              channel,
+              computer,
+              calibrated_offset,
+              active
+            ) VALUES (?1, ?2, 1, 'pt', 1, 'flight', ?3, ?4)
+          ",
+          params![configuration_id, text_id, offset, active],
+        )
+        .unwrap();
+    }
+
+    drop(connection);
+    database
+  }
+
+  /// The calibrated offset of every mapping, keyed by configuration and text
+  /// ID.
+  fn read_offsets(database: &Database) -> HashMap<(String, String), f64> {
+    database
+      .connection
+      .blocking_lock()
+      .prepare(
+        "SELECT configuration_id, text_id, calibrated_offset FROM NodeMappings",
+      )
+      .unwrap()
+      .query_map([], |row| Ok(((row.get(0)?, row.get(1)?), row.get(2)?)))
+      .unwrap()
+      .collect::<rusqlite::Result<_>>()
+      .unwrap()
+  }
+
+  fn offset(
+    offsets: &HashMap<(String, String), f64>,
+    configuration_id: &str,
+    text_id: &str,
+  ) -> f64 {
+    offsets[&(configuration_id.to_string(), text_id.to_string())]
+  }
+
+  fn zeroed(previous_offset: f64, offset: f64) -> ZeroedSensor {
+    ZeroedSensor {
+      previous_offset,
+      offset,
+      statistics: SampleStatistics {
+        mean: previous_offset - offset,
+        standard_deviation: 0.01,
+        samples: 50,
+      },
+    }
+  }
+
+  #[test]
+  fn zero_calibration_sets_offsets_of_the_chosen_sensors() {
+    let database = database();
+
+    let sensors = HashMap::from([
+      ("PT1".to_string(), zeroed(0.5, 1.5)),
+      ("PT2".to_string(), zeroed(1.0, -0.25)),
+    ]);
+
+    let calibration_id =
+      block_on(database.apply_zero_calibration(2.0, &sensors)).unwrap();
+
+    let offsets = read_offsets(&database);
+    assert_eq!(offset(&offsets, "active", "PT1"), 1.5);
+    assert_eq!(offset(&offsets, "active", "PT2"), -0.25);
+
+    // sensors left out of the calibration and other configurations keep theirs
+    assert_eq!(offset(&offsets, "active", "PT3"), 2.0);
+    assert_eq!(offset(&offsets, "inactive", "PT1"), 9.0);
+
+    let history = block_on(database.zero_calibrations(10)).unwrap();
+    assert_eq!(history.len(), 1);
+
+    let calibration = &history[0];
+    assert_eq!(calibration.calibration_id, calibration_id);
+    assert_eq!(calibration.configuration_id, "active");
+    assert_eq!(calibration.window, 2.0);
+    assert_eq!(calibration.reverted_at, None);
+    assert_eq!(calibration.sensors, sensors);
+  }
+
+  #[test]
+  fn reverting_restores_offsets_newest_first() {
+    let database = database();
+
+    let first = HashMap::from([
+      ("PT1".to_string(), zeroed(0.5, 1.5)),
+      ("PT2".to_string(), zeroed(1.0, -0.25)),
+    ]);
+    let second = HashMap::from([("PT1".to_string(), zeroed(1.5, 3.0))]);
+
+    let first_id =
+      block_on(database.apply_zero_calibration(2.0, &first)).unwrap();
+    let second_id =
+      block_on(database.apply_zero_calibration(1.0, &second)).unwrap();
+
+    let reverted = block_on(database.revert_zero_calibration())
+      .unwrap()
+      .unwrap();
+
+    assert_eq!(reverted.calibration_id, second_id);
+    assert!(reverted.reverted_at.is_some());
+    assert_eq!(reverted.sensors, second);
+
+    // only the sensor in the latest calibration goes back to its old offset
+    let offsets = read_offsets(&database);
+    assert_eq!(offset(&offsets, "active", "PT1"), 1.5);
+    assert_eq!(offset(&offsets, "active", "PT2"), -0.25);
+    assert_eq!(offset(&offsets, "active", "PT3"), 2.0);
+
+    let history = block_on(database.zero_calibrations(10)).unwrap();
+    let reverted_at: HashMap<i64, Option<f64>> = history
+      .iter()
+      .map(|calibration| (calibration.calibration_id, calibration.reverted_at))
+      .collect();
+
+    assert_eq!(reverted_at[&first_id], None);
+    assert_eq!(reverted_at[&second_id], reverted.reverted_at);
+
+    let reverted = block_on(database.revert_zero_calibration())
+      .unwrap()
+      .unwrap();
+    assert_eq!(reverted.calibration_id, first_id);
+
+    let offsets = read_offsets(&database);
+    assert_eq!(offset(&offsets, "active", "PT1"), 0.5);
+    assert_eq!(offset(&offsets, "active", "PT2"), 1.0);
+    assert_eq!(offset(&offsets, "active", "PT3"), 2.0);
+    assert_eq!(offset(&offsets, "inactive", "PT1"), 9.0);
+
+    assert_eq!(block_on(database.revert_zero_calibration()).unwrap(), None);
+  }
+}
@@ -170,7 +170,19 @@ impl Database {
           min,
           calibrated_offset,
           powered_threshold,
-          normally_closed
+          normally_closed,
+          fault_timeout,
+          abort_on_fault,
+          warning_low,
+          warning_high,
+          critical_low,
+          critical_high,
+          abort_on_critical,
+          critical_persistence,
+          calibration,
+          expression,
+          radio,
+          radio_priority
         FROM NodeMappings
         WHERE active = TRUE
       ",
@@ -188,6 +200,18 @@ impl Database {
           calibrated_offset: row.get(7)?,
           powered_threshold: row.get(8)?,
           normally_closed: row.get(9)?,
+          fault_timeout: row.get(10)?,
+          abort_on_fault: row.get(11)?,
+          warning_low: row.get(12)?,
+          warning_high: row.get(13)?,
+          critical_low: row.get(14)?,
+          critical_high: row.get(15)?,
+          abort_on_critical: row.get(16)?,
+          critical_persistence: row.get(17)?,
+          calibration: row.get(18)?,
+          expression: row.get(19)?,
+          radio: row.get(20)?,
+          radio_priority: row.get(21)?,
         })
       })?;
     let mappings = rows.collect::<rusqlite::Result<Vec<_>>>()?;
@@ -1,3 +1,4 @@
+use super::flight::ReplyError;
 use axum::{http::StatusCode, response::IntoResponse};
 
 /// Any error that the server can throw in a route function.
@@ -8,6 +9,9 @@ pub enum ServerError {
 
   /// Error that may be converted directly into a `Response`.
   Raw(String, StatusCode),
+
+  /// Error with a structured JSON body, for clients that act on its details.
+  Json(serde_json::Value, StatusCode),
 }
 
 impl From<rusqlite::Error> for ServerError {
@@ -20,11 +24,23 @@ impl IntoResponse for ServerError {
   fn into_response(self) -> axum::response::Response {
     match self {
       Self::Sql(error) => {
-        (StatusCode::INTERNAL_SERVER_ERROR, error.to_string())
+        (StatusCode::INTERNAL_SERVER_ERROR, error.to_string()).into_response()
       }
-      Self::Raw(message, status) => (status, message),
+      Self::Raw(message, status) => (status, message).into_response(),
+      Self::Json(body, status) => (status, axum::Json(body)).into_response(),
     }
-    .into_response()
+  }
+}
+
+impl From<ReplyError> for ServerError {
+  fn from(error: ReplyError) -> Self {
+    let status = match error {
+      ReplyError::Rejected(_) => StatusCode::UNPROCESSABLE_ENTITY,
+      ReplyError::TimedOut => StatusCode::GATEWAY_TIMEOUT,
+      ReplyError::Disconnected => StatusCode::BAD_GATEWAY,
+    };
+
+    ServerError::Raw(error.to_string(), status)
   }
 }
 
@@ -1,6 +1,8 @@
 use common::comm::{
   Computer,
   FlightControlMessage,
+  FlightControlRequest,
+  FlightStatusMessage,
   NodeMapping,
   Sequence,
   Trigger,
@@ -10,6 +12,7 @@ use common::comm::{
 	ValveSafeState,
 };
 use jeflog::warn;
+use rusqlite::OptionalExtension;
 use postcard::experimental::max_size::MaxSize;
 use socket2::{Domain, Protocol, Socket, Type};
 use std::{
@@ -19,11 +22,26 @@ use std::{
   mem::{size_of, zeroed},
   net::{IpAddr, SocketAddr as StdSocketAddr, UdpSocket as StdUdpSocket},
   os::fd::AsRawFd,
+  sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+  },
+  time::Duration,
+};
+use super::{
+  sequence_runs::SequenceRuns,
+  telemetry::{update_live_telemetry, TelemetrySource},
+  Database,
+  Shared,
 };
-use super::{telemetry::{update_live_telemetry, TelemetrySource}, Database, Shared};
 use tokio::{
-  io::{self, AsyncReadExt, AsyncWriteExt},
-  net::{TcpListener, TcpStream},
+  io::{self, AsyncRead, AsyncReadExt, AsyncWriteExt},
+  net::{
+    tcp::OwnedWriteHalf,
+    TcpListener,
+    TcpStream,
+  },
+  sync::{oneshot, Mutex},
 };
 
 /// DSCP marker that identifies radio telemetry from the TEL path.
@@ -33,17 +51,155 @@ use tokio::{
 /// umbilical.
 pub const RADIO_TELEMETRY_DSCP: u8 = 0x2e;
 
+/// How long routes wait for the flight computer to reply to a request before
+/// giving up on it.
+pub const REPLY_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Senders for the replies to requests which haven't been answered yet, keyed
+/// by request ID.
+type PendingReplies =
+  Arc<Mutex<HashMap<u32, oneshot::Sender<Result<(), String>>>>>;
+
+/// Why a request to the flight computer didn't succeed.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ReplyError {
+  /// The flight computer rejected the request, for the given reason.
+  Rejected(String),
+
+  /// No reply arrived within the timeout.
+  TimedOut,
+
+  /// The flight computer disconnected before replying.
+  Disconnected,
+}
+
+impl std::fmt::Display for ReplyError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Self::Rejected(reason) => {
+        write!(f, "flight computer rejected the request: {reason}")
+      }
+      Self::TimedOut => {
+        write!(f, "timed out waiting for the flight computer to reply")
+      }
+      Self::Disconnected => {
+        write!(f, "flight computer disconnected before replying")
+      }
+    }
+  }
+}
+
+impl std::error::Error for ReplyError {}
+
+/// The reply to a request sent to the flight computer, which may be waited on
+/// or dropped if the outcome doesn't matter.
+#[derive(Debug)]
+pub struct PendingReply {
+  id: u32,
+  receiver: oneshot::Receiver<Result<(), String>>,
+  replies: PendingReplies,
+}
+
+impl PendingReply {
+  /// Waits up to `timeout` for the flight computer to acknowledge the request.
+  pub async fn wait(self, timeout: Duration) -> Result<(), ReplyError> {
+    match tokio::time::timeout(timeout, self.receiver).await {
+      Ok(Ok(Ok(()))) => Ok(()),
+      Ok(Ok(Err(reason))) => Err(ReplyError::Rejected(reason)),
+      Ok(Err(_)) => Err(ReplyError::Disconnected),
+      Err(_) => {
+        self.replies.lock().await.remove(&self.id);
+        Err(ReplyError::TimedOut)
+      }
+    }
+  }
+
+  /// Waits for the reply in the background, warning if the request failed.
+  /// For requests which no route is waiting on, such as the full update sent
+  /// when flight connects.
+  pub fn warn_on_failure(self, request: &'static str) {
+    tokio::spawn(async move {
+      if let Err(error) = self.wait(REPLY_TIMEOUT).await {
+        warn!("Flight didn't accept the {request} sent to it: {error}");
+      }
+    });
+  }
+}
+
 /// Struct capable of performing thread-safe operations on a flight computer
 /// connection, thus capable of being passed to route handlers.
 #[derive(Debug)]
 pub struct FlightComputer {
   database: Database,
-  stream: TcpStream,
+  stream: OwnedWriteHalf,
+
+  /// Set by the task reading status messages once the stream is closed.
+  closed: Arc<AtomicBool>,
+
+  /// The ID given to the next request sent.
+  next_request_id: u32,
+
+  replies: PendingReplies,
 }
 
 impl FlightComputer {
+  /// Wraps a newly accepted stream, spawning a task which reads the status
+  /// messages sent back by the flight computer.
+  fn new(
+    stream: TcpStream,
+    database: Database,
+    sequence_runs: Arc<Mutex<SequenceRuns>>,
+  ) -> Self {
+    let (reader, writer) = stream.into_split();
+    let closed = Arc::new(AtomicBool::new(false));
+    let replies = PendingReplies::default();
+
+    tokio::spawn(receive_status_messages(
+      reader,
+      database.clone(),
+      sequence_runs,
+      replies.clone(),
+      closed.clone(),
+    ));
+
+    FlightComputer {
+      database,
+      stream: writer,
+      closed,
+      next_request_id: 0,
+      replies,
+    }
+  }
+
+  /// Sends a message to the flight computer, returning its reply to be
+  /// waited on.
+  pub async fn send_message(
+    &mut self,
+    message: FlightControlMessage,
+  ) -> anyhow::Result<PendingReply> {
+    let id = self.next_request_id;
+    self.next_request_id = self.next_request_id.wrapping_add(1);
+
+    let serialized = postcard::to_allocvec(&FlightControlRequest { id, message })?;
+
+    // register for the reply before sending, as it may arrive immediately.
+    let (sender, receiver) = oneshot::channel();
+    self.replies.lock().await.insert(id, sender);
+
+    if let Err(error) = self.send_bytes(&serialized).await {
+      self.replies.lock().await.remove(&id);
+      return Err(error.into());
+    }
+
+    Ok(PendingReply {
+      id,
+      receiver,
+      replies: self.replies.clone(),
+    })
+  }
+
   /// Send a slice of bytes along the TCP connection to the flight computer.
-  pub async fn send_bytes(&mut self, bytes: &[u8]) -> io::Result<()> {
+  async fn send_bytes(&mut self, bytes: &[u8]) -> io::Result<()> {
     // get length of message, and send that first
 		let length = u16::try_from(bytes.len()).map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "message too long"))?;
 		// send length of message
@@ -62,8 +218,9 @@ impl FlightComputer {
     Ok(addr.port())
   }
 
-  /// Sends the given set of mappings to the flight computer.
-  pub async fn send_mappings(&mut self) -> anyhow::Result<()> {
+  /// Sends the active mappings to the flight computer, returning its reply to
+  /// be waited on, as flight rejects the whole set if any of them is invalid.
+  pub async fn send_mappings(&mut self) -> anyhow::Result<PendingReply> {
     let mappings = self
       .database
       .connection
@@ -81,7 +238,19 @@ impl FlightComputer {
 					min,
 					calibrated_offset,
 					powered_threshold,
-					normally_closed
+					normally_closed,
+					fault_timeout,
+					abort_on_fault,
+					warning_low,
+					warning_high,
+					critical_low,
+					critical_high,
+					abort_on_critical,
+					critical_persistence,
+					calibration,
+					expression,
+					radio,
+					radio_priority
 				FROM NodeMappings WHERE active = TRUE
 			",
       )?
@@ -97,30 +266,35 @@ impl FlightComputer {
           calibrated_offset: row.get(7)?,
           powered_threshold: row.get(8)?,
           normally_closed: row.get(9)?,
+          fault_timeout: row.get(10)?,
+          abort_on_fault: row.get(11)?,
+          warning_low: row.get(12)?,
+          warning_high: row.get(13)?,
+          critical_low: row.get(14)?,
+          critical_high: row.get(15)?,
+          abort_on_critical: row.get(16)?,
+          critical_persistence: row.get(17)?,
+          calibration: row.get(18)?,
+          expression: row.get(19)?,
+          radio: row.get(20)?,
+          radio_priority: row.get(21)?,
         })
       })?
       .collect::<Result<Vec<NodeMapping>, rusqlite::Error>>()?;
 
-    let message = FlightControlMessage::Mappings(mappings);
-    let serialized = postcard::to_allocvec(&message)?;
-
-    self.send_bytes(&serialized).await?;
-
-    Ok(())
+    self.send_message(FlightControlMessage::Mappings(mappings)).await
   }
 
-  /// Sends one abort stage to flight
-	pub async fn send_abort_stage_config(&mut self, stage : AbortStageConfig) -> anyhow::Result<()> {
-    let message = FlightControlMessage::AbortStageConfig(stage);
-    let serialized = postcard::to_allocvec(&message)?;
-
-    self.send_bytes(&serialized).await?;
-
-    Ok(())
+  /// Sends one abort stage to flight, returning its reply to be waited on.
+	pub async fn send_abort_stage_config(&mut self, stage : AbortStageConfig) -> anyhow::Result<PendingReply> {
+    self.send_message(FlightControlMessage::AbortStageConfig(stage)).await
 	}
 
-	/// Send all abort stages in the SQL database to flight
-  pub async fn send_all_abort_configs(&mut self) -> anyhow::Result<()> {
+	/// Send all abort stages in the SQL database to flight, returning the reply
+	/// to each.
+  pub async fn send_all_abort_configs(
+    &mut self,
+  ) -> anyhow::Result<Vec<PendingReply>> {
 
 		let stages = self
       .database
@@ -147,73 +321,160 @@ impl FlightComputer {
 			})?
 			.collect::<Result<Vec<AbortStageConfig>, rusqlite::Error>>()?;
 
+		let mut replies = Vec::with_capacity(stages.len());
 		for stage in stages {
-			self.send_abort_stage_config(stage).await?;
+			replies.push(self.send_abort_stage_config(stage).await?);
 		}
 
-		Ok(())
+		Ok(replies)
   }
 
   /// Sends the given sequence to the flight computer to be executed.
   pub async fn send_sequence(
     &mut self,
     sequence: Sequence,
-  ) -> anyhow::Result<()> {
-    let message = FlightControlMessage::Sequence(sequence);
-    let serialized = postcard::to_allocvec(&message)?;
-
-    self.send_bytes(&serialized).await?;
-    Ok(())
+  ) -> anyhow::Result<PendingReply> {
+    self.send_message(FlightControlMessage::Sequence(sequence)).await
   }
 
   /// Instructs the flight computer to stop a sequence.
-  pub async fn stop_sequence(&mut self, name: String) -> anyhow::Result<()> {
-    let message = FlightControlMessage::StopSequence(name);
-    let serialized = postcard::to_allocvec(&message)?;
+  pub async fn stop_sequence(
+    &mut self,
+    name: String,
+  ) -> anyhow::Result<PendingReply> {
+    self.send_message(FlightControlMessage::StopSequence(name)).await
+  }
 
-    self.send_bytes(&serialized).await?;
-    Ok(())
+  /// Instructs the flight computer to pause a sequence.
+  pub async fn pause_sequence(
+    &mut self,
+    name: String,
+  ) -> anyhow::Result<PendingReply> {
+    self.send_message(FlightControlMessage::PauseSequence(name)).await
+  }
+
+  /// Instructs the flight computer to resume a paused sequence.
+  pub async fn resume_sequence(
+    &mut self,
+    name: String,
+  ) -> anyhow::Result<PendingReply> {
+    self.send_message(FlightControlMessage::ResumeSequence(name)).await
+  }
+
+  /// Instructs the flight computer to step a paused sequence past the wait it
+  /// is held at.
+  pub async fn step_sequence(
+    &mut self,
+    name: String,
+  ) -> anyhow::Result<PendingReply> {
+    self.send_message(FlightControlMessage::StepSequence(name)).await
   }
 
   /// Instructs the flight computer to abort.
-  pub async fn abort(&mut self) -> anyhow::Result<()> {
-    let message = FlightControlMessage::Abort;
-    let serialized = postcard::to_allocvec(&message)?;
+  pub async fn abort(&mut self) -> anyhow::Result<PendingReply> {
+    self.send_message(FlightControlMessage::Abort).await
+  }
 
-    self.send_bytes(&serialized).await?;
-    Ok(())
+  /// Sends a single trigger to the flight computer, replacing any trigger of
+  /// the same name, and returns its reply to be waited on.
+  pub async fn send_trigger(
+    &mut self,
+    trigger: Trigger,
+  ) -> anyhow::Result<PendingReply> {
+    self.send_message(FlightControlMessage::Trigger(trigger)).await
   }
 
   /// Sends all triggers stored in the database to the flight computer, active
-  /// or not.
-  pub async fn send_trigger(&mut self, trigger: Trigger) -> anyhow::Result<()> {
-    let message = FlightControlMessage::Trigger(trigger);
-    let serialized = postcard::to_allocvec(&message)?;
+  /// or not, returning the reply to each.
+  pub async fn send_all_triggers(
+    &mut self,
+  ) -> anyhow::Result<Vec<PendingReply>> {
+    let triggers = self
+      .database
+      .connection
+      .lock()
+      .await
+      .prepare("SELECT name, condition, script, active FROM Triggers")?
+      .query_and_then([], |row| {
+        Ok(Trigger {
+          name: row.get(0)?,
+          condition: row.get(1)?,
+          script: row.get(2)?,
+          active: row.get(3)?,
+        })
+      })?
+      .collect::<Result<Vec<Trigger>, rusqlite::Error>>()?;
 
-    self.send_bytes(&serialized).await?;
-    Ok(())
+    let mut replies = Vec::with_capacity(triggers.len());
+    for trigger in triggers {
+      replies.push(self.send_trigger(trigger).await?);
+    }
+
+    Ok(replies)
+  }
+
+  /// Instructs the flight computer to forget a trigger.
+  pub async fn delete_trigger(
+    &mut self,
+    name: String,
+  ) -> anyhow::Result<PendingReply> {
+    self.send_message(FlightControlMessage::DeleteTrigger(name)).await
   }
 
   /// Checks if the underlying TCP stream has been closed.
   pub fn check_closed(&self) -> bool {
-    let mut buffer = [0u8; 1];
-
-    match self.stream.try_read(&mut buffer) {
-      // if the flight stream reads zero bytes, it's closed.
-      // this indicates that the current flight computer should not be there.
-      Ok(size) => size == 0,
-      // if the flight stream errors out with WouldBlock, it just means no
-      // packet is waiting, otherwise it's a real error
-      Err(e) => e.kind() != std::io::ErrorKind::WouldBlock,
-    }
+    self.closed.load(Ordering::Relaxed)
+  }
+
+  /// Sends the stored abort sequence, if there is one, for flight to persist,
+  /// returning its reply to be waited on.
+  pub async fn send_abort_sequence(
+    &mut self,
+  ) -> anyhow::Result<Option<PendingReply>> {
+    let script = self
+      .database
+      .connection
+      .lock()
+      .await
+      .query_row(
+        "SELECT script FROM Sequences WHERE name = 'abort'",
+        [],
+        |row| row.get::<_, String>(0),
+      )
+      .optional()?;
+
+    let Some(script) = script else {
+      return Ok(None);
+    };
+
+    let reply = self
+      .send_sequence(Sequence {
+        name: "abort".to_string(),
+        script,
+        arguments: HashMap::new(),
+      })
+      .await?;
+
+    Ok(Some(reply))
   }
 
-  /// Sends a comprehensive update of mappings, triggers, and abort sequence to
-  /// flight.
+  /// Sends a comprehensive update of mappings, triggers, abort sequence, and
+  /// abort stages to flight. No route waits on these, so any rejection is
+  /// logged as a warning once it arrives.
   pub async fn update(&mut self) -> anyhow::Result<()> {
-    self.send_mappings().await?;
+    self.send_mappings().await?.warn_on_failure("mappings");
 
-    // TODO: send triggers and abort sequence automatically
+    for reply in self.send_all_triggers().await? {
+      reply.warn_on_failure("trigger");
+    }
+
+    if let Some(reply) = self.send_abort_sequence().await? {
+      reply.warn_on_failure("abort sequence");
+    }
+
+    for reply in self.send_all_abort_configs().await? {
+      reply.warn_on_failure("abort stage");
+    }
 
     Ok(())
   }
@@ -227,6 +488,7 @@ pub fn auto_connect(server: &Shared) -> impl Future<Output = io::Result<()>> {
   let database = server.database.clone();
   let flight = server.flight.clone();
   let ground = server.ground.clone();
+  let sequence_runs = server.sequence_runs.clone();
 
   async move {
     let listener = TcpListener::bind("0.0.0.0:5025").await?;
@@ -269,19 +531,17 @@ pub fn auto_connect(server: &Shared) -> impl Future<Output = io::Result<()>> {
           // one there already. otherwise, this defaults to gracefully closing
           // the new connection on drop.
           if flight.is_none() {
-            let mut new_flight = FlightComputer {
+            let mut new_flight = FlightComputer::new(
               stream,
-              database: database.clone(),
-            };
+              database.clone(),
+              sequence_runs.clone(),
+            );
 
             if let Err(error) = new_flight.update().await {
               warn!("Failed to send update to new flight: {error}");
               continue;
             }
 
-            // send all abort configs by default
-            let _ = new_flight.send_all_abort_configs().await;
-
             *flight = Some(new_flight);
           }
         }
@@ -289,33 +549,22 @@ pub fn auto_connect(server: &Shared) -> impl Future<Output = io::Result<()>> {
           let mut ground = ground.0.lock().await;
 
           if let Some(existing) = &*ground {
-            let mut buffer = [0; 1];
-
-            // if the flight stream reads zero bytes, it's closed. this
-            // indicates that the current flight computer should not be there.
-            if existing
-              .stream
-              .try_read(&mut buffer)
-              .is_ok_and(|size| size == 0)
-            {
+            if existing.check_closed() {
               *ground = None;
             }
           }
 
           if ground.is_none() {
-            let mut new_ground = FlightComputer {
+            let mut new_ground = FlightComputer::new(
               stream,
-              database: database.clone(),
-            };
+              database.clone(),
+              sequence_runs.clone(),
+            );
 
             if let Err(error) = new_ground.update().await {
               warn!("Failed to send update to new flight: {error}");
               continue;
             }
-
-            // send all abort configs by default
-            let _ = new_ground.send_all_abort_configs().await;
-            
             *ground = Some(new_ground);
           }
         }
@@ -324,6 +573,69 @@ pub fn auto_connect(server: &Shared) -> impl Future<Output = io::Result<()>> {
   }
 }
 
+/// Reads the length-prefixed status messages sent back by a flight computer
+/// until its stream closes.
+async fn receive_status_messages(
+  mut reader: impl AsyncRead + Unpin,
+  database: Database,
+  sequence_runs: Arc<Mutex<SequenceRuns>>,
+  replies: PendingReplies,
+  closed: Arc<AtomicBool>,
+) {
+  let mut buffer = Vec::new();
+
+  loop {
+    let mut length = [0; 2];
+
+    if reader.read_exact(&mut length).await.is_err() {
+      break;
+    }
+
+    buffer.resize(u16::from_be_bytes(length) as usize, 0);
+
+    if reader.read_exact(&mut buffer).await.is_err() {
+      break;
+    }
+
+    let message = match postcard::from_bytes::<FlightStatusMessage>(&buffer) {
+      Ok(message) => message,
+      Err(error) => {
+        warn!("Failed to deserialize status message from flight: {error}");
+        continue;
+      }
+    };
+
+    let (id, result) = match message {
+      FlightStatusMessage::Ack { id } => (id, Ok(())),
+      FlightStatusMessage::Nack { id, reason } => (id, Err(reason)),
+      message => {
+        let finished = sequence_runs.lock().await.apply(message);
+
+        if let Some(record) = finished {
+          database.record_sequence_run(&record).await;
+        }
+
+        continue;
+      }
+    };
+
+    let unheard = match replies.lock().await.remove(&id) {
+      Some(sender) => sender.send(result).err(),
+      None => Some(result),
+    };
+
+    // nobody is waiting on this reply, so a rejection would otherwise go
+    // unnoticed.
+    if let Some(Err(reason)) = unheard {
+      warn!("Flight computer rejected request {id}: {reason}");
+    }
+  }
+
+  // dropping the senders tells everyone still waiting that flight is gone.
+  replies.lock().await.clear();
+  closed.store(true, Ordering::Relaxed);
+}
+
 /// Repeatedly receives vehicle state information from the flight computer.
 pub fn receive_vehicle_state(
   shared: &Shared,
@@ -356,23 +668,21 @@ pub fn receive_vehicle_state(
                 .map_err(|error| error.to_string())
             }
             TelemetrySource::Radio => {
-              let schema_guard = radio_schema.lock().await;
-              let Some(schema) = schema_guard.schema() else {
+              let mut schema_guard = radio_schema.lock().await;
+              if schema_guard.schema().is_none() {
                 warn!("Discarding radio telemetry packet because no active radio schema is available.");
                 continue;
-              };
+              }
 
-              VehicleState::decompress_compaq_with_schema(
-                &frame_buffer[..datagram_size],
-                schema,
-              )
-              .map_err(|error| format_radio_decode_error(error))
+              schema_guard
+                .decode(&frame_buffer[..datagram_size])
+                .map_err(|error| format_radio_decode_error(error))
             }
           };
 
           match new_state {
             Ok(state) => {
-              update_live_telemetry(telemetry.get(source), state, datagram_size).await;
+              update_live_telemetry(telemetry.get(source), source, state, datagram_size).await;
             }
             Err(error) => warn!(
               "Failed to deserialize {} telemetry: {error}",
@@ -510,6 +820,13 @@ fn sockaddr_to_socket_addr(
 
 fn format_radio_decode_error(error: VehicleStateCompaqError) -> String {
   match error {
+    VehicleStateCompaqError::SchemaMismatch { expected, received } => format!(
+      "radio schema mismatch: frame was sent with schema {received:08x} but \
+      the active mappings have schema {expected:08x}"
+    ),
+    VehicleStateCompaqError::MissingKeyframe => "dropped radio delta frame: \
+      its keyframe was lost, waiting for the next keyframe"
+      .to_string(),
     VehicleStateCompaqError::Schema(_)
     | VehicleStateCompaqError::PolicyDesynchronized => {
       format!("radio schema mismatch: {error:?}")
@@ -517,3 +834,171 @@ fn format_radio_decode_error(error: VehicleStateCompaqError) -> String {
     _ => format!("{error:?}"),
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use common::comm::SequenceOutputStream;
+  use tokio::io::AsyncWrite;
+
+  /// Connects a `FlightComputer` to a socket standing in for flight.
+  async fn connect() -> (FlightComputer, TcpStream) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+
+    let (servo_side, flight_side) =
+      tokio::join!(TcpStream::connect(address), listener.accept());
+
+    let flight = FlightComputer::new(
+      servo_side.unwrap(),
+      Database::volatile().unwrap(),
+      Arc::new(Mutex::new(SequenceRuns::default())),
+    );
+
+    (flight, flight_side.unwrap().0)
+  }
+
+  async fn receive_request(stream: &mut TcpStream) -> FlightControlRequest {
+    let mut length = [0; 2];
+    stream.read_exact(&mut length).await.unwrap();
+
+    let mut buffer = vec![0; u16::from_be_bytes(length) as usize];
+    stream.read_exact(&mut buffer).await.unwrap();
+
+    postcard::from_bytes(&buffer).unwrap()
+  }
+
+  async fn send_status(
+    stream: &mut (impl AsyncWrite + Unpin),
+    message: FlightStatusMessage,
+  ) {
+    let serialized = postcard::to_allocvec(&message).unwrap();
+    let length = serialized.len() as u16;
+
+    stream.write_all(&length.to_be_bytes()).await.unwrap();
+    stream.write_all(&serialized).await.unwrap();
+  }
+
+  #[tokio::test]
+  async fn replies_reach_the_request_with_their_id() {
+    let (mut flight, mut stream) = connect().await;
+
+    let abort = flight.abort().await.unwrap();
+    let camera = flight
+      .send_message(FlightControlMessage::CameraEnable(true))
+      .await
+      .unwrap();
+
+    let abort_id = receive_request(&mut stream).await.id;
+    let camera_id = receive_request(&mut stream).await.id;
+    assert_ne!(abort_id, camera_id);
+
+    // replies may arrive in any order.
+    send_status(
+      &mut stream,
+      FlightStatusMessage::Nack {
+        id: camera_id,
+        reason: String::from("no camera"),
+      },
+    )
+    .await;
+    send_status(&mut stream, FlightStatusMessage::Ack { id: abort_id }).await;
+
+    assert_eq!(abort.wait(REPLY_TIMEOUT).await, Ok(()));
+    assert_eq!(
+      camera.wait(REPLY_TIMEOUT).await,
+      Err(ReplyError::Rejected(String::from("no camera")))
+    );
+  }
+
+  #[tokio::test]
+  async fn unanswered_requests_time_out() {
+    let (mut flight, mut stream) = connect().await;
+
+    let reply = flight.abort().await.unwrap();
+    let id = receive_request(&mut stream).await.id;
+
+    assert_eq!(
+      reply.wait(Duration::from_millis(50)).await,
+      Err(ReplyError::TimedOut)
+    );
+    assert!(
+      flight.replies.lock().await.is_empty(),
+      "A request which timed out should stop waiting for its reply"
+    );
+
+    // a late reply is ignored, and later requests are still answered.
+    send_status(&mut stream, FlightStatusMessage::Ack { id }).await;
+
+    let reply = flight.abort().await.unwrap();
+    let id = receive_request(&mut stream).await.id;
+    send_status(&mut stream, FlightStatusMessage::Ack { id }).await;
+
+    assert_eq!(reply.wait(REPLY_TIMEOUT).await, Ok(()));
+  }
+
+  #[tokio::test]
+  async fn disconnecting_fails_pending_requests() {
+    let (mut flight, mut stream) = connect().await;
+
+    let reply = flight.abort().await.unwrap();
+    receive_request(&mut stream).await;
+    drop(stream);
+
+    assert_eq!(
+      reply.wait(REPLY_TIMEOUT).await,
+      Err(ReplyError::Disconnected)
+    );
+  }
+
+  #[tokio::test]
+  async fn replies_are_picked_out_from_other_status_messages() {
+    let (reader, mut writer) = io::duplex(1024);
+    let replies = PendingReplies::default();
+    let closed = Arc::new(AtomicBool::new(false));
+
+    let (ack_sender, ack) = oneshot::channel();
+    let (nack_sender, nack) = oneshot::channel();
+    replies.lock().await.insert(3, ack_sender);
+    replies.lock().await.insert(7, nack_sender);
+
+    let receiver = tokio::spawn(receive_status_messages(
+      reader,
+      Database::volatile().unwrap(),
+      Arc::new(Mutex::new(SequenceRuns::default())),
+      replies.clone(),
+      closed.clone(),
+    ));
+
+    send_status(
+      &mut writer,
+      FlightStatusMessage::SequenceOutput {
+        pid: 1,
+        stream: SequenceOutputStream::Stdout,
+        line: String::from("hello"),
+      },
+    )
+    .await;
+    send_status(
+      &mut writer,
+      FlightStatusMessage::Nack {
+        id: 7,
+        reason: String::from("unknown valve"),
+      },
+    )
+    .await;
+
+    // a reply to a request nobody is waiting on is dropped.
+    send_status(&mut writer, FlightStatusMessage::Ack { id: 42 }).await;
+    send_status(&mut writer, FlightStatusMessage::Ack { id: 3 }).await;
+
+    assert_eq!(ack.await, Ok(Ok(())));
+    assert_eq!(nack.await, Ok(Err(String::from("unknown valve"))));
+
+    drop(writer);
+    receiver.await.unwrap();
+
+    assert!(closed.load(Ordering::Relaxed));
+    assert!(replies.lock().await.is_empty());
+  }
+}
@@ -1,3 +1,6 @@
+/// Fitting of sensor calibration curves from captured reference points.
+pub mod calibration;
+
 /// Server database components.
 pub mod database;
 
@@ -13,10 +16,18 @@ pub mod telemetry;
 /// All server API route functions.
 pub mod routes;
 
+/// Static checks run on sequences before they are sent to flight.
+pub mod sequence_check;
+
+/// Sequence runs reported by the flight computer and their output.
+pub mod sequence_runs;
+
 use axum::Router;
+pub use calibration::CalibrationPoints;
 pub use database::Database;
 pub use error::{ServerError as Error, ServerResult as Result};
 pub use flight::FlightComputer;
+pub use sequence_runs::SequenceRuns;
 pub use telemetry::{LiveTelemetry, RadioSchemaCache, TelemetrySource, TelemetryState};
 use tower_http::cors::{self, CorsLayer};
 
@@ -45,6 +56,12 @@ pub struct Shared {
 
   /// Cached radio decompression schema derived from active mappings.
   pub radio_schema: Arc<Mutex<RadioSchemaCache>>,
+
+  /// The sequences currently running on the flight computer.
+  pub sequence_runs: Arc<Mutex<SequenceRuns>>,
+
+  /// The calibration points captured so far, awaiting a fit.
+  pub calibration_points: Arc<Mutex<CalibrationPoints>>,
 }
 
 /// The server, constructed with all route functions ready.
@@ -75,6 +92,8 @@ impl Server {
       ground: Arc::new((Mutex::new(None), Notify::new())),
       telemetry: TelemetryState::new(),
       radio_schema: Arc::new(Mutex::new(RadioSchemaCache::default())),
+      sequence_runs: Arc::new(Mutex::new(SequenceRuns::default())),
+      calibration_points: Arc::new(Mutex::new(CalibrationPoints::default())),
     };
 
     Ok(Server { shared })
@@ -117,6 +136,25 @@ impl Server {
         post(routes::activate_configuration),
       )
       .route("/operator/calibrate", post(routes::calibrate))
+      .route(
+        "/operator/calibrate/history",
+        get(routes::get_calibration_history),
+      )
+      .route("/operator/calibrate/revert", post(routes::revert_calibration))
+      .route(
+        "/operator/calibration/points",
+        get(routes::get_calibration_points),
+      )
+      .route(
+        "/operator/calibration/points",
+        post(routes::capture_calibration_point),
+      )
+      .route(
+        "/operator/calibration/points",
+        delete(routes::clear_calibration_points),
+      )
+      .route("/operator/calibration/fit", post(routes::fit_calibration))
+      .route("/operator/calibration", delete(routes::clear_calibration))
       .route("/operator/sequence", get(routes::retrieve_sequences))
       .route("/operator/sequence", put(routes::save_sequence))
       .route("/operator/sequence", delete(routes::delete_sequence))
@@ -126,6 +164,10 @@ impl Server {
       .route("/operator/set-stage", put(routes::set_abort_config))
       .route("/operator/run-sequence", post(routes::run_sequence))
       .route("/operator/stop-sequence", post(routes::stop_sequence))
+      .route("/operator/pause-sequence", post(routes::pause_sequence))
+      .route("/operator/resume-sequence", post(routes::resume_sequence))
+      .route("/operator/step-sequence", post(routes::step_sequence))
+      .route("/operator/sequence-runs", get(routes::get_sequence_runs))
       .route("/operator/abort", post(routes::abort))
       .route("/operator/trigger", get(routes::get_triggers))
       .route("/operator/trigger", put(routes::set_trigger))
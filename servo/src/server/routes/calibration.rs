@@ -0,0 +1,419 @@
+use axum::{
+  body::Bytes,
+  extract::{Query, State},
+  Json,
+};
+use common::comm::{Calibration, SensorType};
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, time::Duration};
+use tokio::time::{self as tokio_time, Instant};
+
+use crate::server::{
+  self,
+  calibration::{
+    self,
+    CalibrationModel,
+    CalibrationPoint,
+    SampleStatistics,
+    ZeroCalibration,
+    ZeroedSensor,
+  },
+  error::{bad_request, internal, not_found},
+  Shared,
+};
+
+use super::mappings::send_mappings_to_flight;
+
+/// The number of seconds readings are averaged over when zeroing if the
+/// request doesn't specify a window.
+pub const DEFAULT_ZERO_WINDOW: f64 = 1.0;
+
+/// The longest window readings may be averaged over when zeroing, in seconds.
+pub const MAX_ZERO_WINDOW: f64 = 30.0;
+
+/// The number of calibrations returned from history if no limit is given.
+const DEFAULT_CALIBRATION_HISTORY_LIMIT: u32 = 20;
+
+/// Request struct for zeroing sensors. Every field is optional, and an empty
+/// request body zeroes every pressure transducer and load cell over the
+/// default window.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct ZeroCalibrationRequest {
+  /// The sensors to zero, or every pressure transducer and load cell in the
+  /// active configuration if omitted.
+  pub sensors: Option<Vec<String>>,
+
+  /// The number of seconds to average readings over.
+  pub window: Option<f64>,
+}
+
+/// Response struct for zeroing sensors.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ZeroCalibrationResponse {
+  /// The ID of the calibration in history, used to identify it when viewing
+  /// or reverting it.
+  pub calibration_id: i64,
+
+  /// The offset changes made to each sensor that was zeroed.
+  pub sensors: HashMap<String, ZeroedSensor>,
+
+  /// Sensors which were to be zeroed but didn't report any readings during
+  /// the window, and so were left unchanged.
+  pub missing: Vec<String>,
+}
+
+/// Query parameters for retrieving calibration history.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct CalibrationHistoryQuery {
+  /// The maximum number of calibrations to include, newest first.
+  pub limit: Option<u32>,
+}
+
+/// Request struct naming a single sensor.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct CalibrationSensorRequest {
+  /// The name of the sensor, as in its mapping.
+  pub sensor: String,
+}
+
+/// Request struct for capturing a calibration point from live telemetry.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct CapturePointRequest {
+  /// The name of the sensor, as in its mapping.
+  pub sensor: String,
+
+  /// The value the sensor should currently be reading.
+  pub reference: f64,
+}
+
+/// Request struct for fitting a calibration curve.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct FitCalibrationRequest {
+  /// The name of the sensor, as in its mapping.
+  pub sensor: String,
+
+  /// The kind of curve to fit through the captured points.
+  pub model: CalibrationModel,
+}
+
+/// Response struct for a fitted calibration curve.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct FitCalibrationResponse {
+  /// The curve now stored in the mapping of the sensor.
+  pub calibration: Calibration,
+
+  /// For each captured point, in the order captured, how far the curve is
+  /// from its reference value.
+  pub residuals: Vec<f64>,
+}
+
+/// Route function which zeroes pressure transducers and load cells by averaging
+/// their readings in umbilical telemetry over a window and folding the mean
+/// into their calibrated offsets, then sends the updated mappings to flight.
+pub async fn calibrate(
+  State(shared): State<Shared>,
+  body: Bytes,
+) -> server::Result<Json<ZeroCalibrationResponse>> {
+  let request: ZeroCalibrationRequest = if body.is_empty() {
+    ZeroCalibrationRequest::default()
+  } else {
+    serde_json::from_slice(&body).map_err(bad_request)?
+  };
+
+  let window = request.window.unwrap_or(DEFAULT_ZERO_WINDOW);
+
+  if !(window > 0.0 && window <= MAX_ZERO_WINDOW) {
+    return Err(bad_request(format!(
+      "window must be more than 0 and at most {MAX_ZERO_WINDOW} seconds"
+    )));
+  }
+
+  let zeroable: HashMap<String, f64> = shared
+    .database
+    .active_mappings()
+    .await
+    .map_err(internal)?
+    .into_iter()
+    .filter(|mapping| {
+      matches!(mapping.sensor_type, SensorType::Pt | SensorType::LoadCell)
+        && !mapping.is_virtual()
+    })
+    .map(|mapping| (mapping.text_id, mapping.calibrated_offset))
+    .collect();
+
+  let sensors: Vec<String> = match request.sensors {
+    Some(sensors) => {
+      if let Some(unknown) =
+        sensors.iter().find(|sensor| !zeroable.contains_key(*sensor))
+      {
+        return Err(bad_request(format!(
+          "\"{unknown}\" is not an active pressure transducer or load cell"
+        )));
+      }
+
+      sensors
+    }
+    None => zeroable.keys().cloned().collect(),
+  };
+
+  let readings =
+    sample_readings(&shared, &sensors, Duration::from_secs_f64(window)).await;
+
+  let mut zeroed = HashMap::new();
+  let mut missing = Vec::new();
+
+  for sensor in sensors {
+    let previous_offset = zeroable[&sensor];
+
+    let Some(statistics) = readings
+      .get(&sensor)
+      .and_then(|readings| SampleStatistics::of(readings))
+    else {
+      missing.push(sensor);
+      continue;
+    };
+
+    // flight subtracts the offset from every reading, so the readings averaged
+    // here already have the previous offset removed.
+    zeroed.insert(
+      sensor,
+      ZeroedSensor {
+        previous_offset,
+        offset: previous_offset + statistics.mean,
+        statistics,
+      },
+    );
+  }
+
+  if zeroed.is_empty() {
+    return Err(not_found(
+      "no readings of the sensors to zero were received during the window",
+    ));
+  }
+
+  let calibration_id = shared
+    .database
+    .apply_zero_calibration(window, &zeroed)
+    .await
+    .map_err(internal)?;
+
+  send_mappings_to_flight(&shared).await?;
+
+  missing.sort();
+
+  Ok(Json(ZeroCalibrationResponse {
+    calibration_id,
+    sensors: zeroed,
+    missing,
+  }))
+}
+
+/// Route function which returns the most recent zero calibrations, newest
+/// first, along with the offsets each one changed.
+pub async fn get_calibration_history(
+  State(shared): State<Shared>,
+  Query(query): Query<CalibrationHistoryQuery>,
+) -> server::Result<Json<Vec<ZeroCalibration>>> {
+  let calibrations = shared
+    .database
+    .zero_calibrations(query.limit.unwrap_or(DEFAULT_CALIBRATION_HISTORY_LIMIT))
+    .await
+    .map_err(internal)?;
+
+  Ok(Json(calibrations))
+}
+
+/// Route function which undoes the most recent zero calibration that hasn't
+/// already been undone, restoring the offsets it replaced and sending the
+/// updated mappings to flight.
+pub async fn revert_calibration(
+  State(shared): State<Shared>,
+) -> server::Result<Json<ZeroCalibration>> {
+  let calibration = shared
+    .database
+    .revert_zero_calibration()
+    .await
+    .map_err(internal)?
+    .ok_or_else(|| not_found("no calibrations left to revert"))?;
+
+  send_mappings_to_flight(&shared).await?;
+
+  Ok(Json(calibration))
+}
+
+/// Collects every reading of the given sensors in umbilical telemetry until
+/// the window elapses. Sensors that have never reported a raw reading are
+/// skipped, as their value is only a placeholder, as are stale readings left
+/// over from a board that stopped reporting.
+async fn sample_readings(
+  shared: &Shared,
+  sensors: &[String],
+  window: Duration,
+) -> HashMap<String, Vec<f64>> {
+  let (vehicle, updated) = shared.telemetry.umbilical.vehicle.as_ref();
+  let deadline = Instant::now() + window;
+  let mut readings: HashMap<String, Vec<f64>> = HashMap::new();
+
+  while tokio_time::timeout_at(deadline, updated.notified()).await.is_ok() {
+    let vehicle = vehicle.lock().await;
+
+    for sensor in sensors {
+      if !vehicle.raw_readings.contains_key(sensor) {
+        continue;
+      }
+
+      if let Some(measurement) = vehicle
+        .sensor_readings
+        .get(sensor)
+        .filter(|measurement| !measurement.stale)
+      {
+        readings
+          .entry(sensor.clone())
+          .or_default()
+          .push(measurement.value);
+      }
+    }
+  }
+
+  readings
+}
+
+/// Route function which pairs the latest raw reading of a sensor in umbilical
+/// telemetry with a reference value, returning every point captured for it.
+pub async fn capture_calibration_point(
+  State(shared): State<Shared>,
+  Json(request): Json<CapturePointRequest>,
+) -> server::Result<Json<Vec<CalibrationPoint>>> {
+  let mapping = shared
+    .database
+    .active_mappings()
+    .await?
+    .into_iter()
+    .find(|mapping| mapping.text_id == request.sensor)
+    .ok_or_else(|| not_found(format!("no active mapping \"{}\"", request.sensor)))?;
+
+  if mapping.sensor_type == SensorType::Valve {
+    return Err(bad_request(format!(
+      "\"{}\" is a valve and can't be calibrated",
+      request.sensor
+    )));
+  }
+
+  if mapping.is_virtual() {
+    return Err(bad_request(format!(
+      "\"{}\" is a virtual sensor and has no raw readings to calibrate",
+      request.sensor
+    )));
+  }
+
+  let raw = shared
+    .telemetry
+    .umbilical
+    .vehicle
+    .0
+    .lock()
+    .await
+    .raw_readings
+    .get(&request.sensor)
+    .copied()
+    .ok_or_else(|| {
+      not_found(format!("no reading of \"{}\" received yet", request.sensor))
+    })?;
+
+  let point = CalibrationPoint {
+    raw,
+    reference: request.reference,
+  };
+
+  let mut points = shared.calibration_points.lock().await;
+  Ok(Json(points.add(&request.sensor, point).to_vec()))
+}
+
+/// Route function which returns the calibration points captured so far for
+/// every sensor.
+pub async fn get_calibration_points(
+  State(shared): State<Shared>,
+) -> Json<HashMap<String, Vec<CalibrationPoint>>> {
+  Json(shared.calibration_points.lock().await.all().clone())
+}
+
+/// Route function which discards the calibration points captured for a
+/// sensor.
+pub async fn clear_calibration_points(
+  State(shared): State<Shared>,
+  Json(request): Json<CalibrationSensorRequest>,
+) {
+  shared.calibration_points.lock().await.clear(&request.sensor);
+}
+
+/// Route function which fits a calibration curve through the points captured
+/// for a sensor, stores it in the sensor's active mapping, and sends the
+/// updated mappings to flight.
+pub async fn fit_calibration(
+  State(shared): State<Shared>,
+  Json(request): Json<FitCalibrationRequest>,
+) -> server::Result<Json<FitCalibrationResponse>> {
+  let points = shared
+    .calibration_points
+    .lock()
+    .await
+    .get(&request.sensor)
+    .to_vec();
+
+  let calibration =
+    calibration::fit(&points, request.model).map_err(bad_request)?;
+
+  set_calibration(&shared, &request.sensor, Some(&calibration)).await?;
+  shared.calibration_points.lock().await.clear(&request.sensor);
+
+  let residuals = points
+    .iter()
+    .map(|point| calibration.apply(point.raw) - point.reference)
+    .collect();
+
+  Ok(Json(FitCalibrationResponse {
+    calibration,
+    residuals,
+  }))
+}
+
+/// Route function which removes the calibration curve of a sensor, returning
+/// it to the default conversion of its sensor type.
+pub async fn clear_calibration(
+  State(shared): State<Shared>,
+  Json(request): Json<CalibrationSensorRequest>,
+) -> server::Result<()> {
+  set_calibration(&shared, &request.sensor, None).await
+}
+
+/// Stores a calibration curve in the active mapping of a sensor and sends the
+/// updated mappings to flight.
+async fn set_calibration(
+  shared: &Shared,
+  sensor: &str,
+  calibration: Option<&Calibration>,
+) -> server::Result<()> {
+  let updated = shared
+    .database
+    .connection
+    .lock()
+    .await
+    .execute(
+      "
+        UPDATE NodeMappings
+        SET calibration = ?1
+        WHERE text_id = ?2 AND active = TRUE
+      ",
+      params![calibration, sensor],
+    )
+    .map_err(internal)?;
+
+  if updated == 0 {
+    return Err(not_found(format!("no active mapping \"{sensor}\"")));
+  }
+
+  send_mappings_to_flight(shared).await?;
+
+  Ok(())
+}
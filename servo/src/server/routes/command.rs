@@ -1,11 +1,13 @@
 use crate::server::{
   self,
   error::{bad_request, internal},
+  flight::REPLY_TIMEOUT,
   Shared,
 };
 use axum::{extract::State, Json};
 use common::comm::{bms, FlightControlMessage, reco, Sequence};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
 /// Request struct containing all necessary information to execute a command.
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -49,12 +51,13 @@ pub struct RecoGuiCommandRequest {
   command: RecoGuiCommandPayload,
 }
 
-/// Route handler to dispatch a single manual operator command
+/// Route handler to dispatch a single manual operator command, responding
+/// once the flight computer has carried it out or rejected it.
 pub async fn dispatch_operator_command(
   State(shared): State<Shared>,
   Json(request): Json<OperatorCommandRequest>,
 ) -> server::Result<()> {
-  if let Some(flight) = shared.flight.0.lock().await.as_mut() {
+  let reply = if let Some(flight) = shared.flight.0.lock().await.as_mut() {
     let command = match request.command.as_str() {
       "click_valve" => {
         let target = request
@@ -72,6 +75,7 @@ pub async fn dispatch_operator_command(
         common::comm::FlightControlMessage::Sequence(Sequence {
           name: "command".to_owned(),
           script,
+          arguments: HashMap::new(),
         })
       }
       // Currently does nothing until the flight side is finalized
@@ -102,13 +106,12 @@ pub async fn dispatch_operator_command(
       _ => return Err(bad_request("unrecognized command identifier")),
     };
 
-    let serialized = postcard::to_allocvec(&command).map_err(internal)?;
-
-    flight.send_bytes(&serialized).await.map_err(internal)?;
+    flight.send_message(command).await.map_err(internal)?
   } else {
     return Err(internal("flight computer not connected"));
-  }
+  };
 
+  reply.wait(REPLY_TIMEOUT).await?;
   Ok(())
 }
 
@@ -117,7 +120,7 @@ pub async fn send_reco_gui_command(
   State(shared): State<Shared>,
   Json(request): Json<RecoGuiCommandRequest>,
 ) -> server::Result<()> {
-  if let Some(flight) = shared.flight.0.lock().await.as_mut() {
+  let reply = if let Some(flight) = shared.flight.0.lock().await.as_mut() {
     let RecoGuiCommandRequest { target, command } = request;
 
     let gui_command = match command {
@@ -145,13 +148,12 @@ pub async fn send_reco_gui_command(
       target,
       command: gui_command,
     });
-    let serialized = postcard::to_allocvec(&message).map_err(internal)?;
-
-    flight.send_bytes(&serialized).await.map_err(internal)?;
+    flight.send_message(message).await.map_err(internal)?
   } else {
     return Err(internal("flight computer not connected"));
-  }
+  };
 
+  reply.wait(REPLY_TIMEOUT).await?;
   Ok(())
 }
 
@@ -167,12 +169,18 @@ pub async fn enable_camera(
   State(shared): State<Shared>,
   Json(request): Json<CameraEnable>,
 )-> server::Result<()> {
-  if let Some(flight) = shared.flight.0.lock().await.as_mut() {
-    let message = FlightControlMessage::CameraEnable(request.enabled);
-    let serialized = postcard::to_allocvec(&message).map_err(internal)?;
-
-    flight.send_bytes(&serialized).await.map_err(internal)?;
+  let reply = match shared.flight.0.lock().await.as_mut() {
+    Some(flight) => {
+      let message = FlightControlMessage::CameraEnable(request.enabled);
+      Some(flight.send_message(message).await.map_err(internal)?)
+    }
+    None => None,
+  };
+
+  if let Some(reply) = reply {
+    reply.wait(REPLY_TIMEOUT).await?;
   }
+
   Ok(())
 }
 
@@ -188,12 +196,18 @@ pub async fn arm_lugs(
   State(shared): State<Shared>,
   Json(request): Json<LugArm>,
 )-> server::Result<()> {
-  if let Some(flight) = shared.flight.0.lock().await.as_mut() {
-    let message = FlightControlMessage::DetonatorArm(request.armed);
-    let serialized = postcard::to_allocvec(&message).map_err(internal)?;
-
-    flight.send_bytes(&serialized).await.map_err(internal)?;
+  let reply = match shared.flight.0.lock().await.as_mut() {
+    Some(flight) => {
+      let message = FlightControlMessage::DetonatorArm(request.armed);
+      Some(flight.send_message(message).await.map_err(internal)?)
+    }
+    None => None,
+  };
+
+  if let Some(reply) = reply {
+    reply.wait(REPLY_TIMEOUT).await?;
   }
+
   Ok(())
 }
 
@@ -208,11 +222,17 @@ pub async fn detonate_lugs(
   State(shared): State<Shared>,
   Json(request): Json<LugDetonate>,
 )-> server::Result<()> {
-  if let Some(flight) = shared.flight.0.lock().await.as_mut() {
-    let message = FlightControlMessage::DetonateEnable(request.enabled);
-    let serialized = postcard::to_allocvec(&message).map_err(internal)?;
-
-    flight.send_bytes(&serialized).await.map_err(internal)?;
+  let reply = match shared.flight.0.lock().await.as_mut() {
+    Some(flight) => {
+      let message = FlightControlMessage::DetonateEnable(request.enabled);
+      Some(flight.send_message(message).await.map_err(internal)?)
+    }
+    None => None,
+  };
+
+  if let Some(reply) = reply {
+    reply.wait(REPLY_TIMEOUT).await?;
   }
+
   Ok(())
 }
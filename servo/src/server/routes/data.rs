@@ -1,7 +1,7 @@
 use crate::server::{
   self,
   error::{bad_request, internal},
-  telemetry::TelemetrySource,
+  telemetry::{RadioSchemaStats, TelemetrySource},
   LiveTelemetry,
   Shared,
 };
@@ -25,6 +25,62 @@ use std::{
 };
 use tokio::{fs, time::MissedTickBehavior};
 
+/// The exported columns of a single sensor, with an entry for each vehicle
+/// state.
+#[derive(Clone, Debug, Default, PartialEq)]
+struct SensorColumns {
+  readings: Vec<f64>,
+  units: Vec<i32>,
+
+  /// Whether each reading was left over from a board that had stopped
+  /// reporting, or missing altogether.
+  stale: Vec<u8>,
+
+  /// When the board originally sampled each reading.
+  sampled_at: Vec<f64>,
+}
+
+impl SensorColumns {
+  fn of(name: &str, vehicle_states: &[(f64, VehicleState)]) -> Self {
+    let mut columns = SensorColumns {
+      readings: Vec::with_capacity(vehicle_states.len()),
+      units: Vec::with_capacity(vehicle_states.len()),
+      stale: Vec::with_capacity(vehicle_states.len()),
+      sampled_at: Vec::with_capacity(vehicle_states.len()),
+    };
+
+    // Yes I know iterating through the vehicle states for every sensor / valve
+    // is dumb, but I'm avoiding storing the entirety of the vehicle state in
+    // memory twice, so each sensor is grabbed seperately
+    for (_, state) in vehicle_states {
+      let value = state.sensor_readings.get(name);
+      // Put in bad data if nothing is found
+      match value {
+        Some(x) => {
+          columns.readings.push(x.value);
+
+          // Should never panic unless absurd amounts of units are added
+          let id = x.unit as i32;
+          columns.units.push(id);
+
+          columns.stale.push(x.stale as u8);
+          columns.sampled_at.push(x.timestamp);
+        }
+        // Immature but nobody will see this and not realize it's garbage data.
+        // Might replace with an infinity or something
+        None => {
+          columns.readings.push(-6942069420.0);
+          columns.units.push(-69);
+          columns.stale.push(1);
+          columns.sampled_at.push(f64::NAN);
+        }
+      };
+    }
+
+    columns
+  }
+}
+
 /// Request struct for export requests.
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct ExportRequest {
@@ -52,6 +108,7 @@ pub struct TelemetrySourceStats {
 pub struct TelemetryStatsResponse {
   umbilical: TelemetrySourceStats,
   tel: TelemetrySourceStats,
+  radio_schema: RadioSchemaStats,
 }
 
 impl TelemetrySourceQuery {
@@ -128,45 +185,33 @@ pub fn make_hdf5_file(
     .create("timestamps")?;
 
   for name in sensor_names {
-    let mut reading_vec = Vec::with_capacity(vehicle_states.len());
-    let mut unit_vec = Vec::with_capacity(vehicle_states.len());
-
-    // Yes I know iterating through the vehicle states for every sensor / valve
-    // is dumb, but I'm avoiding storing the entirety of the vehicle state in
-    // memory twice, so each sensor is grabbed seperately
-    for (_, state) in vehicle_states {
-      let value = state.sensor_readings.get(name);
-      // Put in bad data if nothing is found
-      match value {
-        Some(x) => {
-          reading_vec.push(x.value);
-
-          // Should never panic unless absurd amounts of units are added
-          let id = x.unit as i32;
-          unit_vec.push(id);
-        }
-        // Immature but nobody will see this and not realize it's garbage data.
-        // Might replace with an infinity or something
-        None => {
-          reading_vec.push(-6942069420.0);
-          unit_vec.push(-69);
-        }
-      };
-    }
+    let columns = SensorColumns::of(name, vehicle_states);
     let curr_sensor_group = sensors_group.create_group(name.as_str())?;
 
     // Make datasets
     curr_sensor_group
       .new_dataset_builder()
       .deflate(9)
-      .with_data(&reading_vec)
+      .with_data(&columns.readings)
       .create("readings")?;
 
     curr_sensor_group
       .new_dataset_builder()
       .deflate(9)
-      .with_data(&unit_vec)
+      .with_data(&columns.units)
       .create("units")?;
+
+    curr_sensor_group
+      .new_dataset_builder()
+      .deflate(9)
+      .with_data(&columns.stale)
+      .create("stale")?;
+
+    curr_sensor_group
+      .new_dataset_builder()
+      .deflate(9)
+      .with_data(&columns.sampled_at)
+      .create("sampled_at")?;
   }
 
   // A vector of all the possible ValveStates seen. Used to create the
@@ -334,8 +379,9 @@ pub async fn export(
           content += ",";
 
           // currently, if there is no data here, the column is empty.
-          // we may want to change this.
-          if let Some(reading) = reading {
+          // we may want to change this. stale readings are only the last
+          // value of a silent board, so they are left empty as well.
+          if let Some(reading) = reading.filter(|reading| !reading.stale) {
             content += &format!("{:.3}", &reading.value);
           }
         }
@@ -465,6 +511,7 @@ pub async fn telemetry_stats(
   Json(TelemetryStatsResponse {
     umbilical: telemetry_source_stats(&shared.telemetry.umbilical).await,
     tel: telemetry_source_stats(&shared.telemetry.radio).await,
+    radio_schema: shared.radio_schema.lock().await.stats(),
   })
 }
 
@@ -552,6 +599,51 @@ mod tests {
   use rand::{Rng, RngCore};
   use std::collections::HashMap;
 
+  fn reading(value: f64, timestamp: f64, stale: bool) -> Measurement {
+    Measurement {
+      value,
+      unit: Unit::Psi,
+      timestamp,
+      board_id: Some("sam-01".to_string()),
+      stale,
+    }
+  }
+
+  #[test]
+  fn sensor_columns_mark_stale_and_missing_readings() {
+    let states: Vec<(f64, VehicleState)> = [
+      Some(reading(300.0, 10.0, false)),
+      // the board went quiet, so its last reading is repeated
+      Some(reading(300.0, 10.0, true)),
+      None,
+      Some(reading(310.0, 12.5, false)),
+    ]
+    .into_iter()
+    .enumerate()
+    .map(|(i, reading)| {
+      let mut state = VehicleState::new();
+
+      if let Some(reading) = reading {
+        state.sensor_readings.insert("FUEL_PT".to_string(), reading);
+      }
+
+      (i as f64, state)
+    })
+    .collect();
+
+    let columns = SensorColumns::of("FUEL_PT", &states);
+
+    assert_eq!(columns.readings, vec![300.0, 300.0, -6942069420.0, 310.0]);
+    let psi = Unit::Psi as i32;
+    assert_eq!(columns.units, vec![psi, psi, -69, psi]);
+    assert_eq!(columns.stale, vec![0, 1, 1, 0]);
+
+    assert_eq!(columns.sampled_at[0], 10.0);
+    assert_eq!(columns.sampled_at[1], 10.0);
+    assert!(columns.sampled_at[2].is_nan());
+    assert_eq!(columns.sampled_at[3], 12.5);
+  }
+
   #[test]
   fn test_hdf5_file_creation() {
     // Do the same test a few times just cause this does use RNG
@@ -606,21 +698,31 @@ mod tests {
       for _ in 0..count {
         let mut state = VehicleState {
           valve_states: HashMap::new(),
+          valve_mismatches: HashMap::new(),
           bms: Bms::default(),
           fc_sensors: FcSensors::default(),
           gps: None,
           gps_valid: false,
           reco: Default::default(),
           reco_valid: false,
+          reco_timestamp: 0.0,
+          reco_disagreement: Default::default(),
           rbf: Default::default(),
           sensor_readings: HashMap::new(),
+          raw_readings: HashMap::new(),
+          redline_alarms: HashMap::new(),
           rolling: HashMap::new(),
+          command_delivery: HashMap::new(),
           abort_stage: AbortStage {
             name: "default".to_string(),
             abort_condition: String::new(),
             aborted: false,
             valve_safe_states: HashMap::new(),
-          }
+          },
+          triggers: HashMap::new(),
+          sequences: HashMap::new(),
+          valve_claims: HashMap::new(),
+          valve_commanders: HashMap::new(),
         };
 
         for i in 0..4 {
@@ -662,6 +764,9 @@ mod tests {
               Measurement {
                 value: x,
                 unit: sensor_units[i],
+                timestamp: 0.0,
+                board_id: None,
+                stale: false,
               },
             );
           } else {
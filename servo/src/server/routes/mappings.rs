@@ -1,5 +1,9 @@
 use axum::{extract::State, Json};
-use common::comm::{NodeMapping, AbortStageConfig, ValveSafeState, FlightControlMessage};use rusqlite::params;
+use common::{
+  comm::{Calibration, NodeMapping, AbortStageConfig, SensorType, ValveSafeState, FlightControlMessage, VehicleStateSchemaError},
+  condition,
+};
+use rusqlite::params;
 use serde::{Deserialize, Serialize};
 use serde_json::Value as JsonValue;
 use std::collections::{HashMap, HashSet};
@@ -7,9 +11,15 @@ use std::collections::{HashMap, HashSet};
 use crate::server::{
   self,
   error::{bad_request, internal, not_found, ServerResult},
+  flight::REPLY_TIMEOUT,
+  telemetry::radio_compression_schema,
   Shared,
 };
 
+/// How many of the lowest-priority radio mappings are suggested for removal
+/// when the radio subset of a configuration has too many valves or sensors.
+const RADIO_CUT_SUGGESTIONS: usize = 5;
+
 async fn refresh_radio_schema(shared: &Shared) -> server::Result<()> {
   let active_mappings = shared.database.active_mappings().await.map_err(internal)?;
   shared
@@ -166,6 +176,208 @@ fn validate_mapping_identifier(mapping: &NodeMapping) -> ServerResult<()> {
   Ok(())
 }
 
+/// Validates the fault detection settings of a mapping, which only apply to
+/// valves and require a positive timeout
+fn validate_valve_fault(mapping: &NodeMapping) -> ServerResult<()> {
+  let text_id = &mapping.text_id;
+
+  if mapping.sensor_type != SensorType::Valve
+    && (mapping.fault_timeout.is_some() || mapping.abort_on_fault.is_some())
+  {
+    return Err(bad_request(format!(
+      "mapping \"{text_id}\" sets fault detection but is not a valve"
+    )));
+  }
+
+  if let Some(timeout) = mapping.fault_timeout {
+    if !timeout.is_finite() || timeout <= 0.0 {
+      return Err(bad_request(format!(
+        "mapping \"{text_id}\" has a fault timeout that is not positive"
+      )));
+    }
+  } else if mapping.abort_on_fault == Some(true) {
+    return Err(bad_request(format!(
+      "mapping \"{text_id}\" aborts on fault but has no fault timeout"
+    )));
+  }
+
+  Ok(())
+}
+
+/// Validates the redline limits of a mapping, which only apply to sensors and
+/// must be ordered from critical low up to critical high
+fn validate_redlines(mapping: &NodeMapping) -> ServerResult<()> {
+  let text_id = &mapping.text_id;
+  let limits = [
+    mapping.critical_low,
+    mapping.warning_low,
+    mapping.warning_high,
+    mapping.critical_high,
+  ];
+
+  if mapping.sensor_type == SensorType::Valve
+    && (limits.iter().any(Option::is_some)
+      || mapping.abort_on_critical.is_some()
+      || mapping.critical_persistence.is_some())
+  {
+    return Err(bad_request(format!(
+      "mapping \"{text_id}\" sets redlines but is a valve"
+    )));
+  }
+
+  if limits.iter().flatten().any(|limit| !limit.is_finite()) {
+    return Err(bad_request(format!(
+      "mapping \"{text_id}\" has a redline that is not a finite number"
+    )));
+  }
+
+  let ordered: Vec<f64> = limits.into_iter().flatten().collect();
+  if ordered.windows(2).any(|pair| pair[0] > pair[1]) {
+    return Err(bad_request(format!(
+      "mapping \"{text_id}\" has redlines out of order, which must be \
+      critical low <= warning low <= warning high <= critical high"
+    )));
+  }
+
+  if let Some(persistence) = mapping.critical_persistence {
+    if !persistence.is_finite() || persistence < 0.0 {
+      return Err(bad_request(format!(
+        "mapping \"{text_id}\" has a critical persistence that is negative"
+      )));
+    }
+  }
+
+  if mapping.abort_on_critical == Some(true)
+    && mapping.critical_low.is_none()
+    && mapping.critical_high.is_none()
+  {
+    return Err(bad_request(format!(
+      "mapping \"{text_id}\" aborts on a critical redline but has none"
+    )));
+  }
+
+  Ok(())
+}
+
+/// Validates the calibration curve of a mapping, which flight applies to live
+/// readings and so must give a finite, well-defined value for every reading
+fn validate_calibration(mapping: &NodeMapping) -> ServerResult<()> {
+  let text_id = &mapping.text_id;
+
+  match &mapping.calibration {
+    None => Ok(()),
+    Some(Calibration::Polynomial(coefficients)) => {
+      if coefficients.is_empty() {
+        return Err(bad_request(format!(
+          "mapping \"{text_id}\" has a polynomial calibration with no \
+          coefficients"
+        )));
+      }
+
+      if coefficients.iter().any(|coefficient| !coefficient.is_finite()) {
+        return Err(bad_request(format!(
+          "mapping \"{text_id}\" has a calibration coefficient that is not a \
+          finite number"
+        )));
+      }
+
+      Ok(())
+    }
+    Some(Calibration::PiecewiseLinear(points)) => {
+      if points.len() < 2 {
+        return Err(bad_request(format!(
+          "mapping \"{text_id}\" has a piecewise linear calibration with fewer \
+          than two points"
+        )));
+      }
+
+      if points
+        .iter()
+        .any(|(raw, value)| !raw.is_finite() || !value.is_finite())
+      {
+        return Err(bad_request(format!(
+          "mapping \"{text_id}\" has a calibration point that is not a finite \
+          number"
+        )));
+      }
+
+      if points.windows(2).any(|pair| pair[0].0 >= pair[1].0) {
+        return Err(bad_request(format!(
+          "mapping \"{text_id}\" has calibration points which are not sorted \
+          by strictly increasing raw value"
+        )));
+      }
+
+      Ok(())
+    }
+  }
+}
+
+/// Validates that a virtual sensor has an expression it can be computed from.
+/// The expression itself is checked against the whole configuration by
+/// `validate_mappings`.
+fn validate_virtual_sensor(mapping: &NodeMapping) -> ServerResult<()> {
+  let text_id = &mapping.text_id;
+
+  let Some(expression) = &mapping.expression else {
+    return Ok(());
+  };
+
+  if mapping.sensor_type == SensorType::Valve {
+    return Err(bad_request(format!(
+      "mapping \"{text_id}\" has an expression but is a valve"
+    )));
+  }
+
+  if expression.trim().is_empty() {
+    return Err(bad_request(format!(
+      "mapping \"{text_id}\" has an empty expression"
+    )));
+  }
+
+  if mapping.calibration.is_some() {
+    return Err(bad_request(format!(
+      "mapping \"{text_id}\" is a virtual sensor, which has no raw readings \
+      to apply a calibration curve to"
+    )));
+  }
+
+  Ok(())
+}
+
+/// Validates that the radio subset of a configuration can be compressed,
+/// suggesting the lowest-priority radio mappings to remove if it can't. A
+/// subset too large for one frame is fine, as flight splits it across several.
+fn validate_radio_subset(mappings: &[NodeMapping]) -> ServerResult<()> {
+  let problem = match radio_compression_schema(mappings) {
+    Ok(_) => return Ok(()),
+    Err(VehicleStateSchemaError::TooManyValves) => "valves",
+    Err(VehicleStateSchemaError::TooManySensors) => "sensors",
+  };
+
+  let mut radio: Vec<&NodeMapping> =
+    mappings.iter().filter(|mapping| mapping.radio).collect();
+
+  radio.sort_by(|a, b| {
+    a.radio_priority
+      .cmp(&b.radio_priority)
+      .then_with(|| a.text_id.cmp(&b.text_id))
+  });
+
+  let lowest = radio
+    .iter()
+    .take(RADIO_CUT_SUGGESTIONS)
+    .map(|mapping| format!("\"{}\"", mapping.text_id))
+    .collect::<Vec<_>>()
+    .join(", ");
+
+  Err(bad_request(format!(
+    "too many {problem} are sent over radio, as at most {} of each fit in \
+    radio telemetry; the lowest-priority radio mappings are {lowest}",
+    u8::MAX
+  )))
+}
+
 /// Validates the text_id's of a list of mappings against python variable naming
 /// conventions
 fn validate_mappings(mappings: &Vec<NodeMapping>) -> ServerResult<()> {
@@ -186,12 +398,41 @@ fn validate_mappings(mappings: &Vec<NodeMapping>) -> ServerResult<()> {
 
     // Validate name against python naming rules
     validate_mapping_identifier(mapping)?;
+
+    validate_valve_fault(mapping)?;
+    validate_redlines(mapping)?;
+    validate_calibration(mapping)?;
+    validate_virtual_sensor(mapping)?;
   }
 
+  // the same checks the flight computer makes before accepting mappings, as it
+  // rejects the whole configuration if any expression is invalid.
+  condition::order_virtual_sensors(mappings).map_err(bad_request)?;
+
+  validate_radio_subset(mappings)?;
+
   // Yay they all passed
   Ok(())
 }
 
+/// Sends the active mappings to flight, if it is connected, and waits for it to
+/// accept them so that a rejection reaches the operator instead of leaving
+/// flight on its old mappings unnoticed.
+pub(crate) async fn send_mappings_to_flight(
+  shared: &Shared,
+) -> server::Result<()> {
+  let reply = match shared.flight.0.lock().await.as_mut() {
+    Some(flight) => Some(flight.send_mappings().await.map_err(internal)?),
+    None => None,
+  };
+
+  if let Some(reply) = reply {
+    reply.wait(REPLY_TIMEOUT).await?;
+  }
+
+  Ok(())
+}
+
 /// A route function which retrieves the current stored mappings.
 pub async fn get_mappings(
   State(shared): State<Shared>,
@@ -212,7 +453,19 @@ pub async fn get_mappings(
 				min,
 				calibrated_offset,
 				powered_threshold,
-				normally_closed
+				normally_closed,
+				fault_timeout,
+				abort_on_fault,
+				warning_low,
+				warning_high,
+				critical_low,
+				critical_high,
+				abort_on_critical,
+				critical_persistence,
+				calibration,
+				expression,
+				radio,
+				radio_priority
 			FROM NodeMappings
 		",
     )
@@ -231,6 +484,18 @@ pub async fn get_mappings(
         calibrated_offset: row.get(8)?,
         powered_threshold: row.get(9)?,
         normally_closed: row.get(10)?,
+        fault_timeout: row.get(11)?,
+        abort_on_fault: row.get(12)?,
+        warning_low: row.get(13)?,
+        warning_high: row.get(14)?,
+        critical_low: row.get(15)?,
+        critical_high: row.get(16)?,
+        abort_on_critical: row.get(17)?,
+        critical_persistence: row.get(18)?,
+        calibration: row.get(19)?,
+        expression: row.get(20)?,
+        radio: row.get(21)?,
+        radio_priority: row.get(22)?,
       };
 
       Ok((configuration_id, mapping))
@@ -337,8 +602,20 @@ pub async fn post_mappings(
 					calibrated_offset,
 					powered_threshold,
 					normally_closed,
+					fault_timeout,
+					abort_on_fault,
+					warning_low,
+					warning_high,
+					critical_low,
+					critical_high,
+					abort_on_critical,
+					critical_persistence,
+					calibration,
+					expression,
+					radio,
+					radio_priority,
 					active
-				) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, TRUE)
+				) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21, ?22, ?23, TRUE)
 			",
         params![
           request.configuration_id,
@@ -352,6 +629,18 @@ pub async fn post_mappings(
           mapping.calibrated_offset,
           mapping.powered_threshold,
           mapping.normally_closed,
+          mapping.fault_timeout,
+          mapping.abort_on_fault,
+          mapping.warning_low,
+          mapping.warning_high,
+          mapping.critical_low,
+          mapping.critical_high,
+          mapping.abort_on_critical,
+          mapping.critical_persistence,
+          mapping.calibration,
+          mapping.expression,
+          mapping.radio,
+          mapping.radio_priority,
         ],
       )
       .map_err(internal)?;
@@ -360,9 +649,7 @@ pub async fn post_mappings(
   drop(database);
   refresh_radio_schema(&shared).await?;
 
-  if let Some(flight) = shared.flight.0.lock().await.as_mut() {
-    flight.send_mappings().await.map_err(internal)?;
-  }
+  send_mappings_to_flight(&shared).await?;
 
   Ok(())
 }
@@ -393,8 +680,20 @@ pub async fn put_mappings(
 					calibrated_offset,
 					powered_threshold,
 					normally_closed,
+					fault_timeout,
+					abort_on_fault,
+					warning_low,
+					warning_high,
+					critical_low,
+					critical_high,
+					abort_on_critical,
+					critical_persistence,
+					calibration,
+					expression,
+					radio,
+					radio_priority,
 					active
-				) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, TRUE)
+				) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21, ?22, ?23, TRUE)
 				ON CONFLICT (configuration_id, text_id) DO UPDATE SET
 					board_id = excluded.board_id,
 					channel = excluded.channel,
@@ -404,6 +703,18 @@ pub async fn put_mappings(
 					offset = excluded.offset,
 					powered_threshold = excluded.powered_threshold,
 					normally_closed = excluded.normally_closed,
+					fault_timeout = excluded.fault_timeout,
+					abort_on_fault = excluded.abort_on_fault,
+					warning_low = excluded.warning_low,
+					warning_high = excluded.warning_high,
+					critical_low = excluded.critical_low,
+					critical_high = excluded.critical_high,
+					abort_on_critical = excluded.abort_on_critical,
+					critical_persistence = excluded.critical_persistence,
+					calibration = excluded.calibration,
+					expression = excluded.expression,
+					radio = excluded.radio,
+					radio_priority = excluded.radio_priority,
 					active = excluded.active
 			",
         params![
@@ -418,6 +729,18 @@ pub async fn put_mappings(
           mapping.calibrated_offset,
           mapping.powered_threshold,
           mapping.normally_closed,
+          mapping.fault_timeout,
+          mapping.abort_on_fault,
+          mapping.warning_low,
+          mapping.warning_high,
+          mapping.critical_low,
+          mapping.critical_high,
+          mapping.abort_on_critical,
+          mapping.critical_persistence,
+          mapping.calibration,
+          mapping.expression,
+          mapping.radio,
+          mapping.radio_priority,
         ],
       )
       .map_err(internal)?;
@@ -426,9 +749,7 @@ pub async fn put_mappings(
   drop(database);
   refresh_radio_schema(&shared).await?;
 
-  if let Some(flight) = shared.flight.0.lock().await.as_mut() {
-    flight.send_mappings().await.map_err(internal)?;
-  }
+  send_mappings_to_flight(&shared).await?;
 
   Ok(())
 }
@@ -452,8 +773,21 @@ pub async fn save_abort_config(
     )
     .map_err(internal)?;
 
-  if let Some(flight) = shared.flight.0.lock().await.as_mut() {
-    flight.send_all_abort_configs().await.map_err(internal)?;
+  send_abort_configs_to_flight(&shared).await?;
+
+  Ok(())
+}
+
+/// Sends every stored abort stage to flight, if it is connected, and waits for
+/// flight to accept each of them.
+async fn send_abort_configs_to_flight(shared: &Shared) -> server::Result<()> {
+  let replies = match shared.flight.0.lock().await.as_mut() {
+    Some(flight) => flight.send_all_abort_configs().await.map_err(internal)?,
+    None => Vec::new(),
+  };
+
+  for reply in replies {
+    reply.wait(REPLY_TIMEOUT).await?;
   }
 
   Ok(())
@@ -465,18 +799,25 @@ pub struct SetAbortConfig {
   stage_name : String
 }
 
-/// Add / update a specified abort config
+/// Sets the current abort stage on flight, responding once flight has either
+/// set it or rejected it.
 pub async fn set_abort_config(
   State(shared): State<Shared>,
   Json(request): Json<SetAbortConfig>,
 ) -> server::Result<()> {
   let stage_name = request.stage_name;
-  if let Some(flight) = shared.flight.0.lock().await.as_mut() {
-    let message = FlightControlMessage::SetAbortStage(stage_name);
-    let serialized = postcard::to_allocvec(&message).map_err(internal)?;
-    flight.send_bytes(&serialized).await.map_err(internal)?;
+  let reply = match shared.flight.0.lock().await.as_mut() {
+    Some(flight) => {
+      let message = FlightControlMessage::SetAbortStage(stage_name);
+      Some(flight.send_message(message).await.map_err(internal)?)
+    }
+    None => None,
+  };
+
+  if let Some(reply) = reply {
+    reply.wait(REPLY_TIMEOUT).await?;
   }
-  
+
   Ok(())
 }
 
@@ -524,9 +865,7 @@ pub async fn delete_mappings(
   drop(database);
   refresh_radio_schema(&shared).await?;
 
-  if let Some(flight) = shared.flight.0.lock().await.as_mut() {
-    flight.send_mappings().await.map_err(internal)?;
-  }
+  send_mappings_to_flight(&shared).await?;
 
   Ok(())
 }
@@ -553,9 +892,7 @@ pub async fn delete_abort_config(
 
 
 
-  if let Some(flight) = shared.flight.0.lock().await.as_mut() {
-    flight.send_all_abort_configs().await.map_err(internal)?;
-  }
+  send_abort_configs_to_flight(&shared).await?;
 
   Ok(())
 }
@@ -591,9 +928,7 @@ pub async fn activate_configuration(
 
   if rows_updated > 0 {
     refresh_radio_schema(&shared).await?;
-    if let Some(flight) = shared.flight.0.lock().await.as_mut() {
-      flight.send_mappings().await.map_err(internal)?;
-    }
+    send_mappings_to_flight(&shared).await?;
   } else {
     return Err(bad_request("configuration_id does not exist"));
   }
@@ -620,61 +955,10 @@ pub async fn get_active_configuration(
   Ok(Json(ActiveConfiguration { configuration_id }))
 }
 
-/// Maps sensor names (stored in mappings) to calibrated offset floats.
-pub type CalibratedOffsets = HashMap<String, f64>;
-
-/// Route handler to calibrate all sensors in the current configuration.
-pub async fn calibrate(
-  State(shared): State<Shared>,
-) -> server::Result<Json<CalibratedOffsets>> {
-  let database = shared.database.connection.lock().await;
-
-  let to_calibrate = database
-    .prepare(
-      "
-			SELECT text_id
-			FROM NodeMappings
-			WHERE
-				sensor_type IN ('pt', 'load_cell')
-				AND active
-		",
-    )
-    .map_err(internal)?
-    .query_and_then([], |row| row.get(0))
-    .map_err(internal)?
-    .collect::<rusqlite::Result<Vec<String>>>()
-    .map_err(internal)?;
-
-  let vehicle_state = shared.telemetry.umbilical.vehicle.0.lock().await.clone();
-  let mut updated = HashMap::new();
-
-  for sensor in to_calibrate {
-    if let Some(measurement) = vehicle_state.sensor_readings.get(&sensor) {
-      database
-        .execute(
-          "
-					UPDATE NodeMappings
-					SET calibrated_offset = ?1
-					WHERE text_id = ?2
-				",
-          params![sensor, measurement.value],
-        )
-        .map_err(internal)?;
-
-      updated.insert(sensor.clone(), measurement.value);
-    }
-  }
-
-  if let Some(flight) = shared.flight.0.lock().await.as_mut() {
-    flight.send_mappings().await.map_err(internal)?;
-  }
-
-  Ok(Json(updated))
-}
-
 #[cfg(test)]
 mod tests {
   use super::*;
+  use crate::server::error::ServerError;
 
   const SAMPLE_VALID_MAPPING_NAMES: [&str; 17] = [
     "KBT_V",
@@ -716,14 +1000,7 @@ mod tests {
     let mut mapping = NodeMapping {
       text_id: String::new(),
       board_id: String::from("sam01"),
-      sensor_type: common::comm::SensorType::Pt,
-      channel: 0,
-      computer: common::comm::Computer::Flight,
-      max: None,
-      min: None,
-      calibrated_offset: 0.0,
-      powered_threshold: None,
-      normally_closed: None,
+      ..Default::default()
     };
     let mut mapping_vector = Vec::<NodeMapping>::new();
     for name in SAMPLE_VALID_MAPPING_NAMES {
@@ -751,14 +1028,7 @@ mod tests {
     let mut mapping = NodeMapping {
       text_id: String::new(),
       board_id: String::from("sam01"),
-      sensor_type: common::comm::SensorType::Pt,
-      channel: 0,
-      computer: common::comm::Computer::Flight,
-      max: None,
-      min: None,
-      calibrated_offset: 0.0,
-      powered_threshold: None,
-      normally_closed: None,
+      ..Default::default()
     };
     let base_mapping_vector: Vec<NodeMapping> = SAMPLE_VALID_MAPPING_NAMES
       .iter()
@@ -788,14 +1058,7 @@ mod tests {
     let mut mapping = NodeMapping {
       text_id: String::new(),
       board_id: String::from("sam01"),
-      sensor_type: common::comm::SensorType::Pt,
-      channel: 0,
-      computer: common::comm::Computer::Flight,
-      max: None,
-      min: None,
-      calibrated_offset: 0.0,
-      powered_threshold: None,
-      normally_closed: None,
+      ..Default::default()
     };
     for name in SAMPLE_INVALID_MAPPING_NAMES {
       mapping.text_id = String::from(name);
@@ -812,14 +1075,7 @@ mod tests {
     let mut mapping = NodeMapping {
       text_id: String::new(),
       board_id: String::from("sam01"),
-      sensor_type: common::comm::SensorType::Pt,
-      channel: 0,
-      computer: common::comm::Computer::Flight,
-      max: None,
-      min: None,
-      calibrated_offset: 0.0,
-      powered_threshold: None,
-      normally_closed: None,
+      ..Default::default()
     };
     let base_mapping_vector: Vec<NodeMapping> = SAMPLE_VALID_MAPPING_NAMES
       .iter()
@@ -843,4 +1099,237 @@ mod tests {
       }
     }
   }
+
+  #[test]
+  fn fault_detection_only_on_valves() {
+    let mut mapping = NodeMapping {
+      text_id: String::from("BBV"),
+      board_id: String::from("sam01"),
+      sensor_type: common::comm::SensorType::Valve,
+      powered_threshold: Some(0.1),
+      normally_closed: Some(true),
+      fault_timeout: Some(0.5),
+      abort_on_fault: Some(true),
+      ..Default::default()
+    };
+    assert!(validate_valve_fault(&mapping).is_ok());
+
+    mapping.fault_timeout = None;
+    assert!(
+      validate_valve_fault(&mapping).is_err(),
+      "Aborting on fault should require a fault timeout"
+    );
+
+    mapping.fault_timeout = Some(0.0);
+    assert!(
+      validate_valve_fault(&mapping).is_err(),
+      "A fault timeout of zero should be invalid"
+    );
+
+    mapping.fault_timeout = Some(0.5);
+    mapping.sensor_type = common::comm::SensorType::Pt;
+    assert!(
+      validate_valve_fault(&mapping).is_err(),
+      "Fault detection should be invalid on a sensor"
+    );
+  }
+
+  #[test]
+  fn redlines_must_be_ordered() {
+    let mut mapping = NodeMapping {
+      text_id: String::from("FUEL_PT"),
+      board_id: String::from("sam01"),
+      max: Some(1000.0),
+      min: Some(0.0),
+      warning_high: Some(600.0),
+      critical_high: Some(750.0),
+      abort_on_critical: Some(true),
+      ..Default::default()
+    };
+    assert!(validate_redlines(&mapping).is_ok());
+
+    mapping.critical_persistence = Some(-0.1);
+    assert!(
+      validate_redlines(&mapping).is_err(),
+      "A negative critical persistence should be invalid"
+    );
+
+    mapping.critical_persistence = Some(0.25);
+    assert!(validate_redlines(&mapping).is_ok());
+
+    mapping.critical_high = Some(500.0);
+    assert!(
+      validate_redlines(&mapping).is_err(),
+      "A critical high below the warning high should be invalid"
+    );
+
+    mapping.critical_high = None;
+    assert!(
+      validate_redlines(&mapping).is_err(),
+      "Aborting on a critical redline should require one"
+    );
+
+    mapping.abort_on_critical = None;
+    mapping.warning_low = Some(f64::NAN);
+    assert!(
+      validate_redlines(&mapping).is_err(),
+      "A redline that is not a number should be invalid"
+    );
+  }
+
+  #[test]
+  fn calibration_curves_must_be_well_defined() {
+    let mut mapping = NodeMapping {
+      text_id: String::from("FUEL_PT"),
+      board_id: String::from("sam01"),
+      calibration: Some(Calibration::Polynomial(vec![1.0, 250.0])),
+      ..Default::default()
+    };
+    assert!(validate_calibration(&mapping).is_ok());
+
+    mapping.calibration =
+      Some(Calibration::PiecewiseLinear(vec![(0.0, 0.0), (0.5, 100.0)]));
+    assert!(validate_calibration(&mapping).is_ok());
+
+    let invalid = [
+      (Calibration::Polynomial(vec![]), "An empty polynomial"),
+      (
+        Calibration::Polynomial(vec![1.0, f64::NAN]),
+        "A NaN coefficient",
+      ),
+      (Calibration::PiecewiseLinear(vec![]), "An empty table"),
+      (
+        Calibration::PiecewiseLinear(vec![(0.0, 0.0)]),
+        "A single point",
+      ),
+      (
+        Calibration::PiecewiseLinear(vec![(0.0, 0.0), (f64::INFINITY, 1.0)]),
+        "An infinite point",
+      ),
+      (
+        Calibration::PiecewiseLinear(vec![(1.0, 0.0), (0.0, 1.0)]),
+        "An unsorted table",
+      ),
+      (
+        Calibration::PiecewiseLinear(vec![(0.0, 0.0), (0.0, 1.0), (1.0, 2.0)]),
+        "A table with a duplicate raw value",
+      ),
+    ];
+
+    for (calibration, description) in invalid {
+      mapping.calibration = Some(calibration);
+      assert!(
+        validate_calibration(&mapping).is_err(),
+        "{description} should be invalid"
+      );
+    }
+  }
+
+  #[test]
+  fn virtual_sensors_are_not_valves() {
+    let mut mapping = NodeMapping {
+      text_id: String::from("INJ_DP"),
+      board_id: String::from("flight"),
+      expression: Some(String::from("FUEL_PT - INJ_PT")),
+      ..Default::default()
+    };
+    assert!(validate_virtual_sensor(&mapping).is_ok());
+
+    mapping.expression = Some(String::from("  "));
+    assert!(
+      validate_virtual_sensor(&mapping).is_err(),
+      "An empty expression should be invalid"
+    );
+
+    mapping.expression = Some(String::from("max(FUEL_PT, OX_PT)"));
+    mapping.sensor_type = common::comm::SensorType::Valve;
+    assert!(
+      validate_virtual_sensor(&mapping).is_err(),
+      "A valve should not be able to be virtual"
+    );
+  }
+
+  #[test]
+  fn virtual_sensor_expressions_are_checked_against_the_configuration() {
+    let sensor = |text_id: &str, expression: Option<&str>| NodeMapping {
+      text_id: String::from(text_id),
+      board_id: String::from("sam-01"),
+      expression: expression.map(String::from),
+      ..Default::default()
+    };
+
+    let mut mappings = vec![
+      sensor("FUEL_PT", None),
+      sensor("INJ_PT", None),
+      sensor("INJ_DP", Some("FUEL_PT - INJ_PT")),
+      sensor("INJ_DP_AVG", Some("moving_average(INJ_DP, 500ms)")),
+    ];
+    assert!(validate_mappings(&mappings).is_ok());
+
+    mappings[2].expression = Some(String::from("FUEL_PT - - "));
+    assert!(
+      validate_mappings(&mappings).is_err(),
+      "An expression which doesn't parse should be invalid"
+    );
+
+    mappings[2].expression = Some(String::from("FUEL_PT - INJ_TP"));
+    assert!(
+      validate_mappings(&mappings).is_err(),
+      "An expression naming an unknown sensor should be invalid"
+    );
+
+    mappings[2].expression = Some(String::from("FUEL_PT - INJ_DP_AVG"));
+    assert!(
+      validate_mappings(&mappings).is_err(),
+      "Virtual sensors reading each other in a cycle should be invalid"
+    );
+  }
+
+  #[test]
+  fn radio_subset_must_fit_in_the_wire_format() {
+    let mapping = NodeMapping {
+      text_id: String::new(),
+      board_id: String::from("sam-21"),
+      radio: true,
+      radio_priority: 1,
+      ..Default::default()
+    };
+
+    let mut mappings: Vec<NodeMapping> = (0..12)
+      .map(|i| NodeMapping {
+        text_id: format!("PT{i:03}"),
+        ..mapping.clone()
+      })
+      .collect();
+    assert!(validate_radio_subset(&mappings).is_ok());
+
+    // more sensors than the wire format can index, which is only fine while
+    // they stay out of radio telemetry.
+    mappings.extend((12..300).map(|i| NodeMapping {
+      text_id: format!("PT{i:03}"),
+      radio: false,
+      ..mapping.clone()
+    }));
+    assert!(validate_radio_subset(&mappings).is_ok());
+
+    // several frames' worth of sensors are split across frames by flight.
+    for mapping in &mut mappings[12..200] {
+      mapping.radio = true;
+    }
+    assert!(validate_radio_subset(&mappings).is_ok());
+
+    for mapping in &mut mappings[200..] {
+      mapping.radio = true;
+    }
+    mappings[150].radio_priority = 0;
+
+    let Err(ServerError::Raw(message, _)) = validate_radio_subset(&mappings)
+    else {
+      panic!("A radio subset with too many sensors should be invalid");
+    };
+    assert!(
+      message.contains("\"PT150\""),
+      "The lowest-priority radio mapping should be suggested for removal"
+    );
+  }
 }
@@ -1,6 +1,10 @@
 /// Route functions requiring admin privilages for execution.
 pub mod admin;
 
+/// Route functions for zeroing sensors, capturing calibration points, and
+/// fitting sensor calibration curves.
+pub mod calibration;
+
 /// Route functions related to operator commands.
 pub mod command;
 
@@ -18,6 +22,7 @@ pub mod sequence;
 pub mod trigger;
 
 pub use admin::*;
+pub use calibration::*;
 pub use command::*;
 pub use data::*;
 pub use mappings::*;
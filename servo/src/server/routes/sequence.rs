@@ -1,14 +1,82 @@
-use axum::{extract::State, Json};
-use common::comm::Sequence;
-use rusqlite::params;
+use axum::{
+  extract::{Query, State},
+  http::StatusCode,
+  Json,
+};
+use common::comm::{NodeMapping, Sequence, SequenceParameter, SequenceValue};
+use jeflog::warn;
+use rusqlite::{params, types::Type, Row};
 use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::HashMap;
 
 use crate::server::{
   self,
-  error::{bad_request, internal},
+  error::{bad_request, internal, ServerError},
+  flight::REPLY_TIMEOUT,
+  sequence_check,
+  sequence_runs::SequenceRunRecord,
   Shared,
 };
 
+/// The number of finished runs returned when no limit is given.
+const DEFAULT_FINISHED_RUN_LIMIT: u32 = 20;
+
+/// Checks `script` and the parameters it declares against `mappings` before
+/// it is stored or sent to flight, rejecting it with the list of diagnostics
+/// if any problems are found.
+///
+/// If the check itself can't be performed, such as when `python3` isn't
+/// installed, the script is let through with a warning rather than blocking
+/// operations.
+async fn check_script(
+  mappings: &[NodeMapping],
+  script: &str,
+  parameters: &[SequenceParameter],
+) -> server::Result<()> {
+  sequence_check::check_parameters(parameters, mappings)
+    .map_err(bad_request)?;
+
+  let mappings = mappings.to_vec();
+  let script = script.to_owned();
+  let parameters = parameters.to_vec();
+
+  let result = tokio::task::spawn_blocking(move || {
+    sequence_check::check_sequence(&script, &mappings, &parameters)
+  })
+  .await
+  .map_err(internal)?;
+
+  match result {
+    Ok(diagnostics) if diagnostics.is_empty() => Ok(()),
+    Ok(diagnostics) => Err(ServerError::Json(
+      json!({ "diagnostics": diagnostics }),
+      StatusCode::BAD_REQUEST,
+    )),
+    Err(error) => {
+      warn!("Skipping static check of sequence: {error}");
+      Ok(())
+    }
+  }
+}
+
+/// Reads the JSON-encoded parameters of a sequence from column `index` of a
+/// row of the `Sequences` table.
+fn parameters_at(
+  row: &Row<'_>,
+  index: usize,
+) -> rusqlite::Result<Vec<SequenceParameter>> {
+  let json = row.get::<_, String>(index)?;
+
+  serde_json::from_str(&json).map_err(|error| {
+    rusqlite::Error::FromSqlConversionFailure(
+      index,
+      Type::Text,
+      Box::new(error),
+    )
+  })
+}
+
 /// Used in sequences response struct to attach the configuration ID.
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct SequenceWithConfiguration {
@@ -20,6 +88,9 @@ pub struct SequenceWithConfiguration {
 
   /// The ID of the configuration associated with the sequence.
   pub configuration_id: Option<String>,
+
+  /// The parameters which must be given values to run the sequence.
+  pub parameters: Vec<SequenceParameter>,
 }
 
 /// Response struct for getting the sequences stored in the database.
@@ -38,13 +109,14 @@ pub async fn retrieve_sequences(
     .connection
     .lock()
     .await
-    .prepare("SELECT name, script, configuration_id FROM Sequences")
+    .prepare("SELECT name, script, configuration_id, parameters FROM Sequences")
     .map_err(internal)?
     .query_map([], |row| {
       Ok(SequenceWithConfiguration {
         name: row.get(0)?,
         script: row.get(1)?,
         configuration_id: row.get(2)?,
+        parameters: parameters_at(row, 3)?,
       })
     })
     .map_err(internal)?
@@ -65,9 +137,18 @@ pub struct SaveSequenceRequest {
 
   /// The Base64-encoded script to save.
   pub script: String,
+
+  /// The typed parameters the script expects to be bound when it's run.
+  #[serde(default)]
+  pub parameters: Vec<SequenceParameter>,
 }
 
 /// A route function which saves a sequence without running it.
+///
+/// The sequence is rejected with a list of diagnostics if it doesn't compile
+/// or relies on mappings which aren't active, and with a message if its
+/// parameters can't be bound. The abort sequence can't declare parameters, as
+/// nothing could supply their values when it's run.
 pub async fn save_sequence(
   State(shared): State<Shared>,
   Json(request): Json<SaveSequenceRequest>,
@@ -76,28 +157,52 @@ pub async fn save_sequence(
     .map_err(bad_request)
     .and_then(|bytes| String::from_utf8(bytes).map_err(bad_request))?;
 
+  if request.name == "abort" && !request.parameters.is_empty() {
+    return Err(bad_request("the abort sequence can't declare parameters"));
+  }
+
+  let mappings = shared.database.active_mappings().await?;
+  check_script(&mappings, &decoded_script, &request.parameters).await?;
+
+  let parameters =
+    serde_json::to_string(&request.parameters).map_err(internal)?;
+
   shared
     .database
     .connection
     .lock()
     .await
     .execute(
-      "INSERT OR REPLACE INTO Sequences (name, configuration_id, script)
-      VALUES (?1, ?2, ?3)",
-      params![request.name, request.configuration_id, decoded_script],
+      "INSERT OR REPLACE INTO Sequences
+        (name, configuration_id, script, parameters)
+      VALUES (?1, ?2, ?3, ?4)",
+      params![
+        request.name,
+        request.configuration_id,
+        decoded_script,
+        parameters
+      ],
     )
     .map_err(internal)?;
 
   // if the incoming sequence is the abort sequence, immediately send it over to
   // flight to be saved, _not run_.
   if request.name == "abort" {
-    if let Some(flight) = shared.flight.0.lock().await.as_mut() {
-      let sequence = Sequence {
-        name: request.name,
-        script: decoded_script,
-      };
+    let reply = match shared.flight.0.lock().await.as_mut() {
+      Some(flight) => {
+        let sequence = Sequence {
+          name: request.name,
+          script: decoded_script,
+          arguments: HashMap::new(),
+        };
+
+        Some(flight.send_sequence(sequence).await.map_err(internal)?)
+      }
+      None => None,
+    };
 
-      flight.send_sequence(sequence).await.map_err(internal)?;
+    if let Some(reply) = reply {
+      reply.wait(REPLY_TIMEOUT).await?;
     }
   }
 
@@ -136,10 +241,20 @@ pub struct RunSequenceRequest {
   /// Force the sequence to be executed, even if the configuration IDs do not
   /// match.
   pub force: Option<bool>,
+
+  /// The values of the sequence's parameters by name. Parameters left out
+  /// take their defaults.
+  #[serde(default)]
+  pub arguments: HashMap<String, SequenceValue>,
 }
 
 /// Route function which receives a sequence and sends it directly to the flight
 /// computer.
+///
+/// The stored script, other than the abort sequence, is checked again against
+/// the active mappings, as they may have changed since it was saved, and the
+/// given arguments are checked against its parameters. Responds once flight
+/// has started the sequence or rejected it.
 pub async fn run_sequence(
   State(shared): State<Shared>,
   Json(request): Json<RunSequenceRequest>,
@@ -147,38 +262,52 @@ pub async fn run_sequence(
   // TODO: Add check for active configuration against the configuration_id in
   // the database.
 
-  let sequence = shared
+  let (script, parameters) = shared
     .database
     .connection
     .lock()
     .await
     .query_row(
-      "SELECT script FROM Sequences WHERE name = ?1",
+      "SELECT script, parameters FROM Sequences WHERE name = ?1",
       [&request.name],
-      |row| {
-        Ok(Sequence {
-          name: request.name.clone(),
-          script: row.get(0)?,
-        })
-      },
+      |row| Ok((row.get::<_, String>(0)?, parameters_at(row, 1)?)),
+    )
+    .map_err(bad_request)?;
+
+  let mut sequence = Sequence {
+    name: request.name,
+    script,
+    arguments: HashMap::new(),
+  };
+
+  // an abort must never be held up, even by a stale abort sequence.
+  if sequence.name != "abort" {
+    let mappings = shared.database.active_mappings().await?;
+
+    sequence.arguments = sequence_check::resolve_arguments(
+      &parameters,
+      &request.arguments,
+      &mappings,
     )
     .map_err(bad_request)?;
 
-  if let Some(flight) = shared.flight.0.lock().await.as_mut() {
+    check_script(&mappings, &sequence.script, &parameters).await?;
+  }
+
+  let reply = if let Some(flight) = shared.flight.0.lock().await.as_mut() {
     // special case for abort sequence, because sending it over just saves it
     // so we need to send an actual abort control message if we want to run it
     if sequence.name == "abort" {
-      flight.abort().await.map_err(internal)?;
-
-      return Ok(());
+      flight.abort().await.map_err(internal)?
+    } else {
+      // otherwise, send the sequence as normal to the flight computer
+      flight.send_sequence(sequence).await.map_err(internal)?
     }
-
-    // otherwise, send the sequence as normal to the flight computer
-    flight.send_sequence(sequence).await.map_err(internal)?;
   } else {
     return Err(internal("flight computer not connected"));
-  }
+  };
 
+  reply.wait(REPLY_TIMEOUT).await?;
   Ok(())
 }
 
@@ -194,7 +323,7 @@ pub async fn stop_sequence(
   State(shared): State<Shared>,
   Json(request): Json<StopSequenceRequest>,
 ) -> server::Result<()> {
-  shared
+  let reply = shared
     .flight
     .0
     .lock()
@@ -205,12 +334,83 @@ pub async fn stop_sequence(
     .await
     .map_err(internal)?;
 
+  reply.wait(REPLY_TIMEOUT).await?;
+  Ok(())
+}
+
+/// Request struct for pausing, resuming, or stepping a sequence.
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct ControlSequenceRequest {
+  /// Name of the running sequence to control.
+  pub name: String,
+}
+
+/// Route function which instructs the flight computer to pause a sequence,
+/// holding it at its current or next wait.
+pub async fn pause_sequence(
+  State(shared): State<Shared>,
+  Json(request): Json<ControlSequenceRequest>,
+) -> server::Result<()> {
+  let reply = shared
+    .flight
+    .0
+    .lock()
+    .await
+    .as_mut()
+    .ok_or(internal("flight computer not connected"))?
+    .pause_sequence(request.name)
+    .await
+    .map_err(internal)?;
+
+  reply.wait(REPLY_TIMEOUT).await?;
+  Ok(())
+}
+
+/// Route function which instructs the flight computer to resume a paused
+/// sequence.
+pub async fn resume_sequence(
+  State(shared): State<Shared>,
+  Json(request): Json<ControlSequenceRequest>,
+) -> server::Result<()> {
+  let reply = shared
+    .flight
+    .0
+    .lock()
+    .await
+    .as_mut()
+    .ok_or(internal("flight computer not connected"))?
+    .resume_sequence(request.name)
+    .await
+    .map_err(internal)?;
+
+  reply.wait(REPLY_TIMEOUT).await?;
+  Ok(())
+}
+
+/// Route function which instructs the flight computer to let a paused
+/// sequence past the wait it's held at, holding it again at the next one.
+pub async fn step_sequence(
+  State(shared): State<Shared>,
+  Json(request): Json<ControlSequenceRequest>,
+) -> server::Result<()> {
+  let reply = shared
+    .flight
+    .0
+    .lock()
+    .await
+    .as_mut()
+    .ok_or(internal("flight computer not connected"))?
+    .step_sequence(request.name)
+    .await
+    .map_err(internal)?;
+
+  reply.wait(REPLY_TIMEOUT).await?;
   Ok(())
 }
 
 /// Route function which instructs the flight computer to abort.
 pub async fn abort(State(shared): State<Shared>) -> server::Result<()> {
-  shared
+  let reply = shared
     .flight
     .0
     .lock()
@@ -221,5 +421,50 @@ pub async fn abort(State(shared): State<Shared>) -> server::Result<()> {
     .await
     .map_err(internal)?;
 
+  reply.wait(REPLY_TIMEOUT).await?;
   Ok(())
 }
+
+/// Query parameters for retrieving sequence runs.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct SequenceRunsQuery {
+  /// Only include runs of the sequence with this name.
+  pub name: Option<String>,
+
+  /// The maximum number of finished runs to include, newest first.
+  pub limit: Option<u32>,
+}
+
+/// Response struct for retrieving sequence runs and their output.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct SequenceRunsResponse {
+  /// Runs still in progress on the flight computer, oldest first.
+  pub running: Vec<SequenceRunRecord>,
+
+  /// The most recent finished runs, newest first.
+  pub finished: Vec<SequenceRunRecord>,
+}
+
+/// Route function which retrieves the running and recently finished sequence
+/// runs along with everything they wrote to stdout and stderr.
+pub async fn get_sequence_runs(
+  State(shared): State<Shared>,
+  Query(query): Query<SequenceRunsQuery>,
+) -> server::Result<Json<SequenceRunsResponse>> {
+  let mut running = shared.sequence_runs.lock().await.running();
+
+  if let Some(name) = &query.name {
+    running.retain(|record| &record.run.name == name);
+  }
+
+  let finished = shared
+    .database
+    .finished_sequence_runs(
+      query.name.as_deref(),
+      query.limit.unwrap_or(DEFAULT_FINISHED_RUN_LIMIT),
+    )
+    .await
+    .map_err(internal)?;
+
+  Ok(Json(SequenceRunsResponse { running, finished }))
+}
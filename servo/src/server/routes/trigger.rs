@@ -3,7 +3,7 @@ use common::comm::Trigger;
 use rusqlite::params;
 use serde::{Deserialize, Serialize};
 
-use crate::server::{self, error::internal, Shared};
+use crate::server::{self, error::internal, flight::REPLY_TIMEOUT, Shared};
 
 /// Route function which returns all existing triggers in the database.
 pub async fn get_triggers(
@@ -58,23 +58,28 @@ pub async fn set_trigger(
 
   drop(database);
 
-  if let Some(flight) = shared.flight.0.lock().await.as_mut() {
-    flight.send_trigger(request).await.map_err(internal)?;
+  let reply = match shared.flight.0.lock().await.as_mut() {
+    Some(flight) => Some(flight.send_trigger(request).await.map_err(internal)?),
+    None => None,
+  };
+
+  if let Some(reply) = reply {
+    reply.wait(REPLY_TIMEOUT).await?;
   }
 
   Ok(())
 }
 
-/// Request struct used to delete a trigger from the database and make it
-/// inactive on the flight computer.
+/// Request struct used to delete a trigger from the database and the flight
+/// computer.
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct DeleteTriggerRequest {
   /// The name of the trigger to be deleted.
   pub name: String,
 }
 
-/// Route function which deletes a trigger from the database and sets it
-/// inactive on the flight computer.
+/// Route function which deletes a trigger from the database and the flight
+/// computer.
 pub async fn delete_trigger(
   State(shared): State<Shared>,
   Json(request): Json<DeleteTriggerRequest>,
@@ -90,16 +95,18 @@ pub async fn delete_trigger(
 
   drop(database);
 
-  if let Some(flight) = shared.flight.0.lock().await.as_mut() {
-    flight
-      .send_trigger(Trigger {
-        name: request.name,
-        condition: "False".to_owned(),
-        script: "".to_owned(),
-        active: false,
-      })
-      .await
-      .map_err(internal)?;
+  let reply = match shared.flight.0.lock().await.as_mut() {
+    Some(flight) => Some(
+      flight
+        .delete_trigger(request.name)
+        .await
+        .map_err(internal)?,
+    ),
+    None => None,
+  };
+
+  if let Some(reply) = reply {
+    reply.wait(REPLY_TIMEOUT).await?;
   }
 
   Ok(())
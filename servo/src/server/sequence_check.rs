@@ -0,0 +1,620 @@
+use common::comm::{
+  NodeMapping,
+  SensorType,
+  SequenceArgument,
+  SequenceParameter,
+  SequenceValue,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+  collections::HashMap,
+  io::{self, Write},
+  process::{Command, Stdio},
+};
+
+/// Names bound by `from common import *` and the rest of the prelude that
+/// flight prepends to every sequence, besides the mappings themselves.
+///
+/// Must be kept in sync with the module defined in `common/src/sequence` and
+/// with `flight2::sequence::run`.
+const PRELUDE_NAMES: [&str; 67] = [
+  // prelude constants
+  "OPEN",
+  "CLOSED",
+  // unit classes and their constants
+  "Current",
+  "Duration",
+  "ElectricPotential",
+  "Force",
+  "Pressure",
+  "Temperature",
+  "A",
+  "mA",
+  "s",
+  "ms",
+  "us",
+  "V",
+  "mV",
+  "lbf",
+  "psi",
+  "K",
+  // device classes
+  "Sensor",
+  "Valve",
+  "ValveClaim",
+  "ValveState",
+  "ValveSafeState",
+  "IntervalIterator",
+  // vehicle state classes
+  "Vector3",
+  "ImuReading",
+  "BarometerReading",
+  "GpsReading",
+  "BusReading",
+  "BmsReading",
+  "RecoReading",
+  // exceptions
+  "StaleReadingError",
+  "ValveNotFoundError",
+  "AbortStageNotFoundError",
+  "BoardNotFoundError",
+  "CommandRejectedError",
+  "ValveClaimedError",
+  "SendCommandIpcError",
+  "CommandTimeoutError",
+  // functions
+  "wait_for",
+  "wait_until",
+  "abort",
+  "interval",
+  "claim",
+  "create_abort_stage",
+  "set_abort_stage",
+  "send_sams_abort",
+  "curr_abort_stage",
+  "curr_abort_condition",
+  "aborted_in_this_stage",
+  "send_reco_launch",
+  "reco_init_ekf",
+  "read_umbilical_voltage",
+  "reco_recvd_launch",
+  "read_imu",
+  "read_magnetometer",
+  "read_barometer",
+  "read_gps",
+  "read_bms",
+  "read_reco",
+  "launch_lug_arm",
+  "launch_lug_detonate",
+  "set_servo_disconnect_monitoring",
+  "sam_camera_toggle",
+  // module attributes
+  "__layout_fingerprint__",
+  "__name__",
+  "__file__",
+];
+
+/// Words which can't be used as names in Python, and so can't name a sequence
+/// parameter either.
+const PYTHON_KEYWORDS: [&str; 35] = [
+  "False", "None", "True", "and", "as", "assert", "async", "await", "break",
+  "class", "continue", "def", "del", "elif", "else", "except", "finally",
+  "for", "from", "global", "if", "import", "in", "is", "lambda", "nonlocal",
+  "not", "or", "pass", "raise", "return", "try", "while", "with", "yield",
+];
+
+/// Compiles the script read from stdin and reports, as JSON, either the
+/// syntax error preventing compilation or every name the script loads
+/// without binding it itself.
+///
+/// Bindings are collected across the whole script regardless of scope, so a
+/// name assigned only inside one function is also accepted elsewhere. This
+/// errs on the side of missing a typo rather than rejecting a valid script.
+const ANALYZER: &str = r#"
+import ast, builtins, json, sys
+
+source = sys.stdin.read()
+
+try:
+    tree = ast.parse(source, "<sequence>")
+    compile(tree, "<sequence>", "exec")
+except SyntaxError as e:
+    json.dump({"syntax_error": {
+        "line": e.lineno or 0,
+        "column": e.offset or 0,
+        "message": e.msg,
+    }}, sys.stdout)
+    sys.exit(0)
+
+bound = set(dir(builtins))
+for node in ast.walk(tree):
+    if isinstance(node, ast.Name) and not isinstance(node.ctx, ast.Load):
+        bound.add(node.id)
+    elif isinstance(node, (ast.FunctionDef, ast.AsyncFunctionDef, ast.ClassDef)):
+        bound.add(node.name)
+    elif isinstance(node, ast.arg):
+        bound.add(node.arg)
+    elif isinstance(node, ast.alias):
+        bound.add((node.asname or node.name).split(".")[0])
+    elif isinstance(node, ast.ExceptHandler) and node.name:
+        bound.add(node.name)
+    elif isinstance(node, (ast.Global, ast.Nonlocal)):
+        bound.update(node.names)
+    elif type(node).__name__ in ("MatchAs", "MatchStar") and node.name:
+        bound.add(node.name)
+    elif type(node).__name__ == "MatchMapping" and node.rest:
+        bound.add(node.rest)
+
+names = []
+devices = []
+for node in ast.walk(tree):
+    if isinstance(node, ast.Name) and isinstance(node.ctx, ast.Load):
+        if node.id not in bound:
+            names.append([node.id, node.lineno, node.col_offset + 1])
+    elif (
+        isinstance(node, ast.Call)
+        and isinstance(node.func, ast.Name)
+        and node.func.id in ("Valve", "Sensor")
+        and node.args
+        and isinstance(node.args[0], ast.Constant)
+        and isinstance(node.args[0].value, str)
+    ):
+        argument = node.args[0]
+        devices.append([
+            node.func.id,
+            argument.value,
+            argument.lineno,
+            argument.col_offset + 1,
+        ])
+
+json.dump({"names": names, "devices": devices}, sys.stdout)
+"#;
+
+/// The kind of problem found in a sequence.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DiagnosticKind {
+  /// The script does not compile.
+  SyntaxError,
+
+  /// A name is used which is neither defined by the script, built into
+  /// Python, bound by the prelude from an active mapping, nor a parameter.
+  UnknownName,
+
+  /// A `Valve` or `Sensor` is constructed with a name that has no active
+  /// mapping of that kind.
+  UnknownDevice,
+}
+
+/// A single problem found in a sequence, located by line and column.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct SequenceDiagnostic {
+  /// What kind of problem this is.
+  pub kind: DiagnosticKind,
+
+  /// The line of the script the problem is on, starting at 1.
+  pub line: u32,
+
+  /// The column of the line the problem starts at, starting at 1.
+  pub column: u32,
+
+  /// A description of the problem.
+  pub message: String,
+}
+
+/// The raw output of `ANALYZER`.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum AnalyzerReport {
+  SyntaxError {
+    syntax_error: RawSyntaxError,
+  },
+  Names {
+    names: Vec<(String, u32, u32)>,
+    devices: Vec<(String, String, u32, u32)>,
+  },
+}
+
+#[derive(Debug, Deserialize)]
+struct RawSyntaxError {
+  line: u32,
+  column: u32,
+  message: String,
+}
+
+/// Compiles `script` and checks every name it relies on the prelude for
+/// against `mappings` and the names of its `parameters`, returning the
+/// problems found in order of appearance.
+///
+/// An empty list means the script is safe to send to flight, as far as can
+/// be told without running it. Requires `python3` to be installed.
+pub fn check_sequence(
+  script: &str,
+  mappings: &[NodeMapping],
+  parameters: &[SequenceParameter],
+) -> io::Result<Vec<SequenceDiagnostic>> {
+  let mut analyzer = Command::new("python3")
+    .args(["-c", ANALYZER])
+    .stdin(Stdio::piped())
+    .stdout(Stdio::piped())
+    .stderr(Stdio::piped())
+    .spawn()?;
+
+  if let Some(mut stdin) = analyzer.stdin.take() {
+    stdin.write_all(script.as_bytes())?;
+  }
+
+  let output = analyzer.wait_with_output()?;
+
+  if !output.status.success() {
+    return Err(io::Error::other(format!(
+      "sequence analyzer failed: {}",
+      String::from_utf8_lossy(&output.stderr).trim()
+    )));
+  }
+
+  let report = serde_json::from_slice::<AnalyzerReport>(&output.stdout)
+    .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+
+  Ok(diagnose(report, mappings, parameters))
+}
+
+/// Turns the names reported by the analyzer into diagnostics.
+fn diagnose(
+  report: AnalyzerReport,
+  mappings: &[NodeMapping],
+  parameters: &[SequenceParameter],
+) -> Vec<SequenceDiagnostic> {
+  let (names, devices) = match report {
+    AnalyzerReport::SyntaxError { syntax_error } => {
+      return vec![SequenceDiagnostic {
+        kind: DiagnosticKind::SyntaxError,
+        line: syntax_error.line,
+        column: syntax_error.column,
+        message: syntax_error.message,
+      }];
+    }
+    AnalyzerReport::Names { names, devices } => (names, devices),
+  };
+
+  let mappings = sensor_types(mappings);
+
+  let mut diagnostics = Vec::new();
+
+  for (name, line, column) in names {
+    if PRELUDE_NAMES.contains(&name.as_str())
+      || mappings.contains_key(name.as_str())
+      || parameters.iter().any(|parameter| parameter.name == name)
+    {
+      continue;
+    }
+
+    diagnostics.push(SequenceDiagnostic {
+      kind: DiagnosticKind::UnknownName,
+      line,
+      column,
+      message: format!(
+        "'{name}' is not defined and is neither an active mapping nor a \
+        parameter"
+      ),
+    });
+  }
+
+  for (class, name, line, column) in devices {
+    let message = match (class.as_str(), mappings.get(name.as_str())) {
+      (_, None) => format!("'{name}' is not an active mapping"),
+      ("Valve", Some(sensor_type)) if *sensor_type != SensorType::Valve => {
+        format!("'{name}' is mapped as a sensor, not a valve")
+      }
+      ("Sensor", Some(SensorType::Valve)) => {
+        format!("'{name}' is mapped as a valve, not a sensor")
+      }
+      _ => continue,
+    };
+
+    diagnostics.push(SequenceDiagnostic {
+      kind: DiagnosticKind::UnknownDevice,
+      line,
+      column,
+      message,
+    });
+  }
+
+  diagnostics.sort_by_key(|diagnostic| (diagnostic.line, diagnostic.column));
+  diagnostics
+}
+
+/// Checks the parameters declared by a sequence, returning why they can't be
+/// bound alongside the prelude and `mappings` if they can't.
+pub fn check_parameters(
+  parameters: &[SequenceParameter],
+  mappings: &[NodeMapping],
+) -> Result<(), String> {
+  let mappings = sensor_types(mappings);
+
+  for (index, parameter) in parameters.iter().enumerate() {
+    let name = parameter.name.as_str();
+
+    if !is_identifier(name) {
+      return Err(format!("parameter '{name}' is not a valid Python name"));
+    }
+
+    if PRELUDE_NAMES.contains(&name) {
+      return Err(format!("parameter '{name}' would shadow the prelude"));
+    }
+
+    if mappings.contains_key(name) {
+      return Err(format!("parameter '{name}' would shadow an active mapping"));
+    }
+
+    if parameters[..index].iter().any(|other| other.name == name) {
+      return Err(format!("parameter '{name}' is declared more than once"));
+    }
+
+    if let Some(default) = &parameter.default {
+      argument(parameter, default, &mappings)
+        .map_err(|error| format!("default of parameter {error}"))?;
+    }
+  }
+
+  Ok(())
+}
+
+/// Checks the values given to run a sequence against the parameters it
+/// declares, filling in defaults, and returns the arguments to bind in it.
+pub fn resolve_arguments(
+  parameters: &[SequenceParameter],
+  values: &HashMap<String, SequenceValue>,
+  mappings: &[NodeMapping],
+) -> Result<HashMap<String, SequenceArgument>, String> {
+  let mappings = sensor_types(mappings);
+
+  if let Some(unknown) = values
+    .keys()
+    .find(|name| !parameters.iter().any(|parameter| &parameter.name == *name))
+  {
+    return Err(format!("the sequence has no parameter named '{unknown}'"));
+  }
+
+  parameters
+    .iter()
+    .map(|parameter| {
+      let value = values
+        .get(&parameter.name)
+        .or(parameter.default.as_ref())
+        .ok_or_else(|| {
+          format!("no value was given for parameter '{}'", parameter.name)
+        })?;
+
+      let argument = argument(parameter, value, &mappings)
+        .map_err(|error| format!("value of parameter {error}"))?;
+
+      Ok((parameter.name.clone(), argument))
+    })
+    .collect()
+}
+
+/// Checks a single value given for `parameter`, including that a valve is
+/// actively mapped as one.
+fn argument(
+  parameter: &SequenceParameter,
+  value: &SequenceValue,
+  mappings: &HashMap<&str, SensorType>,
+) -> Result<SequenceArgument, String> {
+  let name = &parameter.name;
+  let argument = parameter
+    .kind
+    .argument(value)
+    .map_err(|error| format!("'{name}' is invalid: {error}"))?;
+
+  if let SequenceArgument::Valve(valve) = &argument {
+    if mappings.get(valve.as_str()) != Some(&SensorType::Valve) {
+      return Err(format!(
+        "'{name}' is invalid: '{valve}' is not a mapped valve"
+      ));
+    }
+  }
+
+  Ok(argument)
+}
+
+fn sensor_types(mappings: &[NodeMapping]) -> HashMap<&str, SensorType> {
+  mappings
+    .iter()
+    .map(|mapping| (mapping.text_id.as_str(), mapping.sensor_type))
+    .collect()
+}
+
+/// Whether `name` can be bound and referred to as a Python name. Only ASCII
+/// names are accepted, even though Python allows others.
+fn is_identifier(name: &str) -> bool {
+  let mut characters = name.chars();
+
+  let starts_well = characters
+    .next()
+    .is_some_and(|first| first.is_ascii_alphabetic() || first == '_');
+
+  starts_well
+    && characters.all(|rest| rest.is_ascii_alphanumeric() || rest == '_')
+    && !PYTHON_KEYWORDS.contains(&name)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use common::comm::SequenceParameterKind;
+
+  fn mapping(text_id: &str, sensor_type: SensorType) -> NodeMapping {
+    NodeMapping {
+      text_id: text_id.to_string(),
+      board_id: "sam-01".to_string(),
+      sensor_type,
+      channel: 1,
+      ..Default::default()
+    }
+  }
+
+  #[test]
+  fn syntax_errors_are_reported_alone() {
+    let report = AnalyzerReport::SyntaxError {
+      syntax_error: RawSyntaxError {
+        line: 3,
+        column: 8,
+        message: "invalid syntax".to_string(),
+      },
+    };
+
+    let diagnostics = diagnose(report, &[], &[]);
+
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].kind, DiagnosticKind::SyntaxError);
+    assert_eq!((diagnostics[0].line, diagnostics[0].column), (3, 8));
+  }
+
+  #[test]
+  fn unknown_names_and_devices_are_reported_in_order() {
+    let mappings = [
+      mapping("BBV", SensorType::Valve),
+      mapping("FUEL_PT", SensorType::Pt),
+    ];
+
+    let report = AnalyzerReport::Names {
+      names: vec![
+        ("BVV".to_string(), 4, 1),
+        ("BBV".to_string(), 2, 1),
+        ("FUEL_PT".to_string(), 3, 10),
+        ("wait_for".to_string(), 5, 1),
+        ("psi".to_string(), 3, 24),
+      ],
+      devices: vec![
+        ("Valve".to_string(), "FUEL_PT".to_string(), 1, 13),
+        ("Sensor".to_string(), "FUEL_PT".to_string(), 6, 14),
+        ("Valve".to_string(), "OX_MAIN".to_string(), 7, 13),
+      ],
+    };
+
+    let diagnostics = diagnose(report, &mappings, &[]);
+    let found: Vec<_> = diagnostics
+      .iter()
+      .map(|diagnostic| (diagnostic.kind, diagnostic.line))
+      .collect();
+
+    assert_eq!(
+      found,
+      [
+        (DiagnosticKind::UnknownDevice, 1),
+        (DiagnosticKind::UnknownName, 4),
+        (DiagnosticKind::UnknownDevice, 7),
+      ]
+    );
+  }
+
+  fn parameter(
+    name: &str,
+    kind: SequenceParameterKind,
+    default: Option<SequenceValue>,
+  ) -> SequenceParameter {
+    SequenceParameter {
+      name: name.to_string(),
+      kind,
+      default,
+    }
+  }
+
+  #[test]
+  fn parameters_must_be_unique_names_with_valid_defaults() {
+    let mappings = [
+      mapping("BBV", SensorType::Valve),
+      mapping("FUEL_PT", SensorType::Pt),
+    ];
+
+    let rejected = [
+      parameter("hold time", SequenceParameterKind::Duration, None),
+      parameter("lambda", SequenceParameterKind::Number, None),
+      parameter("psi", SequenceParameterKind::Pressure, None),
+      parameter("BBV", SequenceParameterKind::Valve, None),
+      parameter(
+        "hold",
+        SequenceParameterKind::Duration,
+        Some(SequenceValue::Number(-1.0)),
+      ),
+      parameter(
+        "vent",
+        SequenceParameterKind::Valve,
+        Some(SequenceValue::Text("FUEL_PT".to_string())),
+      ),
+    ];
+
+    for parameter in rejected {
+      let name = parameter.name.clone();
+      assert!(
+        check_parameters(&[parameter], &mappings).is_err(),
+        "'{name}' was accepted"
+      );
+    }
+
+    let duplicated = [
+      parameter("target", SequenceParameterKind::Pressure, None),
+      parameter("target", SequenceParameterKind::Pressure, None),
+    ];
+
+    assert!(check_parameters(&duplicated, &mappings).is_err());
+
+    let accepted = [
+      parameter("target", SequenceParameterKind::Pressure, None),
+      parameter(
+        "vent",
+        SequenceParameterKind::Valve,
+        Some(SequenceValue::Text("BBV".to_string())),
+      ),
+    ];
+
+    assert_eq!(check_parameters(&accepted, &mappings), Ok(()));
+  }
+
+  #[test]
+  fn arguments_are_typed_and_fall_back_to_defaults() {
+    let mappings = [mapping("BBV", SensorType::Valve)];
+    let parameters = [
+      parameter("target", SequenceParameterKind::Pressure, None),
+      parameter(
+        "hold",
+        SequenceParameterKind::Duration,
+        Some(SequenceValue::Number(2.5)),
+      ),
+      parameter("vent", SequenceParameterKind::Valve, None),
+    ];
+
+    let values = HashMap::from([
+      ("target".to_string(), SequenceValue::Number(300.0)),
+      ("vent".to_string(), SequenceValue::Text("BBV".to_string())),
+    ]);
+
+    let arguments = resolve_arguments(&parameters, &values, &mappings).unwrap();
+
+    assert_eq!(arguments["target"], SequenceArgument::Pressure(300.0));
+    assert_eq!(arguments["hold"], SequenceArgument::Duration(2.5));
+    assert_eq!(
+      arguments["vent"],
+      SequenceArgument::Valve("BBV".to_string())
+    );
+
+    let missing = HashMap::from([(
+      "vent".to_string(),
+      SequenceValue::Text("BBV".to_string()),
+    )]);
+    assert!(resolve_arguments(&parameters, &missing, &mappings).is_err());
+
+    let mut unknown = values.clone();
+    unknown.insert("extra".to_string(), SequenceValue::Number(1.0));
+    assert!(resolve_arguments(&parameters, &unknown, &mappings).is_err());
+
+    let mut mistyped = values.clone();
+    mistyped.insert("target".to_string(), SequenceValue::Text("high".into()));
+    assert!(resolve_arguments(&parameters, &mistyped, &mappings).is_err());
+
+    let mut unmapped = values;
+    unmapped.insert("vent".to_string(), SequenceValue::Text("OX".to_string()));
+    assert!(resolve_arguments(&parameters, &unmapped, &mappings).is_err());
+  }
+}
@@ -0,0 +1,291 @@
+use common::comm::{
+  FlightStatusMessage,
+  SequenceOutputStream,
+  SequenceRun,
+  SequenceStatus,
+};
+use jeflog::warn;
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use super::Database;
+
+/// The most output lines kept for a single run. Anything a sequence prints
+/// beyond this is dropped so that a runaway `print` loop can't exhaust memory.
+pub const MAX_OUTPUT_LINES: usize = 5_000;
+
+/// A single line written by a sequence.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct SequenceOutputLine {
+  /// The stream the line was written to.
+  pub stream: SequenceOutputStream,
+
+  /// The line itself, without its trailing newline.
+  pub line: String,
+}
+
+/// A sequence run along with all of the output Servo has received from it.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct SequenceRunRecord {
+  /// The run as reported by the flight computer.
+  #[serde(flatten)]
+  pub run: SequenceRun,
+
+  /// The output of the run, in the order it was written.
+  pub output: Vec<SequenceOutputLine>,
+
+  /// Whether lines were dropped after reaching `MAX_OUTPUT_LINES`.
+  pub truncated: bool,
+}
+
+/// The sequence runs currently in progress on the flight computer. Finished
+/// runs are moved into the `SequenceRuns` table of the database.
+#[derive(Clone, Debug, Default)]
+pub struct SequenceRuns {
+  running: HashMap<u32, SequenceRunRecord>,
+}
+
+impl SequenceRuns {
+  /// Returns every run in progress, oldest first.
+  pub fn running(&self) -> Vec<SequenceRunRecord> {
+    let mut running: Vec<_> = self.running.values().cloned().collect();
+    running.sort_by(|a, b| a.run.started_at.total_cmp(&b.run.started_at));
+    running
+  }
+
+  /// Applies a sequence-related status message from the flight computer,
+  /// returning the record of a run if it has just finished. Other messages
+  /// are ignored.
+  pub fn apply(
+    &mut self,
+    message: FlightStatusMessage,
+  ) -> Option<SequenceRunRecord> {
+    match message {
+      FlightStatusMessage::SequenceStarted(run) => {
+        self.running.insert(
+          run.pid,
+          SequenceRunRecord {
+            run,
+            output: Vec::new(),
+            truncated: false,
+          },
+        );
+
+        None
+      }
+      FlightStatusMessage::SequenceOutput { pid, stream, line } => {
+        // output from a run that started before Servo connected is dropped,
+        // as there is nothing to attach it to until the run finishes.
+        if let Some(record) = self.running.get_mut(&pid) {
+          if record.output.len() < MAX_OUTPUT_LINES {
+            record.output.push(SequenceOutputLine { stream, line });
+          } else {
+            record.truncated = true;
+          }
+        }
+
+        None
+      }
+      FlightStatusMessage::SequenceFinished(run) => {
+        let mut record =
+          self.running.remove(&run.pid).unwrap_or(SequenceRunRecord {
+            run: run.clone(),
+            output: Vec::new(),
+            truncated: false,
+          });
+
+        record.run = run;
+        Some(record)
+      }
+      FlightStatusMessage::Ack { .. } | FlightStatusMessage::Nack { .. } => None,
+    }
+  }
+}
+
+impl Database {
+  /// Stores a finished sequence run so that it can be viewed later.
+  pub async fn record_sequence_run(&self, record: &SequenceRunRecord) {
+    let (exit_code, signal) = match record.run.status {
+      SequenceStatus::Exited { code } => (Some(code), None),
+      SequenceStatus::Killed { signal } => (None, Some(signal)),
+      SequenceStatus::Running | SequenceStatus::Paused { .. } => (None, None),
+    };
+
+    let output = match postcard::to_allocvec(&record.output) {
+      Ok(output) => output,
+      Err(error) => {
+        warn!("Failed to serialize sequence output into Postcard: {error}");
+        return;
+      }
+    };
+
+    let result = self.connection.lock().await.execute(
+      "
+        INSERT INTO SequenceRuns (
+          name,
+          pid,
+          started_at,
+          finished_at,
+          exit_code,
+          signal,
+          output,
+          truncated
+        ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+      ",
+      params![
+        record.run.name,
+        record.run.pid,
+        record.run.started_at,
+        record.run.finished_at,
+        exit_code,
+        signal,
+        output,
+        record.truncated,
+      ],
+    );
+
+    if let Err(error) = result {
+      warn!("Failed to insert sequence run into database: {error}");
+    }
+  }
+
+  /// Returns the most recent finished sequence runs, newest first, optionally
+  /// only those of the sequence with the given name.
+  pub async fn finished_sequence_runs(
+    &self,
+    name: Option<&str>,
+    limit: u32,
+  ) -> rusqlite::Result<Vec<SequenceRunRecord>> {
+    let connection = self.connection.lock().await;
+    let mut statement = connection.prepare(
+      "
+        SELECT
+          name,
+          pid,
+          started_at,
+          finished_at,
+          exit_code,
+          signal,
+          output,
+          truncated
+        FROM SequenceRuns
+        WHERE ?1 IS NULL OR name = ?1
+        ORDER BY finished_at DESC
+        LIMIT ?2
+      ",
+    )?;
+
+    let records = statement
+      .query_and_then(params![name, limit], |row| {
+        let status = match (row.get(4)?, row.get(5)?) {
+          (Some(code), _) => SequenceStatus::Exited { code },
+          (None, Some(signal)) => SequenceStatus::Killed { signal },
+          (None, None) => SequenceStatus::Running,
+        };
+
+        let bytes = row.get::<_, Vec<u8>>(6)?;
+        let output = postcard::from_bytes(&bytes).map_err(|error| {
+          rusqlite::Error::FromSqlConversionFailure(
+            6,
+            rusqlite::types::Type::Blob,
+            Box::new(error),
+          )
+        })?;
+
+        Ok::<_, rusqlite::Error>(SequenceRunRecord {
+          run: SequenceRun {
+            name: row.get(0)?,
+            pid: row.get(1)?,
+            started_at: row.get(2)?,
+            finished_at: row.get(3)?,
+            status,
+          },
+          output,
+          truncated: row.get(7)?,
+        })
+      })?
+      .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    Ok(records)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn run(pid: u32, status: SequenceStatus) -> SequenceRun {
+    SequenceRun {
+      name: "purge".to_string(),
+      pid,
+      started_at: 1.0,
+      finished_at: None,
+      status,
+    }
+  }
+
+  #[test]
+  fn finished_run_keeps_output_in_order() {
+    let mut runs = SequenceRuns::default();
+
+    let started = runs.apply(FlightStatusMessage::SequenceStarted(run(
+      7,
+      SequenceStatus::Running,
+    )));
+    assert!(started.is_none());
+
+    for (stream, line) in [
+      (SequenceOutputStream::Stdout, "opening BBV"),
+      (SequenceOutputStream::Stderr, "Traceback (most recent call last):"),
+    ] {
+      runs.apply(FlightStatusMessage::SequenceOutput {
+        pid: 7,
+        stream,
+        line: line.to_string(),
+      });
+    }
+
+    assert_eq!(runs.running().len(), 1);
+
+    let mut finished = run(7, SequenceStatus::Exited { code: 1 });
+    finished.finished_at = Some(2.0);
+
+    let record = runs
+      .apply(FlightStatusMessage::SequenceFinished(finished.clone()))
+      .expect("a finished run should produce a record");
+
+    assert!(runs.running().is_empty());
+    assert_eq!(record.run, finished);
+    assert_eq!(record.output.len(), 2);
+    assert_eq!(record.output[1].stream, SequenceOutputStream::Stderr);
+    assert!(!record.truncated);
+  }
+
+  #[test]
+  fn output_beyond_limit_is_truncated() {
+    let mut runs = SequenceRuns::default();
+    runs.apply(FlightStatusMessage::SequenceStarted(run(
+      3,
+      SequenceStatus::Running,
+    )));
+
+    for _ in 0..=MAX_OUTPUT_LINES {
+      runs.apply(FlightStatusMessage::SequenceOutput {
+        pid: 3,
+        stream: SequenceOutputStream::Stdout,
+        line: "spam".to_string(),
+      });
+    }
+
+    let record = runs
+      .apply(FlightStatusMessage::SequenceFinished(run(
+        3,
+        SequenceStatus::Killed { signal: 9 },
+      )))
+      .unwrap();
+
+    assert_eq!(record.output.len(), MAX_OUTPUT_LINES);
+    assert!(record.truncated);
+  }
+}
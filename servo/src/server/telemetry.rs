@@ -2,12 +2,18 @@ use common::comm::{
   NodeMapping,
   SensorType,
   VehicleState,
+  VehicleStateCompaqError,
+  VehicleStateCompressionSchema,
   VehicleStateDecompressionSchema,
-  include_in_radio_telemetry,
+  VehicleStateDeltaDecoder,
+  VehicleStateSchemaError,
+  VehicleStateSlice,
   sam::Unit,
 };
+use jeflog::{pass, warn};
+use serde::Serialize;
 use std::{
-  collections::HashSet,
+  collections::{HashMap, HashSet, VecDeque},
   fmt::{self, Display, Formatter},
   sync::Arc, time::Duration,
 };
@@ -119,33 +125,291 @@ impl TelemetryState {
   }
 }
 
-/// Cached radio decompression schema derived from the active mappings.
+/// How many radio schemas are kept, including the current one, so that frames
+/// sent by flight before it received new mappings can still be decoded.
+const RECENT_RADIO_SCHEMAS: usize = 4;
+
+/// How long a valve, sensor, or other part of the radio state may go without
+/// arriving in a frame before it is reported as out of date.
+const RADIO_FIELD_TIME_TO_LIVE: Duration = Duration::from_secs(2);
+
+/// Cached radio decompression schemas derived from the active mappings.
 #[derive(Clone, Debug, Default)]
 pub struct RadioSchemaCache {
   active_mappings: Vec<NodeMapping>,
-  schema: Option<VehicleStateDecompressionSchema>,
+
+  /// The schemas of the most recent mapping sets, newest first.
+  schemas: VecDeque<VehicleStateDecompressionSchema>,
+
+  /// Holds the last keyframe so delta frames from flight can be rebuilt.
+  decoder: VehicleStateDeltaDecoder,
+
+  /// The radio state rebuilt from every frame received so far, which is
+  /// needed when flight splits telemetry across several frames.
+  merged: RadioStateMerge,
+
+  stats: RadioSchemaStats,
+}
+
+/// Counts of how radio frames have matched the cached schemas.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct RadioSchemaStats {
+  /// The fingerprint of the schema of the active mappings.
+  pub fingerprint: Option<u32>,
+
+  /// The fingerprints of every schema frames may be decoded with, newest
+  /// first.
+  pub recent_fingerprints: Vec<u32>,
+
+  /// Frames decoded with a schema older than the current one.
+  pub previous_schema_frames: u64,
+
+  /// Frames discarded because they didn't match any cached schema.
+  pub mismatched_frames: u64,
+
+  /// The fingerprint of the last frame that didn't match any cached schema.
+  pub last_mismatched_fingerprint: Option<u32>,
+
+  /// Delta frames discarded because the keyframe they build on was lost.
+  pub missing_keyframe_frames: u64,
+
+  /// How long ago each part of the radio state last arrived in a frame.
+  pub freshness: RadioFreshness,
+}
+
+/// The time since each part of the radio state last arrived in a frame, in
+/// seconds.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct RadioFreshness {
+  /// The age of each valve state.
+  pub valves: HashMap<String, f64>,
+
+  /// The age of each sensor reading.
+  pub sensors: HashMap<String, f64>,
+
+  /// The age of the BMS state, if it has been received.
+  pub bms: Option<f64>,
+
+  /// The age of the flight computer's sensors, if they have been received.
+  pub fc_sensors: Option<f64>,
+
+  /// The age of the GPS state, if it has been received.
+  pub gps: Option<f64>,
+
+  /// The age of the RECO state, if it has been received.
+  pub reco: Option<f64>,
+}
+
+/// Merges the slices of the radio state sent in separate frames, tracking
+/// when each part of it last arrived.
+#[derive(Clone, Debug, Default)]
+struct RadioStateMerge {
+  state: VehicleState,
+  valves: HashMap<String, Instant>,
+  sensors: HashMap<String, Instant>,
+  bms: Option<Instant>,
+  fc_sensors: Option<Instant>,
+  gps: Option<Instant>,
+  reco: Option<Instant>,
+}
+
+impl RadioStateMerge {
+  /// Overwrites the parts of the merged state held by a slice, returning the
+  /// merged state with the parts which haven't arrived recently marked out of
+  /// date.
+  fn merge(&mut self, slice: VehicleStateSlice, now: Instant) -> VehicleState {
+    let VehicleStateSlice {
+      state,
+      bms,
+      fc_sensors,
+      gps,
+      reco,
+    } = slice;
+
+    for (name, valve_state) in state.valve_states {
+      self.valves.insert(name.clone(), now);
+      self.state.valve_states.insert(name, valve_state);
+    }
+
+    for (name, measurement) in state.sensor_readings {
+      self.sensors.insert(name.clone(), now);
+      self.state.sensor_readings.insert(name, measurement);
+    }
+
+    if bms {
+      self.state.bms = state.bms;
+      self.bms = Some(now);
+    }
+
+    if fc_sensors {
+      self.state.fc_sensors = state.fc_sensors;
+      self.fc_sensors = Some(now);
+    }
+
+    if gps {
+      self.state.gps = state.gps;
+      self.state.gps_valid = state.gps_valid;
+      self.gps = Some(now);
+    }
+
+    if reco {
+      self.state.reco = state.reco;
+      self.state.reco_valid = state.reco_valid;
+      self.state.reco_disagreement = state.reco_disagreement;
+      self.reco = Some(now);
+    }
+
+    let is_stale = |received: Option<&Instant>| {
+      received.is_none_or(|received| now - *received > RADIO_FIELD_TIME_TO_LIVE)
+    };
+
+    let mut merged = self.state.clone();
+
+    for (name, measurement) in &mut merged.sensor_readings {
+      measurement.stale |= is_stale(self.sensors.get(name));
+    }
+
+    merged.gps_valid &= !is_stale(self.gps.as_ref());
+    merged.reco_valid &= !is_stale(self.reco.as_ref());
+    merged
+  }
+
+  /// Forgets every valve and sensor which isn't in any of the given schemas.
+  fn retain(&mut self, schemas: &VecDeque<VehicleStateDecompressionSchema>) {
+    let valves: HashSet<&String> =
+      schemas.iter().flat_map(|schema| schema.valve_keys()).collect();
+    let sensors: HashSet<&String> =
+      schemas.iter().flat_map(|schema| schema.sensor_keys()).collect();
+
+    self.state.valve_states.retain(|name, _| valves.contains(name));
+    self.valves.retain(|name, _| valves.contains(name));
+    self.state.sensor_readings.retain(|name, _| sensors.contains(name));
+    self.sensors.retain(|name, _| sensors.contains(name));
+  }
+
+  fn freshness(&self, now: Instant) -> RadioFreshness {
+    let age = |received: &Instant| (now - *received).as_secs_f64();
+
+    RadioFreshness {
+      valves: self
+        .valves
+        .iter()
+        .map(|(name, received)| (name.clone(), age(received)))
+        .collect(),
+      sensors: self
+        .sensors
+        .iter()
+        .map(|(name, received)| (name.clone(), age(received)))
+        .collect(),
+      bms: self.bms.as_ref().map(age),
+      fc_sensors: self.fc_sensors.as_ref().map(age),
+      gps: self.gps.as_ref().map(age),
+      reco: self.reco.as_ref().map(age),
+    }
+  }
 }
 
 impl RadioSchemaCache {
-  /// Rebuilds the cached schema from the current active mapping set.
+  /// Rebuilds the cached schema from the current active mapping set, keeping
+  /// the schemas of a few previous mapping sets.
   pub fn refresh(&mut self, active_mappings: Vec<NodeMapping>) {
     let schema = build_radio_schema(&active_mappings);
     self.active_mappings = active_mappings;
-    self.schema = Some(schema);
+
+    self
+      .schemas
+      .retain(|recent| recent.fingerprint() != schema.fingerprint());
+    self.schemas.push_front(schema);
+    self.schemas.truncate(RECENT_RADIO_SCHEMAS);
+    self.merged.retain(&self.schemas);
   }
 
   /// Returns the active cached schema, if one has been built yet.
   pub fn schema(&self) -> Option<&VehicleStateDecompressionSchema> {
-    self.schema.as_ref()
+    self.schemas.front()
+  }
+
+  /// Decodes a radio frame with whichever cached schema it was compressed
+  /// with, counting frames that match none of them. Delta frames are rebuilt
+  /// from the last keyframe received, and frames holding a slice of the state
+  /// are merged with the slices before them.
+  pub fn decode(
+    &mut self,
+    bytes: &[u8],
+  ) -> Result<VehicleState, VehicleStateCompaqError> {
+    let Some(fingerprint) = VehicleState::compaq_fingerprint(bytes) else {
+      return Err(VehicleStateCompaqError::Deserialize(
+        postcard::Error::DeserializeUnexpectedEnd,
+      ));
+    };
+
+    let position = self
+      .schemas
+      .iter()
+      .position(|schema| schema.fingerprint() == fingerprint);
+
+    match position {
+      Some(0) => {}
+      Some(_) => self.stats.previous_schema_frames += 1,
+      None => {
+        self.stats.mismatched_frames += 1;
+        self.stats.last_mismatched_fingerprint = Some(fingerprint);
+      }
+    }
+
+    // without a matching schema, decoding with the current one reports why
+    // the frame can't be decoded.
+    let Some(schema) = self.schemas.get(position.unwrap_or(0)) else {
+      return Err(VehicleStateCompaqError::PolicyDesynchronized);
+    };
+
+    let decoded = self.decoder.decode_slice(bytes, schema);
+
+    if let Err(VehicleStateCompaqError::MissingKeyframe) = decoded {
+      self.stats.missing_keyframe_frames += 1;
+    }
+
+    Ok(self.merged.merge(decoded?, Instant::now()))
+  }
+
+  /// Returns how radio frames have matched the cached schemas.
+  pub fn stats(&self) -> RadioSchemaStats {
+    RadioSchemaStats {
+      fingerprint: self.schema().map(|schema| schema.fingerprint()),
+      recent_fingerprints: self
+        .schemas
+        .iter()
+        .map(|schema| schema.fingerprint())
+        .collect(),
+      freshness: self.merged.freshness(Instant::now()),
+      ..self.stats.clone()
+    }
   }
 }
 
+/// Builds the schema flight compresses the radio subset of the given mappings
+/// with, which fails if the subset is too large for the wire format.
+pub fn radio_compression_schema(
+  mappings: &[NodeMapping],
+) -> Result<VehicleStateCompressionSchema, VehicleStateSchemaError> {
+  let schema = build_radio_schema(mappings);
+
+  VehicleStateCompressionSchema::new(
+    schema.valve_keys().iter().cloned(),
+    schema
+      .sensor_keys()
+      .iter()
+      .cloned()
+      .zip(schema.sensor_units().iter().copied()),
+  )
+}
+
 fn build_radio_schema(
   active_mappings: &[NodeMapping],
 ) -> VehicleStateDecompressionSchema {
   let flight_mappings: Vec<_> = active_mappings
     .iter()
-    .filter(|mapping| include_in_radio_telemetry(mapping))
+    .filter(|mapping| mapping.radio)
     .collect();
 
   let valve_keys: Vec<_> = flight_mappings
@@ -173,10 +437,55 @@ fn should_omit_radio_sensor(sensor_name: &str, valve_keys: &HashSet<String>) ->
     .is_some_and(|valve_name| valve_keys.contains(valve_name))
 }
 
+/// The names of the RECO MCUs, in the order of `VehicleState::reco`.
+const RECO_MCU_NAMES: [&str; 3] = ["A", "B", "C"];
+
+/// Logs every RECO MCU which started or stopped disagreeing with the vote
+/// across all three since the previous state from a telemetry source.
+fn log_reco_disagreement(
+  source: TelemetrySource,
+  previous: &VehicleState,
+  current: &VehicleState,
+) {
+  let mcus = RECO_MCU_NAMES
+    .iter()
+    .zip(&previous.reco_disagreement)
+    .zip(&current.reco_disagreement);
+
+  for ((name, previous), current) in mcus {
+    if previous == current {
+      continue;
+    }
+
+    if !current.any() {
+      pass!("RECO MCU {name} agrees with the vote again over {source} telemetry.");
+      continue;
+    }
+
+    let reasons: Vec<&str> = [
+      (current.missing, "missing"),
+      (current.attitude, "attitude"),
+      (current.position, "position"),
+      (current.altitude, "altitude"),
+      (current.velocity, "velocity"),
+      (current.flags, "flags"),
+    ]
+    .into_iter()
+    .filter_map(|(disagrees, reason)| disagrees.then_some(reason))
+    .collect();
+
+    warn!(
+      "RECO MCU {name} disagrees with the vote over {source} telemetry: {}.",
+      reasons.join(", "),
+    );
+  }
+}
+
 /// Replaces the live state for a telemetry source and updates its arrival
 /// timing and packet-size statistics.
 pub async fn update_live_telemetry(
   telemetry: &LiveTelemetry,
+  source: TelemetrySource,
   state: VehicleState,
   packet_size: usize,
 ) {
@@ -201,7 +510,10 @@ pub async fn update_live_telemetry(
     );
   }
 
-  *telemetry.vehicle.0.lock().await = state;
+  let mut vehicle = telemetry.vehicle.0.lock().await;
+  log_reco_disagreement(source, &vehicle, &state);
+  *vehicle = state;
+  drop(vehicle);
   telemetry.vehicle.1.notify_waiters();
   *packet_size_lock = Some(packet_size);
   telemetry.packet_size.1.notify_waiters();
@@ -213,6 +525,7 @@ pub async fn update_live_telemetry(
 #[cfg(test)]
 mod tests {
   use super::*;
+  use common::comm::{SlicePriorities, VehicleStateSlicer, RADIO_PAYLOAD_MTU};
 
   fn mapping(
     text_id: &str,
@@ -224,33 +537,174 @@ mod tests {
       board_id: board_id.to_string(),
       sensor_type,
       channel: 1,
-      computer: common::comm::Computer::Flight,
-      max: None,
-      min: None,
-      calibrated_offset: 0.0,
-      powered_threshold: None,
-      normally_closed: None,
+      ..Default::default()
+    }
+  }
+
+  fn radio_mapping(
+    text_id: &str,
+    board_id: &str,
+    sensor_type: SensorType,
+  ) -> NodeMapping {
+    NodeMapping {
+      radio: true,
+      ..mapping(text_id, board_id, sensor_type)
     }
   }
 
   #[test]
-  fn radio_schema_uses_only_vehicle_sam_mappings_and_omits_valve_helpers() {
+  fn radio_schema_uses_only_radio_mappings_and_omits_valve_helpers() {
     let schema = build_radio_schema(&[
-      mapping("VLV01", "sam-21", SensorType::Valve),
-      mapping("VLV01_I", "sam-21", SensorType::RailCurrent),
-      mapping("VLV01_V", "sam-21", SensorType::RailVoltage),
-      mapping("VLV02", "sam-31", SensorType::Valve),
-      mapping("VLV02_I", "sam-31", SensorType::RailCurrent),
-      mapping("VLV02_V", "sam-31", SensorType::RailVoltage),
-      mapping("PT01", "sam-21", SensorType::Pt),
-      mapping("PT02", "sam-31", SensorType::Pt),
+      radio_mapping("VLV01", "sam-21", SensorType::Valve),
+      radio_mapping("VLV01_I", "sam-21", SensorType::RailCurrent),
+      radio_mapping("VLV01_V", "sam-21", SensorType::RailVoltage),
+      radio_mapping("VLV02", "sam-31", SensorType::Valve),
+      radio_mapping("VLV02_I", "sam-31", SensorType::RailCurrent),
+      radio_mapping("VLV02_V", "sam-31", SensorType::RailVoltage),
+      radio_mapping("PT01", "sam-21", SensorType::Pt),
+      radio_mapping("PT02", "sam-31", SensorType::Pt),
+      radio_mapping("TANK_PT", "sam-01", SensorType::Pt),
+      mapping("PT03", "sam-21", SensorType::Pt),
       mapping("GROUND_PT", "sam-01", SensorType::Pt),
       mapping("GROUND_VALVE", "sam-01", SensorType::Valve),
       mapping("GROUND_VALVE_I", "sam-01", SensorType::RailCurrent),
     ]);
 
     assert_eq!(schema.valve_keys(), ["VLV01", "VLV02"]);
-    assert_eq!(schema.sensor_keys(), ["PT01", "PT02"]);
-    assert_eq!(schema.sensor_units(), [Unit::Psi, Unit::Psi]);
+    assert_eq!(schema.sensor_keys(), ["PT01", "PT02", "TANK_PT"]);
+    assert_eq!(schema.sensor_units(), [Unit::Psi, Unit::Psi, Unit::Psi]);
+  }
+
+  fn radio_frame(mappings: &[NodeMapping]) -> Vec<u8> {
+    let schema = build_radio_schema(mappings);
+    let mut state = VehicleState::new();
+
+    for (key, unit) in schema.sensor_keys().iter().zip(schema.sensor_units())
+    {
+      state.sensor_readings.insert(
+        key.clone(),
+        common::comm::Measurement {
+          value: 100.0,
+          unit: *unit,
+          timestamp: 0.0,
+          board_id: None,
+          stale: false,
+        },
+      );
+    }
+
+    let schema = VehicleStateCompressionSchema::from_state(&state)
+      .expect("schema creation should succeed");
+    let mut buf = [0u8; 256];
+    let size = state
+      .compress_compaq_with_schema(&mut buf, &schema)
+      .expect("compaq compression should succeed");
+
+    buf[..size].to_vec()
+  }
+
+  #[test]
+  fn radio_schema_cache_decodes_frames_from_previous_mappings() {
+    let old_mappings = vec![
+      radio_mapping("PT01", "sam-21", SensorType::Pt),
+      radio_mapping("PT02", "sam-21", SensorType::Pt),
+    ];
+    let new_mappings = vec![
+      radio_mapping("PT01", "sam-21", SensorType::Pt),
+      radio_mapping("PT03", "sam-21", SensorType::Pt),
+    ];
+    let unknown_mappings = vec![
+      radio_mapping("PT01", "sam-21", SensorType::Pt),
+      radio_mapping("PT04", "sam-21", SensorType::Pt),
+    ];
+
+    let mut cache = RadioSchemaCache::default();
+    cache.refresh(old_mappings.clone());
+    cache.refresh(new_mappings.clone());
+
+    let decoded = cache
+      .decode(&radio_frame(&old_mappings))
+      .expect("a frame sent with the previous mappings should decode");
+    assert!(decoded.sensor_readings.contains_key("PT02"));
+
+    let decoded = cache
+      .decode(&radio_frame(&new_mappings))
+      .expect("a frame sent with the current mappings should decode");
+    assert!(decoded.sensor_readings.contains_key("PT03"));
+
+    assert!(matches!(
+      cache.decode(&radio_frame(&unknown_mappings)),
+      Err(VehicleStateCompaqError::SchemaMismatch { .. })
+    ));
+
+    let stats = cache.stats();
+    assert_eq!(stats.previous_schema_frames, 1);
+    assert_eq!(stats.mismatched_frames, 1);
+    assert_eq!(
+      stats.fingerprint,
+      Some(build_radio_schema(&new_mappings).fingerprint())
+    );
+    assert_eq!(
+      stats.last_mismatched_fingerprint,
+      Some(build_radio_schema(&unknown_mappings).fingerprint())
+    );
+  }
+
+  #[test]
+  fn radio_schema_cache_merges_slices_from_separate_frames() {
+    let mappings: Vec<_> = (0..120)
+      .map(|i| radio_mapping(&format!("PT{i:03}"), "sam-21", SensorType::Pt))
+      .collect();
+    let schema = radio_compression_schema(&mappings)
+      .expect("schema creation should succeed");
+    let mut state = VehicleState::new();
+
+    for (i, (key, unit)) in schema
+      .sensor_keys()
+      .iter()
+      .zip(schema.sensor_units())
+      .enumerate()
+    {
+      state.sensor_readings.insert(
+        key.clone(),
+        common::comm::Measurement {
+          value: i as f64,
+          unit: *unit,
+          timestamp: 0.0,
+          board_id: None,
+          stale: false,
+        },
+      );
+    }
+
+    let mut cache = RadioSchemaCache::default();
+    cache.refresh(mappings);
+
+    let mut slicer = VehicleStateSlicer::new();
+    let priorities = SlicePriorities::default();
+    let mut buf = [0u8; RADIO_PAYLOAD_MTU];
+
+    let size = slicer
+      .encode(&state, &mut buf, &schema, &priorities)
+      .expect("slicing should succeed");
+    let first = cache.decode(&buf[..size]).expect("a slice should decode");
+    assert!(first.sensor_readings.len() < state.sensor_readings.len());
+
+    let mut merged = first;
+    while merged.sensor_readings.len() < state.sensor_readings.len() {
+      let size = slicer
+        .encode(&state, &mut buf, &schema, &priorities)
+        .expect("slicing should succeed");
+      merged = cache.decode(&buf[..size]).expect("a slice should decode");
+    }
+
+    for (key, measurement) in &state.sensor_readings {
+      assert_eq!(merged.sensor_readings[key].value, measurement.value);
+      assert!(!merged.sensor_readings[key].stale);
+    }
+
+    let freshness = cache.stats().freshness;
+    assert_eq!(freshness.sensors.len(), state.sensor_readings.len());
+    assert!(freshness.gps.is_some());
   }
 }
@@ -81,6 +81,9 @@ pub fn emulate_flight() -> anyhow::Result<()> {
       Measurement {
         value: rand::random::<f64>() * 120.0,
         unit: Unit::Psi,
+        timestamp: 0.0,
+        board_id: None,
+        stale: false,
       },
     );
     mock_vehicle_state.sensor_readings.insert(
@@ -88,6 +91,9 @@ pub fn emulate_flight() -> anyhow::Result<()> {
       Measurement {
         value: rand::random::<f64>() * 1000.0,
         unit: Unit::Psi,
+        timestamp: 0.0,
+        board_id: None,
+        stale: false,
       },
     );
     mock_vehicle_state.sensor_readings.insert(
@@ -95,6 +101,9 @@ pub fn emulate_flight() -> anyhow::Result<()> {
       Measurement {
         value: 2.2,
         unit: Unit::Volts,
+        timestamp: 0.0,
+        board_id: None,
+        stale: false,
       },
     );
     mock_vehicle_state.sensor_readings.insert(
@@ -102,6 +111,9 @@ pub fn emulate_flight() -> anyhow::Result<()> {
       Measurement {
         value: 0.01,
         unit: Unit::Amps,
+        timestamp: 0.0,
+        board_id: None,
+        stale: false,
       },
     );
     mock_vehicle_state.sensor_readings.insert(
@@ -109,6 +121,9 @@ pub fn emulate_flight() -> anyhow::Result<()> {
       Measurement {
         value: 24.0,
         unit: Unit::Volts,
+        timestamp: 0.0,
+        board_id: None,
+        stale: false,
       },
     );
     mock_vehicle_state.sensor_readings.insert(
@@ -116,6 +131,9 @@ pub fn emulate_flight() -> anyhow::Result<()> {
       Measurement {
         value: 0.10,
         unit: Unit::Amps,
+        timestamp: 0.0,
+        board_id: None,
+        stale: false,
       },
     );
     mock_vehicle_state.sensor_readings.insert(
@@ -123,6 +141,9 @@ pub fn emulate_flight() -> anyhow::Result<()> {
       Measurement {
         value: 1000.0,
         unit: Unit::Volts,
+        timestamp: 0.0,
+        board_id: None,
+        stale: false,
       },
     );
     mock_vehicle_state.sensor_readings.insert(
@@ -130,6 +151,9 @@ pub fn emulate_flight() -> anyhow::Result<()> {
       Measurement {
         value: 0.0,
         unit: Unit::Amps,
+        timestamp: 0.0,
+        board_id: None,
+        stale: false,
       },
     );
     raw = postcard::to_allocvec(&mock_vehicle_state)?;
@@ -1,17 +1,33 @@
+use super::upload::{report_response, sequence_value};
+use anyhow::anyhow;
 use serde_json::json;
+use std::collections::HashMap;
+
+/// Tool function used to send a sequence to be run on the flight computer,
+/// with its parameters given as `NAME=VALUE` pairs.
+pub fn run<'a>(
+  sequence: &str,
+  arguments: impl Iterator<Item = &'a String>,
+) -> anyhow::Result<()> {
+  let arguments = arguments
+    .map(|argument| {
+      let (name, value) = argument
+        .split_once('=')
+        .ok_or_else(|| anyhow!("argument '{argument}' is not NAME=VALUE"))?;
+
+      Ok((name.to_owned(), sequence_value(value)))
+    })
+    .collect::<anyhow::Result<HashMap<_, _>>>()?;
 
-/// Tool function used to send a sequence to be run on the flight computer.
-pub fn run(sequence: &str) -> anyhow::Result<()> {
   let client = reqwest::blocking::Client::new();
   let response = client
     .post("http://localhost:7200/operator/run-sequence")
     .json(&json!({
       "name": sequence,
-      "force": true
+      "force": true,
+      "arguments": arguments,
     }))
     .send()?;
 
-  println!("{response:#?}");
-
-  Ok(())
+  report_response(response, sequence)
 }
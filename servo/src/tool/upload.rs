@@ -1,8 +1,93 @@
+use crate::server::sequence_check::SequenceDiagnostic;
+use anyhow::anyhow;
+use common::comm::{SequenceParameter, SequenceParameterKind, SequenceValue};
+use jeflog::fail;
+use reqwest::{blocking::Response, StatusCode};
+use serde::Deserialize;
 use serde_json::json;
 use std::{fs, path::Path};
 
-/// Tool function used to upload a sequence to be stored on the control server.
-pub fn upload(sequence_path: &Path) -> anyhow::Result<()> {
+/// The body of a response rejecting a sequence which failed static checks.
+#[derive(Deserialize)]
+struct DiagnosticsResponse {
+  diagnostics: Vec<SequenceDiagnostic>,
+}
+
+/// Prints the diagnostics of a sequence rejected by the control server, each
+/// prefixed by `source` and its location, or the raw response otherwise.
+pub(super) fn report_response(
+  response: Response,
+  source: &str,
+) -> anyhow::Result<()> {
+  if response.status() != StatusCode::BAD_REQUEST {
+    println!("{response:#?}");
+    return Ok(());
+  }
+
+  let body = response.text()?;
+
+  let Ok(rejection) = serde_json::from_str::<DiagnosticsResponse>(&body) else {
+    fail!("Control server rejected the sequence: {body}");
+    return Err(anyhow::anyhow!("sequence rejected"));
+  };
+
+  for diagnostic in &rejection.diagnostics {
+    fail!(
+      "{source}:{}:{}: {}",
+      diagnostic.line,
+      diagnostic.column,
+      diagnostic.message
+    );
+  }
+
+  Err(anyhow::anyhow!(
+    "sequence failed {} static check(s)",
+    rejection.diagnostics.len()
+  ))
+}
+
+/// Reads a parameter value given on the command line, which is a number if it
+/// parses as one and text otherwise.
+pub(super) fn sequence_value(value: &str) -> SequenceValue {
+  value
+    .parse()
+    .map(SequenceValue::Number)
+    .unwrap_or_else(|_| SequenceValue::Text(value.to_owned()))
+}
+
+/// Reads a parameter declared on the command line as `NAME:KIND[=DEFAULT]`,
+/// where `KIND` is spelled as in the control server's API, such as
+/// `pressure` or `valve`.
+fn parameter(declaration: &str) -> anyhow::Result<SequenceParameter> {
+  let (name, rest) = declaration.split_once(':').ok_or_else(|| {
+    anyhow!("parameter '{declaration}' is not NAME:KIND[=DEFAULT]")
+  })?;
+
+  let (kind, default) = match rest.split_once('=') {
+    Some((kind, default)) => (kind, Some(sequence_value(default))),
+    None => (rest, None),
+  };
+
+  let kind = serde_json::from_value::<SequenceParameterKind>(json!(kind))
+    .map_err(|_| anyhow!("parameter '{name}' has unknown kind '{kind}'"))?;
+
+  Ok(SequenceParameter {
+    name: name.to_owned(),
+    kind,
+    default,
+  })
+}
+
+/// Tool function used to upload a sequence to be stored on the control server,
+/// along with the parameters it declares.
+pub fn upload<'a>(
+  sequence_path: &Path,
+  parameters: impl Iterator<Item = &'a String>,
+) -> anyhow::Result<()> {
+  let parameters = parameters
+    .map(|declaration| parameter(declaration))
+    .collect::<anyhow::Result<Vec<_>>>()?;
+
   let name = sequence_path
     .file_stem()
     .expect("given path does not have a file stem")
@@ -16,11 +101,10 @@ pub fn upload(sequence_path: &Path) -> anyhow::Result<()> {
     .put("http://localhost:7200/operator/sequence")
     .json(&json!({
       "name": name,
-      "script": script
+      "script": script,
+      "parameters": parameters,
     }))
     .send()?;
 
-  println!("{response:#?}");
-
-  Ok(())
+  report_response(response, &sequence_path.to_string_lossy())
 }
@@ -79,6 +79,18 @@ pub fn build_mappings() -> Vec<NodeMapping> {
       calibrated_offset: 0.0,
       powered_threshold: Some(0.05),
       normally_closed: Some(true),
+      fault_timeout: None,
+      abort_on_fault: None,
+      warning_low: None,
+      warning_high: None,
+      critical_low: None,
+      critical_high: None,
+      abort_on_critical: None,
+      critical_persistence: None,
+      calibration: None,
+      expression: None,
+      radio: true,
+      radio_priority: 0,
     });
 
     mappings.push(NodeMapping {
@@ -92,6 +104,18 @@ pub fn build_mappings() -> Vec<NodeMapping> {
       calibrated_offset: 0.0,
       powered_threshold: None,
       normally_closed: None,
+      fault_timeout: None,
+      abort_on_fault: None,
+      warning_low: None,
+      warning_high: None,
+      critical_low: None,
+      critical_high: None,
+      abort_on_critical: None,
+      critical_persistence: None,
+      calibration: None,
+      expression: None,
+      radio: true,
+      radio_priority: 0,
     });
 
     mappings.push(NodeMapping {
@@ -105,6 +129,18 @@ pub fn build_mappings() -> Vec<NodeMapping> {
       calibrated_offset: 0.0,
       powered_threshold: None,
       normally_closed: None,
+      fault_timeout: None,
+      abort_on_fault: None,
+      warning_low: None,
+      warning_high: None,
+      critical_low: None,
+      critical_high: None,
+      abort_on_critical: None,
+      critical_persistence: None,
+      calibration: None,
+      expression: None,
+      radio: true,
+      radio_priority: 0,
     });
 
   }
@@ -121,6 +157,18 @@ pub fn build_mappings() -> Vec<NodeMapping> {
       calibrated_offset: 0.0,
       powered_threshold: None,
       normally_closed: None,
+      fault_timeout: None,
+      abort_on_fault: None,
+      warning_low: None,
+      warning_high: None,
+      critical_low: None,
+      critical_high: None,
+      abort_on_critical: None,
+      critical_persistence: None,
+      calibration: None,
+      expression: None,
+      radio: true,
+      radio_priority: 0,
     });
   }
 
@@ -136,6 +184,18 @@ pub fn build_mappings() -> Vec<NodeMapping> {
       calibrated_offset: 0.0,
       powered_threshold: None,
       normally_closed: None,
+      fault_timeout: None,
+      abort_on_fault: None,
+      warning_low: None,
+      warning_high: None,
+      critical_low: None,
+      critical_high: None,
+      abort_on_critical: None,
+      critical_persistence: None,
+      calibration: None,
+      expression: None,
+      radio: true,
+      radio_priority: 0,
     });
   }
 
@@ -151,6 +211,18 @@ pub fn build_mappings() -> Vec<NodeMapping> {
       calibrated_offset: 0.0,
       powered_threshold: None,
       normally_closed: None,
+      fault_timeout: None,
+      abort_on_fault: None,
+      warning_low: None,
+      warning_high: None,
+      critical_low: None,
+      critical_high: None,
+      abort_on_critical: None,
+      critical_persistence: None,
+      calibration: None,
+      expression: None,
+      radio: true,
+      radio_priority: 0,
     });
   }
 
@@ -166,6 +238,18 @@ pub fn build_mappings() -> Vec<NodeMapping> {
       calibrated_offset: 0.0,
       powered_threshold: None,
       normally_closed: None,
+      fault_timeout: None,
+      abort_on_fault: None,
+      warning_low: None,
+      warning_high: None,
+      critical_low: None,
+      critical_high: None,
+      abort_on_critical: None,
+      critical_persistence: None,
+      calibration: None,
+      expression: None,
+      radio: true,
+      radio_priority: 0,
     });
   }
 
@@ -181,6 +265,18 @@ pub fn build_mappings() -> Vec<NodeMapping> {
       calibrated_offset: 0.0,
       powered_threshold: None,
       normally_closed: None,
+      fault_timeout: None,
+      abort_on_fault: None,
+      warning_low: None,
+      warning_high: None,
+      critical_low: None,
+      critical_high: None,
+      abort_on_critical: None,
+      critical_persistence: None,
+      calibration: None,
+      expression: None,
+      radio: true,
+      radio_priority: 0,
     });
   }
 
@@ -196,6 +292,18 @@ pub fn build_mappings() -> Vec<NodeMapping> {
       calibrated_offset: 0.0,
       powered_threshold: Some(0.05),
       normally_closed: Some(true),
+      fault_timeout: None,
+      abort_on_fault: None,
+      warning_low: None,
+      warning_high: None,
+      critical_low: None,
+      critical_high: None,
+      abort_on_critical: None,
+      critical_persistence: None,
+      calibration: None,
+      expression: None,
+      radio: false,
+      radio_priority: 0,
     });
   }
 
@@ -211,6 +319,18 @@ pub fn build_mappings() -> Vec<NodeMapping> {
       calibrated_offset: 0.0,
       powered_threshold: None,
       normally_closed: None,
+      fault_timeout: None,
+      abort_on_fault: None,
+      warning_low: None,
+      warning_high: None,
+      critical_low: None,
+      critical_high: None,
+      abort_on_critical: None,
+      critical_persistence: None,
+      calibration: None,
+      expression: None,
+      radio: false,
+      radio_priority: 0,
     });
   }
 
@@ -230,10 +350,7 @@ pub fn count_valve_helper_sensors(mappings: &[NodeMapping]) -> usize {
 pub fn count_non_radio_mappings(mappings: &[NodeMapping]) -> usize {
   mappings
     .iter()
-    .filter(|mapping| {
-      !mapping.board_id.starts_with("sam-2")
-      && !mapping.board_id.starts_with("sam-3")
-    })
+    .filter(|mapping| !mapping.radio)
     .count()
 }
 